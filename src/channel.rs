@@ -2,7 +2,7 @@
 
 #![allow(unused_imports)]
 
-use {io, Evented, Ready, Poll, PollOpt, Registration, SetReadiness, Token};
+use {io, poll, Evented, Ready, Poll, PollOpt, Registration, SetReadiness, Token};
 use lazycell::{LazyCell, AtomicLazyCell};
 use std::any::Any;
 use std::fmt;
@@ -199,7 +199,7 @@ impl SenderCtl {
         if 0 == cnt {
             // Toggle readiness to readable
             if let Some(set_readiness) = self.inner.set_readiness.borrow() {
-                try!(set_readiness.set_readiness(Ready::readable()));
+                try!(poll::set_readiness_unlimited(&set_readiness, Ready::readable()));
             }
         }
 
@@ -229,7 +229,7 @@ impl ReceiverCtl {
         if first == 1 {
             // Unset readiness
             if let Some(set_readiness) = self.inner.set_readiness.borrow() {
-                try!(set_readiness.set_readiness(Ready::none()));
+                try!(poll::set_readiness_unlimited(&set_readiness, Ready::none()));
             }
         }
 
@@ -240,7 +240,7 @@ impl ReceiverCtl {
             // There are still pending messages. Since readiness was
             // previously unset, it must be reset here
             if let Some(set_readiness) = self.inner.set_readiness.borrow() {
-                try!(set_readiness.set_readiness(Ready::readable()));
+                try!(poll::set_readiness_unlimited(&set_readiness, Ready::readable()));
             }
         }
 
@@ -254,12 +254,12 @@ impl Evented for ReceiverCtl {
             return Err(io::Error::new(io::ErrorKind::Other, "receiver already registered"));
         }
 
-        let (registration, set_readiness) = Registration::new(poll, token, interest, opts);
+        let (registration, set_readiness) = try!(Registration::try_new(poll, token, interest, opts));
 
 
         if self.inner.pending.load(Ordering::Relaxed) > 0 {
             // TODO: Don't drop readiness
-            let _ = set_readiness.set_readiness(Ready::readable());
+            let _ = poll::set_readiness_unlimited(&set_readiness, Ready::readable());
         }
 
         self.registration.fill(registration).ok().expect("unexpected state encountered");
@@ -0,0 +1,185 @@
+//! Per-token deadlines, see `Poll::set_deadline`.
+//!
+//! Deadlines are kept in a hashed wheel owned by the `Poll` instance and only
+//! touched from the polling thread. Every call to `poll` disarms the
+//! deadlines of the tokens it returns events for, then reports the expired
+//! ones as `Ready::timeout()` events.
+
+use {Ready, Token};
+use event::Event;
+use sys;
+use std::{cmp, u64};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Width of a wheel slot in milliseconds
+const TICK_MS: u64 = 8;
+
+// Number of slots, a power of two. A deadline more than a revolution away
+// shares its slot with closer ones and is skipped until its tick comes up.
+const NUM_SLOTS: usize = 256;
+
+pub struct DeadlineWheel {
+    // Tick 0's time instant
+    start: Instant,
+    // Entries by slot, allocated when the first deadline is set
+    slots: Vec<Vec<Entry>>,
+    // The armed deadline of each token. Disarming a token only removes it
+    // from here, its entry is dropped once its slot is visited.
+    armed: HashMap<Token, Instant>,
+    // All the ticks before this one have been expired
+    tick: u64,
+}
+
+#[derive(Copy, Clone)]
+struct Entry {
+    token: Token,
+    deadline: Instant,
+    // Tick at which the entry expires, never before the wheel's current tick
+    tick: u64,
+}
+
+impl DeadlineWheel {
+    pub fn new() -> DeadlineWheel {
+        DeadlineWheel {
+            start: Instant::now(),
+            slots: Vec::new(),
+            armed: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Returns true if no deadline is armed.
+    pub fn is_empty(&self) -> bool {
+        self.armed.is_empty()
+    }
+
+    /// Arms the deadline of `token`, replacing the previous one if any.
+    pub fn set(&mut self, token: Token, deadline: Instant) {
+        if self.slots.is_empty() {
+            self.slots = (0..NUM_SLOTS).map(|_| Vec::new()).collect();
+        }
+
+        let tick = cmp::max(self.tick_of(deadline), self.tick);
+
+        self.slots[slot(tick)].push(Entry {
+            token: token,
+            deadline: deadline,
+            tick: tick,
+        });
+        self.armed.insert(token, deadline);
+    }
+
+    /// Disarms the deadline of `token`, returns false if none was armed.
+    pub fn clear(&mut self, token: Token) -> bool {
+        self.armed.remove(&token).is_some()
+    }
+
+    /// Returns how long the selector may block before the next deadline is
+    /// due, or `None` if no deadline is armed.
+    ///
+    /// When no deadline falls within the next revolution of the wheel, this
+    /// is the time left until the end of that revolution, so the result may
+    /// be earlier than any deadline.
+    pub fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        if self.armed.is_empty() {
+            return None;
+        }
+
+        let armed = &self.armed;
+
+        for tick in self.tick..self.tick + NUM_SLOTS as u64 {
+            let next = self.slots[slot(tick)].iter()
+                .filter(|e| e.tick == tick && armed.get(&e.token) == Some(&e.deadline))
+                .map(|e| e.deadline)
+                .min();
+
+            if let Some(deadline) = next {
+                return Some(saturating_sub(deadline, now));
+            }
+        }
+
+        let end = self.start + Duration::from_millis((self.tick + NUM_SLOTS as u64) * TICK_MS);
+        Some(saturating_sub(end, now))
+    }
+
+    /// Disarms the deadlines of the tokens with an event in `events`, then
+    /// pushes an event for each deadline expired at `now`, as long as
+    /// `events` has room. The deadlines left over stay armed and are
+    /// delivered by the next call.
+    pub fn poll(&mut self, events: &mut sys::Events, now: Instant) {
+        if self.armed.is_empty() {
+            return;
+        }
+
+        for i in 0..events.len() {
+            let token = events.get(i).unwrap().token();
+            self.armed.remove(&token);
+        }
+
+        let now_tick = self.tick_of(now);
+
+        // Past a full revolution, every slot is visited once
+        let end = cmp::min(now_tick, self.tick + NUM_SLOTS as u64 - 1);
+
+        for tick in self.tick..end + 1 {
+            let armed = &mut self.armed;
+            let mut full = false;
+
+            self.slots[slot(tick)].retain(|e| {
+                if armed.get(&e.token) != Some(&e.deadline) {
+                    // Disarmed or replaced by a later call to `set`
+                    return false;
+                }
+
+                if e.tick > now_tick || e.deadline > now {
+                    return true;
+                }
+
+                if events.len() >= events.capacity() {
+                    full = true;
+                    return true;
+                }
+
+                trace!("deadline expired for {:?}", e.token);
+                armed.remove(&e.token);
+                events.push_event(Event::new(Ready::timeout(), e.token));
+
+                false
+            });
+
+            if full {
+                // Resume from this slot
+                self.tick = tick;
+                return;
+            }
+        }
+
+        self.tick = now_tick;
+    }
+
+    fn tick_of(&self, at: Instant) -> u64 {
+        if at <= self.start {
+            return 0;
+        }
+
+        let elapsed = at - self.start;
+        let ms = elapsed.as_secs()
+            .saturating_mul(1_000)
+            .saturating_add(u64::from(elapsed.subsec_nanos() / 1_000_000));
+
+        ms / TICK_MS
+    }
+}
+
+fn slot(tick: u64) -> usize {
+    (tick & (NUM_SLOTS as u64 - 1)) as usize
+}
+
+fn saturating_sub(later: Instant, earlier: Instant) -> Duration {
+    if later > earlier {
+        later - earlier
+    } else {
+        Duration::from_millis(0)
+    }
+}
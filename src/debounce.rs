@@ -0,0 +1,221 @@
+//! Debouncing of readiness bursts
+//!
+//! `Debounce` wraps an `Evented` value and turns a burst of readiness events
+//! into a single event, delivered once the burst has settled. This is useful
+//! for sources that fire many events in a short time, such as an inotify
+//! file descriptor, when the handler only needs to run once per burst.
+
+use {io, poll, Evented, Events, Poll, PollOpt, Ready, Registration, SetReadiness, ShutdownHandle, Token};
+use lazycell::LazyCell;
+use std::{fmt, thread};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// Wraps an `Evented` value and delivers its readiness debounced.
+///
+/// The inner value is registered with a private `Poll` instance, watched by
+/// a background thread. When the inner value becomes ready, a window of
+/// `window` starts. Once it elapses, the `Debounce` becomes ready with the
+/// union of the readiness kinds observed during the window, delivering a
+/// single event to the `Poll` it is registered with. What happens when the
+/// inner value fires again during the window depends on the
+/// `DebounceMode`.
+///
+/// The inner value is watched edge-triggered, for the interest given to
+/// `new`. When handling a debounced event, drain the inner value (e.g. read
+/// until `WouldBlock`), otherwise it does not fire again. The interest and
+/// options given when registering the `Debounce` itself apply to the
+/// debounced events: with `PollOpt::edge()`, each settled burst is delivered
+/// once. With `PollOpt::level()`, the readiness keeps being delivered until
+/// `clear_readiness` is called.
+pub struct Debounce<E> {
+    inner: E,
+    window: Duration,
+    mode: DebounceMode,
+    // Private instance the inner value is registered with, moved to the
+    // background thread on registration
+    watcher: RefCell<Option<Poll>>,
+    shutdown: ShutdownHandle,
+    thread: RefCell<Option<thread::JoinHandle<()>>>,
+    // Set on registration with Poll
+    registered: LazyCell<Registered>,
+}
+
+/// How a `Debounce` treats events arriving during its window.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DebounceMode {
+    /// Every event restarts the window. The debounced event is only
+    /// delivered once the inner value has been quiet for the whole window,
+    /// so a continuous stream of events delays it indefinitely.
+    Extend,
+
+    /// The window starts with the first event of a burst and is not
+    /// extended. Events arriving during it are merged into the delivered
+    /// readiness, so at most one event is delivered per window.
+    Fixed,
+}
+
+struct Registered {
+    registration: Registration,
+    set_readiness: SetReadiness,
+}
+
+const INNER: Token = Token(0);
+
+impl<E: Evented> Debounce<E> {
+    /// Wraps `inner`, watching it for `interest` and debouncing its events
+    /// over `window`.
+    pub fn new(inner: E, interest: Ready, window: Duration, mode: DebounceMode) -> io::Result<Debounce<E>> {
+        let watcher = try!(Poll::new());
+        try!(watcher.register(&inner, INNER, interest, PollOpt::edge()));
+
+        Ok(Debounce {
+            inner: inner,
+            window: window,
+            mode: mode,
+            shutdown: watcher.shutdown_handle(),
+            watcher: RefCell::new(Some(watcher)),
+            thread: RefCell::new(None),
+            registered: LazyCell::new(),
+        })
+    }
+}
+
+impl<E> Debounce<E> {
+    /// Returns a reference to the inner value.
+    pub fn get_ref(&self) -> &E {
+        &self.inner
+    }
+
+    /// Returns the debounce window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the debounce mode.
+    pub fn mode(&self) -> DebounceMode {
+        self.mode
+    }
+
+    /// Clears the readiness of the `Debounce`, so that a level-triggered
+    /// registration stops reporting it until the next burst settles.
+    pub fn clear_readiness(&self) -> io::Result<()> {
+        match self.registered.borrow() {
+            Some(registered) => poll::set_readiness_unlimited(&registered.set_readiness, Ready::none()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> Evented for Debounce<E> {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        if self.registered.borrow().is_some() {
+            return Err(io::Error::new(io::ErrorKind::Other, "debounce already registered"));
+        }
+
+        let watcher = match self.watcher.borrow_mut().take() {
+            Some(watcher) => watcher,
+            None => return Err(io::Error::new(io::ErrorKind::Other, "debounce already registered")),
+        };
+
+        let (registration, set_readiness) = try!(Registration::try_new(poll, token, interest, opts));
+        let thread = spawn_debounce_thread(watcher, set_readiness.clone(), self.window, self.mode);
+
+        *self.thread.borrow_mut() = Some(thread);
+
+        self.registered.fill(Registered {
+            registration: registration,
+            set_readiness: set_readiness,
+        }).ok().expect("debounce already registered");
+
+        Ok(())
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        match self.registered.borrow() {
+            Some(registered) => registered.registration.update(poll, token, interest, opts),
+            None => Err(io::Error::new(io::ErrorKind::Other, "debounce not registered")),
+        }
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        match self.registered.borrow() {
+            Some(registered) => registered.registration.deregister(poll),
+            None => Err(io::Error::new(io::ErrorKind::Other, "debounce not registered")),
+        }
+    }
+}
+
+impl<E> Drop for Debounce<E> {
+    fn drop(&mut self) {
+        // Stop the background thread before the inner value goes away
+        let _ = self.shutdown.shutdown();
+
+        if let Some(thread) = self.thread.borrow_mut().take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for Debounce<E> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Debounce")
+            .field("inner", &self.inner)
+            .field("window", &self.window)
+            .field("mode", &self.mode)
+            .field("registered", &self.registered.borrow().is_some())
+            .finish()
+    }
+}
+
+fn spawn_debounce_thread(watcher: Poll, set_readiness: SetReadiness, window: Duration, mode: DebounceMode)
+    -> thread::JoinHandle<()>
+{
+    thread::spawn(move || {
+        let mut events = Events::with_capacity(16);
+        let mut pending = Ready::none();
+        let mut deadline: Option<Instant> = None;
+
+        loop {
+            let timeout = deadline.map(|deadline| {
+                let now = Instant::now();
+
+                if deadline > now {
+                    deadline - now
+                } else {
+                    Duration::from_millis(0)
+                }
+            });
+
+            // Fails for good once the `Debounce` is dropped
+            match watcher.poll(&mut events, timeout) {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return,
+            }
+
+            for event in events.iter() {
+                pending.insert(event.kind());
+
+                match mode {
+                    DebounceMode::Extend => deadline = Some(Instant::now() + window),
+                    DebounceMode::Fixed => {
+                        if deadline.is_none() {
+                            deadline = Some(Instant::now() + window);
+                        }
+                    }
+                }
+            }
+
+            if let Some(d) = deadline {
+                if Instant::now() >= d {
+                    trace!("debounce window elapsed; readiness={:?}", pending);
+                    let _ = poll::set_readiness_unlimited(&set_readiness, pending);
+
+                    pending = Ready::none();
+                    deadline = None;
+                }
+            }
+        }
+    })
+}
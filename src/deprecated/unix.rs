@@ -167,7 +167,7 @@ impl UnixListener {
     }
 
     pub fn accept(&self) -> io::Result<UnixStream> {
-        self.sys.accept().map(From::from)
+        self.sys.accept().map(|(sys, _)| From::from(sys))
     }
 
     pub fn try_clone(&self) -> io::Result<UnixListener> {
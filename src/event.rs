@@ -1,5 +1,5 @@
 use token::Token;
-use std::{fmt, ops};
+use std::{fmt, io, ops};
 
 /// Configures readiness polling behavior for a given `Evented` value.
 #[derive(Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
@@ -31,31 +31,90 @@ impl PollOpt {
         PollOpt(0x100)
     }
 
+    /// Wakes up a single `Poll` when a handle registered with several
+    /// instances becomes ready, rather than all of them.
+    ///
+    /// This avoids the thundering herd when multiple threads, each with its
+    /// own `Poll`, accept connections from the same listener. It maps to
+    /// `EPOLLEXCLUSIVE` (Linux 4.5 and later) and is ignored by the other
+    /// selectors and by custom `Registration` values.
+    ///
+    /// The kernel only accepts the flag when registering: `reregister` fails
+    /// for an exclusive registration, deregister and register it again
+    /// instead. It cannot be combined with `oneshot` or `urgent`, and the
+    /// peer shutting down its write half is not reported as hup.
+    #[inline]
+    pub fn exclusive() -> PollOpt {
+        PollOpt(0x200)
+    }
+
     #[inline]
     pub fn all() -> PollOpt {
         PollOpt::edge() | PollOpt::level() | PollOpt::oneshot()
     }
 
+    /// Returns a builder that checks the combination of options.
+    ///
+    /// The `|` operator accepts any combination, including contradictory
+    /// ones such as `edge() | level()`, which `Poll` treats as
+    /// edge-triggered. `PollOptBuilder::build` returns an error for those
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::PollOpt;
+    ///
+    /// let opts = PollOpt::builder().edge().oneshot().build().unwrap();
+    /// assert_eq!(PollOpt::edge() | PollOpt::oneshot(), opts);
+    ///
+    /// assert!(PollOpt::builder().edge().level().build().is_err());
+    /// ```
+    #[inline]
+    pub fn builder() -> PollOptBuilder {
+        PollOptBuilder { opts: PollOpt::empty() }
+    }
+
+    /// Returns true if the options request edge-triggered notifications.
+    ///
+    /// This is true as long as `PollOpt::edge()` is set, regardless of any
+    /// other option such as `oneshot`.
     #[inline]
     pub fn is_edge(&self) -> bool {
         self.contains(PollOpt::edge())
     }
 
+    /// Returns true if the options explicitly request level-triggered
+    /// notifications.
+    ///
+    /// Note that `PollOpt::empty()` is neither edge nor level. `Poll` treats
+    /// any registration that is not edge-triggered as level-triggered.
     #[inline]
     pub fn is_level(&self) -> bool {
         self.contains(PollOpt::level())
     }
 
+    /// Returns true if the registration is disarmed after delivering its
+    /// first event. It must be re-registered to receive further events.
     #[inline]
     pub fn is_oneshot(&self) -> bool {
         self.contains(PollOpt::oneshot())
     }
 
+    /// Returns true if the options request urgent (out-of-band) data
+    /// notifications.
     #[inline]
     pub fn is_urgent(&self) -> bool {
         self.contains(PollOpt::urgent())
     }
 
+    /// Returns true if only one of the `Poll` instances the handle is
+    /// registered with should be woken up, see `PollOpt::exclusive`.
+    #[inline]
+    pub fn is_exclusive(&self) -> bool {
+        self.contains(PollOpt::exclusive())
+    }
+
     #[inline]
     pub fn bits(&self) -> usize {
         self.0
@@ -77,6 +136,68 @@ impl PollOpt {
     }
 }
 
+/// Builds a `PollOpt`, rejecting contradictory options. See
+/// `PollOpt::builder`.
+#[derive(Copy, Clone, Debug)]
+pub struct PollOptBuilder {
+    opts: PollOpt,
+}
+
+impl PollOptBuilder {
+    /// Requests edge-triggered notifications.
+    #[inline]
+    pub fn edge(mut self) -> PollOptBuilder {
+        self.opts.insert(PollOpt::edge());
+        self
+    }
+
+    /// Requests level-triggered notifications.
+    #[inline]
+    pub fn level(mut self) -> PollOptBuilder {
+        self.opts.insert(PollOpt::level());
+        self
+    }
+
+    /// Disarms the registration after its first event.
+    #[inline]
+    pub fn oneshot(mut self) -> PollOptBuilder {
+        self.opts.insert(PollOpt::oneshot());
+        self
+    }
+
+    /// Requests urgent (out-of-band) data notifications.
+    #[inline]
+    pub fn urgent(mut self) -> PollOptBuilder {
+        self.opts.insert(PollOpt::urgent());
+        self
+    }
+
+    /// Wakes up a single `Poll` per event, see `PollOpt::exclusive`.
+    #[inline]
+    pub fn exclusive(mut self) -> PollOptBuilder {
+        self.opts.insert(PollOpt::exclusive());
+        self
+    }
+
+    /// Returns the options, or an error of kind `InvalidInput` if both
+    /// `edge` and `level` were requested, or `exclusive` along with
+    /// `oneshot` or `urgent`.
+    ///
+    /// Requesting neither `edge` nor `level` is valid and yields a level-triggered
+    /// registration, like `PollOpt::empty()`.
+    pub fn build(self) -> io::Result<PollOpt> {
+        if self.opts.is_edge() && self.opts.is_level() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "edge and level are mutually exclusive"));
+        }
+
+        if self.opts.is_exclusive() && (self.opts.is_oneshot() || self.opts.is_urgent()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "exclusive cannot be combined with oneshot or urgent"));
+        }
+
+        Ok(self.opts)
+    }
+}
+
 impl ops::BitOr for PollOpt {
     type Output = PollOpt;
 
@@ -143,6 +264,21 @@ impl fmt::Debug for PollOpt {
     }
 }
 
+/// Formats the options as the set flags separated by `|`, for example
+/// `edge|oneshot`. `PollOpt::empty()` is formatted as `(none)`.
+impl fmt::Display for PollOpt {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (PollOpt::edge(),    "edge"),
+            (PollOpt::level(),   "level"),
+            (PollOpt::oneshot(), "oneshot"),
+            (PollOpt::urgent(),  "urgent"),
+            (PollOpt::exclusive(), "exclusive")];
+
+        write_flags(fmt, &flags, |flag| self.contains(flag))
+    }
+}
+
 /// A set of readiness events returned by `Poll`.
 #[derive(Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
 pub struct Ready(usize);
@@ -178,6 +314,31 @@ impl Ready {
         Ready(0x10)
     }
 
+    /// Priority data is available: TCP urgent (out-of-band) data, or an
+    /// exceptional condition on files such as sysfs attributes or GPIO
+    /// values, which signal a change this way.
+    ///
+    /// It maps to `EPOLLPRI`, which epoll also reports as readable, and to
+    /// `EVFILT_EXCEPT` with `NOTE_OOB` on the kqueue platforms providing it
+    /// (macOS, iOS, DragonFly and OpenBSD). It is not reported on the other
+    /// platforms.
+    ///
+    /// Unlike the other readiness kinds, it is not part of `Ready::all()` and
+    /// must be requested explicitly.
+    #[inline]
+    pub fn priority() -> Ready {
+        Ready(0x20)
+    }
+
+    /// A deadline set with `Poll::set_deadline` expired before any event
+    /// arrived for the token.
+    ///
+    /// It is never part of an interest, and is not part of `Ready::all()`.
+    #[inline]
+    pub fn timeout() -> Ready {
+        Ready(0x40)
+    }
+
     #[inline]
     pub fn all() -> Ready {
         Ready::readable() |
@@ -188,7 +349,16 @@ impl Ready {
 
     #[inline]
     pub fn is_none(&self) -> bool {
-        (*self & !Ready::drop()) == Ready::none()
+        // `!` only keeps the kinds of `Ready::all()`, which would drop
+        // priority and timeout
+        self.0 & !Ready::drop().0 == 0
+    }
+
+    /// Returns true if no readiness is set. This is the same as `is_none`
+    /// and is true for `Ready::none()` and `Ready::default()`.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.is_none()
     }
 
     #[inline]
@@ -211,6 +381,20 @@ impl Ready {
         self.contains(Ready::hup())
     }
 
+    /// Returns true if the value includes priority readiness, see
+    /// `Ready::priority`.
+    #[inline]
+    pub fn is_priority(&self) -> bool {
+        self.contains(Ready::priority())
+    }
+
+    /// Returns true if the value includes timeout readiness, see
+    /// `Ready::timeout`.
+    #[inline]
+    pub fn is_timeout(&self) -> bool {
+        self.contains(Ready::timeout())
+    }
+
     #[inline]
     pub fn insert(&mut self, other: Ready) {
         self.0 |= other.0;
@@ -232,6 +416,12 @@ impl Ready {
     }
 }
 
+impl Default for Ready {
+    fn default() -> Ready {
+        Ready::none()
+    }
+}
+
 impl ops::BitOr for Ready {
     type Output = Ready;
 
@@ -285,6 +475,8 @@ impl fmt::Debug for Ready {
             (Ready::writable(), "Writable"),
             (Ready::error(),    "Error"),
             (Ready::hup(),      "Hup"),
+            (Ready::priority(), "Priority"),
+            (Ready::timeout(),  "Timeout"),
             (Ready::drop(),     "Drop")];
 
         try!(write!(fmt, "Ready {{"));
@@ -304,6 +496,131 @@ impl fmt::Debug for Ready {
     }
 }
 
+/// Formats the readiness as the set flags separated by `|`, for example
+/// `readable|writable`. `Ready::none()` is formatted as `(none)`.
+impl fmt::Display for Ready {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (Ready::readable(), "readable"),
+            (Ready::writable(), "writable"),
+            (Ready::error(),    "error"),
+            (Ready::hup(),      "hup"),
+            (Ready::priority(), "priority"),
+            (Ready::timeout(),  "timeout")];
+
+        write_flags(fmt, &flags, |flag| self.contains(flag))
+    }
+}
+
+// Writes the names of the flags for which `is_set` returns true, separated by
+// `|`, or `(none)`.
+fn write_flags<T: Copy, F: Fn(T) -> bool>(fmt: &mut fmt::Formatter, flags: &[(T, &str)], is_set: F) -> fmt::Result {
+    let mut one = false;
+
+    for &(flag, msg) in flags {
+        if is_set(flag) {
+            if one { try!(write!(fmt, "|")) }
+            try!(write!(fmt, "{}", msg));
+
+            one = true
+        }
+    }
+
+    if !one {
+        try!(write!(fmt, "(none)"));
+    }
+
+    Ok(())
+}
+
+/// The readiness a handle is registered for with `Poll`.
+///
+/// `Interest` can only express the combinations that are meaningful when
+/// registering: readable, writable and priority. It is never empty. `Ready`, on
+/// the other hand, is what `Poll` reports in events and also contains `hup`
+/// and `error`, which are delivered regardless of the interest.
+///
+/// `Poll::register` and `Poll::reregister` accept both an `Interest` and a
+/// `Ready`, an `Interest` converts to the equivalent `Ready`.
+///
+/// ```
+/// use mio::{Interest, Ready};
+///
+/// let interest = Interest::readable() | Interest::writable();
+/// assert!(interest.is_readable());
+/// assert_eq!(Ready::readable() | Ready::writable(), Ready::from(interest));
+/// ```
+#[derive(Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct Interest(Ready);
+
+impl Interest {
+    /// Interest in readable readiness.
+    #[inline]
+    pub fn readable() -> Interest {
+        Interest(Ready::readable())
+    }
+
+    /// Interest in writable readiness.
+    #[inline]
+    pub fn writable() -> Interest {
+        Interest(Ready::writable())
+    }
+
+    /// Interest in priority readiness, see `Ready::priority`.
+    #[inline]
+    pub fn priority() -> Interest {
+        Interest(Ready::priority())
+    }
+
+    #[inline]
+    pub fn is_readable(&self) -> bool {
+        self.0.is_readable()
+    }
+
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.0.is_writable()
+    }
+
+    #[inline]
+    pub fn is_priority(&self) -> bool {
+        self.0.is_priority()
+    }
+
+    #[inline]
+    pub fn contains(&self, other: Interest) -> bool {
+        self.0.contains(other.0)
+    }
+}
+
+impl ops::BitOr for Interest {
+    type Output = Interest;
+
+    #[inline]
+    fn bitor(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+}
+
+impl From<Interest> for Ready {
+    fn from(interest: Interest) -> Ready {
+        interest.0
+    }
+}
+
+impl fmt::Debug for Interest {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (Interest::readable(), "Readable"),
+            (Interest::writable(), "Writable"),
+            (Interest::priority(), "Priority")];
+
+        try!(write!(fmt, "Interest {{"));
+        try!(write_flags(fmt, &flags, |flag| self.contains(flag)));
+        write!(fmt, "}}")
+    }
+}
+
 /// An readiness event returned by `Poll`.
 ///
 /// Event represents the raw event that the OS-specific selector
@@ -334,6 +651,45 @@ impl Event {
     pub fn token(&self) -> Token {
         self.token
     }
+
+    /// Returns true if the event contains readable readiness. Shorthand for
+    /// `event.kind().is_readable()`.
+    #[inline]
+    pub fn is_readable(&self) -> bool {
+        self.kind.is_readable()
+    }
+
+    /// Returns true if the event contains writable readiness.
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.kind.is_writable()
+    }
+
+    /// Returns true if the event contains error readiness.
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self.kind.is_error()
+    }
+
+    /// Returns true if the event contains hup readiness.
+    #[inline]
+    pub fn is_hup(&self) -> bool {
+        self.kind.is_hup()
+    }
+
+    /// Returns true if the event contains priority readiness, see
+    /// `Ready::priority`.
+    #[inline]
+    pub fn is_priority(&self) -> bool {
+        self.kind.is_priority()
+    }
+
+    /// Returns true if the event is a deadline expiring, see
+    /// `Poll::set_deadline`.
+    #[inline]
+    pub fn is_timeout(&self) -> bool {
+        self.kind.is_timeout()
+    }
 }
 
 /*
@@ -370,3 +726,14 @@ pub fn drop() -> Ready {
 pub fn kind_mut(event: &mut Event) -> &mut Ready {
     &mut event.kind
 }
+
+/// Every public readiness kind, in the order events are split by `Poll` when
+/// readiness is not coalesced.
+pub const KINDS: [Ready; 6] = [
+    Ready(0x001), // readable
+    Ready(0x002), // writable
+    Ready(0x004), // error
+    Ready(0x008), // hup
+    Ready(0x020), // priority
+    Ready(0x040), // timeout
+];
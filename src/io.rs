@@ -7,11 +7,48 @@ pub use std::io::{Read, Write, Result, Error, ErrorKind};
 pub trait Evented {
     fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()>;
 
+    /// Changes the token, interest and options of a value already
+    /// registered with `poll`.
+    ///
+    /// The previous interest is not passed in. Values backed by a userspace
+    /// `Registration` can query it with `Registration::interest` before
+    /// updating. For values backed by an OS handle, mio does not track the
+    /// interest at all (it lives in the kernel's epoll / kqueue / IOCP
+    /// state), so an implementation that needs it must remember it itself.
     fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> Result<()>;
 
     fn deregister(&self, poll: &Poll) -> Result<()>;
 }
 
+/// A socket whose pending error can be retrieved.
+///
+/// When the selector reports `Ready::error()` for a socket, the error itself
+/// is stored in the socket (`SO_ERROR`) and can be fetched with `take_error`,
+/// whatever the socket type. This is implemented by all the socket types in
+/// `mio::net` and, on Unix, by `EventedFd` for sockets owned by the caller.
+///
+/// # Examples
+///
+/// ```
+/// use mio::{Event, TakeError};
+/// use std::io;
+///
+/// fn handle_error<S: TakeError>(socket: &S, event: &Event) -> io::Result<()> {
+///     if event.kind().is_error() {
+///         if let Some(err) = try!(socket.take_error()) {
+///             return Err(err);
+///         }
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub trait TakeError {
+    /// Returns the pending error of the socket, clearing it in the process.
+    /// Returns `None` if there is none.
+    fn take_error(&self) -> Result<Option<Error>>;
+}
+
 // TODO: Delete this
 /// A helper trait to provide the map_non_block function on Results.
 pub trait MapNonBlock<T> {
@@ -96,6 +96,7 @@ extern crate log;
 #[cfg(test)]
 extern crate env_logger;
 
+mod deadline;
 mod event;
 mod io;
 mod iovec;
@@ -105,18 +106,23 @@ mod sys;
 mod token;
 
 pub mod channel;
+pub mod debounce;
+pub mod process;
 pub mod timer;
 
 /// EventLoop and other deprecated types
 pub mod deprecated;
 
 pub use event::{
+    Interest,
     PollOpt,
+    PollOptBuilder,
     Ready,
     Event,
 };
 pub use io::{
     Evented,
+    TakeError,
     would_block,
 };
 pub use iovec::IoVec;
@@ -124,15 +130,35 @@ pub use net::{
     tcp,
     udp,
 };
+#[cfg(unix)]
+pub use net::uds;
 pub use poll::{
     Poll,
+    PollBuilder,
+    AwakenerKind,
     Events,
+    EventsDrain,
+    EventsIntoIter,
     EventsIter,
+    EventsIterMut,
+    EventMut,
+    CancelToken,
+    CondvarWaiter,
+    ContentionStats,
+    PollCondvar,
+    PollReason,
+    ReadinessGroup,
     Registration,
     SetReadiness,
+    ShutdownHandle,
+    Timeout,
+    WakeHandle,
+    Waker,
+    select,
 };
 pub use token::{
     Token,
+    TokenAllocator,
 };
 
 #[cfg(unix)]
@@ -142,6 +168,11 @@ pub mod unix {
     pub use sys::{
         EventedFd,
     };
+
+    #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+        target_os = "freebsd", target_os = "ios", target_os = "macos",
+        target_os = "netbsd", target_os = "openbsd"))]
+    pub use sys::KqueueTimer;
 }
 
 /// Windows-only extensions to the mio crate.
@@ -194,10 +225,17 @@ pub mod unix {
 /// the I/O operations are completed a custom function pointer is called which
 /// typically modifies a `SetReadiness` set by `Evented` methods which will get
 /// later hooked into the mio event loop.
+///
+/// This module also provides `NamedPipe`, the Windows counterpart of Unix
+/// domain sockets, implemented with these two types.
+///
+/// Handles that are not used for overlapped I/O, but are waitable kernel
+/// objects such as events or processes, are registered with `EventedHandle`
+/// instead. It becomes readable once the object is signaled.
 #[cfg(windows)]
 pub mod windows {
 
-    pub use sys::{Overlapped, Binding};
+    pub use sys::{Overlapped, Binding, EventedHandle, NamedPipe};
 }
 
 // Conversion utilities
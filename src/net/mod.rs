@@ -3,10 +3,17 @@
 pub mod tcp;
 pub mod udp;
 
+#[cfg(unix)]
+pub mod uds;
+
 use {poll, Poll};
 use std::io;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use net2::{TcpBuilder, UdpBuilder};
+#[cfg(unix)]
+use net2::unix::{UnixTcpBuilderExt, UnixUdpBuilderExt};
+
 /// Used to associate an IO type with a Selector
 #[derive(Debug)]
 struct SelectorId {
@@ -39,3 +46,112 @@ impl Clone for SelectorId {
         }
     }
 }
+
+/// Socket options applied before binding, used by
+/// `TcpListener::bind_with_options` and `UdpSocket::bind_with_options`.
+///
+/// The options left unset keep the defaults of `bind`.
+///
+/// # Examples
+///
+/// Each event loop thread binds its own listener to the same port, the
+/// kernel balances the incoming connections between them.
+///
+/// ```
+/// # #[cfg(unix)]
+/// # fn main() {
+/// use mio::tcp::{BindOptions, TcpListener};
+///
+/// let addr = "127.0.0.1:0".parse().unwrap();
+/// let options = BindOptions::new().reuse_port(true);
+///
+/// let first = TcpListener::bind_with_options(&addr, &options).unwrap();
+/// let addr = first.local_addr().unwrap();
+/// let second = TcpListener::bind_with_options(&addr, &options).unwrap();
+/// # drop(second);
+/// # }
+/// # #[cfg(not(unix))]
+/// # fn main() {}
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BindOptions {
+    reuse_address: Option<bool>,
+    reuse_port: bool,
+}
+
+impl BindOptions {
+    /// Returns options with the defaults of `bind`.
+    pub fn new() -> BindOptions {
+        BindOptions::default()
+    }
+
+    /// Sets the `SO_REUSEADDR` option.
+    ///
+    /// By default, it is set for TCP listeners on Unix only, like the
+    /// standard library does, and left unset for UDP sockets.
+    pub fn reuse_address(mut self, reuse: bool) -> BindOptions {
+        self.reuse_address = Some(reuse);
+        self
+    }
+
+    /// Sets the `SO_REUSEPORT` option, so that several sockets can be bound
+    /// to the same address and port. Not set by default.
+    ///
+    /// All the sockets sharing the port must set it. Binding with it set
+    /// fails on platforms without `SO_REUSEPORT`, such as Windows.
+    pub fn reuse_port(mut self, reuse: bool) -> BindOptions {
+        self.reuse_port = reuse;
+        self
+    }
+
+    fn apply_tcp(&self, sock: &TcpBuilder) -> io::Result<()> {
+        // Set SO_REUSEADDR by default, but only on Unix (mirrors what libstd
+        // does)
+        if let Some(reuse) = self.reuse_address.or(if cfg!(unix) { Some(true) } else { None }) {
+            try!(sock.reuse_address(reuse));
+        }
+
+        if self.reuse_port {
+            try!(reuse_port_tcp(sock));
+        }
+
+        Ok(())
+    }
+
+    fn apply_udp(&self, sock: &UdpBuilder) -> io::Result<()> {
+        if let Some(reuse) = self.reuse_address {
+            try!(sock.reuse_address(reuse));
+        }
+
+        if self.reuse_port {
+            try!(reuse_port_udp(sock));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn reuse_port_tcp(sock: &TcpBuilder) -> io::Result<()> {
+    sock.reuse_port(true).map(|_| ())
+}
+
+#[cfg(unix)]
+fn reuse_port_udp(sock: &UdpBuilder) -> io::Result<()> {
+    sock.reuse_port(true).map(|_| ())
+}
+
+#[cfg(windows)]
+fn reuse_port_tcp(_: &TcpBuilder) -> io::Result<()> {
+    Err(reuse_port_unsupported())
+}
+
+#[cfg(windows)]
+fn reuse_port_udp(_: &UdpBuilder) -> io::Result<()> {
+    Err(reuse_port_unsupported())
+}
+
+#[cfg(windows)]
+fn reuse_port_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "SO_REUSEPORT is not supported on this platform")
+}
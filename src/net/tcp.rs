@@ -1,11 +1,13 @@
 //! Primitives for working with TCP
 
+use std::cell::Cell;
 use std::io::{Read, Write};
 use std::net::{self, SocketAddr, SocketAddrV4, SocketAddrV6, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
 
-use net2::TcpBuilder;
+use net2;
 
-use {io, sys, Evented, Ready, Poll, PollOpt, Token, IoVec};
+use {io, sys, Evented, Ready, Poll, PollOpt, TakeError, Token, IoVec};
 use super::SelectorId;
 
 /*
@@ -21,6 +23,7 @@ pub struct TcpStream {
 }
 
 pub use std::net::Shutdown;
+pub use super::BindOptions;
 
 impl TcpStream {
     /// Create a new TCP stream and issue a non-blocking connect to the
@@ -29,13 +32,11 @@ impl TcpStream {
     /// This convenience method is available and uses the system's default
     /// options when creating a socket which is then connected. If fine-grained
     /// control over the creation of the socket is desired, you can use
-    /// `net2::TcpBuilder` to configure a socket and then pass its socket to
-    /// `TcpStream::connect_stream` to transfer ownership into mio and schedule
-    /// the connect operation.
+    /// `TcpBuilder` to configure a socket before connecting it.
     pub fn connect(addr: &SocketAddr) -> io::Result<TcpStream> {
         let sock = try!(match *addr {
-            SocketAddr::V4(..) => TcpBuilder::new_v4(),
-            SocketAddr::V6(..) => TcpBuilder::new_v6(),
+            SocketAddr::V4(..) => sys::tcp_builder(false),
+            SocketAddr::V6(..) => sys::tcp_builder(true),
         });
         // Required on Windows for a future `connect_overlapped` operation to be
         // executed successfully.
@@ -89,6 +90,27 @@ impl TcpStream {
         })
     }
 
+    /// Creates a new `TcpStream` from a connected `std::net::TcpStream`, such
+    /// as one obtained from another library. The stream is set to
+    /// non-blocking mode.
+    ///
+    /// This is the same as `from_stream`.
+    pub fn from_std(stream: net::TcpStream) -> io::Result<TcpStream> {
+        TcpStream::from_stream(stream)
+    }
+
+    /// Converts this stream back into a `std::net::TcpStream`.
+    ///
+    /// The returned stream is still in non-blocking mode. The stream should
+    /// be deregistered before it is converted.
+    ///
+    /// On Windows, the socket stays associated with the completion port of
+    /// the `Poll` it was registered with, and a duplicate handle to the
+    /// socket is returned.
+    pub fn into_std(self) -> io::Result<net::TcpStream> {
+        self.sys.into_std()
+    }
+
     /// Returns the socket address of the remote peer of this TCP connection.
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
         self.sys.peer_addr()
@@ -169,6 +191,24 @@ impl TcpStream {
         self.sys.keepalive_ms()
     }
 
+    /// Sets whether keepalive messages are enabled to be sent on this socket.
+    ///
+    /// This is the same as `set_keepalive_ms`, with the idle time given as a
+    /// `Duration`.
+    pub fn set_keepalive(&self, keepalive: Option<Duration>) -> io::Result<()> {
+        self.sys.set_keepalive(keepalive)
+    }
+
+    /// Returns whether keepalive messages are enabled on this socket, and if so
+    /// the idle time before a probe is sent.
+    ///
+    /// For more information about this option, see [`set_keepalive`][link].
+    ///
+    /// [link]: #method.set_keepalive
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        self.sys.keepalive()
+    }
+
     /// Sets the value for the `IP_TTL` option on this socket.
     ///
     /// This value sets the time-to-live field that is used in every packet sent
@@ -186,6 +226,29 @@ impl TcpStream {
         self.sys.ttl()
     }
 
+    /// Sets the value of the `SO_LINGER` option on this socket.
+    ///
+    /// This value controls how the socket is closed when data remains to be
+    /// sent. If `Some` is given, closing the socket blocks for up to that
+    /// duration while the data is sent. With `Some(Duration::from_secs(0))`,
+    /// the connection is reset on close and the unsent data is discarded.
+    /// `None` restores the default of closing in the background.
+    ///
+    /// Since closing a socket with a linger duration may block, it is best
+    /// used for resets.
+    pub fn set_linger(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.sys.set_linger(dur)
+    }
+
+    /// Gets the value of the `SO_LINGER` option on this socket.
+    ///
+    /// For more information about this option, see [`set_linger`][link].
+    ///
+    /// [link]: #method.set_linger
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        self.sys.linger()
+    }
+
     /// Get the value of the `SO_ERROR` option on this socket.
     ///
     /// This will retrieve the stored error in the underlying socket, clearing
@@ -296,6 +359,12 @@ impl Evented for TcpStream {
     }
 }
 
+impl TakeError for TcpStream {
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        TcpStream::take_error(self)
+    }
+}
+
 /*
  *
  * ===== TcpListener =====
@@ -320,20 +389,25 @@ impl TcpListener {
     /// 4. Call `listen` on the socket to prepare it to receive new connections.
     ///
     /// If fine-grained control over the binding and listening process for a
-    /// socket is desired then the `net2::TcpBuilder` methods can be used in
-    /// combination with the `TcpListener::from_listener` method to transfer
-    /// ownership into mio.
+    /// socket is desired then `TcpBuilder` can be used to configure the
+    /// socket before it listens.
     pub fn bind(addr: &SocketAddr) -> io::Result<TcpListener> {
+        TcpListener::bind_with_options(addr, &BindOptions::new())
+    }
+
+    /// Like `bind`, with the socket options set before binding taken from
+    /// `options`.
+    ///
+    /// This allows, for example, several listeners to share a port with
+    /// `SO_REUSEPORT`, see `BindOptions`.
+    pub fn bind_with_options(addr: &SocketAddr, options: &BindOptions) -> io::Result<TcpListener> {
         // Create the socket
         let sock = try!(match *addr {
-            SocketAddr::V4(..) => TcpBuilder::new_v4(),
-            SocketAddr::V6(..) => TcpBuilder::new_v6(),
+            SocketAddr::V4(..) => sys::tcp_builder(false),
+            SocketAddr::V6(..) => sys::tcp_builder(true),
         });
 
-        // Set SO_REUSEADDR, but only on Unix (mirrors what libstd does)
-        if cfg!(unix) {
-            try!(sock.reuse_address(true));
-        }
+        try!(options.apply_tcp(&sock));
 
         // Bind the socket
         try!(sock.bind(addr));
@@ -365,6 +439,28 @@ impl TcpListener {
         })
     }
 
+    /// Creates a new `TcpListener` from a listening `std::net::TcpListener`,
+    /// such as one inherited through socket activation. The listener is set
+    /// to non-blocking mode.
+    ///
+    /// Unlike `from_listener`, the address is read from the listener.
+    pub fn from_std(listener: net::TcpListener) -> io::Result<TcpListener> {
+        let addr = try!(listener.local_addr());
+        TcpListener::from_listener(listener, &addr)
+    }
+
+    /// Converts this listener back into a `std::net::TcpListener`.
+    ///
+    /// The returned listener is still in non-blocking mode. The listener
+    /// should be deregistered before it is converted.
+    ///
+    /// On Windows, the socket stays associated with the completion port of
+    /// the `Poll` it was registered with, and a duplicate handle to the
+    /// socket is returned.
+    pub fn into_std(self) -> io::Result<net::TcpListener> {
+        self.sys.into_std()
+    }
+
     /// Accepts a new `TcpStream`.
     ///
     /// Returns a `Ok(None)` when the socket `WOULDBLOCK`, this means the stream
@@ -465,6 +561,147 @@ impl Evented for TcpListener {
     }
 }
 
+impl TakeError for TcpListener {
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        TcpListener::take_error(self)
+    }
+}
+
+/*
+ *
+ * ===== TcpBuilder =====
+ *
+ */
+
+/// A TCP socket that is configured before it is bound, connected or
+/// listening.
+///
+/// Some socket options only take effect when set before `bind`, `connect` or
+/// `listen`. The builder creates the socket, lets these options be set, and
+/// then turns into a `TcpStream` with `connect` or a `TcpListener` with
+/// `listen`.
+///
+/// # Examples
+///
+/// ```
+/// use mio::tcp::TcpBuilder;
+///
+/// let addr = "127.0.0.1:0".parse().unwrap();
+///
+/// let builder = TcpBuilder::new_v4().unwrap();
+/// builder.reuse_address(true).unwrap();
+/// builder.bind(&addr).unwrap();
+///
+/// let listener = builder.listen(1024).unwrap();
+/// # drop(listener);
+/// ```
+#[derive(Debug)]
+pub struct TcpBuilder {
+    sock: net2::TcpBuilder,
+    bound: Cell<bool>,
+}
+
+impl TcpBuilder {
+    /// Creates a new IPv4 TCP socket.
+    pub fn new_v4() -> io::Result<TcpBuilder> {
+        sys::tcp_builder(false).map(TcpBuilder::new)
+    }
+
+    /// Creates a new IPv6 TCP socket.
+    pub fn new_v6() -> io::Result<TcpBuilder> {
+        sys::tcp_builder(true).map(TcpBuilder::new)
+    }
+
+    fn new(sock: net2::TcpBuilder) -> TcpBuilder {
+        TcpBuilder {
+            sock: sock,
+            bound: Cell::new(false),
+        }
+    }
+
+    /// Sets the `SO_REUSEADDR` option.
+    pub fn reuse_address(&self, reuse: bool) -> io::Result<&TcpBuilder> {
+        self.sock.reuse_address(reuse).map(|_| self)
+    }
+
+    /// Sets the `SO_REUSEPORT` option, so that several sockets can be bound
+    /// to the same address and port.
+    #[cfg(unix)]
+    pub fn reuse_port(&self, reuse: bool) -> io::Result<&TcpBuilder> {
+        use net2::unix::UnixTcpBuilderExt;
+
+        self.sock.reuse_port(reuse).map(|_| self)
+    }
+
+    /// Sets the `IPV6_V6ONLY` option, restricting an IPv6 socket to IPv6
+    /// traffic.
+    pub fn only_v6(&self, only_v6: bool) -> io::Result<&TcpBuilder> {
+        self.sock.only_v6(only_v6).map(|_| self)
+    }
+
+    /// Sets the `IP_TTL` option.
+    pub fn ttl(&self, ttl: u32) -> io::Result<&TcpBuilder> {
+        self.sock.ttl(ttl).map(|_| self)
+    }
+
+    /// Sets or clears the close-on-exec flag. Sockets are created with the
+    /// flag set, so that they are not inherited by child processes.
+    ///
+    /// Streams accepted by a `TcpListener` always have the flag set.
+    #[cfg(unix)]
+    pub fn cloexec(&self, cloexec: bool) -> io::Result<&TcpBuilder> {
+        sys::set_close_on_exec(self.sock.as_raw_fd(), cloexec).map(|_| self)
+    }
+
+    /// Sets the `IP_TOS` option, the type of service field of the IPv4
+    /// packets sent from the socket.
+    #[cfg(unix)]
+    pub fn tos(&self, tos: u32) -> io::Result<&TcpBuilder> {
+        sys::set_tos(self.sock.as_raw_fd(), tos).map(|_| self)
+    }
+
+    /// Sets the `SO_BINDTODEVICE` option, so that the socket only uses the
+    /// network interface named `interface`. This usually requires the
+    /// `CAP_NET_RAW` capability.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_device(&self, interface: &str) -> io::Result<&TcpBuilder> {
+        sys::set_bind_device(self.sock.as_raw_fd(), interface).map(|_| self)
+    }
+
+    /// Binds the socket to `addr`.
+    pub fn bind(&self, addr: &SocketAddr) -> io::Result<&TcpBuilder> {
+        try!(self.sock.bind(addr));
+        self.bound.set(true);
+        Ok(self)
+    }
+
+    /// Returns the local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.sock.local_addr()
+    }
+
+    /// Listens for connections on the bound socket, and converts it into a
+    /// `TcpListener`.
+    pub fn listen(self, backlog: i32) -> io::Result<TcpListener> {
+        let listener = try!(self.sock.listen(backlog));
+        let addr = try!(listener.local_addr());
+        TcpListener::from_listener(listener, &addr)
+    }
+
+    /// Issues a non-blocking connect to `addr`, and converts the socket into
+    /// a `TcpStream`, see `TcpStream::connect_stream`.
+    ///
+    /// On Windows, a socket that is not bound yet is bound to the unspecified
+    /// address first.
+    pub fn connect(self, addr: &SocketAddr) -> io::Result<TcpStream> {
+        if cfg!(windows) && !self.bound.get() {
+            try!(self.sock.bind(&inaddr_any(addr)));
+        }
+
+        TcpStream::connect_stream(try!(self.sock.to_tcp_stream()), addr)
+    }
+}
+
 /*
  *
  * ===== UNIX ext =====
@@ -1,9 +1,13 @@
 //! Primitives for working with UDP
 
-use {io, sys, Evented, Ready, Poll, PollOpt, Token};
+use {io, sys, Evented, Ready, Poll, PollOpt, TakeError, Token};
 use super::SelectorId;
 use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr};
 
+use net2;
+
+pub use super::BindOptions;
+
 #[derive(Debug)]
 pub struct UdpSocket {
     sys: sys::UdpSocket,
@@ -13,7 +17,23 @@ pub struct UdpSocket {
 impl UdpSocket {
     /// Creates a UDP socket from the given address.
     pub fn bind(addr: &SocketAddr) -> io::Result<UdpSocket> {
-        let socket = try!(net::UdpSocket::bind(addr));
+        UdpSocket::bind_with_options(addr, &BindOptions::new())
+    }
+
+    /// Like `bind`, with the socket options set before binding taken from
+    /// `options`.
+    ///
+    /// This allows, for example, several sockets to receive the datagrams
+    /// sent to a port with `SO_REUSEPORT`, see `BindOptions`.
+    pub fn bind_with_options(addr: &SocketAddr, options: &BindOptions) -> io::Result<UdpSocket> {
+        let sock = try!(match *addr {
+            SocketAddr::V4(..) => sys::udp_builder(false),
+            SocketAddr::V6(..) => sys::udp_builder(true),
+        });
+
+        try!(options.apply_udp(&sock));
+
+        let socket = try!(sock.bind(addr));
         UdpSocket::from_socket(socket)
     }
 
@@ -34,6 +54,30 @@ impl UdpSocket {
         })
     }
 
+    /// Creates a new `UdpSocket` from a bound `std::net::UdpSocket`, such as
+    /// one configured by another library. The socket is set to non-blocking
+    /// mode.
+    ///
+    /// This is the same as `from_socket`. On Windows, the socket is
+    /// associated with the completion port of the `Poll` it is first
+    /// registered with.
+    pub fn from_std(socket: net::UdpSocket) -> io::Result<UdpSocket> {
+        UdpSocket::from_socket(socket)
+    }
+
+    /// Converts this socket back into a `std::net::UdpSocket`.
+    ///
+    /// The returned socket is still in non-blocking mode. The socket should
+    /// be deregistered before it is converted.
+    ///
+    /// On Windows, the socket stays associated with the completion port of
+    /// the `Poll` it was registered with, and a duplicate handle to the
+    /// socket is returned. A datagram that was already received by mio but
+    /// not yet read with `recv_from` is lost.
+    pub fn into_std(self) -> io::Result<net::UdpSocket> {
+        self.sys.into_std()
+    }
+
     /// Returns the socket address that this socket was created from.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.sys.local_addr()
@@ -54,6 +98,32 @@ impl UdpSocket {
             })
     }
 
+    /// Connects the socket to `addr`. Datagrams sent with `send` go to this
+    /// address, and only datagrams from this address are received.
+    ///
+    /// Connecting also lets the socket report the ICMP errors caused by the
+    /// datagrams it sent, such as a port unreachable, through `take_error` or
+    /// as an error of the next `send` or `recv`.
+    pub fn connect(&self, addr: &SocketAddr) -> io::Result<()> {
+        self.sys.connect(addr)
+    }
+
+    /// Sends data on the socket to the address it is connected to. On
+    /// success, returns the number of bytes written.
+    ///
+    /// Returns `Ok(None)` if the datagram cannot be sent without blocking.
+    pub fn send(&self, buf: &[u8]) -> io::Result<Option<usize>> {
+        self.sys.send(buf)
+    }
+
+    /// Receives data from the address the socket is connected to. On
+    /// success, returns the number of bytes read.
+    ///
+    /// Returns `Ok(None)` if no datagram is available.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        self.sys.recv(buf)
+    }
+
     /// Sends data on the socket to the given address. On success, returns the
     /// number of bytes written.
     ///
@@ -71,6 +141,97 @@ impl UdpSocket {
         self.sys.recv_from(buf)
     }
 
+    /// Sends a batch of datagrams, each to its own address. On success,
+    /// returns the number of datagrams sent, which may be less than
+    /// `msgs.len()` if the socket buffer fills up.
+    ///
+    /// On Linux, the whole batch is sent with a single `sendmmsg` call. On
+    /// other platforms, the datagrams are sent one at a time with `send_to`.
+    ///
+    /// Returns `Ok(None)` if no datagram could be sent without blocking.
+    pub fn send_mmsg(&self, msgs: &[(&[u8], SocketAddr)])
+                     -> io::Result<Option<usize>> {
+        if msgs.is_empty() {
+            return Ok(Some(0));
+        }
+
+        self.send_mmsg_sys(msgs)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn send_mmsg_sys(&self, msgs: &[(&[u8], SocketAddr)])
+                     -> io::Result<Option<usize>> {
+        self.sys.send_mmsg(msgs)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn send_mmsg_sys(&self, msgs: &[(&[u8], SocketAddr)])
+                     -> io::Result<Option<usize>> {
+        let mut sent = 0;
+
+        for &(buf, ref target) in msgs {
+            match self.send_to(buf, target) {
+                Ok(Some(_)) => sent += 1,
+                Ok(None) => break,
+                // Like `sendmmsg`, an error is only reported when nothing
+                // was sent
+                Err(e) => {
+                    if sent == 0 {
+                        return Err(e);
+                    }
+                    break;
+                }
+            }
+        }
+
+        Ok(if sent == 0 { None } else { Some(sent) })
+    }
+
+    /// Receives a batch of datagrams, one into each of `bufs`. On success,
+    /// returns the length and source address of each datagram received, in
+    /// the order of `bufs`. Fewer datagrams than buffers may be received.
+    ///
+    /// On Linux, the whole batch is received with a single `recvmmsg` call.
+    /// On other platforms, the datagrams are received one at a time with
+    /// `recv_from`.
+    ///
+    /// Returns `Ok(None)` if no datagram is available.
+    pub fn recv_mmsg(&self, bufs: &mut [&mut [u8]])
+                     -> io::Result<Option<Vec<(usize, SocketAddr)>>> {
+        if bufs.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
+
+        self.recv_mmsg_sys(bufs)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn recv_mmsg_sys(&self, bufs: &mut [&mut [u8]])
+                     -> io::Result<Option<Vec<(usize, SocketAddr)>>> {
+        self.sys.recv_mmsg(bufs)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn recv_mmsg_sys(&self, bufs: &mut [&mut [u8]])
+                     -> io::Result<Option<Vec<(usize, SocketAddr)>>> {
+        let mut received = Vec::new();
+
+        for buf in bufs.iter_mut() {
+            match self.recv_from(buf) {
+                Ok(Some(datagram)) => received.push(datagram),
+                Ok(None) => break,
+                Err(e) => {
+                    if received.is_empty() {
+                        return Err(e);
+                    }
+                    break;
+                }
+            }
+        }
+
+        Ok(if received.is_empty() { None } else { Some(received) })
+    }
+
     /// Gets the value of the `SO_BROADCAST` option for this socket.
     ///
     /// For more information about this option, see
@@ -128,6 +289,41 @@ impl UdpSocket {
         self.sys.set_multicast_ttl_v4(ttl)
     }
 
+    /// Sets the value of the `IP_MULTICAST_IF` option for this socket.
+    ///
+    /// Selects the local interface, by address, that outgoing multicast
+    /// packets are sent from. If it's equal to `INADDR_ANY` then the system
+    /// chooses the interface.
+    pub fn set_multicast_if_v4(&self, interface: &Ipv4Addr) -> io::Result<()> {
+        self.sys.set_multicast_if_v4(interface)
+    }
+
+    /// Sets the value of the `IPV6_MULTICAST_IF` option for this socket.
+    ///
+    /// Selects the local interface, by index, that outgoing multicast packets
+    /// are sent from. An index of 0 lets the system choose the interface.
+    pub fn set_multicast_if_v6(&self, interface: u32) -> io::Result<()> {
+        self.sys.set_multicast_if_v6(interface)
+    }
+
+    /// Gets the value of the `IPV6_MULTICAST_HOPS` option for this socket.
+    ///
+    /// For more information about this option, see
+    /// [`set_multicast_hops_v6`][link].
+    ///
+    /// [link]: #method.set_multicast_hops_v6
+    pub fn multicast_hops_v6(&self) -> io::Result<u32> {
+        self.sys.multicast_hops_v6()
+    }
+
+    /// Sets the value of the `IPV6_MULTICAST_HOPS` option for this socket.
+    ///
+    /// The IPv6 counterpart of `set_multicast_ttl_v4`: the hop limit of
+    /// outgoing multicast packets. The default value is 1.
+    pub fn set_multicast_hops_v6(&self, hops: u32) -> io::Result<()> {
+        self.sys.set_multicast_hops_v6(hops)
+    }
+
     /// Gets the value of the `IPV6_MULTICAST_LOOP` option for this socket.
     ///
     /// For more information about this option, see
@@ -216,6 +412,9 @@ impl UdpSocket {
     /// This will retrieve the stored error in the underlying socket, clearing
     /// the field in the process. This can be useful for checking errors between
     /// calls.
+    ///
+    /// On a connected socket, this reports the ICMP errors caused by the
+    /// datagrams previously sent.
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.sys.take_error()
     }
@@ -236,6 +435,106 @@ impl Evented for UdpSocket {
     }
 }
 
+impl TakeError for UdpSocket {
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        UdpSocket::take_error(self)
+    }
+}
+
+/*
+ *
+ * ===== UdpBuilder =====
+ *
+ */
+
+/// A UDP socket that is configured before it is bound.
+///
+/// Some socket options only take effect when set before `bind`. The builder
+/// creates the socket, lets these options be set, and then turns into a
+/// `UdpSocket` with `bind`.
+///
+/// # Examples
+///
+/// ```
+/// use mio::udp::UdpBuilder;
+///
+/// let addr = "127.0.0.1:0".parse().unwrap();
+///
+/// let builder = UdpBuilder::new_v4().unwrap();
+/// builder.reuse_address(true).unwrap();
+///
+/// let socket = builder.bind(&addr).unwrap();
+/// # drop(socket);
+/// ```
+#[derive(Debug)]
+pub struct UdpBuilder {
+    sock: net2::UdpBuilder,
+}
+
+impl UdpBuilder {
+    /// Creates a new IPv4 UDP socket.
+    pub fn new_v4() -> io::Result<UdpBuilder> {
+        sys::udp_builder(false).map(|sock| UdpBuilder { sock: sock })
+    }
+
+    /// Creates a new IPv6 UDP socket.
+    pub fn new_v6() -> io::Result<UdpBuilder> {
+        sys::udp_builder(true).map(|sock| UdpBuilder { sock: sock })
+    }
+
+    /// Sets the `SO_REUSEADDR` option.
+    pub fn reuse_address(&self, reuse: bool) -> io::Result<&UdpBuilder> {
+        self.sock.reuse_address(reuse).map(|_| self)
+    }
+
+    /// Sets the `SO_REUSEPORT` option, so that several sockets can be bound
+    /// to the same address and port.
+    #[cfg(unix)]
+    pub fn reuse_port(&self, reuse: bool) -> io::Result<&UdpBuilder> {
+        use net2::unix::UnixUdpBuilderExt;
+
+        self.sock.reuse_port(reuse).map(|_| self)
+    }
+
+    /// Sets the `IPV6_V6ONLY` option, restricting an IPv6 socket to IPv6
+    /// traffic.
+    pub fn only_v6(&self, only_v6: bool) -> io::Result<&UdpBuilder> {
+        self.sock.only_v6(only_v6).map(|_| self)
+    }
+
+    /// Sets the `IP_TTL` option.
+    pub fn ttl(&self, ttl: u32) -> io::Result<&UdpBuilder> {
+        self.sock.ttl(ttl).map(|_| self)
+    }
+
+    /// Sets or clears the close-on-exec flag. Sockets are created with the
+    /// flag set, so that they are not inherited by child processes.
+    #[cfg(unix)]
+    pub fn cloexec(&self, cloexec: bool) -> io::Result<&UdpBuilder> {
+        sys::set_close_on_exec(self.sock.as_raw_fd(), cloexec).map(|_| self)
+    }
+
+    /// Sets the `IP_TOS` option, the type of service field of the IPv4
+    /// packets sent from the socket.
+    #[cfg(unix)]
+    pub fn tos(&self, tos: u32) -> io::Result<&UdpBuilder> {
+        sys::set_tos(self.sock.as_raw_fd(), tos).map(|_| self)
+    }
+
+    /// Sets the `SO_BINDTODEVICE` option, so that the socket only uses the
+    /// network interface named `interface`. This usually requires the
+    /// `CAP_NET_RAW` capability.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_device(&self, interface: &str) -> io::Result<&UdpBuilder> {
+        sys::set_bind_device(self.sock.as_raw_fd(), interface).map(|_| self)
+    }
+
+    /// Binds the socket to `addr`, and converts it into a `UdpSocket`.
+    pub fn bind(self, addr: &SocketAddr) -> io::Result<UdpSocket> {
+        UdpSocket::from_socket(try!(self.sock.bind(addr)))
+    }
+}
+
 /*
  *
  * ===== UNIX ext =====
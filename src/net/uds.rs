@@ -0,0 +1,366 @@
+//! Primitives for working with Unix domain sockets
+
+use std::io::{Read, Write};
+use std::os::unix::io::{IntoRawFd, AsRawFd, FromRawFd, RawFd};
+use std::path::Path;
+
+use {io, sys, Evented, Ready, Poll, PollOpt, TakeError, Token, IoVec};
+use super::SelectorId;
+
+pub use std::net::Shutdown;
+pub use sys::SocketAddr;
+
+/*
+ *
+ * ===== UnixStream =====
+ *
+ */
+
+/// A non-blocking Unix domain stream socket.
+#[derive(Debug)]
+pub struct UnixStream {
+    sys: sys::UnixSocket,
+    selector_id: SelectorId,
+}
+
+impl UnixStream {
+    /// Create a new Unix domain stream and issue a non-blocking connect to
+    /// the socket bound to `path`.
+    ///
+    /// The connection may not be established yet when this returns. Like with
+    /// `TcpStream::connect`, the stream becomes writable once it is
+    /// connected, and `take_error` reports a failed connection.
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
+        UnixStream::connect_addr(&try!(SocketAddr::from_pathname(path)))
+    }
+
+    /// Like `connect`, to any address, such as an address in the Linux
+    /// abstract namespace.
+    pub fn connect_addr(addr: &SocketAddr) -> io::Result<UnixStream> {
+        let sock = try!(sys::UnixSocket::stream());
+
+        match sock.connect_addr(addr) {
+            Ok(()) => {}
+            Err(ref e) if e.raw_os_error() == Some(::libc::EINPROGRESS) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(UnixStream::new(sock))
+    }
+
+    /// Creates an unnamed pair of connected sockets.
+    ///
+    /// Both halves are in non-blocking mode and ready to be registered.
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = try!(sys::UnixSocket::pair());
+        Ok((UnixStream::new(a), UnixStream::new(b)))
+    }
+
+    fn new(sys: sys::UnixSocket) -> UnixStream {
+        UnixStream {
+            sys: sys,
+            selector_id: SelectorId::new(),
+        }
+    }
+
+    /// Returns the socket address of the local half of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.sys.local_addr()
+    }
+
+    /// Returns the socket address of the remote half of this connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.sys.peer_addr()
+    }
+
+    /// Creates a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixStream` is a reference to the same stream that this
+    /// object references. Both handles will read and write the same stream of
+    /// data.
+    pub fn try_clone(&self) -> io::Result<UnixStream> {
+        self.sys.try_clone().map(|s| {
+            UnixStream {
+                sys: s,
+                selector_id: self.selector_id.clone(),
+            }
+        })
+    }
+
+    /// Shuts down the read, write, or both halves of this connection.
+    ///
+    /// This function will cause all pending and future I/O on the specified
+    /// portions to return immediately with an appropriate value (see the
+    /// documentation of `Shutdown`).
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.sys.shutdown(how)
+    }
+
+    /// Get the value of the `SO_ERROR` option on this socket.
+    ///
+    /// This will retrieve the stored error in the underlying socket, clearing
+    /// the field in the process. This can be useful for checking errors between
+    /// calls.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.sys.take_error()
+    }
+
+    /// Sends data on the stream along with the file descriptor `fd`, which
+    /// the peer receives with `recv_with_fd`. On success, returns the number
+    /// of bytes written.
+    ///
+    /// The descriptor is duplicated into the receiving process, `fd` stays
+    /// open and owned by the caller. `buf` must not be empty, the descriptor
+    /// is attached to the first byte written. If the socket is not writable,
+    /// a "would block" error is returned.
+    pub fn send_with_fd(&self, buf: &[u8], fd: RawFd) -> io::Result<usize> {
+        self.sys.write_send_fd(buf, fd)
+    }
+
+    /// Receives data from the stream, along with the file descriptor sent
+    /// with `send_with_fd`, if any. On success, returns the number of bytes
+    /// read and the descriptor.
+    ///
+    /// The received descriptor is owned by the caller, and has close-on-exec
+    /// set. If no data is available, a "would block" error is returned.
+    pub fn recv_with_fd(&self, buf: &mut [u8]) -> io::Result<(usize, Option<RawFd>)> {
+        self.sys.read_recv_fd(buf)
+    }
+
+    /// Read in a list of buffers all at once.
+    ///
+    /// The buffers provided will be filled in sequentially. A buffer will be
+    /// entirely filled up before the next is written to. If no bytes are
+    /// available to be read yet then a "would block" error is returned.
+    ///
+    /// This corresponds to the `readv` syscall.
+    pub fn read_bufs(&self, bufs: &mut [&mut IoVec]) -> io::Result<usize> {
+        self.sys.readv(bufs)
+    }
+
+    /// Write a list of buffers all at once.
+    ///
+    /// The buffers provided will be written sequentially. A buffer will be
+    /// entirely written before the next is written. If the socket is not
+    /// currently writable then a "would block" error is returned.
+    ///
+    /// This corresponds to the `writev` syscall.
+    pub fn write_bufs(&self, bufs: &[&IoVec]) -> io::Result<usize> {
+        self.sys.writev(bufs)
+    }
+}
+
+impl Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.sys).read(buf)
+    }
+}
+
+impl<'a> Read for &'a UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.sys).read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.sys).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.sys).flush()
+    }
+}
+
+impl<'a> Write for &'a UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.sys).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.sys).flush()
+    }
+}
+
+impl Evented for UnixStream {
+    fn register(&self, poll: &Poll, token: Token,
+                interest: Ready, opts: PollOpt) -> io::Result<()> {
+        try!(self.selector_id.associate_selector(poll));
+        self.sys.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token,
+                  interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.sys.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.sys.deregister(poll)
+    }
+}
+
+impl TakeError for UnixStream {
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        UnixStream::take_error(self)
+    }
+}
+
+/*
+ *
+ * ===== UnixListener =====
+ *
+ */
+
+/// A non-blocking Unix domain socket listening for stream connections.
+#[derive(Debug)]
+pub struct UnixListener {
+    sys: sys::UnixSocket,
+    selector_id: SelectorId,
+}
+
+impl UnixListener {
+    /// Convenience method to bind a new listener to `path` to receive new
+    /// connections.
+    ///
+    /// The socket file is created by this call and is not removed when the
+    /// listener is dropped.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        UnixListener::bind_addr(&try!(SocketAddr::from_pathname(path)))
+    }
+
+    /// Like `bind`, to any address.
+    ///
+    /// # Examples
+    ///
+    /// On Linux, a listener can be bound to an address in the abstract
+    /// namespace, which leaves no socket file to clean up.
+    ///
+    /// ```
+    /// # #[cfg(any(target_os = "linux", target_os = "android"))]
+    /// # fn main() {
+    /// use mio::uds::{SocketAddr, UnixListener, UnixStream};
+    ///
+    /// let addr = SocketAddr::from_abstract_name(b"mio-doc-example").unwrap();
+    /// let listener = UnixListener::bind_addr(&addr).unwrap();
+    /// let stream = UnixStream::connect_addr(&addr).unwrap();
+    /// # drop((listener, stream));
+    /// # }
+    /// # #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    /// # fn main() {}
+    /// ```
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixListener> {
+        let sock = try!(sys::UnixSocket::stream());
+        try!(sock.bind_addr(addr));
+        try!(sock.listen(1024));
+
+        Ok(UnixListener {
+            sys: sock,
+            selector_id: SelectorId::new(),
+        })
+    }
+
+    /// Accepts a new `UnixStream`.
+    ///
+    /// Returns a "would block" error when there is no pending connection. If
+    /// a stream is accepted, the address of the peer is returned along with
+    /// it.
+    pub fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        self.sys.accept().map(|(s, a)| (UnixStream::new(s), a))
+    }
+
+    /// Returns the local socket address of this listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.sys.local_addr()
+    }
+
+    /// Creates a new independently owned handle to the underlying socket.
+    ///
+    /// The returned `UnixListener` is a reference to the same socket that
+    /// this object references. Both handles can be used to accept incoming
+    /// connections.
+    pub fn try_clone(&self) -> io::Result<UnixListener> {
+        self.sys.try_clone().map(|s| {
+            UnixListener {
+                sys: s,
+                selector_id: self.selector_id.clone(),
+            }
+        })
+    }
+
+    /// Get the value of the `SO_ERROR` option on this socket.
+    ///
+    /// This will retrieve the stored error in the underlying socket, clearing
+    /// the field in the process. This can be useful for checking errors between
+    /// calls.
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        self.sys.take_error()
+    }
+}
+
+impl Evented for UnixListener {
+    fn register(&self, poll: &Poll, token: Token,
+                interest: Ready, opts: PollOpt) -> io::Result<()> {
+        try!(self.selector_id.associate_selector(poll));
+        self.sys.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token,
+                  interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.sys.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.sys.deregister(poll)
+    }
+}
+
+impl TakeError for UnixListener {
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        UnixListener::take_error(self)
+    }
+}
+
+/*
+ *
+ * ===== UNIX ext =====
+ *
+ */
+
+impl IntoRawFd for UnixStream {
+    fn into_raw_fd(self) -> RawFd {
+        self.sys.into_raw_fd()
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sys.as_raw_fd()
+    }
+}
+
+impl FromRawFd for UnixStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixStream {
+        UnixStream::new(FromRawFd::from_raw_fd(fd))
+    }
+}
+
+impl IntoRawFd for UnixListener {
+    fn into_raw_fd(self) -> RawFd {
+        self.sys.into_raw_fd()
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sys.as_raw_fd()
+    }
+}
+
+impl FromRawFd for UnixListener {
+    unsafe fn from_raw_fd(fd: RawFd) -> UnixListener {
+        UnixListener {
+            sys: FromRawFd::from_raw_fd(fd),
+            selector_id: SelectorId::new(),
+        }
+    }
+}
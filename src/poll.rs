@@ -1,12 +1,19 @@
 use {sys, Evented, Token};
+use deadline::DeadlineWheel;
 use event::{self, Ready, Event, PollOpt};
-use std::{fmt, io, mem, ptr, usize};
-use std::cell::{UnsafeCell, Cell};
+use std::{cmp, fmt, io, mem, ptr, slice, usize};
+use std::borrow::Cow;
+use std::cell::{UnsafeCell, Cell, RefCell};
+use std::collections::HashMap;
 use std::isize;
 use std::marker;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, AtomicPtr, Ordering};
-use std::time::Duration;
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, AtomicPtr, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
 
 const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 
@@ -32,6 +39,27 @@ const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 ///
 /// Cross platform portability is provided for Mio's TCP & UDP implementations.
 ///
+/// ## Threads
+///
+/// `Poll` is `Send` but not `Sync`. It may be created and have handles
+/// registered with it on one thread, then be moved to another thread that
+/// runs the event loop. Moving a value to another thread (with
+/// `thread::spawn`, a channel, a `Mutex`, ...) already establishes a
+/// happens-before relationship, so the first call to `poll` on the new thread
+/// observes every registration made before the move, as well as all
+/// readiness set with `SetReadiness` before the move. No additional
+/// synchronization is required from the caller.
+///
+/// Readiness set concurrently from other threads while `Poll` is in flight is
+/// not lost either: the readiness queue itself synchronizes producers with
+/// the thread calling `poll`, whichever thread that ends up being.
+///
+/// Because `Poll` is not `Sync`, only one thread can ever be calling `poll`
+/// on a given instance. `poll` therefore takes no lock and does not track
+/// waiting pollers: the only atomic operations it performs are those needed
+/// to drain the readiness queue and to tell producers whether a wakeup is
+/// required.
+///
 /// ## Examples
 ///
 /// ```no_run
@@ -60,6 +88,87 @@ pub struct Poll {
 
     // Custom readiness queue
     readiness_queue: ReadinessQueue,
+
+    // Destination registrations for `forward_ready`, keyed by source token
+    forwards: RefCell<HashMap<Token, SetReadiness>>,
+
+    // Registrations backing the handles returned by `register_wakeup_token`
+    wake_tokens: RefCell<HashMap<Token, Registration>>,
+
+    // Scratch buffer used by `poll_into`, allocated on first use
+    scratch: RefCell<Option<Events>>,
+
+    // Deadlines set with `set_deadline`
+    deadlines: RefCell<DeadlineWheel>,
+
+    // Deliver a single event per custom registration, see
+    // `set_coalesce_readiness`
+    coalesce: Cell<bool>,
+
+    // Number of zero-timeout probes before blocking, see `new_with_spin`
+    spin_iters: usize,
+
+    // Whether to time the selector call, see `enable_block_timing`
+    block_timing: Cell<bool>,
+
+    // Time spent in the selector by the last call to `poll`
+    last_block: Cell<Option<Duration>>,
+}
+
+/// Configures and creates a `Poll` instance.
+///
+/// Obtained with `Poll::builder`. The defaults are those of `Poll::new`.
+///
+/// # Examples
+///
+/// ```
+/// use mio::{AwakenerKind, Poll};
+///
+/// let poll = Poll::builder()
+///     .cloexec(true)
+///     .poll_into_capacity(64)
+///     .awakener(AwakenerKind::Pipe)
+///     .build()
+///     .unwrap();
+///
+/// assert!(poll.has_userspace());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PollBuilder {
+    poll_into_capacity: Option<usize>,
+    cloexec: bool,
+    awakener: AwakenerKind,
+    userspace: bool,
+}
+
+/// The mechanism used to wake up a `Poll` blocked in the selector when
+/// readiness is set from another thread, see `PollBuilder::awakener`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwakenerKind {
+    /// A pipe, which uses two file descriptors. This is the default where
+    /// `UserEvent` is not available.
+    Pipe,
+
+    /// An eventfd, which uses a single file descriptor. Only available on
+    /// Linux and Android, `PollBuilder::build` fails on other Unix
+    /// platforms.
+    EventFd,
+
+    /// A kqueue `EVFILT_USER` event, which needs no file descriptor of its
+    /// own. This is the default on macOS, iOS, FreeBSD and DragonFly, and
+    /// `PollBuilder::build` fails on the other Unix platforms.
+    UserEvent,
+}
+
+impl Default for AwakenerKind {
+    /// `UserEvent` where kqueue supports `EVFILT_USER`, `Pipe` otherwise.
+    fn default() -> AwakenerKind {
+        if cfg!(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly")) {
+            AwakenerKind::UserEvent
+        } else {
+            AwakenerKind::Pipe
+        }
+    }
 }
 
 /// Handle to a Poll registration. Used for registering custom types for event
@@ -68,13 +177,136 @@ pub struct Registration {
     inner: RegistrationInner,
 }
 
+/// Handle used to shut down a `Poll` instance from another thread.
+///
+/// Obtained with `Poll::shutdown_handle`. Once `shutdown` is called, any
+/// in-progress call to `Poll::poll` is woken up and it, as well as all
+/// subsequent calls, return an error of kind `BrokenPipe`.
+pub struct ShutdownHandle {
+    // `ReadinessQueue` is not `Sync`, the lock makes the handle shareable
+    queue: Mutex<ReadinessQueue>,
+}
+
+/// Handle used to cancel a single call to `Poll::poll` from another thread.
+///
+/// Obtained with `Poll::cancel_token`. Each call to `cancel` makes exactly
+/// one call to `poll` return early: the one currently blocked, or else the
+/// next one. Unlike `ShutdownHandle`, the `Poll` instance keeps working
+/// afterwards.
+#[derive(Clone)]
+pub struct CancelToken {
+    queue: ReadinessQueue,
+}
+
+/// Handle used to wake up a `Poll` instance with a specific token.
+///
+/// Obtained with `Poll::register_wakeup_token`. Calling `wake` makes the next
+/// call to `Poll::poll` return a readable event for the token, so wakeups are
+/// dispatched like any other event. `WakeHandle` is `Send` and `Sync`.
+#[derive(Clone)]
+pub struct WakeHandle {
+    set_readiness: SetReadiness,
+}
+
+/// Wakes up a `Poll` instance from another thread, with a readable event
+/// for a specific token.
+///
+/// On Unix, unlike `WakeHandle`, a `Waker` doesn't go through the readiness
+/// queue: it owns an awakener of its own (a pipe or an eventfd), registered
+/// with the OS selector. Waking costs a system call, but no `Registration`,
+/// and it also works with a `Poll` created with `Poll::new_no_userspace`.
+/// On Windows, the completion port only carries the `Poll` awakener and
+/// I/O completions, so a `Waker` sets readiness on a `Registration` like
+/// the I/O handles do. `Waker` is `Send` and `Sync`, share it with an `Arc`
+/// to wake from several threads.
+///
+/// # Examples
+///
+/// ```
+/// use mio::{Events, Poll, Token, Waker};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let poll = Poll::new().unwrap();
+/// let mut events = Events::with_capacity(1024);
+///
+/// let waker = Arc::new(Waker::new(&poll, Token(10)).unwrap());
+/// let remote = waker.clone();
+///
+/// thread::spawn(move || {
+///     remote.wake().unwrap();
+/// });
+///
+/// poll.poll(&mut events, None).unwrap();
+/// assert_eq!(events.get(0).unwrap().token(), Token(10));
+/// ```
+pub struct Waker {
+    #[cfg(unix)]
+    awakener: sys::Awakener,
+    // Only kept to keep the registration alive, the lock makes `Waker` `Sync`
+    #[cfg(windows)]
+    _registration: Mutex<Registration>,
+    #[cfg(windows)]
+    set_readiness: SetReadiness,
+}
+
 /// Used to update readiness for an associated `Registration`. `SetReadiness`
 /// is `Sync` which allows it to be updated across threads.
-#[derive(Clone)]
 pub struct SetReadiness {
     inner: RegistrationInner,
 }
 
+/// A group of `SetReadiness` handles whose readiness is updated together.
+///
+/// All handles in the group must belong to the same `Poll` instance. Calling
+/// `set_all` queues every node for processing before issuing a single
+/// wakeup, so one call to `Poll::poll` observes the whole group.
+#[derive(Clone)]
+pub struct ReadinessGroup {
+    members: Vec<SetReadiness>,
+}
+
+/// A condition variable whose waiters are woken up through `Poll`.
+///
+/// A `Poll` based consumer calls `register` to obtain a `CondvarWaiter` and
+/// gets a readable event for the waiter's token every time it is notified.
+/// Producers on any thread call `notify_one` or `notify_all`. Each
+/// notification results in exactly one event, the readiness is cleared once
+/// it has been delivered.
+///
+/// Notifications are never lost: a waiter notified before it polls gets the
+/// event on its next call to `poll`, and a `notify_one` made while no waiter
+/// is registered is kept and delivered to the next waiter that registers.
+/// As with any condition variable, the consumer should re-check the
+/// condition it waits for when it gets the event.
+#[derive(Clone)]
+pub struct PollCondvar {
+    inner: Arc<Mutex<CondvarState>>,
+}
+
+/// A waiter registered with a `PollCondvar`, obtained with
+/// `PollCondvar::register`.
+///
+/// Dropping the waiter removes it from the condition variable.
+pub struct CondvarWaiter {
+    registration: Registration,
+    id: usize,
+    condvar: PollCondvar,
+}
+
+struct CondvarState {
+    waiters: Vec<(usize, SetReadiness)>,
+
+    // Used to assign waiter ids
+    next_id: usize,
+
+    // Rotates `notify_one` through the waiters
+    next_waiter: usize,
+
+    // `notify_one` calls made while no waiter was registered
+    permits: usize,
+}
+
 struct RegistrationInner {
     // ARC pointer to the Poll's readiness queue
     queue: ReadinessQueue,
@@ -90,8 +322,9 @@ struct ReadinessQueue {
 }
 
 struct ReadinessQueueInner {
-    // Used to wake up `Poll` when readiness is set in another thread.
-    awakener: sys::Awakener,
+    // Used to wake up `Poll` when readiness is set in another thread. `None`
+    // for a `Poll` created with `new_no_userspace`.
+    awakener: Option<sys::Awakener>,
 
     // All readiness nodes are owned by the `Poll` instance and live either in
     // this linked list or in a `readiness_wheel` linked list.
@@ -102,12 +335,90 @@ struct ReadinessQueueInner {
 
     // A fake readiness node used to indicate that `Poll::poll` will block.
     sleep_token: Box<ReadinessNode>,
+
+    // Set once the `Poll` has been shut down.
+    shutdown: AtomicBool,
+
+    // Number of pending `CancelToken::cancel` calls, each one consumed by a
+    // single call to `poll`
+    cancelled: AtomicUsize,
+
+    // Number of nodes in the `head_readiness` list. Counted before a node is
+    // linked and after it is taken, so it never underflows.
+    queued_nodes: AtomicUsize,
+
+    // Set with `Poll::set_readiness_limit`, `usize::MAX` when unset
+    readiness_limit: AtomicUsize,
+
+    // Cleared when the `Poll` is dropped. `SetReadiness` handles keep the
+    // queue alive, but nothing waits on the awakener anymore.
+    poll_alive: AtomicBool,
+
+    // Number of times a producer lost the race on `head_readiness` and had
+    // to retry, see `Poll::queue_contention_stats`
+    enqueue_retries: AtomicUsize,
+
+    // Number of times `poll` had to retry dequeuing a node because its
+    // `queued` state changed concurrently
+    dequeue_retries: AtomicUsize,
+
+    // Internal decisions recorded once `Poll::attach_trace` is called
+    #[cfg(test)]
+    trace: Mutex<Option<Vec<TraceEvent>>>,
+
+    // Runs once right before the next enqueue CAS, used to force contention
+    #[cfg(test)]
+    enqueue_hook: Mutex<Option<Box<dyn FnMut() + Send>>>,
+}
+
+/// An internal decision recorded by a `PollTrace`.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TraceEvent {
+    // `prepare_for_sleep` was called, true if the sleep marker was set
+    PrepareForSleep(bool),
+    // The selector returned, true if the awakener fired
+    Awoken(bool),
+    // A producer queued a node, true if `Poll` had to be woken up
+    Enqueued(Token, bool),
+    // The awakener was written to
+    Wakeup,
+    // `poll` dequeued a node with the given readiness
+    Dequeued(Token, Ready),
+    // `poll` pushed a node back onto the readiness queue
+    Requeued(Token),
+    // `poll` released a dropped node
+    Released(Token),
+}
+
+/// Records the internal decisions of a `Poll` instance, see
+/// `Poll::attach_trace`.
+#[cfg(test)]
+struct PollTrace {
+    queue: ReadinessQueue,
+}
+
+// Records an internal decision when tracing, compiled out of non-test builds.
+macro_rules! record_trace {
+    ($queue:expr, $event:expr) => {
+        #[cfg(test)]
+        $queue.record($event);
+    }
 }
 
 struct ReadyList {
     head: ReadyRef,
 }
 
+// Nodes taken by `ReadinessQueue::poll`. If processing a node panics, e.g.
+// in a drop callback, the nodes that were not processed yet are pushed back
+// onto the readiness queue. They are still flagged as queued, so otherwise
+// producers would never queue them again and their readiness would be lost.
+struct ReadyListGuard<'a> {
+    queue: &'a ReadinessQueue,
+    list: ReadyList,
+}
+
 struct ReadyRef {
     ptr: *mut ReadinessNode,
 }
@@ -126,6 +437,19 @@ struct ReadinessNode {
     // instance.
     registration_data: UnsafeCell<RegistrationData>,
 
+    // Invoked once the node is freed. Only accessed when the node is
+    // created, freed, or migrated by the thread that owns the `Poll`
+    // instance and the `Registration`, so never concurrently.
+    drop_callback: UnsafeCell<Option<Box<dyn FnOnce() + Send>>>,
+
+    // Name given with `Registration::new_named`, for diagnostics. Only set
+    // when the node is created or migrated, read-only afterwards.
+    name: Option<Cow<'static, str>>,
+
+    // Set by `Registration::migrate`, the registration that readiness set on
+    // this node is forwarded to.
+    migrated: AtomicPtr<RegistrationInner>,
+
     // ===== Fields accessed by any thread ====
     //
     // Used when the node is queued in the readiness linked list. Accessing
@@ -141,23 +465,74 @@ struct ReadinessNode {
 
     // Tracks the number of `ReadyRef` pointers
     ref_count: AtomicUsize,
+
+    // Number of times readiness was set while the node was already queued,
+    // for diagnostics only
+    coalesced: AtomicUsize,
+
+    // Edge events only report readiness not set at the previous delivery.
+    // Only set when the node is created or migrated, before it is shared.
+    incremental: bool,
+
+    // Deliver hup once the last `SetReadiness` is dropped. Only set when the
+    // node is created or migrated, before it is shared.
+    producer_hup: bool,
+
+    // Number of `SetReadiness` handles, only tracked for `producer_hup`
+    // nodes
+    producers: AtomicUsize,
+
+    // Set by `Registration::disable_and_release`. Once `Poll` has detached
+    // the node, it is freed by whichever handle is dropped last.
+    release: AtomicUsize,
+
+    // The Token used to register the `Evented` with `Poll` (the write
+    // position). Only written by the thread that owns `Poll`, but stored
+    // atomically so that `SetReadiness` may read it from any thread.
+    token: AtomicUsize,
+
+    // The token `poll` last delivered an event with (the read position).
+    // Copied from `token` by `poll` when it dequeues the node.
+    read_token: AtomicUsize,
 }
 
 struct RegistrationData {
-    // The Token used to register the `Evented` with`Poll`
-    token: Token,
-
     // The registration interest
     interest: Ready,
 
     // Poll opts
     opts: PollOpt,
+
+    // Delivery priority, higher values are returned first by `poll`
+    priority: u8,
+
+    // Clear the readiness once `poll` has delivered it
+    auto_reset: bool,
+
+    // Readiness seen by the previous delivery, used by incremental nodes
+    last_delivered: Ready,
 }
 
 const NODE_QUEUED_FLAG: usize = 1;
 
+// States of `ReadinessNode::release`, see `Registration::disable_and_release`
+const RELEASE_NONE: usize = 0;
+const RELEASE_REQUESTED: usize = 1;
+const RELEASE_DETACHED: usize = 2;
+const RELEASE_DROPPED: usize = 3;
+
 const AWAKEN: Token = Token(usize::MAX);
 
+// Capacity of the scratch `Events` used by `Poll::poll_into`
+const POLL_INTO_CAPACITY: usize = 1024;
+
+// Tokens reserved for `Poll::with_wake_tokens`. The range ends below the top
+// 256 tokens, which are left for `AWAKEN` and other internal tokens (such as
+// the deprecated `EventLoop`'s).
+const MAX_WAKE_TOKENS: usize = 0x1_0000;
+const WAKE_TOKENS_END: usize = usize::MAX - 0xff;
+const WAKE_TOKENS_START: usize = WAKE_TOKENS_END - MAX_WAKE_TOKENS;
+
 /*
  *
  * ===== Poll =====
@@ -167,22 +542,117 @@ const AWAKEN: Token = Token(usize::MAX);
 impl Poll {
     /// Return a new `Poll` handle using a default configuration.
     pub fn new() -> io::Result<Poll> {
-        let poll = Poll {
-            selector: try!(sys::Selector::new()),
-            readiness_queue: try!(ReadinessQueue::new()),
+        PollBuilder::new().build()
+    }
+
+    /// Returns a builder to configure the new `Poll` instance.
+    pub fn builder() -> PollBuilder {
+        PollBuilder::new()
+    }
+
+    /// Return a new `Poll` handle that only polls the OS selector.
+    ///
+    /// Such an instance has no readiness queue for custom `Registration`
+    /// values and no awakener. On Unix, this saves the awakener's pipe (two
+    /// file descriptors) and its slot in the selector. Each call to `poll`
+    /// is then a single selector call, without the sleep handoff with
+    /// producers and the readiness queue drain, see
+    /// `bench_poll_no_userspace` for the difference.
+    ///
+    /// Everything relying on custom registrations is unavailable:
+    /// `Registration::try_new` and `Registration::migrate` return an error,
+    /// and so does registering a `Timer` or a channel `Receiver`;
+    /// `Registration::new` panics. Since nothing can interrupt a blocked
+    /// call to `poll`, `shutdown` only takes effect when `poll` returns on
+    /// its own.
+    pub fn new_no_userspace() -> io::Result<Poll> {
+        PollBuilder::new().userspace(false).build()
+    }
+
+    fn new_inner(awakener: Option<sys::Awakener>, cloexec: bool) -> io::Result<Poll> {
+        Ok(Poll {
+            selector: try!(sys::Selector::new(cloexec)),
+            readiness_queue: ReadinessQueue::new(awakener),
+            forwards: RefCell::new(HashMap::new()),
+            wake_tokens: RefCell::new(HashMap::new()),
+            scratch: RefCell::new(None),
+            deadlines: RefCell::new(DeadlineWheel::new()),
+            coalesce: Cell::new(true),
+            spin_iters: 0,
+            block_timing: Cell::new(false),
+            last_block: Cell::new(None),
             _marker: marker::PhantomData,
-        };
+        })
+    }
 
-        // Register the notification wakeup FD with the IO poller
-        try!(poll.readiness_queue.inner().awakener.register(&poll, AWAKEN, Ready::readable(), PollOpt::edge()));
+    /// Returns false if the instance was created with `new_no_userspace`, or
+    /// with `PollBuilder::userspace` disabled.
+    pub fn has_userspace(&self) -> bool {
+        self.readiness_queue.inner().awakener.is_some()
+    }
 
+    /// Return a new `Poll` handle that busy-polls before blocking.
+    ///
+    /// When `poll` is called with a non-zero timeout and nothing is ready,
+    /// it first checks the readiness queue and probes the selector with a
+    /// zero timeout, up to `spin_iters` times, and only blocks if all of
+    /// them come back empty. Events arriving during the spin are returned
+    /// without paying for a blocking system call and the wakeup, at the
+    /// cost of burning CPU while idle. `Poll::new` is the same as
+    /// `new_with_spin(0)`.
+    ///
+    /// This only pays off when the polling thread has a core to itself.
+    /// Otherwise the spinning thread competes with the threads producing the
+    /// events and latency gets worse, see `bench_spin_wakeup_latency`.
+    pub fn new_with_spin(spin_iters: usize) -> io::Result<Poll> {
+        let mut poll = try!(Poll::new());
+        poll.spin_iters = spin_iters;
         Ok(poll)
     }
 
+    /// Return a new `Poll` handle along with `count` handles that wake it up
+    /// with a dedicated token each.
+    ///
+    /// The registrations backing the handles are created once, up front, so
+    /// calling `WakeHandle::wake` never allocates. The handles use tokens
+    /// from a reserved range: the `i`th handle delivers events for
+    /// `Token(usize::MAX - 0x100ff + i)`. Use `WakeHandle::token` to identify
+    /// them when dispatching events. At most 65536 wake tokens are
+    /// available, and registering any handle with a token in the reserved
+    /// range, on any `Poll`, fails.
+    pub fn with_wake_tokens(count: usize) -> io::Result<(Poll, Vec<WakeHandle>)> {
+        if count > MAX_WAKE_TOKENS {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many wake tokens"));
+        }
+
+        let poll = try!(Poll::new());
+        let mut handles = Vec::with_capacity(count);
+
+        {
+            let mut wake_tokens = poll.wake_tokens.borrow_mut();
+
+            for i in 0..count {
+                let token = Token(WAKE_TOKENS_START + i);
+                let (registration, set_readiness) = Registration::new(&poll, token, Ready::readable(), PollOpt::edge());
+
+                wake_tokens.insert(token, registration);
+                handles.push(WakeHandle { set_readiness: set_readiness });
+            }
+        }
+
+        Ok((poll, handles))
+    }
+
     /// Register an `Evented` handle with the `Poll` instance.
-    pub fn register<E: ?Sized>(&self, io: &E, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()>
-        where E: Evented
+    ///
+    /// The interest is given as an `Interest`, which only allows the
+    /// meaningful combinations of readable, writable and priority, or as a
+    /// `Ready`.
+    /// Either way, the handle receives it as a `Ready`.
+    pub fn register<E: ?Sized, I>(&self, io: &E, token: Token, interest: I, opts: PollOpt) -> io::Result<()>
+        where E: Evented, I: Into<Ready>
     {
+        let interest = interest.into();
         try!(validate_args(token, interest));
 
         /*
@@ -199,9 +669,13 @@ impl Poll {
     }
 
     /// Re-register an `Evented` handle with the `Poll` instance.
-    pub fn reregister<E: ?Sized>(&self, io: &E, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()>
-        where E: Evented
+    ///
+    /// Like with `register`, the interest is given as an `Interest` or as a
+    /// `Ready`.
+    pub fn reregister<E: ?Sized, I>(&self, io: &E, token: Token, interest: I, opts: PollOpt) -> io::Result<()>
+        where E: Evented, I: Into<Ready>
     {
+        let interest = interest.into();
         try!(validate_args(token, interest));
 
         trace!("registering with poller");
@@ -212,6 +686,73 @@ impl Poll {
         Ok(())
     }
 
+    /// Like `register`, and arms a deadline for `token`, see
+    /// `set_deadline`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::*;
+    /// use mio::tcp::TcpListener;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(1024);
+    ///
+    /// let addr = "127.0.0.1:0".parse().unwrap();
+    /// let listener = TcpListener::bind(&addr).unwrap();
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    ///
+    /// poll.register_with_deadline(&listener, Token(0), Ready::readable(), PollOpt::edge(), deadline).unwrap();
+    ///
+    /// // Nobody connects, the deadline fires
+    /// poll.poll(&mut events, None).unwrap();
+    /// let event = events.get(0).unwrap();
+    ///
+    /// assert_eq!(event.token(), Token(0));
+    /// assert!(event.is_timeout());
+    /// ```
+    pub fn register_with_deadline<E: ?Sized, I>(&self, io: &E, token: Token, interest: I, opts: PollOpt, deadline: Instant) -> io::Result<()>
+        where E: Evented, I: Into<Ready>
+    {
+        try!(self.register(io, token, interest, opts));
+        self.set_deadline(token, deadline)
+    }
+
+    /// Arms a deadline for `token`, replacing the previous one.
+    ///
+    /// If `poll` returns no event for `token` before `deadline`, it returns a
+    /// `Ready::timeout()` event for it once the deadline passes. Deadlines
+    /// are one-shot: any event for `token`, including the timeout itself,
+    /// disarms it. Call `set_deadline` again to rearm it, for example after
+    /// each read on an idle connection. A deadline already in the past fires
+    /// on the next call to `poll`.
+    ///
+    /// While a deadline is armed, `poll` wakes up to deliver it, whatever its
+    /// own timeout. The variants that wait once, such as
+    /// `poll_once_or_timeout` or `poll_with_blocking_flag`, also wake up
+    /// for it, and may then return without events before the deadline is
+    /// due. Deadlines are tracked by token only: deregistering a handle does
+    /// not disarm its deadline, use `clear_deadline`.
+    ///
+    /// Reserved tokens (see `Token::is_reserved`) are rejected.
+    pub fn set_deadline(&self, token: Token, deadline: Instant) -> io::Result<()> {
+        if is_reserved_token(token) {
+            return Err(io::Error::new(io::ErrorKind::Other, "invalid token"));
+        }
+
+        self.deadlines.borrow_mut().set(token, deadline);
+        Ok(())
+    }
+
+    /// Disarms the deadline of `token` set with `set_deadline`.
+    ///
+    /// Returns false if no deadline was armed, including when it already
+    /// fired.
+    pub fn clear_deadline(&self, token: Token) -> bool {
+        self.deadlines.borrow_mut().clear(token)
+    }
+
     /// Deregister an `Evented` handle with the `Poll` instance.
     pub fn deregister<E: ?Sized>(&self, io: &E) -> io::Result<()>
         where E: Evented
@@ -226,87 +767,1146 @@ impl Poll {
 
     /// Block the current thread and wait until any `Evented` values registered
     /// with the `Poll` instance are ready or the given timeout has elapsed.
-    pub fn poll(&self,
-                events: &mut Events,
-                timeout: Option<Duration>) -> io::Result<usize> {
-        let timeout = if !self.readiness_queue.is_empty() {
-            trace!("custom readiness queue has pending events");
-            // Never block if the readiness queue has pending events
-            Some(Duration::from_millis(0))
-        } else if !self.readiness_queue.prepare_for_sleep() {
-            Some(Duration::from_millis(0))
+    ///
+    /// The timeout may be given as a bare `Duration`, as `Some(duration)` or
+    /// as `None` to block until an event arrives, see `Timeout`.
+    ///
+    /// Returns an error if the `Poll` instance has been shut down, see
+    /// `shutdown`.
+    ///
+    /// When the underlying system call fails (`epoll_wait`, `kevent` or
+    /// `GetQueuedCompletionStatusEx`), the returned error is the OS error
+    /// itself, so `raw_os_error()` gives the original code, for example to
+    /// tell `ENOMEM` from `EINVAL`.
+    ///
+    /// # Migrating
+    ///
+    /// `poll` used to take an `Option<Duration>`. Existing calls passing
+    /// `Some(..)` or `None` compile unchanged. `Some(duration)` can be
+    /// shortened to `duration`:
+    ///
+    /// ```
+    /// use mio::*;
+    /// use std::time::Duration;
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(1024);
+    ///
+    /// poll.poll(&mut events, Some(Duration::from_millis(10))).unwrap();
+    /// poll.poll(&mut events, Duration::from_millis(10)).unwrap();
+    /// ```
+    pub fn poll<T: Timeout>(&self,
+                            events: &mut Events,
+                            timeout: T) -> io::Result<usize> {
+        self.poll_deadlines(events, timeout.into_timeout()).map(|res| res.len())
+    }
+
+    /// Like `poll`, with the wait bounded by a deadline rather than a
+    /// timeout.
+    ///
+    /// Returns once events are available or `deadline` has passed, in which
+    /// case the returned number of events is zero. The remaining time is
+    /// recomputed from `deadline` on each wait, so wake ups that bring no
+    /// events, and selector calls interrupted by a signal, do not end the
+    /// wait early or stretch it. A deadline in the past polls without
+    /// blocking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::*;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(1024);
+    ///
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// let n = poll.poll_until(&mut events, deadline).unwrap();
+    ///
+    /// assert_eq!(0, n);
+    /// assert!(Instant::now() >= deadline);
+    /// ```
+    pub fn poll_until(&self, events: &mut Events, deadline: Instant) -> io::Result<usize> {
+        loop {
+            let now = Instant::now();
+            let timeout = if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_millis(0)
+            };
+
+            match self.poll2(events, Some(timeout)) {
+                Ok(ref res) if res.len() > 0 => return Ok(res.len()),
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    // Like `poll_once_or_timeout`, the queue is drained by
+                    // the next wait
+                    events.inner.truncate(0);
+                    events.handled.clear();
+                    events.user_start = 0;
+                }
+                Err(e) => return Err(e),
+            }
+
+            if Instant::now() >= deadline {
+                return Ok(0);
+            }
+        }
+    }
+
+    /// Waits on the selector exactly once and returns, whatever woke it up.
+    ///
+    /// This performs a single call into the OS selector followed by a single
+    /// drain of the custom readiness queue. It returns as soon as the
+    /// selector does: after the timeout, when an event arrives, or when the
+    /// `Poll` is only woken up, for example by a `SetReadiness` whose
+    /// readiness does not match the registration's interest. In the last
+    /// case, the returned number of events is zero even though `timeout`
+    /// has not elapsed.
+    ///
+    /// `poll` never re-enters the selector either, but it reports a selector
+    /// call interrupted by a signal as an `Interrupted` error, leaving the
+    /// retry to the caller. Here, an interrupted call is one of the ways the
+    /// single wait can end: it returns `Ok(0)` with `events` emptied, and the
+    /// readiness queue is drained by the next call. Callers counting loop
+    /// iterations, e.g. for backpressure accounting, can rely on one call
+    /// being one wait.
+    pub fn poll_once_or_timeout<T: Timeout>(&self, events: &mut Events, timeout: T) -> io::Result<usize> {
+        match self.poll2(events, timeout.into_timeout()) {
+            Ok(res) => Ok(res.len()),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                events.inner.truncate(0);
+                events.handled.clear();
+                events.user_start = 0;
+                Ok(0)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like `poll`, but events from custom `Registration` values that were
+    /// not marked as handled are returned again.
+    ///
+    /// After this returns, every event in `events` is unhandled. Use
+    /// `Events::iter_mut` to mark the events that have been processed. On the
+    /// next call to `poll_mut` with the same buffer, events from the
+    /// readiness queue (custom `Registration` values) that were left
+    /// unhandled are returned again, after the newly polled events, unless
+    /// the same token is delivered again anyway. `poll_mut` does not block
+    /// while it has such events to return.
+    ///
+    /// Events from the OS selector are never returned again, the OS cannot
+    /// replay an edge. Passing the buffer to `poll`, or to another `Poll`
+    /// instance's `poll`, discards the handled state: nothing is carried
+    /// over by the following `poll_mut`.
+    pub fn poll_mut<T: Timeout>(&self, events: &mut Events, timeout: T) -> io::Result<usize> {
+        let mut carried = Vec::new();
+
+        for (i, &handled) in events.handled.iter().enumerate().skip(events.user_start) {
+            if !handled {
+                carried.push(events.get(i).unwrap());
+            }
+        }
+
+        let timeout = if carried.is_empty() {
+            timeout.into_timeout()
         } else {
-            timeout
+            Some(Duration::from_millis(0))
         };
 
-        // First get selector events
-        let awoken = try!(self.selector.select(&mut events.inner, AWAKEN,
-                                               timeout));
+        let res = try!(self.poll2(events, timeout));
 
-        if awoken {
-            self.readiness_queue.inner().awakener.cleanup();
+        for event in carried {
+            let redelivered = (res.sys_events..events.len())
+                .any(|i| events.get(i).unwrap().token() == event.token());
+
+            if !redelivered {
+                events.inner.push_event(event);
+            }
         }
 
-        // Poll custom event queue
-        self.readiness_queue.poll(&mut events.inner);
+        let len = events.len();
+        events.handled.resize(len, false);
 
-        // Return number of polled events
-        Ok(events.len())
+        Ok(len)
     }
-}
 
-fn validate_args(token: Token, interest: Ready) -> io::Result<()> {
-    if token == AWAKEN {
-        return Err(io::Error::new(io::ErrorKind::Other, "invalid token"));
+    /// Like `poll`, but returns the events produced by this call as a slice.
+    ///
+    /// The slice borrows `events`, so it must be dropped before `events` is
+    /// used again. Its length is `events.len()`.
+    ///
+    /// With kqueue and IOCP, the slice points directly at the buffer the
+    /// events were written to. epoll reports its own `epoll_event` structure,
+    /// so there the events are converted into a second buffer kept by
+    /// `events`, which is reused by later calls.
+    pub fn poll_slice<'a, T: Timeout>(&self, events: &'a mut Events, timeout: T) -> io::Result<&'a [Event]> {
+        try!(self.poll(events, timeout));
+        Ok(events.inner.as_slice())
     }
 
-    if !interest.is_readable() && !interest.is_writable() {
-        return Err(io::Error::new(io::ErrorKind::Other, "interest must include readable or writable"));
+    /// Returns the timeout the OS selector actually waits for when `poll` is
+    /// given `requested`.
+    ///
+    /// Timeouts are never rounded down, so `poll` does not return before
+    /// `requested` has elapsed (unless an event arrives). kqueue, and epoll on
+    /// Linux 5.11 and later (through `epoll_pwait2`), wait with nanosecond
+    /// precision. Where the selector only has millisecond resolution (older
+    /// kernels, android, IOCP), the timeout is rounded up to the next
+    /// millisecond: a `Duration` of 1500 microseconds waits for 2
+    /// milliseconds, and a non-zero `Duration` below one millisecond waits
+    /// for a full millisecond rather than returning immediately. Very large
+    /// timeouts are capped to what the selector accepts.
+    pub fn effective_timeout(requested: Duration) -> Duration {
+        sys::Selector::effective_timeout(requested)
     }
 
-    Ok(())
-}
-
-impl fmt::Debug for Poll {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "Poll")
+    /// Enables or disables detecting registered file descriptors that were
+    /// closed without being deregistered first.
+    ///
+    /// Closing a file descriptor removes it from the OS selector (epoll or
+    /// kqueue) without notice, so no further event is ever delivered for its
+    /// token. With validation enabled, every file descriptor registered from
+    /// then on is tracked, and on each call to `poll` the tracked ones are
+    /// checked. A file descriptor found closed yields a final event with
+    /// `Ready::error() | Ready::hup()` for its token and is no longer
+    /// tracked. Deregistering stops tracking as usual.
+    ///
+    /// This is best-effort and meant for debugging. If the number of a
+    /// closed file descriptor has already been reused by a new one, nothing
+    /// is detected. Checking costs one system call per tracked file
+    /// descriptor on every call to `poll`. Disabling validation forgets all
+    /// tracked file descriptors. On Windows this does nothing, closed handles
+    /// already complete their pending operations with an error.
+    pub fn set_fd_validation(&self, enabled: bool) {
+        self.selector.set_fd_validation(enabled);
     }
-}
-
-/// A buffer for I/O events to get placed into, passed to `Poll::poll`.
-///
-/// This structure is normally re-used on each turn of the event loop and will
-/// contain any I/O events that happen during a `poll`. After a call to `poll`
-/// returns the various accessor methods on this structure can be used to
-/// iterate over the underlying events that ocurred.
-pub struct Events {
-    inner: sys::Events,
-}
-
-/// Iterate an Events structure
-pub struct EventsIter<'a> {
-    inner: &'a Events,
-    pos: usize,
-}
 
-impl Events {
-    /// Create a net blank set of events capable of holding up to `capacity`
-    /// events.
+    /// Exercises the awakener once so that the first cross-thread wakeup is
+    /// not slower than the following ones.
     ///
-    /// This parameter typically is an indicator on how many events can be
-    /// returned each turn of the event loop, but it is not necessarily a hard
-    /// limit across platforms.
-    pub fn with_capacity(capacity: usize) -> Events {
-        Events {
-            inner: sys::Events::with_capacity(capacity),
+    /// Waking up a `Poll` blocked in `poll` from another thread goes through
+    /// an internal awakener. On Unix, this writes to and drains its pipe, so
+    /// the first real wakeup does not pay for touching it. How much this
+    /// saves depends on the system and may well be nothing, the
+    /// `bench_first_wakeup_latency` benchmark measures it. It is a no-op on
+    /// Windows, where waking up posts a completion status and there is
+    /// nothing to warm up.
+    ///
+    /// Call this once after creating the `Poll`, before latency matters. It
+    /// does not produce any event, although a platform may report the
+    /// primed wakeup, returning early from the next `poll` without events.
+    pub fn prime_awakener(&self) -> io::Result<()> {
+        match self.readiness_queue.inner().awakener {
+            Some(ref awakener) => awakener.prime(),
+            None => Ok(()),
         }
     }
 
-    /// Returns the `idx`-th event.
+    /// Returns how often the readiness queue of custom `Registration` values
+    /// was contended.
     ///
-    /// Returns `None` if `idx` is greater than the length of this event buffer.
-    pub fn get(&self, idx: usize) -> Option<Event> {
-        self.inner.get(idx)
+    /// Readiness is queued by the threads calling `set_readiness` and taken
+    /// by `poll` without locking. When threads race, the losers retry. High
+    /// retry counts relative to the number of events mean producers are
+    /// spending time fighting over the queue, typically because there are
+    /// more producer threads than the single `poll` thread can keep up with.
+    ///
+    /// The counters are only updated when a retry happens, uncontended
+    /// operations do not touch them.
+    pub fn queue_contention_stats(&self) -> ContentionStats {
+        let inner = self.readiness_queue.inner();
+
+        ContentionStats {
+            enqueue_retries: inner.enqueue_retries.load(Ordering::Relaxed),
+            dequeue_retries: inner.dequeue_retries.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets whether the readiness of a custom `Registration` is delivered as
+    /// a single event.
+    ///
+    /// By default (`true`), a registration that is both readable and
+    /// writable when `poll` processes it yields one event with both kinds.
+    /// When set to `false`, it yields one event per readiness kind (readable,
+    /// writable, error, hup), all with the registration's token, in
+    /// consecutive slots of `Events`. A registration may then use up to four
+    /// slots; like the events of custom registrations in general, they are
+    /// not bounded by the capacity given to `Events::with_capacity`.
+    ///
+    /// The split is applied to whatever a single dequeue delivers. An
+    /// edge-triggered registration yields its events once, a
+    /// level-triggered one yields them again on every call to `poll`.
+    /// Events from the OS selector are not affected.
+    pub fn set_coalesce_readiness(&self, coalesce: bool) {
+        self.coalesce.set(coalesce);
+    }
+
+    /// Returns whether the readiness of a custom `Registration` is delivered
+    /// as a single event, see `set_coalesce_readiness`.
+    pub fn coalesce_readiness(&self) -> bool {
+        self.coalesce.get()
+    }
+
+    /// Sets whether `poll` measures how long it blocks in the selector.
+    ///
+    /// When enabled, every call to `poll` (and its variants) takes a
+    /// timestamp before and after waiting on the OS selector, and the
+    /// difference is available with `last_block_duration`. The rest of the
+    /// call, i.e. processing the readiness queue, is not included, which
+    /// separates idle waiting from processing. Disabled by default, to keep
+    /// the clock reads off the hot path.
+    pub fn enable_block_timing(&self, enable: bool) {
+        self.block_timing.set(enable);
+
+        if !enable {
+            self.last_block.set(None);
+        }
+    }
+
+    /// Returns how long the last call to `poll` waited on the OS selector.
+    ///
+    /// Returns `None` if block timing is disabled, see
+    /// `enable_block_timing`, or `poll` has not been called since it was
+    /// enabled. A call that found events while spinning (see
+    /// `new_with_spin`) never blocked and reports a zero duration.
+    pub fn last_block_duration(&self) -> Option<Duration> {
+        self.last_block.get()
+    }
+
+    /// Returns an `Events` capacity suited to the platform's selector.
+    ///
+    /// This is only advisory: any capacity works, a smaller buffer just
+    /// takes more calls to `poll` to drain all the ready events. The value
+    /// is constant for a given platform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::{Events, Poll};
+    /// use std::time::Duration;
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(poll.recommended_capacity());
+    ///
+    /// poll.poll(&mut events, Duration::from_millis(10)).unwrap();
+    /// ```
+    pub fn recommended_capacity(&self) -> usize {
+        self.selector.recommended_capacity()
+    }
+
+    /// Like `poll`, but appends the events to `dst`.
+    ///
+    /// `dst` is not cleared, events already in it are preserved and the new
+    /// events are pushed after them. Returns the number of events appended.
+    ///
+    /// The selector writes into a scratch buffer owned by the `Poll`
+    /// instance. The buffer is allocated on the first call and holds up to
+    /// 1024 events (see `PollBuilder::poll_into_capacity`), so at most that
+    /// many events from the OS selector are returned per call. Any remaining
+    /// events are returned by the next call.
+    pub fn poll_into<T: Timeout>(&self, dst: &mut Vec<Event>, timeout: T) -> io::Result<usize> {
+        let mut scratch = self.scratch.borrow_mut();
+
+        if scratch.is_none() {
+            *scratch = Some(Events::with_capacity(POLL_INTO_CAPACITY));
+        }
+
+        let events = scratch.as_mut().unwrap();
+        let n = try!(self.poll(events, timeout));
+
+        dst.extend(events.iter());
+        Ok(n)
+    }
+
+    /// Like `poll`, but returns the number of events that came from the OS
+    /// selector and from the userspace readiness queue (custom
+    /// `Registration` values) separately, as `(selector, userspace)`.
+    ///
+    /// The sum of both values is what `poll` would have returned.
+    pub fn poll_split<T: Timeout>(&self,
+                                  events: &mut Events,
+                                  timeout: T) -> io::Result<(usize, usize)> {
+        self.poll_deadlines(events, timeout.into_timeout()).map(|res| (res.sys_events, res.user_events))
+    }
+
+    /// Like `poll`, but also returns whether the call could block.
+    ///
+    /// The flag is true if the selector was entered with the requested
+    /// timeout, i.e. the thread could have been put to sleep. It is false if
+    /// `poll` returned without blocking: a zero timeout was requested, or
+    /// the custom readiness queue already had pending events so the selector
+    /// was only checked with a zero timeout. This is what a hybrid
+    /// spin / sleep loop needs to decide whether to keep spinning.
+    pub fn poll_with_blocking_flag<T: Timeout>(&self,
+                                               events: &mut Events,
+                                               timeout: T) -> io::Result<(usize, bool)> {
+        self.poll2(events, timeout.into_timeout()).map(|res| (res.len(), res.blocked))
+    }
+
+    /// Like `poll`, but also waits on raw file descriptors that are not
+    /// registered with this `Poll` instance.
+    ///
+    /// This is a migration aid for loops built around `select(2)` or
+    /// `poll(2)`, whose file descriptors are not wrapped as `Evented` yet.
+    /// On input, each entry of `extra` holds a file descriptor and the
+    /// readiness to wait for. The call returns once any of them is ready,
+    /// or `poll` would have returned anyway, and then overwrites each
+    /// interest with the readiness of the file descriptor, `Ready::none()`
+    /// if it is not ready. Errors and hang ups are reported regardless of
+    /// the interest. The returned count and `events` only cover the
+    /// registered handles, like `poll`.
+    ///
+    /// The extra file descriptors are not added to the selector. This call
+    /// waits on the selector itself and the extra file descriptors with
+    /// `poll(2)`, then collects the events with a non-blocking `poll`. It
+    /// therefore costs several system calls plus a scan of `extra` on every
+    /// call, and is level-triggered for the extra file descriptors. A file
+    /// descriptor that is also registered with this `Poll` is reported in
+    /// both places, according to each one's own interest and options.
+    ///
+    /// Only available on Unix.
+    #[cfg(unix)]
+    pub fn poll_with_extra_fds<T: Timeout>(&self,
+                                           events: &mut Events,
+                                           extra: &mut [(RawFd, Ready)],
+                                           timeout: T) -> io::Result<usize> {
+        if extra.is_empty() {
+            return self.poll(events, timeout);
+        }
+
+        let timeout = timeout.into_timeout();
+        let start = self.deadlines_start();
+
+        let mut fds = Vec::with_capacity(extra.len() + 1);
+        fds.push((self.selector.as_raw_fd(), Ready::readable()));
+        fds.extend(extra.iter().cloned());
+
+        let mut wait = timeout;
+
+        loop {
+            // Same handoff as `select`: the sleep marker makes producers
+            // write to the awakener, which makes the selector readable. A
+            // pending cancellation is consumed by the `poll2` below.
+            let queue = &self.readiness_queue;
+            let pending = self.is_shutdown() || queue.is_cancelled() ||
+                !queue.is_empty() || !queue.prepare_for_sleep();
+
+            let (capped_wait, capped) = if pending {
+                (Some(Duration::from_millis(0)), false)
+            } else {
+                self.deadline_wait(wait)
+            };
+
+            let res = sys::poll_fds(&fds, capped_wait);
+            queue.cancel_sleep();
+
+            let ready = try!(res);
+
+            for (entry, &ready) in extra.iter_mut().zip(ready[1..].iter()) {
+                entry.1 = ready;
+            }
+
+            let res = try!(self.poll2(events, Some(Duration::from_millis(0))));
+
+            if !capped || res.len() != 0 || extra.iter().any(|entry| !entry.1.is_none()) {
+                return Ok(res.len());
+            }
+
+            wait = remaining(start, timeout);
+        }
+    }
+
+    /// Like `poll`, but reports why the call returned.
+    ///
+    /// This distinguishes a timeout that fully elapsed from a call that
+    /// returned straight away without events, for example because a zero
+    /// timeout was requested. See `PollReason` for details.
+    pub fn poll_reason<T: Timeout>(&self,
+                                   events: &mut Events,
+                                   timeout: T) -> io::Result<PollReason> {
+        let timeout = timeout.into_timeout();
+        let res = try!(self.poll2(events, timeout));
+
+        if res.len() > 0 {
+            Ok(PollReason::Events(res.len()))
+        } else if timeout.is_some() && res.blocked && !res.awoken && !res.capped {
+            Ok(PollReason::TimedOut)
+        } else {
+            Ok(PollReason::Immediate)
+        }
+    }
+
+    // Waits until events arrive or `timeout` elapses, like `poll`. Waits cut
+    // short for a deadline that is not due yet are not returned.
+    fn poll_deadlines(&self,
+                      events: &mut Events,
+                      timeout: Option<Duration>) -> io::Result<PollResult> {
+        let start = self.deadlines_start();
+        let mut wait = timeout;
+
+        loop {
+            let res = try!(self.poll2(events, wait));
+
+            if !res.capped || res.len() != 0 {
+                return Ok(res);
+            }
+
+            wait = remaining(start, timeout);
+        }
+    }
+
+    // A single wait, bounded by the next deadline. The wheel may cut the
+    // wait short of any deadline, `capped` tells the callers that loop.
+    fn poll2(&self,
+             events: &mut Events,
+             timeout: Option<Duration>) -> io::Result<PollResult> {
+        let (wait, capped) = self.deadline_wait(timeout);
+
+        let mut res = try!(self.poll_step(events, wait));
+        res.capped = capped;
+
+        Ok(res)
+    }
+
+    // The start of a wait that may be cut short by deadlines, `None` if no
+    // deadline is armed
+    fn deadlines_start(&self) -> Option<Instant> {
+        if self.deadlines.borrow().is_empty() {
+            None
+        } else {
+            Some(Instant::now())
+        }
+    }
+
+    // Bounds `timeout` by the next deadline, returns true if it was cut short
+    fn deadline_wait(&self, timeout: Option<Duration>) -> (Option<Duration>, bool) {
+        let next = {
+            let deadlines = self.deadlines.borrow();
+
+            if deadlines.is_empty() {
+                None
+            } else {
+                deadlines.next_timeout(Instant::now())
+            }
+        };
+
+        match (timeout, next) {
+            (Some(timeout), Some(next)) if next < timeout => (Some(next), true),
+            (None, Some(next)) => (Some(next), true),
+            (timeout, _) => (timeout, false),
+        }
+    }
+
+    // A single call to the selector followed by the readiness queue drain
+    fn poll_step(&self,
+                 events: &mut Events,
+                 timeout: Option<Duration>) -> io::Result<PollResult> {
+        if self.is_shutdown() {
+            return Err(shutdown_error());
+        }
+
+        let spun = if timeout != Some(Duration::from_millis(0)) {
+            try!(self.spin(events))
+        } else {
+            None
+        };
+
+        let (awoken, timeout) = match spun {
+            Some(awoken) => {
+                if self.block_timing.get() {
+                    self.last_block.set(Some(Duration::from_millis(0)));
+                }
+
+                (awoken, Some(Duration::from_millis(0)))
+            }
+            None => {
+                let timeout = if self.readiness_queue.is_cancelled() {
+                    // The awakener is drained in one go, a pending
+                    // cancellation may have no byte left to wake on
+                    Some(Duration::from_millis(0))
+                } else if !self.has_userspace() {
+                    // Nothing but the selector to wait on
+                    timeout
+                } else if !self.readiness_queue.is_empty() {
+                    trace!("custom readiness queue has pending events");
+                    // Never block if the readiness queue has pending events
+                    Some(Duration::from_millis(0))
+                } else if !self.readiness_queue.prepare_for_sleep() {
+                    record_trace!(self.readiness_queue, TraceEvent::PrepareForSleep(false));
+                    Some(Duration::from_millis(0))
+                } else {
+                    record_trace!(self.readiness_queue, TraceEvent::PrepareForSleep(true));
+                    timeout
+                };
+
+                let start = if self.block_timing.get() {
+                    Some(Instant::now())
+                } else {
+                    None
+                };
+
+                // First get selector events
+                let awoken = try!(self.selector.select(&mut events.inner, AWAKEN,
+                                                       timeout));
+
+                if let Some(start) = start {
+                    self.last_block.set(Some(start.elapsed()));
+                }
+
+                (awoken, timeout)
+            }
+        };
+
+        record_trace!(self.readiness_queue, TraceEvent::Awoken(awoken));
+
+        if awoken {
+            if let Some(ref awakener) = self.readiness_queue.inner().awakener {
+                awakener.cleanup();
+            }
+        }
+
+        if self.is_shutdown() {
+            return Err(shutdown_error());
+        }
+
+        let sys_len = events.len();
+
+        if self.readiness_queue.take_cancelled() {
+            // Leave the readiness queue to the next call. Nothing was
+            // dequeued, so the sleep marker may still be set.
+            self.readiness_queue.cancel_sleep();
+
+            events.handled.clear();
+            events.user_start = sys_len;
+
+            return Ok(PollResult {
+                sys_events: sys_len,
+                user_events: 0,
+                blocked: timeout != Some(Duration::from_millis(0)),
+                awoken: awoken,
+                capped: false,
+            });
+        }
+
+        // Poll custom event queue
+        if self.has_userspace() {
+            self.readiness_queue.poll(&mut events.inner, self.coalesce.get());
+        }
+
+        // Events disarm the deadlines of their tokens, the expired ones are
+        // returned along with the events of the readiness queue
+        self.deadlines.borrow_mut().poll(&mut events.inner, Instant::now());
+
+        // Any state kept for `poll_mut` refers to the previous events
+        events.handled.clear();
+        events.user_start = sys_len;
+
+        // Return number of polled events
+        Ok(PollResult {
+            sys_events: sys_len,
+            user_events: events.len() - sys_len,
+            blocked: timeout != Some(Duration::from_millis(0)),
+            awoken: awoken,
+            capped: false,
+        })
+    }
+
+    /// Busy-polls for up to `spin_iters` rounds. Returns `Some(awoken)` once
+    /// the selector has events, which are stored in `events`, or `None` to
+    /// continue with the regular path. That path doesn't block if the
+    /// readiness queue has pending events.
+    fn spin(&self, events: &mut Events) -> io::Result<Option<bool>> {
+        for _ in 0..self.spin_iters {
+            if !self.readiness_queue.is_empty() {
+                return Ok(None);
+            }
+
+            // The sleep marker is not set while spinning, so producers don't
+            // write to the awakener and it can only be ready if it was left
+            // so by a previous call.
+            let awoken = try!(self.selector.select(&mut events.inner, AWAKEN,
+                                                   Some(Duration::from_millis(0))));
+
+            if awoken || !events.inner.is_empty() {
+                return Ok(Some(awoken));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Arrange for readiness observed on this `Poll` for `token` to be
+    /// forwarded to `set_readiness`, typically belonging to a `Registration`
+    /// of another (parent) `Poll` instance.
+    ///
+    /// Each forwarded token needs its own `Registration` in the destination
+    /// `Poll`. The destination sees events with the token of that
+    /// registration, not `token`. Replaces any previous forwarding for
+    /// `token`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::*;
+    /// use std::time::Duration;
+    ///
+    /// let parent = Poll::new().unwrap();
+    /// let child = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(1024);
+    ///
+    /// let (_registration, set_readiness) =
+    ///     Registration::new(&parent, Token(100), Ready::readable(), PollOpt::edge());
+    ///
+    /// child.forward_to(Token(1), set_readiness);
+    /// child.forward_ready(Token(1), Ready::readable()).unwrap();
+    ///
+    /// parent.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    /// assert_eq!(events.get(0).unwrap().token(), Token(100));
+    /// ```
+    pub fn forward_to(&self, token: Token, set_readiness: SetReadiness) {
+        self.forwards.borrow_mut().insert(token, set_readiness);
+    }
+
+    /// Stop forwarding readiness for `token`, returning the destination
+    /// handle if there was one.
+    pub fn stop_forwarding(&self, token: Token) -> Option<SetReadiness> {
+        self.forwards.borrow_mut().remove(&token)
+    }
+
+    /// Forward `ready` for `token` to the destination set up with
+    /// `forward_to`.
+    ///
+    /// The readiness is set directly on the destination registration, no
+    /// `Event` is copied. Returns an error of kind `NotFound` if no
+    /// forwarding exists for `token`.
+    pub fn forward_ready(&self, token: Token, ready: Ready) -> io::Result<()> {
+        match self.forwards.borrow().get(&token) {
+            Some(set_readiness) => set_readiness.set_readiness(ready),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no forwarding for token")),
+        }
+    }
+
+    /// Shut down the `Poll` instance.
+    ///
+    /// All subsequent calls to `poll` return an error. Use `shutdown_handle`
+    /// to shut down the instance from another thread.
+    ///
+    /// The error has kind `BrokenPipe`, which the selector never reports, so
+    /// it can be told apart from a selector failure. Shutting down is
+    /// permanent, and doing it again has no further effect.
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.readiness_queue.shutdown()
+    }
+
+    /// Prepares for waiting on the descriptor returned by `as_raw_fd` from
+    /// another event loop.
+    ///
+    /// Threads setting readiness on custom `Registration` values only wake
+    /// up the selector when `Poll` is about to block. Calling this before
+    /// handing the descriptor to the outer loop makes their next readiness
+    /// change wake it up too. Returns false if readiness is already pending,
+    /// in which case `poll` should be called right away instead of waiting.
+    ///
+    /// The next call to `poll` returns without blocking.
+    ///
+    /// Only available on Unix.
+    #[cfg(unix)]
+    pub fn prepare_external_wait(&self) -> bool {
+        if self.is_shutdown() {
+            return false;
+        }
+
+        if !self.has_userspace() {
+            return true;
+        }
+
+        self.readiness_queue.prepare_for_sleep()
+    }
+
+    /// Returns a handle that can be used to shut down the `Poll` instance from
+    /// any thread, waking up a blocked call to `poll`.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle { queue: Mutex::new(self.readiness_queue.clone()) }
+    }
+
+    /// Returns true if the `Poll` instance has been shut down.
+    pub fn is_shutdown(&self) -> bool {
+        self.readiness_queue.is_shutdown()
+    }
+
+    /// Returns a handle that can be used to cancel a call to `poll` from any
+    /// thread.
+    ///
+    /// A cancelled call returns `Ok` as soon as the selector does, without
+    /// draining the readiness queue: custom `Registration` values that are
+    /// ready are delivered by the next call. Events already reported by the
+    /// OS selector are still returned, so none are lost, but in general a
+    /// cancelled call returns `Ok(0)`.
+    ///
+    /// If the instance was created with `new_no_userspace`, there is no
+    /// awakener to interrupt a blocked call. The cancellation is then
+    /// consumed by the next call to `poll`, which does not block.
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken { queue: self.readiness_queue.clone() }
+    }
+
+    /// Sets a soft limit on the number of custom registrations that are
+    /// ready at the same time, or removes it with `None`.
+    ///
+    /// A registration counts as ready from the moment it is queued by a
+    /// `SetReadiness` call until `poll` dequeues it. A level-triggered
+    /// registration that stays ready is queued again by `poll`, so it keeps
+    /// counting. Once the limit is reached, `SetReadiness::set_readiness`
+    /// (as well as `set_readiness_release` and `ReadinessGroup::set_all`)
+    /// returns an error of kind `WouldBlock` instead of queueing another
+    /// registration, leaving its readiness untouched, so that the producer
+    /// can back off until `poll` catches up.
+    ///
+    /// A registration that is already queued is not counted twice: setting
+    /// its readiness again always succeeds, as does setting it to
+    /// `Ready::none()`. The limit is soft, producers racing each other may
+    /// exceed it slightly.
+    ///
+    /// Readiness set by mio itself is never refused, as the wakeup would be
+    /// lost: this covers `WakeHandle`, `PollCondvar`, channels, timers and
+    /// the final events of released or hung up registrations.
+    pub fn set_readiness_limit(&self, limit: Option<usize>) {
+        let limit = limit.unwrap_or(usize::MAX);
+        self.readiness_queue.inner().readiness_limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Returns the limit set with `set_readiness_limit`.
+    pub fn readiness_limit(&self) -> Option<usize> {
+        match self.readiness_queue.inner().readiness_limit.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Returns a handle that wakes up `poll` with a readable event for
+    /// `token`.
+    ///
+    /// The handle is backed by a `Registration` owned by the `Poll`
+    /// instance. Any number of wake tokens may be registered at the same
+    /// time. Registering a token again replaces the previous registration,
+    /// handles obtained before then no longer produce events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::*;
+    /// use std::thread;
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(1024);
+    ///
+    /// let waker = poll.register_wakeup_token(Token(10)).unwrap();
+    ///
+    /// thread::spawn(move || {
+    ///     waker.wake().unwrap();
+    /// });
+    ///
+    /// poll.poll(&mut events, None).unwrap();
+    /// assert_eq!(events.get(0).unwrap().token(), Token(10));
+    /// ```
+    pub fn register_wakeup_token(&self, token: Token) -> io::Result<WakeHandle> {
+        try!(validate_args(token, Ready::readable()));
+        try!(validate_userspace(self));
+
+        let (registration, set_readiness) = Registration::new(self, token, Ready::readable(), PollOpt::edge());
+        self.wake_tokens.borrow_mut().insert(token, registration);
+
+        Ok(WakeHandle { set_readiness: set_readiness })
+    }
+
+    /// Remove a wake token registered with `register_wakeup_token`.
+    ///
+    /// Returns false if no wake token was registered for `token`.
+    pub fn deregister_wakeup_token(&self, token: Token) -> bool {
+        self.wake_tokens.borrow_mut().remove(&token).is_some()
+    }
+
+    /// Start recording the internal decisions made by this `Poll` instance
+    /// and by producers setting readiness on its registrations.
+    #[cfg(test)]
+    fn attach_trace(&self) -> PollTrace {
+        *self.readiness_queue.inner().trace.lock().unwrap() = Some(Vec::new());
+        PollTrace { queue: self.readiness_queue.clone() }
+    }
+
+    /// Returns a snapshot of the custom registrations associated with this
+    /// `Poll` as `(token, interest, readiness, opts)` tuples, in the order
+    /// the registrations were created.
+    ///
+    /// Only `Registration` values are included. Handles registered with the
+    /// OS selector (sockets, pipes, ...) cannot be enumerated portably and
+    /// are not part of the result. Registrations that have been deregistered
+    /// or dropped are skipped.
+    ///
+    /// This walks every registration node and allocates the result, so it is
+    /// O(n) in the number of registrations. It is intended for debugging and
+    /// introspection, not for use on a hot path.
+    pub fn registered(&self) -> Vec<(Token, Ready, Ready, PollOpt)> {
+        self.readiness_queue.registered()
+            .into_iter()
+            .map(|(token, _, interest, readiness, opts)| (token, interest, readiness, opts))
+            .collect()
+    }
+
+    /// Like `registered`, but includes the name of each registration, see
+    /// `Registration::new_named`.
+    ///
+    /// The names are cloned into the result, which allocates for names that
+    /// were given as a `String`.
+    pub fn registered_named(&self) -> Vec<(Token, Option<Cow<'static, str>>, Ready, Ready, PollOpt)> {
+        self.readiness_queue.registered()
+    }
+}
+
+/// A timeout accepted by `Poll::poll`, its variants and `select`.
+///
+/// Implemented for `Duration`, which blocks for at most that long, and
+/// `Option<Duration>`, where `None` blocks until an event arrives. A local
+/// trait is used rather than `Into<Option<Duration>>` so that a bare `None`
+/// is still accepted without type annotations.
+pub trait Timeout {
+    /// Converts the value to the optional timeout used by the selector.
+    fn into_timeout(self) -> Option<Duration>;
+}
+
+impl Timeout for Duration {
+    fn into_timeout(self) -> Option<Duration> {
+        Some(self)
+    }
+}
+
+impl Timeout for Option<Duration> {
+    fn into_timeout(self) -> Option<Duration> {
+        self
+    }
+}
+
+/// Contention counters of the readiness queue, returned by
+/// `Poll::queue_contention_stats`.
+///
+/// The counters are cumulative since the `Poll` was created.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ContentionStats {
+    enqueue_retries: usize,
+    dequeue_retries: usize,
+}
+
+impl ContentionStats {
+    /// Number of times a thread setting readiness raced with another thread
+    /// queuing a registration (or with `poll` taking the queue) and had to
+    /// try again.
+    pub fn enqueue_retries(&self) -> usize {
+        self.enqueue_retries
+    }
+
+    /// Number of times `poll` had to try again to dequeue a registration
+    /// because its state was concurrently changed.
+    pub fn dequeue_retries(&self) -> usize {
+        self.dequeue_retries
+    }
+}
+
+/// Describes why a call to `Poll::poll_reason` returned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PollReason {
+    /// The given number of events (always greater than zero) was returned.
+    Events(usize),
+
+    /// No events were returned and the requested, non-zero, timeout elapsed.
+    TimedOut,
+
+    /// No events were returned and the call did not wait for the timeout to
+    /// elapse. This happens when a zero timeout was requested, when the
+    /// userspace readiness queue had pending nodes that did not produce
+    /// events, or when `poll` was woken up without yielding events.
+    Immediate,
+}
+
+// Outcome of a single turn of `Poll::poll2`
+struct PollResult {
+    // Number of events returned by the selector
+    sys_events: usize,
+    // Number of events returned by the readiness queue
+    user_events: usize,
+    // True if the selector was entered with a non-zero timeout
+    blocked: bool,
+    // True if the awakener fired
+    awoken: bool,
+    // True if the wait was shortened for a deadline
+    capped: bool,
+}
+
+impl PollResult {
+    fn len(&self) -> usize {
+        self.sys_events + self.user_events
+    }
+}
+
+fn released_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "registration has been released")
+}
+
+fn readiness_limit_error() -> io::Error {
+    io::Error::new(io::ErrorKind::WouldBlock, "readiness limit reached")
+}
+
+fn shutdown_error() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "poll has been shut down")
+}
+
+fn validate_userspace(poll: &Poll) -> io::Result<()> {
+    if !poll.has_userspace() {
+        return Err(io::Error::new(io::ErrorKind::Other, "poll has no readiness queue for custom registrations"));
+    }
+
+    Ok(())
+}
+
+fn validate_args(token: Token, interest: Ready) -> io::Result<()> {
+    if token == AWAKEN {
+        return Err(io::Error::new(io::ErrorKind::Other, "invalid token"));
+    }
+
+    if usize::from(token) >= WAKE_TOKENS_START && usize::from(token) < WAKE_TOKENS_END {
+        return Err(io::Error::new(io::ErrorKind::Other, "token is reserved for wake tokens"));
+    }
+
+    if !interest.is_readable() && !interest.is_writable() && !interest.is_priority() {
+        return Err(io::Error::new(io::ErrorKind::Other, "interest must include readable, writable or priority"));
+    }
+
+    Ok(())
+}
+
+impl Drop for Poll {
+    fn drop(&mut self) {
+        self.readiness_queue.inner().poll_alive.store(false, Ordering::Release);
+    }
+}
+
+impl fmt::Debug for Poll {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Poll")
+    }
+}
+
+/// Returns the epoll / kqueue descriptor backing the `Poll`, so that it can
+/// be nested in another event loop.
+///
+/// The descriptor is readable whenever a handle registered with this `Poll`
+/// is ready. The outer loop must only wait for it to become readable (level
+/// triggered, without consuming anything) and then call `poll` with a zero
+/// timeout to collect the events. `Poll` stays the single consumer of the
+/// descriptor: reading from it, or registering it with `EPOLLEXCLUSIVE` or
+/// as one-shot, is not supported. The descriptor is owned by the `Poll` and
+/// closed when it is dropped.
+///
+/// Readiness set on custom `Registration` values only makes the descriptor
+/// readable while `Poll` is waiting for it, see `prepare_external_wait`.
+#[cfg(unix)]
+impl AsRawFd for Poll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.selector.as_raw_fd()
+    }
+}
+
+/// A buffer for I/O events to get placed into, passed to `Poll::poll`.
+///
+/// This structure is normally re-used on each turn of the event loop and will
+/// contain any I/O events that happen during a `poll`. After a call to `poll`
+/// returns the various accessor methods on this structure can be used to
+/// iterate over the underlying events that ocurred.
+///
+/// Every call to `poll` discards the events left over from the previous call
+/// before filling the buffer, so there is no need to `clear` it in between.
+/// Only `Poll::poll_mut` carries events over, those not marked as handled.
+pub struct Events {
+    inner: sys::Events,
+
+    // Per event "handled" flags, see `Poll::poll_mut`
+    handled: Vec<bool>,
+
+    // Index of the first event that came from the readiness queue
+    user_start: usize,
+}
+
+/// Iterate an Events structure
+pub struct EventsIter<'a> {
+    inner: &'a Events,
+    pos: usize,
+}
+
+/// Drains an Events structure, see `Events::drain`.
+pub struct EventsDrain<'a> {
+    inner: &'a mut Events,
+    pos: usize,
+}
+
+/// Owning iterator over an Events structure.
+pub struct EventsIntoIter {
+    inner: Events,
+    pos: usize,
+}
+
+/// Iterate an Events structure, allowing events to be marked as handled. See
+/// `Poll::poll_mut`.
+pub struct EventsIterMut<'a> {
+    inner: &'a sys::Events,
+    handled: slice::IterMut<'a, bool>,
+    pos: usize,
+}
+
+/// An event returned by `EventsIterMut`.
+pub struct EventMut<'a> {
+    event: Event,
+    handled: &'a mut bool,
+}
+
+impl Events {
+    /// Create a net blank set of events capable of holding up to `capacity`
+    /// events.
+    ///
+    /// This parameter typically is an indicator on how many events can be
+    /// returned each turn of the event loop, but it is not necessarily a hard
+    /// limit across platforms.
+    ///
+    /// A `capacity` of zero is treated as one. The OS selectors need room
+    /// for at least one event (epoll rejects an empty buffer, kqueue and IOCP
+    /// return without waiting), so an empty buffer could never observe any
+    /// readiness.
+    pub fn with_capacity(capacity: usize) -> Events {
+        Events {
+            inner: sys::Events::with_capacity(cmp::max(capacity, 1)),
+            handled: Vec::new(),
+            user_start: 0,
+        }
+    }
+
+    /// Returns the `idx`-th event.
+    ///
+    /// Returns `None` if `idx` is greater than or equal to the length of this
+    /// event buffer.
+    pub fn get(&self, idx: usize) -> Option<Event> {
+        self.inner.get(idx)
+    }
+
+    /// Returns the `idx`-th event without checking that `idx` is in bounds.
+    ///
+    /// This is intended for tight dispatch loops that already iterate over
+    /// `0..len()`.
+    ///
+    /// # Safety
+    ///
+    /// `idx` must be less than `len()`, otherwise the behavior is undefined.
+    pub unsafe fn get_unchecked(&self, idx: usize) -> Event {
+        debug_assert!(idx < self.len());
+        self.inner.get_unchecked(idx)
     }
 
     /// Returns how many events this buffer contains.
@@ -314,15 +1914,146 @@ impl Events {
         self.inner.len()
     }
 
-    /// Returns whether this buffer contains 0 events.
-    pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+    /// Returns the number of events the OS selector can report in a single
+    /// call to `poll`.
+    ///
+    /// This is at least the capacity given to `with_capacity`. Events from
+    /// custom `Registration` values are added on top and are not limited by
+    /// it. When `poll` returns at least `capacity()` events, more events
+    /// may have been ready than the buffer could hold; they are reported by
+    /// the next call, see `reserve` to grow the buffer in that case.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Grows the capacity by at least `additional` events.
+    ///
+    /// The events currently in the buffer are kept, as is the handled state
+    /// used by `Poll::poll_mut`. The backing storage is reallocated at most
+    /// once, and reused by all subsequent calls. The capacity never shrinks,
+    /// create a new `Events` to get a smaller buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::{Events, Poll};
+    /// use std::time::Duration;
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(16);
+    ///
+    /// poll.poll(&mut events, Duration::from_millis(10)).unwrap();
+    ///
+    /// if events.len() >= events.capacity() {
+    ///     // Saturated, double the buffer for the next call
+    ///     let additional = events.capacity();
+    ///     events.reserve(additional);
+    /// }
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    /// Returns whether this buffer contains 0 events.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Removes all the events, keeping the capacity.
+    ///
+    /// The handled state used by `Poll::poll_mut` is discarded as well, so
+    /// the next `poll_mut` with this buffer does not return any event again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::{Events, Poll};
+    /// use std::time::Duration;
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(16);
+    ///
+    /// poll.poll(&mut events, Duration::from_millis(10)).unwrap();
+    /// events.clear();
+    ///
+    /// assert!(events.is_empty());
+    /// assert!(events.capacity() >= 16);
+    /// ```
+    pub fn clear(&mut self) {
+        self.inner.truncate(0);
+        self.handled.clear();
+        self.user_start = 0;
+    }
+
+    pub fn iter<'a>(&'a self) -> EventsIter<'a> {
+        EventsIter {
+            inner: self,
+            pos: 0
+        }
+    }
+
+    /// Removes all the events from the buffer, returning them as an
+    /// iterator.
+    ///
+    /// The buffer is empty once the iterator is dropped, even if it was not
+    /// fully consumed, and keeps its capacity. As with `clear`, the handled
+    /// state used by `Poll::poll_mut` is discarded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::{Event, Events, Poll, Ready, Registration, PollOpt, Token};
+    /// use std::time::Duration;
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let mut events = Events::with_capacity(16);
+    ///
+    /// let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    /// set_readiness.set_readiness(Ready::readable()).unwrap();
+    ///
+    /// poll.poll(&mut events, Duration::from_millis(300)).unwrap();
+    ///
+    /// let mut queue: Vec<Event> = Vec::new();
+    /// queue.extend(events.drain());
+    ///
+    /// assert_eq!(1, queue.len());
+    /// assert!(events.is_empty());
+    /// ```
+    pub fn drain<'a>(&'a mut self) -> EventsDrain<'a> {
+        EventsDrain {
+            inner: self,
+            pos: 0,
+        }
+    }
+
+    /// Sorts the events in place by ascending token.
+    ///
+    /// The sort is stable, events with the same token keep their relative
+    /// order. It takes O(n log n) time in the number of events and does not
+    /// allocate.
+    ///
+    /// Sorting mixes selector and custom events, so the handled state used
+    /// by `Poll::poll_mut` is discarded: the next `poll_mut` with this buffer
+    /// does not return any event again, as if the buffer had been passed to
+    /// `poll`.
+    pub fn sort_by_token(&mut self) {
+        self.inner.sort_by_token();
+        self.handled.clear();
+        self.user_start = self.inner.len();
     }
 
-    pub fn iter(&self) -> EventsIter {
-        EventsIter {
-            inner: self,
-            pos: 0
+    /// Iterate over the events, allowing each one to be marked as handled.
+    ///
+    /// Events start out unhandled. Marking events only has an effect when
+    /// the buffer is passed to `Poll::poll_mut` next, see there.
+    pub fn iter_mut<'a>(&'a mut self) -> EventsIterMut<'a> {
+        let len = self.inner.len();
+        self.handled.resize(len, false);
+
+        EventsIterMut {
+            inner: &self.inner,
+            handled: self.handled.iter_mut(),
+            pos: 0,
         }
     }
 }
@@ -331,25 +2062,550 @@ impl<'a> IntoIterator for &'a Events {
     type Item = Event;
     type IntoIter = EventsIter<'a>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Events {
+    type Item = Event;
+    type IntoIter = EventsIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        EventsIntoIter {
+            inner: self,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for EventsIter<'a> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        // Once the end is reached, `pos` points one past the last event and
+        // the iterator relies on `get` returning `None` for it. This must
+        // never use `get_unchecked`.
+        let ret = self.inner.get(self.pos);
+        self.pos += 1;
+        ret
+    }
+}
+
+impl<'a> Iterator for EventsDrain<'a> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let ret = self.inner.get(self.pos);
+
+        if ret.is_some() {
+            self.pos += 1;
+        }
+
+        ret
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.inner.len() - self.pos;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> Drop for EventsDrain<'a> {
+    fn drop(&mut self) {
+        self.inner.clear();
+    }
+}
+
+impl Iterator for EventsIntoIter {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let ret = self.inner.get(self.pos);
+
+        if ret.is_some() {
+            self.pos += 1;
+        }
+
+        ret
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.inner.len() - self.pos;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> Iterator for EventsIterMut<'a> {
+    type Item = EventMut<'a>;
+
+    fn next(&mut self) -> Option<EventMut<'a>> {
+        let handled = match self.handled.next() {
+            Some(handled) => handled,
+            None => return None,
+        };
+
+        let event = self.inner.get(self.pos).unwrap();
+        self.pos += 1;
+
+        Some(EventMut {
+            event: event,
+            handled: handled,
+        })
+    }
+}
+
+impl<'a> EventMut<'a> {
+    /// Returns the event.
+    pub fn event(&self) -> Event {
+        self.event
+    }
+
+    /// Returns the readiness of the event.
+    pub fn kind(&self) -> Ready {
+        self.event.kind()
+    }
+
+    /// Returns the token of the event.
+    pub fn token(&self) -> Token {
+        self.event.token()
+    }
+
+    /// Returns true if the event has been marked as handled.
+    pub fn is_handled(&self) -> bool {
+        *self.handled
+    }
+
+    /// Mark the event as handled, or not.
+    pub fn set_handled(&mut self, handled: bool) {
+        *self.handled = handled;
+    }
+}
+
+impl<'a> fmt::Debug for EventMut<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("EventMut")
+            .field("event", &self.event)
+            .field("handled", self.handled)
+            .finish()
+    }
+}
+
+/*
+ *
+ * ===== PollBuilder =====
+ *
+ */
+
+impl PollBuilder {
+    fn new() -> PollBuilder {
+        PollBuilder {
+            poll_into_capacity: None,
+            cloexec: true,
+            awakener: AwakenerKind::default(),
+            userspace: true,
+        }
+    }
+
+    /// Sets the capacity of the scratch buffer `Poll::poll_into` drains the
+    /// selector and the readiness queue into, which is then allocated up
+    /// front rather than on the first call. Defaults to 1024 events.
+    ///
+    /// Only `poll_into` uses this buffer, the other variants of `poll` use
+    /// the `Events` they are given.
+    pub fn poll_into_capacity(mut self, capacity: usize) -> PollBuilder {
+        self.poll_into_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets whether the selector's file descriptor is closed on `exec`.
+    /// Defaults to true. Has no effect on Windows.
+    pub fn cloexec(mut self, cloexec: bool) -> PollBuilder {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Sets the awakener used by the readiness queue. Defaults to
+    /// `AwakenerKind::default()`.
+    ///
+    /// On Windows the awakener always posts to the completion port, and the
+    /// kind is ignored.
+    pub fn awakener(mut self, kind: AwakenerKind) -> PollBuilder {
+        self.awakener = kind;
+        self
+    }
+
+    /// Sets whether the userspace readiness queue for custom `Registration`
+    /// values is enabled. Defaults to true, see `Poll::new_no_userspace` for
+    /// what is unavailable without it. The awakener setting is then
+    /// ignored.
+    pub fn userspace(mut self, enabled: bool) -> PollBuilder {
+        self.userspace = enabled;
+        self
+    }
+
+    /// Creates the `Poll` instance.
+    pub fn build(self) -> io::Result<Poll> {
+        let awakener = if self.userspace {
+            Some(try!(sys::Awakener::with_kind(self.awakener)))
+        } else {
+            None
+        };
+
+        let poll = try!(Poll::new_inner(awakener, self.cloexec));
+
+        // Register the notification wakeup FD with the IO poller
+        if let Some(ref awakener) = poll.readiness_queue.inner().awakener {
+            try!(awakener.register(&poll, AWAKEN, Ready::readable(), PollOpt::edge()));
+        }
+
+        if let Some(capacity) = self.poll_into_capacity {
+            *poll.scratch.borrow_mut() = Some(Events::with_capacity(capacity));
+        }
+
+        Ok(poll)
+    }
+}
+
+impl Default for PollBuilder {
+    fn default() -> PollBuilder {
+        PollBuilder::new()
+    }
+}
+
+/*
+ *
+ * ===== select =====
+ *
+ */
+
+/// Blocks until at least one of `polls` has pending events, or until
+/// `timeout` elapses, and returns the indices of the `Poll` instances that
+/// have events.
+///
+/// No event is consumed: call `poll` on the returned instances to get them.
+/// All the instances found ready are returned, in ascending order, so none
+/// of them can starve the others; it is up to the caller to decide in which
+/// order to poll them. An empty `Vec` means the timeout elapsed. A `Poll`
+/// that has been shut down is always reported, its next call to `poll`
+/// returns the shutdown error.
+///
+/// The returned set is a snapshot. An instance may become ready right after
+/// it was checked, it is then reported by the next call to `select`, which
+/// does not block. Conversely, an instance may be reported and `poll`
+/// return no event, which is the same as any spurious wakeup.
+///
+/// # Platform differences
+///
+/// On Unix, the selectors (epoll or kqueue) of all the instances are waited
+/// on at once with `poll(2)`, so `select` returns as soon as one of them is
+/// ready. Custom `Registration` readiness set from another thread wakes it up
+/// as well. The timeout is rounded up to the next millisecond.
+///
+/// On Windows, completion ports can't be waited on together. Each instance
+/// is probed in turn with a zero timeout, which turns completed I/O into
+/// readiness without losing it, and the thread sleeps between rounds, from
+/// 1 millisecond doubling up to 16 milliseconds. Events are therefore noticed
+/// with up to 16 milliseconds of latency. The sleeps never extend past the
+/// timeout.
+///
+/// # Examples
+///
+/// ```
+/// use mio::*;
+/// use std::time::Duration;
+///
+/// let net = Poll::new().unwrap();
+/// let timers = Poll::new().unwrap();
+///
+/// let (_registration, set_readiness) =
+///     Registration::new(&timers, Token(0), Ready::readable(), PollOpt::edge());
+/// set_readiness.set_readiness(Ready::readable()).unwrap();
+///
+/// let ready = mio::select(&[&net, &timers], Some(Duration::from_secs(1))).unwrap();
+/// assert_eq!(vec![1], ready);
+/// ```
+pub fn select<T: Timeout>(polls: &[&Poll], timeout: T) -> io::Result<Vec<usize>> {
+    select_polls(polls, timeout.into_timeout())
+}
+
+#[cfg(unix)]
+fn select_polls(polls: &[&Poll], timeout: Option<Duration>) -> io::Result<Vec<usize>> {
+    let mut pending = vec![false; polls.len()];
+
+    for (i, poll) in polls.iter().enumerate() {
+        // Setting the sleep marker makes producers write to the awakener,
+        // which makes the selector readable.
+        let queue = &poll.readiness_queue;
+        pending[i] = poll.is_shutdown() || !queue.is_empty() || !queue.prepare_for_sleep();
+    }
+
+    let timeout = if pending.iter().any(|&p| p) {
+        Some(Duration::from_millis(0))
+    } else {
+        timeout
+    };
+
+    let fds: Vec<_> = polls.iter().map(|poll| poll.selector.as_raw_fd()).collect();
+    let readable = try!(sys::poll_readable(&fds, timeout));
+
+    let mut ready = Vec::new();
+
+    for (i, poll) in polls.iter().enumerate() {
+        // Don't leave the marker behind, the next call to `poll` would
+        // otherwise skip blocking once.
+        poll.readiness_queue.cancel_sleep();
+
+        if pending[i] || readable[i] {
+            ready.push(i);
+        }
+    }
+
+    Ok(ready)
+}
+
+#[cfg(windows)]
+fn select_polls(polls: &[&Poll], timeout: Option<Duration>) -> io::Result<Vec<usize>> {
+    let deadline = timeout.map(|to| Instant::now() + to);
+    let mut backoff = Duration::from_millis(1);
+    let mut scratch = sys::Events::with_capacity(1024);
+
+    loop {
+        let mut ready = Vec::new();
+
+        for (i, poll) in polls.iter().enumerate() {
+            // Runs the completion callbacks, which set the readiness of the
+            // I/O handles in the readiness queue.
+            try!(poll.selector.select(&mut scratch, AWAKEN, Some(Duration::from_millis(0))));
+
+            if poll.is_shutdown() || !poll.readiness_queue.is_empty() {
+                ready.push(i);
+            }
+        }
+
+        if !ready.is_empty() {
+            return Ok(ready);
+        }
+
+        let sleep = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+
+                if now >= deadline {
+                    return Ok(ready);
+                }
+
+                cmp::min(backoff, deadline - now)
+            }
+            None => backoff,
+        };
+
+        thread::sleep(sleep);
+        backoff = cmp::min(backoff * 2, Duration::from_millis(16));
+    }
+}
+
+// ===== Accessors for internal usage =====
+
+pub fn selector(poll: &Poll) -> &sys::Selector {
+    &poll.selector
+}
+
+// Sets the readiness regardless of `Poll::set_readiness_limit`, for producers
+// within mio that can't retry.
+pub fn set_readiness_unlimited(set_readiness: &SetReadiness, ready: Ready) -> io::Result<()> {
+    set_readiness.inner.set_readiness_current(ready)
+}
+
+// Everything from the wake tokens up is reserved, see `Token::is_reserved`
+pub fn is_reserved_token(token: Token) -> bool {
+    usize::from(token) >= WAKE_TOKENS_START
+}
+
+// What is left of `timeout` since `start`, see `Poll::deadlines_start`
+fn remaining(start: Option<Instant>, timeout: Option<Duration>) -> Option<Duration> {
+    let elapsed = start.map(|start| start.elapsed()).unwrap_or(Duration::from_millis(0));
+
+    timeout.map(|timeout| {
+        if timeout > elapsed {
+            timeout - elapsed
+        } else {
+            Duration::from_millis(0)
+        }
+    })
+}
+
+/*
+ *
+ * ===== ShutdownHandle =====
+ *
+ */
+
+impl ShutdownHandle {
+    /// Shut down the associated `Poll` instance, waking it up if it is
+    /// currently blocked in `poll`. See `Poll::shutdown`.
+    ///
+    /// This is a no-op once the `Poll` has been dropped.
+    pub fn shutdown(&self) -> io::Result<()> {
+        self.queue.lock().unwrap().shutdown()
+    }
+}
+
+impl Clone for ShutdownHandle {
+    fn clone(&self) -> ShutdownHandle {
+        ShutdownHandle { queue: Mutex::new(self.queue.lock().unwrap().clone()) }
+    }
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ShutdownHandle")
+            .field("shutdown", &self.queue.lock().unwrap().is_shutdown())
+            .finish()
+    }
+}
+
+
+/*
+ *
+ * ===== CancelToken =====
+ *
+ */
+
+impl CancelToken {
+    /// Cancels one call to `poll` on the associated `Poll` instance, waking
+    /// it up if it is currently blocked.
+    ///
+    /// Cancellations add up: calling this twice before `poll` runs cancels
+    /// the next two calls.
+    pub fn cancel(&self) -> io::Result<()> {
+        self.queue.cancel()
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CancelToken")
+            .field("pending", &self.queue.inner().cancelled.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+unsafe impl Send for CancelToken { }
+unsafe impl Sync for CancelToken { }
+
+/*
+ *
+ * ===== PollTrace =====
+ *
+ */
+
+#[cfg(test)]
+impl PollTrace {
+    /// Returns the decisions recorded since the last call, in order.
+    fn take(&self) -> Vec<TraceEvent> {
+        match *self.queue.inner().trace.lock().unwrap() {
+            Some(ref mut trace) => mem::replace(trace, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+}
+
+/*
+ *
+ * ===== WakeHandle =====
+ *
+ */
+
+impl WakeHandle {
+    /// Wake up the associated `Poll` instance. The next call to `poll`
+    /// returns a readable event for the handle's token.
+    ///
+    /// Multiple calls to `wake` before `poll` runs are coalesced into a
+    /// single event.
+    pub fn wake(&self) -> io::Result<()> {
+        set_readiness_unlimited(&self.set_readiness, Ready::readable())
+    }
+
+    /// Returns the token events are delivered with.
+    pub fn token(&self) -> Token {
+        self.set_readiness.latest_token()
+    }
+}
+
+impl fmt::Debug for WakeHandle {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WakeHandle")
+            .field("token", &self.token())
+            .finish()
+    }
+}
+
+/*
+ *
+ * ===== Waker =====
+ *
+ */
+
+impl Waker {
+    /// Create a new `Waker` delivering events with `token` to `poll`.
+    ///
+    /// The token follows the same rules as for `Poll::register`, and must
+    /// not be used by another handle registered with `poll`.
+    #[cfg(unix)]
+    pub fn new(poll: &Poll, token: Token) -> io::Result<Waker> {
+        let awakener = try!(sys::Awakener::new());
+        try!(poll.register(&awakener, token, Ready::readable(), PollOpt::edge()));
+
+        Ok(Waker { awakener: awakener })
     }
-}
 
-impl<'a> Iterator for EventsIter<'a> {
-    type Item = Event;
+    /// Create a new `Waker` delivering events with `token` to `poll`.
+    ///
+    /// The token follows the same rules as for `Poll::register`, and must
+    /// not be used by another handle registered with `poll`.
+    #[cfg(windows)]
+    pub fn new(poll: &Poll, token: Token) -> io::Result<Waker> {
+        try!(validate_args(token, Ready::readable()));
+
+        let (registration, set_readiness) =
+            try!(Registration::try_new(poll, token, Ready::readable(), PollOpt::edge()));
+
+        Ok(Waker {
+            _registration: Mutex::new(registration),
+            set_readiness: set_readiness,
+        })
+    }
 
-    fn next(&mut self) -> Option<Event> {
-        let ret = self.inner.get(self.pos);
-        self.pos += 1;
-        ret
+    /// Wake up the associated `Poll` instance. The next call to `poll`
+    /// returns a readable event for the waker's token.
+    ///
+    /// Multiple calls to `wake` before `poll` runs may be coalesced into a
+    /// single event. There is nothing to reset after handling the event.
+    #[cfg(unix)]
+    pub fn wake(&self) -> io::Result<()> {
+        // Drain the previous wakeups first, so that the pipe never fills up
+        // and the write below is always seen as a new edge.
+        self.awakener.cleanup();
+        self.awakener.wakeup()
     }
-}
 
-// ===== Accessors for internal usage =====
+    /// Wake up the associated `Poll` instance. The next call to `poll`
+    /// returns a readable event for the waker's token.
+    ///
+    /// Multiple calls to `wake` before `poll` runs may be coalesced into a
+    /// single event. There is nothing to reset after handling the event.
+    #[cfg(windows)]
+    pub fn wake(&self) -> io::Result<()> {
+        set_readiness_unlimited(&self.set_readiness, Ready::readable())
+    }
+}
 
-pub fn selector(poll: &Poll) -> &sys::Selector {
-    &poll.selector
+impl fmt::Debug for Waker {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Waker")
+            .finish()
+    }
 }
 
 /*
@@ -362,6 +2618,11 @@ impl Registration {
     /// Create a new `Registration` associated with the given `Poll` instance.
     /// The returned `Registration` will be associated with this `Poll` for its
     /// entire lifetime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `poll` was created with `Poll::new_no_userspace`, see
+    /// `try_new`.
     pub fn new(poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> (Registration, SetReadiness) {
         let inner = RegistrationInner::new(poll, token, interest, opts);
         let registration = Registration { inner: inner.clone() };
@@ -370,13 +2631,293 @@ impl Registration {
         (registration, set_readiness)
     }
 
+    /// Like `new`, but returns an error instead of panicking if `poll` was
+    /// created with `Poll::new_no_userspace`.
+    pub fn try_new(poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+        -> io::Result<(Registration, SetReadiness)>
+    {
+        try!(validate_userspace(poll));
+        Ok(Registration::new(poll, token, interest, opts))
+    }
+
+    /// Create a new `Registration` whose events are delivered with the given
+    /// priority.
+    ///
+    /// Within a single call to `Poll::poll`, readiness events for
+    /// registrations with a higher priority are returned before events for
+    /// registrations with a lower priority. Registrations created with `new`
+    /// have a priority of 0. Events from the system selector are always
+    /// returned first, priorities only order the events of `Registration`
+    /// values amongst each other.
+    pub fn new_with_priority(poll: &Poll, token: Token, interest: Ready, opts: PollOpt, priority: u8)
+        -> (Registration, SetReadiness)
+    {
+        let (registration, set_readiness) = Registration::new(poll, token, interest, opts);
+        registration.inner.node().registration_data_mut().priority = priority;
+        (registration, set_readiness)
+    }
+
+    /// Create a new `Registration` whose readiness is cleared as soon as
+    /// `Poll::poll` delivers it.
+    ///
+    /// Each call to `SetReadiness::set_readiness` results in at most one
+    /// event, the handler does not need to reset the readiness to
+    /// `Ready::none()` itself. The readiness is taken atomically while the
+    /// event is being delivered: if `set_readiness` is called again while
+    /// `poll` is delivering the previous value, the new value is kept and
+    /// fires again on the next call to `poll`.
+    ///
+    /// This applies to level-triggered registrations too, the readiness only
+    /// stays set until it has been delivered once.
+    pub fn new_auto_reset(poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+        -> (Registration, SetReadiness)
+    {
+        let (registration, set_readiness) = Registration::new(poll, token, interest, opts);
+        registration.inner.node().registration_data_mut().auto_reset = true;
+        (registration, set_readiness)
+    }
+
+    /// Create a new `Registration` whose edge-triggered events only report
+    /// the readiness that was added since the previous event.
+    ///
+    /// By default, an edge-triggered event carries the readiness stored at
+    /// the time `poll` dequeues the registration. If the readiness went from
+    /// `readable` to `readable | writable` since the last event, the event
+    /// is `readable | writable`. With this registration, it is only
+    /// `writable`. A readiness that is unset and set again between two calls
+    /// to `poll` is not reported again, but once a call to `poll` has
+    /// observed it unset, setting it again is reported.
+    ///
+    /// Level-triggered registrations are not affected and always report the
+    /// full readiness.
+    pub fn new_incremental(poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+        -> (Registration, SetReadiness)
+    {
+        let mut inner = RegistrationInner::new(poll, token, interest, opts);
+        inner.node.as_mut().unwrap().incremental = true;
+
+        let registration = Registration { inner: inner.clone() };
+        let set_readiness = SetReadiness { inner: inner.clone() };
+
+        (registration, set_readiness)
+    }
+
+    /// Create a new `Registration` that is notified once all its producers
+    /// are gone.
+    ///
+    /// When the last `SetReadiness` handle for the registration is dropped,
+    /// `hup` is added to its readiness, so `poll` returns a final event with
+    /// `Ready::hup()` set, regardless of the interest. This is the
+    /// counterpart of a channel receiver observing that all senders
+    /// disconnected. Clones count as distinct handles, the event fires when
+    /// the last of them is dropped.
+    ///
+    /// This is opt-in: with the other constructors, dropping all
+    /// `SetReadiness` handles has no effect on the registration.
+    pub fn new_with_producer_hup(poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+        -> (Registration, SetReadiness)
+    {
+        let mut inner = RegistrationInner::new(poll, token, interest, opts);
+        inner.node.as_mut().unwrap().producer_hup = true;
+
+        let registration = Registration { inner: inner.clone() };
+        let set_readiness = SetReadiness { inner: inner.clone() };
+
+        (registration, set_readiness)
+    }
+
+    /// Create a new `Registration` with a name, for diagnostics.
+    ///
+    /// The name is shown by the `Debug` implementation of `Registration`, by
+    /// `Poll::registered_named` and in the trace logs of `poll`. It doesn't
+    /// affect event delivery, and doesn't need to be unique. Registrations
+    /// created with the other constructors have no name and store nothing
+    /// for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mio::{Poll, PollOpt, Ready, Registration, Token};
+    ///
+    /// let poll = Poll::new().unwrap();
+    /// let (registration, _set_readiness) =
+    ///     Registration::new_named(&poll, Token(0), Ready::readable(), PollOpt::edge(), "accept-listener");
+    ///
+    /// assert_eq!(Some("accept-listener"), registration.name());
+    /// ```
+    pub fn new_named<N>(poll: &Poll, token: Token, interest: Ready, opts: PollOpt, name: N)
+        -> (Registration, SetReadiness)
+        where N: Into<Cow<'static, str>>
+    {
+        let mut inner = RegistrationInner::new(poll, token, interest, opts);
+        inner.node.as_mut().unwrap().name = Some(name.into());
+
+        let registration = Registration { inner: inner.clone() };
+        let set_readiness = SetReadiness { inner: inner.clone() };
+
+        (registration, set_readiness)
+    }
+
+    /// Returns the name given with `new_named`, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.inner.node().name.as_ref().map(|name| &**name)
+    }
+
+    /// Create a new `Registration` that calls `callback` once it has been
+    /// released.
+    ///
+    /// The callback runs exactly once, when the registration's internal
+    /// state is actually freed. This happens after the `Registration` and
+    /// every `SetReadiness` handle have been dropped and `Poll` has processed
+    /// the release, which is usually during the next call to `poll`, or when
+    /// the `Poll` instance itself is dropped. The callback may run on any
+    /// thread.
+    pub fn new_with_drop_callback<F>(poll: &Poll, token: Token, interest: Ready, opts: PollOpt, callback: F)
+        -> (Registration, SetReadiness)
+        where F: FnOnce() + Send + 'static
+    {
+        let mut inner = RegistrationInner::new(poll, token, interest, opts);
+        *inner.node.as_mut().unwrap().drop_callback.get_mut() = Some(Box::new(callback));
+
+        let registration = Registration { inner: inner.clone() };
+        let set_readiness = SetReadiness { inner: inner.clone() };
+
+        (registration, set_readiness)
+    }
+
     pub fn update(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
         self.inner.update(poll, token, interest, opts)
     }
 
+    /// Returns the interest the registration was last registered or updated
+    /// with.
+    ///
+    /// A composite `Evented` built on top of a `Registration` can read this
+    /// in its `reregister` implementation, before calling `update`, to diff
+    /// the previous interest against the new one. `Ready::none()` is
+    /// returned once the registration has been deregistered.
+    ///
+    /// Like `update`, this must be called with the `Poll` instance the
+    /// registration belongs to.
+    pub fn interest(&self, poll: &Poll) -> io::Result<Ready> {
+        Ok(try!(self.inner.registration_data_mut(&poll.readiness_queue)).interest)
+    }
+
+    /// Update the registration, also changing its delivery priority.
+    ///
+    /// See `new_with_priority` for how priorities affect event ordering.
+    pub fn update_with_priority(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt, priority: u8)
+        -> io::Result<()>
+    {
+        try!(self.inner.registration_data_mut(&poll.readiness_queue)).priority = priority;
+        self.inner.update(poll, token, interest, opts)
+    }
+
     pub fn deregister(&self, poll: &Poll) -> io::Result<()> {
         self.inner.update(poll, Token(0), Ready::none(), PollOpt::empty())
     }
+
+    /// Deregister the registration and have `poll` release its internal
+    /// state without waiting for every handle to be dropped.
+    ///
+    /// With `deregister`, the state stays owned by `poll` until the
+    /// `Registration` and all `SetReadiness` handles are gone. Here, the next
+    /// call to `poll` hands it over to the handles: it no longer appears in
+    /// `registered` and is freed as soon as the last handle is dropped,
+    /// without any further processing by `poll`.
+    ///
+    /// The handles stay valid but are inert: `set_readiness` is a no-op and
+    /// the registration can't be updated or migrated anymore.
+    pub fn disable_and_release(&self, poll: &Poll) -> io::Result<()> {
+        if self.inner.is_released() {
+            return Ok(());
+        }
+
+        try!(self.deregister(poll));
+        self.inner.node().release.store(RELEASE_REQUESTED, Ordering::Release);
+
+        // Make sure `poll` sees the node. It is never dequeued again, so
+        // producers stop queuing it from here on.
+        let needs_wakeup = self.inner.queue_for_processing();
+        debug_assert!(!needs_wakeup, "something funky is going on");
+
+        Ok(())
+    }
+
+    /// Move the registration from `from`, the `Poll` instance it belongs
+    /// to, to `to`.
+    ///
+    /// The token, interest, options and current readiness are preserved.
+    /// Existing `SetReadiness` handles keep working, readiness set through
+    /// them is delivered by `to` from then on. The registration no longer
+    /// appears in `from.registered()`, its internal state there is released
+    /// during the next call to `from.poll`.
+    ///
+    /// Readiness is never lost by the move. If readiness is set concurrently
+    /// with the move, the event may be returned by both instances, which is
+    /// the same as any other spurious event.
+    pub fn migrate(&mut self, from: &Poll, to: &Poll) -> io::Result<()> {
+        if self.inner.is_released() {
+            return Err(released_error());
+        }
+
+        let data = try!(self.inner.registration_data_mut(&from.readiness_queue));
+
+        if self.inner.queue.identical(&to.readiness_queue) {
+            return Ok(());
+        }
+
+        try!(validate_userspace(to));
+
+        let mut next = RegistrationInner::new(to, self.inner.node().token(), data.interest, data.opts);
+
+        {
+            let node = self.inner.node();
+            let next_node = next.node.as_mut().unwrap();
+
+            {
+                let next_data = next_node.registration_data_mut();
+                next_data.priority = data.priority;
+                next_data.auto_reset = data.auto_reset;
+                next_data.last_delivered = data.last_delivered;
+            }
+
+            // The callback must only run once the new node is freed. The old
+            // node can't be freed meanwhile, `self` holds a reference to it.
+            *next_node.drop_callback.get_mut() = unsafe { (*node.drop_callback.get()).take() };
+            next_node.incremental = node.incremental;
+            next_node.producer_hup = node.producer_hup;
+            next_node.name = node.name.clone();
+        }
+
+        // Publish the new registration before taking the readiness of the
+        // old node. `SeqCst` pairs with the fence in
+        // `RegistrationInner::set_readiness_current`, so a concurrent
+        // producer either stores its readiness before the swap below, or
+        // observes the new registration and forwards the readiness itself.
+        let forward = Box::into_raw(Box::new(next.clone()));
+        self.inner.node().migrated.store(forward, Ordering::SeqCst);
+
+        let ready = event::from_usize(self.inner.node().events.swap(0, Ordering::SeqCst));
+
+        // `to` is not polled while it is borrowed here, no wakeup is needed.
+        if !ready.is_none() {
+            let needs_wakeup = next.set_readiness_no_wakeup(ready);
+            debug_assert!(!needs_wakeup, "something funky is going on");
+        }
+
+        // Have `from` detach the old node, as with `disable_and_release`.
+        // Producers still holding it forward their readiness to `next`.
+        data.disable();
+        self.inner.node().release.store(RELEASE_REQUESTED, Ordering::Release);
+
+        let needs_wakeup = self.inner.queue_for_processing();
+        debug_assert!(!needs_wakeup, "something funky is going on");
+
+        self.inner = next;
+
+        Ok(())
+    }
 }
 
 impl Drop for Registration {
@@ -389,6 +2930,7 @@ impl Drop for Registration {
 impl fmt::Debug for Registration {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Registration")
+            .field("name", &self.name())
             .finish()
     }
 }
@@ -397,19 +2939,358 @@ unsafe impl Send for Registration { }
 
 impl SetReadiness {
     pub fn readiness(&self) -> Ready {
-        self.inner.readiness()
+        self.inner.current().readiness()
     }
 
+    /// Sets the readiness of the registration.
+    ///
+    /// Readiness is coalesced until `poll` processes the registration: the
+    /// first call that finds the registration idle queues it, and wakes up
+    /// `poll` if it is blocked. Every following call, until `poll` dequeues
+    /// it, only stores the new readiness, which replaces the previous one,
+    /// and costs no queue operation or wakeup. `poll` delivers the readiness
+    /// stored last. `coalesced_count` reports how often this happened.
+    ///
+    /// Setting the readiness to `Ready::none()` never queues the
+    /// registration (except for incremental registrations, see
+    /// `Registration::new_incremental`).
+    ///
+    /// # Ordering
+    ///
+    /// Registrations are delivered in the order they were queued. If a
+    /// thread sets the readiness of registration A, then of registration B,
+    /// both idle, before `poll` runs, A's event comes before B's. Since a
+    /// registration that is already queued keeps its place, the order is the
+    /// one of the calls that queued each registration, not of the latest
+    /// ones. A level-triggered registration that stays ready is queued again
+    /// as it is delivered, so on the next call it comes before anything
+    /// queued after that `poll`, and requeued registrations keep their
+    /// relative order.
+    ///
+    /// Within a single call to `poll`, events from the OS selector always
+    /// come first, and registrations with a priority (see
+    /// `Registration::new_with_priority`) are moved ahead of the default
+    /// ones. Calls made from different threads are only ordered by the
+    /// queue operation itself.
+    ///
+    /// If a limit was set with `Poll::set_readiness_limit` and it has been
+    /// reached, an error of kind `WouldBlock` is returned instead of queueing
+    /// the registration.
     pub fn set_readiness(&self, ready: Ready) -> io::Result<()> {
-        self.inner.set_readiness(ready)
+        try!(self.inner.current().check_readiness_limit(ready));
+        self.inner.set_readiness_current(ready)
+    }
+
+    /// Returns how many times readiness was set while the registration was
+    /// already queued, so no queue operation or wakeup was needed.
+    ///
+    /// This is a diagnostic counter, read with `Relaxed` ordering. It covers
+    /// all handles of the registration and, if it was migrated, counts for
+    /// its current `Poll` instance only.
+    pub fn coalesced_count(&self) -> usize {
+        self.inner.current().node().coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Sets the readiness with release semantics.
+    ///
+    /// Behaves like `set_readiness`, but every write made by the calling
+    /// thread before this call happens-before any read made by a thread
+    /// after it observes the readiness stored here, either through an event
+    /// returned by `Poll::poll` or through `readiness_acquire`. This makes
+    /// the readiness itself usable as the synchronization point of a
+    /// "write data, then mark ready" handoff, without an extra fence on
+    /// either side.
+    ///
+    /// The guarantee only covers the value that is actually observed. If
+    /// another call overwrites the readiness before `poll` reads it, the
+    /// observer synchronizes with that call instead, and only if it was
+    /// also made through `set_readiness_release`.
+    pub fn set_readiness_release(&self, ready: Ready) -> io::Result<()> {
+        try!(self.inner.current().check_readiness_limit(ready));
+
+        // A release fence followed by the store makes the store (and any
+        // forwarded store after a migration) a release operation for
+        // acquire loads reading it.
+        atomic::fence(Ordering::Release);
+        self.inner.set_readiness_current(ready)
+    }
+
+    /// Returns the readiness with acquire semantics.
+    ///
+    /// Pairs with `set_readiness_release`: once the returned value reflects
+    /// a readiness stored by that call, writes made before it are visible.
+    pub fn readiness_acquire(&self) -> Ready {
+        let node = self.inner.current().node();
+        event::from_usize(node.events.load(Ordering::Acquire))
+    }
+
+    /// Returns true if both handles belong to the same registration.
+    ///
+    /// This is a pointer comparison, like `Arc::ptr_eq`: clones of a handle
+    /// compare equal, handles of distinct registrations never do, even if
+    /// they share a token. A migrated registration keeps its identity.
+    pub fn ptr_eq(&self, other: &SetReadiness) -> bool {
+        self.id() == other.id()
+    }
+
+    /// Returns an identifier for the registration, usable as a map key.
+    ///
+    /// This is the address of the registration's internal state, so two
+    /// handles have the same id if and only if `ptr_eq` returns true. The id
+    /// is only unique while the registration is alive, which is at least as
+    /// long as this handle: once the `Registration` and all its
+    /// `SetReadiness` handles are dropped, the state is freed and a new
+    /// registration may reuse the id.
+    pub fn id(&self) -> usize {
+        self.inner.node() as *const ReadinessNode as usize
+    }
+
+    /// Returns the token most recently set for this registration (the write
+    /// position).
+    ///
+    /// The token is only changed by `Registration::update` which, like
+    /// `Poll::poll`, runs on the thread that owns the `Poll` instance. The
+    /// next event `poll` delivers for the registration uses this token.
+    ///
+    /// When called from another thread, the load uses `Acquire` ordering so
+    /// that a token stored by `update` is observed along with everything that
+    /// happened before it on the `Poll` thread, once it becomes visible.
+    pub fn latest_token(&self) -> Token {
+        Token(self.inner.current().node().token.load(Ordering::Acquire))
+    }
+
+    /// Returns the token `poll` last delivered an event for this
+    /// registration with (the read position).
+    ///
+    /// `poll` copies the latest token to the read position when it dequeues
+    /// the registration, so after an `update` the two differ until the next
+    /// event is delivered. Before any event is delivered, this is the token
+    /// the registration was created with.
+    ///
+    /// The load uses `Acquire` ordering, pairing with the `Release` store in
+    /// `poll`: once the new token is observed, so is the event `poll`
+    /// delivered with it.
+    pub fn current_token(&self) -> Token {
+        Token(self.inner.current().node().read_token.load(Ordering::Acquire))
     }
 }
 
 unsafe impl Send for SetReadiness { }
 unsafe impl Sync for SetReadiness { }
 
+impl ReadinessGroup {
+    /// Returns a new, empty, `ReadinessGroup`.
+    pub fn new() -> ReadinessGroup {
+        ReadinessGroup { members: Vec::new() }
+    }
+
+    /// Adds a `SetReadiness` handle to the group.
+    ///
+    /// Returns an error if the handle belongs to a different `Poll` instance
+    /// than the handles already in the group.
+    pub fn push(&mut self, set_readiness: SetReadiness) -> io::Result<()> {
+        if let Some(first) = self.members.first() {
+            if !first.inner.queue.identical(&set_readiness.inner.queue) {
+                return Err(io::Error::new(io::ErrorKind::Other, "registration registered with another instance of Poll"));
+            }
+        }
+
+        self.members.push(set_readiness);
+        Ok(())
+    }
+
+    /// Returns the number of handles in the group.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns true if the group contains no handles.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Set the readiness of every registration in the group.
+    ///
+    /// Every node is queued for processing first and `Poll` is woken up at
+    /// most once at the end. Members whose `Registration` has already been
+    /// dropped have no interest anymore, so they are updated but never yield
+    /// an event.
+    pub fn set_all(&self, ready: Ready) -> io::Result<()> {
+        if let Some(first) = self.members.first() {
+            // All or nothing, see `Poll::set_readiness_limit`
+            let queue = first.inner.current().queue.clone();
+            let limit = queue.inner().readiness_limit.load(Ordering::Relaxed);
+
+            if limit != usize::MAX && !event::is_empty(ready) {
+                let needed = self.members.iter()
+                    .filter(|member| member.inner.current().node().queued.load(Ordering::Acquire) == 0)
+                    .count();
+
+                if needed > 0 && queue.inner().queued_nodes.load(Ordering::Relaxed) + needed > limit {
+                    return Err(readiness_limit_error());
+                }
+            }
+        }
+
+        let mut needs_wakeup = false;
+
+        for member in &self.members {
+            if member.inner.migrated().is_none() {
+                needs_wakeup |= member.inner.set_readiness_no_wakeup(ready);
+            }
+        }
+
+        if let Some(first) = self.members.first() {
+            if needs_wakeup {
+                try!(first.inner.queue.wakeup());
+            }
+        }
+
+        // Registrations that have been migrated, possibly concurrently, are
+        // updated one at a time. See `RegistrationInner::set_readiness_current`.
+        atomic::fence(Ordering::SeqCst);
+
+        for member in &self.members {
+            if member.inner.migrated().is_some() {
+                try!(member.inner.set_readiness_current(ready));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ReadinessGroup {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ReadinessGroup")
+            .field("len", &self.members.len())
+            .finish()
+    }
+}
+
+/*
+ *
+ * ===== PollCondvar =====
+ *
+ */
+
+impl PollCondvar {
+    /// Returns a new `PollCondvar` without any waiter.
+    pub fn new() -> PollCondvar {
+        PollCondvar {
+            inner: Arc::new(Mutex::new(CondvarState {
+                waiters: Vec::new(),
+                next_id: 0,
+                next_waiter: 0,
+                permits: 0,
+            })),
+        }
+    }
+
+    /// Registers a new waiter with `poll`. Notifications are delivered as
+    /// readable events for `token`.
+    ///
+    /// If `notify_one` was called while no waiter was registered, the new
+    /// waiter is notified right away.
+    pub fn register(&self, poll: &Poll, token: Token) -> io::Result<CondvarWaiter> {
+        try!(validate_args(token, Ready::readable()));
+        try!(validate_userspace(poll));
+
+        let (registration, set_readiness) = Registration::new_auto_reset(poll, token, Ready::readable(), PollOpt::edge());
+        let mut state = self.inner.lock().unwrap();
+
+        if state.permits > 0 {
+            state.permits -= 1;
+            try!(set_readiness_unlimited(&set_readiness, Ready::readable()));
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiters.push((id, set_readiness));
+
+        Ok(CondvarWaiter {
+            registration: registration,
+            id: id,
+            condvar: self.clone(),
+        })
+    }
+
+    /// Wakes up one waiter.
+    ///
+    /// Waiters are notified in turn. If no waiter is registered, the
+    /// notification is kept for the next one that registers.
+    pub fn notify_one(&self) -> io::Result<()> {
+        let mut state = self.inner.lock().unwrap();
+
+        if state.waiters.is_empty() {
+            state.permits += 1;
+            return Ok(());
+        }
+
+        let i = state.next_waiter % state.waiters.len();
+        state.next_waiter = i + 1;
+
+        set_readiness_unlimited(&state.waiters[i].1, Ready::readable())
+    }
+
+    /// Wakes up all the registered waiters.
+    ///
+    /// Unlike `notify_one`, this does nothing if no waiter is registered.
+    pub fn notify_all(&self) -> io::Result<()> {
+        let state = self.inner.lock().unwrap();
+
+        for &(_, ref set_readiness) in &state.waiters {
+            try!(set_readiness_unlimited(&set_readiness, Ready::readable()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of registered waiters.
+    pub fn waiters(&self) -> usize {
+        self.inner.lock().unwrap().waiters.len()
+    }
+}
+
+impl fmt::Debug for PollCondvar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.lock().unwrap();
+
+        fmt.debug_struct("PollCondvar")
+            .field("waiters", &state.waiters.len())
+            .field("permits", &state.permits)
+            .finish()
+    }
+}
+
+impl CondvarWaiter {
+    /// Returns the token the waiter's events are delivered with.
+    pub fn token(&self) -> Token {
+        self.registration.inner.node().token()
+    }
+}
+
+impl Drop for CondvarWaiter {
+    fn drop(&mut self) {
+        let mut state = self.condvar.inner.lock().unwrap();
+        let id = self.id;
+
+        state.waiters.retain(|&(waiter, _)| waiter != id);
+    }
+}
+
+impl fmt::Debug for CondvarWaiter {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CondvarWaiter")
+            .field("token", &self.token())
+            .finish()
+    }
+}
+
 impl RegistrationInner {
     fn new(poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> RegistrationInner {
+        assert!(poll.has_userspace(), "poll has no readiness queue for custom registrations");
+
         let queue = poll.readiness_queue.clone();
         let node = queue.new_readiness_node(token, interest, opts, 1);
 
@@ -419,9 +3300,16 @@ impl RegistrationInner {
         }
     }
 
-    fn update(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+    fn update(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        if self.is_released() {
+            return Err(released_error());
+        }
+
         // Update the registration data
-        try!(self.registration_data_mut(&poll.readiness_queue)).update(token, interest, opts);
+        try!(self.registration_data_mut(&poll.readiness_queue)).update(interest, opts);
+
+        // `Release` pairs with the `Acquire` load in `SetReadiness::latest_token`
+        self.node().token.store(token.into(), Ordering::Release);
 
         // If the node is currently ready, re-queue?
         if !event::is_empty(self.readiness()) {
@@ -447,6 +3335,87 @@ impl RegistrationInner {
     }
 
     fn set_readiness(&self, ready: Ready) -> io::Result<()> {
+        if self.set_readiness_no_wakeup(ready) {
+            try!(self.queue.wakeup());
+        }
+
+        Ok(())
+    }
+
+    /// Set the readiness of the registration, following any migration done
+    /// with `Registration::migrate`.
+    fn set_readiness_current(&self, ready: Ready) -> io::Result<()> {
+        if let Some(next) = self.migrated() {
+            return next.set_readiness_current(ready);
+        }
+
+        try!(self.set_readiness(ready));
+
+        // `migrate` may have published the new registration after the check
+        // above and taken the readiness before it was stored. The fence pairs
+        // with the `SeqCst` operations in `migrate`: either the readiness was
+        // moved by `migrate`, or the new registration is visible here. In the
+        // latter case, forward the readiness and clear it from this node so
+        // the original `Poll` doesn't keep reporting it.
+        atomic::fence(Ordering::SeqCst);
+
+        if let Some(next) = self.migrated() {
+            self.node().events.compare_and_swap(event::as_usize(ready), 0, Ordering::Relaxed);
+            return next.set_readiness_current(ready);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the registration this one has been migrated to, if any.
+    fn migrated(&self) -> Option<&RegistrationInner> {
+        let ptr = self.node().migrated.load(Ordering::Acquire);
+
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { &*ptr })
+        }
+    }
+
+    /// Returns the registration currently receiving readiness.
+    fn current(&self) -> &RegistrationInner {
+        match self.migrated() {
+            Some(next) => next.current(),
+            None => self,
+        }
+    }
+
+    /// Returns an error if queueing the node for `ready` would exceed the
+    /// limit set with `Poll::set_readiness_limit`.
+    fn check_readiness_limit(&self, ready: Ready) -> io::Result<()> {
+        let inner = self.queue.inner();
+        let limit = inner.readiness_limit.load(Ordering::Relaxed);
+
+        if limit == usize::MAX || event::is_empty(ready) {
+            return Ok(());
+        }
+
+        // Already counted
+        if self.node().queued.load(Ordering::Acquire) != 0 {
+            return Ok(());
+        }
+
+        if inner.queued_nodes.load(Ordering::Relaxed) >= limit {
+            return Err(readiness_limit_error());
+        }
+
+        Ok(())
+    }
+
+    /// Stores the readiness and queues the node, returns true if `Poll` needs
+    /// to be woken up.
+    fn set_readiness_no_wakeup(&self, ready: Ready) -> bool {
+        // Released nodes are inert
+        if self.is_released() {
+            return false;
+        }
+
         // First store in the new readiness using relaxed as this operation is
         // permitted to be visible ad-hoc. The `queue_for_processing` function
         // will set a `Release` barrier ensuring eventual consistency.
@@ -458,15 +3427,14 @@ impl RegistrationInner {
         // instance, so there is no need to enqueue the node. No barrier is
         // needed in this case since it doesn't really matter when the value
         // becomes visible to other threads.
-        if event::is_empty(ready) {
-            return Ok(());
-        }
-
-        if self.queue_for_processing() {
-            try!(self.queue.wakeup());
+        //
+        // Incremental nodes are the exception, `Poll` has to observe the
+        // cleared readiness so that setting it again is reported.
+        if event::is_empty(ready) && !self.node().incremental {
+            return false;
         }
 
-        Ok(())
+        self.queue_for_processing()
     }
 
     /// Returns true if `Poll` needs to be woken up
@@ -481,8 +3449,11 @@ impl RegistrationInner {
         // If the queued flag was not initially set, then the current thread
         // is assigned the responsibility of enqueuing the node for processing.
         if prev == 0 {
-            self.queue.prepend_readiness_node(self.node.clone())
+            let needs_wakeup = self.queue.prepend_readiness_node(self.node.clone());
+            record_trace!(self.queue, TraceEvent::Enqueued(self.node().token(), needs_wakeup));
+            needs_wakeup
         } else {
+            self.node().coalesced.fetch_add(1, Ordering::Relaxed);
             false
         }
     }
@@ -491,6 +3462,10 @@ impl RegistrationInner {
         self.node.as_ref().unwrap()
     }
 
+    fn is_released(&self) -> bool {
+        self.node().release.load(Ordering::Acquire) != RELEASE_NONE
+    }
+
     fn registration_data_mut(&self, readiness_queue: &ReadinessQueue) -> io::Result<&mut RegistrationData> {
         // `&Poll` is passed in here in order to ensure that this function is
         // only called from the thread that owns the `Poll` value. This is
@@ -540,6 +3515,34 @@ impl Clone for RegistrationInner {
     }
 }
 
+impl Clone for SetReadiness {
+    fn clone(&self) -> SetReadiness {
+        if self.inner.node().producer_hup {
+            // Like the node's `ref_count`, knowledge of the original handle
+            // prevents the count from reaching zero concurrently.
+            self.inner.node().producers.fetch_add(1, Ordering::Relaxed);
+        }
+
+        SetReadiness { inner: self.inner.clone() }
+    }
+}
+
+impl Drop for SetReadiness {
+    fn drop(&mut self) {
+        if !self.inner.node().producer_hup {
+            return;
+        }
+
+        if self.inner.node().producers.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+
+        // Last producer, the readiness can't change anymore
+        let ready = self.inner.current().readiness() | Ready::hup();
+        let _ = self.inner.set_readiness_current(ready);
+    }
+}
+
 impl Drop for RegistrationInner {
     fn drop(&mut self) {
         // Because `fetch_sub` is already atomic, we do not need to synchronize
@@ -551,6 +3554,19 @@ impl Drop for RegistrationInner {
             return;
         }
 
+        if self.is_released() {
+            // Let `poll` release the node if it has not detached it yet,
+            // otherwise free it here.
+            self.node().events.store(event::as_usize(event::drop()), Ordering::Release);
+
+            if self.node().release.swap(RELEASE_DROPPED, Ordering::AcqRel) == RELEASE_DETACHED {
+                atomic::fence(Ordering::Acquire);
+                drop(unsafe { Box::from_raw(self.node.ptr) });
+            }
+
+            return;
+        }
+
         // Signal to the queue that the node is not referenced anymore and can
         // be released / reused
         let _ = self.set_readiness(event::drop());
@@ -564,26 +3580,46 @@ impl Drop for RegistrationInner {
  */
 
 impl ReadinessQueue {
-    fn new() -> io::Result<ReadinessQueue> {
+    fn new(awakener: Option<sys::Awakener>) -> ReadinessQueue {
         let sleep_token = Box::new(ReadinessNode::new(Token(0), Ready::none(), PollOpt::empty(), 0));
 
-        Ok(ReadinessQueue {
+        ReadinessQueue {
             inner: Arc::new(UnsafeCell::new(ReadinessQueueInner {
-                awakener: try!(sys::Awakener::new()),
+                awakener: awakener,
                 head_all_nodes: None,
                 head_readiness: AtomicPtr::new(ptr::null_mut()),
                 // Arguments here don't matter, the node is only used for the
                 // pointer value.
                 sleep_token: sleep_token,
+                shutdown: AtomicBool::new(false),
+                cancelled: AtomicUsize::new(0),
+                queued_nodes: AtomicUsize::new(0),
+                readiness_limit: AtomicUsize::new(usize::MAX),
+                poll_alive: AtomicBool::new(true),
+                enqueue_retries: AtomicUsize::new(0),
+                dequeue_retries: AtomicUsize::new(0),
+                #[cfg(test)]
+                trace: Mutex::new(None),
+                #[cfg(test)]
+                enqueue_hook: Mutex::new(None),
             }))
-        })
+        }
     }
 
-    fn poll(&self, dst: &mut sys::Events) {
-        let ready = self.take_ready();
+    fn poll(&self, dst: &mut sys::Events, coalesce: bool) {
+        let mut ready = ReadyListGuard {
+            queue: self,
+            list: self.take_ready(),
+        };
+        let start = dst.len();
+
+        // Events for registrations with a non-default priority are staged
+        // here and merged in once the queue is drained. When no such
+        // registration is ready, this never allocates.
+        let mut prioritized: Vec<(u8, Event)> = Vec::new();
 
         // TODO: Cap number of nodes processed
-        for node in ready {
+        while let Some(node) = ready.list.next() {
             let mut events;
             let opts;
 
@@ -614,6 +3650,33 @@ impl ReadinessQueue {
                         // dropped nodes are always processed immediately. There is
                         // also no need to unset the queued bit as the node should
                         // not change anymore.
+                        break;
+                    } else if node_ref.release.load(Ordering::Acquire) != RELEASE_NONE {
+                        // Released nodes are detached below. The queued bit
+                        // stays set so that producers never queue them again.
+                        break;
+                    } else if node_ref.auto_reset() {
+                        events = self.take_readiness(node_ref);
+
+                        if event::is_drop(events) {
+                            break;
+                        }
+
+                        // Dequeue the node. A producer that stored readiness
+                        // after it was taken, but while the node was still
+                        // flagged as queued, did not queue the node itself.
+                        // In that case, queue it again so that the newer
+                        // readiness fires on the next call to `poll`.
+                        node_ref.queued.swap(0, Ordering::AcqRel);
+
+                        let pending = node_ref.events.load(Ordering::Acquire);
+
+                        if pending != 0 && node_ref.queued.compare_and_swap(0, NODE_QUEUED_FLAG, Ordering::AcqRel) == 0 {
+                            let needs_wakeup = self.prepend_readiness_node(node.clone());
+                            debug_assert!(!needs_wakeup, "something funky is going on");
+                            record_trace!(self, TraceEvent::Requeued(node_ref.token()));
+                        }
+
                         break;
                     } else if opts.is_edge() || event::is_empty(events) {
                         // An acquire barrier is set in order to re-read the
@@ -632,6 +3695,7 @@ impl ReadinessQueue {
                             break;
                         }
 
+                        self.inner().dequeue_retries.fetch_add(1, Ordering::Relaxed);
                         queued = next;
                     } else {
                         // The node needs to stay queued for readiness, so it gets
@@ -643,23 +3707,70 @@ impl ReadinessQueue {
                         // current thread.
                         let needs_wakeup = self.prepend_readiness_node(node.clone());
                         debug_assert!(!needs_wakeup, "something funky is going on");
+                        record_trace!(self, TraceEvent::Requeued(node_ref.token()));
                         break;
                     }
                 }
+
+                record_trace!(self, TraceEvent::Dequeued(node_ref.token(), events - event::drop()));
+            }
+
+            // Only report the readiness added since the previous delivery
+            if !event::is_drop(events) && opts.is_edge() && node.as_ref().unwrap().incremental {
+                let data = node.as_ref().unwrap().registration_data_mut();
+                let current = events;
+
+                events = current - data.last_delivered;
+                data.last_delivered = current;
             }
 
             // Process the node.
             if event::is_drop(events) {
+                record_trace!(self, TraceEvent::Released(node.as_ref().unwrap().token()));
+
                 // Release the node
                 let _ = self.unlink_node(node);
+            } else if node.as_ref().unwrap().release.load(Ordering::Acquire) != RELEASE_NONE {
+                record_trace!(self, TraceEvent::Released(node.as_ref().unwrap().token()));
+
+                // Hand the node over to the remaining handles, unless the last
+                // one was dropped in the meantime.
+                let node = self.unlink_node(node);
+
+                if node.release.swap(RELEASE_DETACHED, Ordering::AcqRel) != RELEASE_DROPPED {
+                    mem::forget(node);
+                }
             } else if !events.is_none() {
                 let node_ref = node.as_ref().unwrap();
 
                 // TODO: Don't push the event if the capacity of `dst` has
                 // been reached
-                trace!("returning readiness event {:?} {:?}", events,
-                       node_ref.token());
-                dst.push_event(Event::new(events, node_ref.token()));
+                trace!("returning readiness event {:?} {:?} {:?}", events,
+                       node_ref.token(), node_ref.name);
+
+                let token = node_ref.token();
+                let priority = node_ref.priority();
+
+                // Pairs with the `Acquire` load in `SetReadiness::current_token`
+                node_ref.read_token.store(token.into(), Ordering::Release);
+
+                let mut push = |event| {
+                    match priority {
+                        0 => dst.push_event(event),
+                        priority => prioritized.push((priority, event)),
+                    }
+                };
+
+                if coalesce {
+                    push(Event::new(events, token));
+                } else {
+                    // One event per readiness kind, in consecutive slots
+                    for &kind in &event::KINDS {
+                        if events.contains(kind) {
+                            push(Event::new(kind, token));
+                        }
+                    }
+                }
 
                 // If one-shot, disarm the node
                 if opts.is_oneshot() {
@@ -667,14 +3778,118 @@ impl ReadinessQueue {
                 }
             }
         }
+
+        if prioritized.is_empty() {
+            return;
+        }
+
+        // Move the default priority events after the prioritized ones. The
+        // sort is stable, so events of equal priority keep the order in which
+        // they were dequeued.
+        let default: Vec<Event> = (start..dst.len())
+            .map(|i| dst.get(i).unwrap())
+            .collect();
+
+        dst.truncate(start);
+        prioritized.sort_by(|a, b| b.0.cmp(&a.0));
+
+        for (_, event) in prioritized.into_iter() {
+            dst.push_event(event);
+        }
+
+        for event in default.into_iter() {
+            dst.push_event(event);
+        }
+    }
+
+    /// Atomically takes the readiness stored in an auto-reset node, leaving
+    /// `Ready::none()` in its place. The drop flag is never cleared.
+    fn take_readiness(&self, node: &ReadinessNode) -> Ready {
+        let mut curr = node.events.load(Ordering::Acquire);
+
+        loop {
+            if event::is_drop(event::from_usize(curr)) {
+                break;
+            }
+
+            let prev = node.events.compare_and_swap(curr, 0, Ordering::AcqRel);
+
+            if prev == curr {
+                break;
+            }
+
+            curr = prev;
+        }
+
+        node.readiness_mask() & event::from_usize(curr)
     }
 
     fn wakeup(&self) -> io::Result<()> {
-        self.inner().awakener.wakeup()
+        // The awakener is owned by the queue, so writing to it after the
+        // `Poll` is dropped is memory safe. However, with no selector left to
+        // read it, the write is wasted (and on Windows, the completion port is
+        // only kept open by the awakener), so skip it.
+        if !self.inner().poll_alive.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        record_trace!(self, TraceEvent::Wakeup);
+
+        match self.inner().awakener {
+            Some(ref awakener) => awakener.wakeup(),
+            None => Ok(()),
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        // `SeqCst` ensures the flag is visible to `poll` once it observes the
+        // awakener byte written below.
+        self.inner().shutdown.store(true, Ordering::SeqCst);
+        self.wakeup()
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.inner().shutdown.load(Ordering::SeqCst)
+    }
+
+    fn cancel(&self) -> io::Result<()> {
+        // As with `shutdown`, the count must be visible to `poll` once it
+        // observes the awakener byte.
+        self.inner().cancelled.fetch_add(1, Ordering::SeqCst);
+        self.wakeup()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.inner().cancelled.load(Ordering::SeqCst) != 0
+    }
+
+    // Consumes one pending cancellation, if any.
+    fn take_cancelled(&self) -> bool {
+        let mut curr = self.inner().cancelled.load(Ordering::SeqCst);
+
+        while curr != 0 {
+            let prev = self.inner().cancelled.compare_and_swap(curr, curr - 1, Ordering::SeqCst);
+
+            if prev == curr {
+                return true;
+            }
+
+            curr = prev;
+        }
+
+        false
     }
 
     // Attempts to state to sleeping. This involves changing `head_readiness`
     // to `sleep_token`. Returns true if `poll` can sleep.
+    //
+    // There is no lock or condition variable in the sleep handoff, so there
+    // is no notification to miss. A producer that queues a node after the
+    // marker is set replaces it and writes to the awakener. The byte stays in
+    // the pipe (or the packet in the completion port) until `poll` drains it,
+    // so the selector returns immediately even if the write happened before
+    // `poll` entered it. A producer that queued a node before the marker was
+    // set makes this CAS fail, and `poll` doesn't block at all.
     fn prepare_for_sleep(&self) -> bool {
         // Use relaxed as no memory besides the pointer is being sent across
         // threads. Ordering doesn't matter, only the current value of
@@ -683,6 +3898,28 @@ impl ReadinessQueue {
             .compare_and_swap(ptr::null_mut(), self.sleep_token(), Ordering::Relaxed)
     }
 
+    // Undoes a successful `prepare_for_sleep`, if no producer has queued a
+    // node since.
+    fn cancel_sleep(&self) {
+        self.inner().head_readiness
+            .compare_and_swap(self.sleep_token(), ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    /// Takes every node currently queued for readiness, in the order they
+    /// were queued.
+    ///
+    /// Producers push nodes with a single CAS on `head_readiness` (see
+    /// `prepend_readiness_node`) and the whole list is taken here with a
+    /// single swap. A node is therefore either fully linked in the list or
+    /// not in it at all, there is no intermediate state where a producer has
+    /// claimed the head but not yet linked its node. A node that misses the
+    /// swap is observed by the next call to `poll`, and its producer has
+    /// already been told to wake `Poll` up if it was sleeping.
+    ///
+    /// The list is a stack, so it is reversed before being returned. Nodes
+    /// requeued while draining (level-triggered or auto-reset ones) are
+    /// pushed in the order they were dequeued, so they keep their relative
+    /// order on the next call as well.
     fn take_ready(&self) -> ReadyList {
         // Use `Acquire` ordering to ensure being able to read the latest
         // values of all other atomic mutations.
@@ -692,7 +3929,48 @@ impl ReadinessQueue {
             head = ptr::null_mut();
         }
 
-        ReadyList { head: ReadyRef::new(head) }
+        let mut list = ReadyRef::none();
+        let mut curr = ReadyRef::new(head);
+        let mut len = 0;
+
+        while curr.is_some() {
+            let next = curr.as_mut().unwrap().next_readiness.take();
+            curr.as_mut().unwrap().next_readiness = list;
+            list = curr;
+            curr = next;
+            len += 1;
+        }
+
+        self.inner().queued_nodes.fetch_sub(len, Ordering::Relaxed);
+
+        ReadyList { head: list }
+    }
+
+    #[cfg(test)]
+    fn record(&self, event: TraceEvent) {
+        if let Some(ref mut trace) = *self.inner().trace.lock().unwrap() {
+            trace.push(event);
+        }
+    }
+
+    fn registered(&self) -> Vec<(Token, Option<Cow<'static, str>>, Ready, Ready, PollOpt)> {
+        let mut ret = Vec::new();
+        let mut cur = self.inner().head_all_nodes.as_ref();
+
+        while let Some(node) = cur {
+            let interest = node.interest();
+
+            if !interest.is_none() {
+                let readiness = event::from_usize(node.events.load(Ordering::Relaxed)) - event::drop();
+                ret.push((node.token(), node.name.clone(), interest, readiness, node.poll_opts()));
+            }
+
+            cur = node.next_all_nodes.as_ref();
+        }
+
+        // New nodes are pushed at the head of the list
+        ret.reverse();
+        ret
     }
 
     fn new_readiness_node(&self, token: Token, interest: Ready, opts: PollOpt, ref_count: usize) -> ReadyRef {
@@ -717,6 +3995,8 @@ impl ReadinessQueue {
     fn prepend_readiness_node(&self, mut node: ReadyRef) -> bool {
         let mut curr_head = self.inner().head_readiness.load(Ordering::Relaxed);
 
+        self.inner().queued_nodes.fetch_add(1, Ordering::Relaxed);
+
         loop {
             let node_next = if curr_head == self.sleep_token() {
                 ptr::null_mut()
@@ -727,6 +4007,15 @@ impl ReadinessQueue {
             // Update next pointer
             node.as_mut().unwrap().next_readiness = ReadyRef::new(node_next);
 
+            #[cfg(test)]
+            {
+                let hook = self.inner().enqueue_hook.lock().unwrap().take();
+
+                if let Some(mut hook) = hook {
+                    hook();
+                }
+            }
+
             // Update the ref, use release ordering to ensure that mutations to
             // previous atomics are visible if the mutation to the head pointer
             // is.
@@ -736,6 +4025,7 @@ impl ReadinessQueue {
                 return curr_head == self.sleep_token();
             }
 
+            self.inner().enqueue_retries.fetch_add(1, Ordering::Relaxed);
             curr_head = next_head;
         }
     }
@@ -772,20 +4062,43 @@ impl ReadinessNode {
         ReadinessNode {
             next_all_nodes: None,
             prev_all_nodes: ReadyRef::none(),
-            registration_data: UnsafeCell::new(RegistrationData::new(token, interest, opts)),
+            registration_data: UnsafeCell::new(RegistrationData::new(interest, opts)),
+            drop_callback: UnsafeCell::new(None),
+            name: None,
+            migrated: AtomicPtr::new(ptr::null_mut()),
             next_readiness: ReadyRef::none(),
             events: AtomicUsize::new(0),
             queued: AtomicUsize::new(0),
             ref_count: AtomicUsize::new(ref_count),
+            coalesced: AtomicUsize::new(0),
+            incremental: false,
+            producer_hup: false,
+            producers: AtomicUsize::new(1),
+            release: AtomicUsize::new(RELEASE_NONE),
+            token: AtomicUsize::new(token.into()),
+            read_token: AtomicUsize::new(token.into()),
         }
     }
 
     fn poll_events(&self) -> Ready {
-        (self.interest() | event::drop()) & event::from_usize(self.events.load(Ordering::Relaxed))
+        self.readiness_mask() & event::from_usize(self.events.load(Ordering::Acquire))
+    }
+
+    /// The readiness `poll` may deliver for this node
+    fn readiness_mask(&self) -> Ready {
+        let mut mask = self.interest() | event::drop();
+
+        if self.producer_hup {
+            mask.insert(Ready::hup());
+        }
+
+        mask
     }
 
     fn token(&self) -> Token {
-        unsafe { &*self.registration_data.get() }.token
+        // `Relaxed` is sufficient, the token is only mutated by the thread
+        // that owns `Poll`. Other threads only read it as a hint.
+        Token(self.token.load(Ordering::Relaxed))
     }
 
     fn interest(&self) -> Ready {
@@ -796,6 +4109,14 @@ impl ReadinessNode {
         unsafe { &*self.registration_data.get() }.opts
     }
 
+    fn priority(&self) -> u8 {
+        unsafe { &*self.registration_data.get() }.priority
+    }
+
+    fn auto_reset(&self) -> bool {
+        unsafe { &*self.registration_data.get() }.auto_reset
+    }
+
     fn registration_data_mut(&self) -> &mut RegistrationData {
         unsafe { &mut *self.registration_data.get() }
     }
@@ -822,17 +4143,32 @@ impl ReadinessNode {
     }
 }
 
+impl Drop for ReadinessNode {
+    fn drop(&mut self) {
+        let migrated = *self.migrated.get_mut();
+
+        if !migrated.is_null() {
+            drop(unsafe { Box::from_raw(migrated) });
+        }
+
+        if let Some(callback) = self.drop_callback.get_mut().take() {
+            callback();
+        }
+    }
+}
+
 impl RegistrationData {
-    fn new(token: Token, interest: Ready, opts: PollOpt) -> RegistrationData {
+    fn new(interest: Ready, opts: PollOpt) -> RegistrationData {
         RegistrationData {
-            token: token,
             interest: interest,
             opts: opts,
+            priority: 0,
+            auto_reset: false,
+            last_delivered: Ready::none(),
         }
     }
 
-    fn update(&mut self, token: Token, interest: Ready, opts: PollOpt) {
-        self.token = token;
+    fn update(&mut self, interest: Ready, opts: PollOpt) {
         self.interest = interest;
         self.opts = opts;
     }
@@ -858,6 +4194,18 @@ impl Iterator for ReadyList {
     }
 }
 
+impl<'a> Drop for ReadyListGuard<'a> {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            return;
+        }
+
+        for node in &mut self.list {
+            let _ = self.queue.prepend_readiness_node(node);
+        }
+    }
+}
+
 impl ReadyRef {
     fn new(ptr: *mut ReadinessNode) -> ReadyRef {
         ReadyRef { ptr: ptr }
@@ -916,7 +4264,8 @@ impl fmt::Pointer for ReadyRef {
 #[cfg(test)]
 mod test {
     use {Ready, Poll, PollOpt, Registration, SetReadiness, Token, Events};
-    use std::time::Duration;
+    use super::TraceEvent;
+    use std::time::{Duration, Instant};
 
     fn ensure_send<T: Send>(_: &T) {}
     fn ensure_sync<T: Sync>(_: &T) {}
@@ -940,6 +4289,188 @@ mod test {
         cnt
     }
 
+    #[test]
+    pub fn test_trace_no_wakeup_when_not_sleeping() {
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let trace = poll.attach_trace();
+
+        let (_r, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+
+        // The sleep marker was not set, so no awakener write happened
+        assert_eq!(vec![TraceEvent::Enqueued(Token(0), false)], trace.take());
+
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+        assert_eq!(vec![TraceEvent::Awoken(false),
+                        TraceEvent::Dequeued(Token(0), Ready::readable())],
+                   trace.take());
+    }
+
+    #[test]
+    pub fn test_trace_wakeup_when_sleeping() {
+        use std::thread;
+
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let trace = poll.attach_trace();
+
+        let (_r, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+        let th = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            set_readiness.set_readiness(Ready::readable()).unwrap();
+        });
+
+        poll.poll(&mut events, None).unwrap();
+        th.join().unwrap();
+
+        assert_eq!(vec![TraceEvent::PrepareForSleep(true),
+                        TraceEvent::Enqueued(Token(0), true),
+                        TraceEvent::Wakeup,
+                        TraceEvent::Awoken(true),
+                        TraceEvent::Dequeued(Token(0), Ready::readable())],
+                   trace.take());
+    }
+
+    #[test]
+    pub fn test_trace_level_requeue_and_release() {
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+        let trace = poll.attach_trace();
+
+        let (registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::level());
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+        assert_eq!(vec![TraceEvent::Enqueued(Token(1), false),
+                        TraceEvent::Awoken(false),
+                        TraceEvent::Requeued(Token(1)),
+                        TraceEvent::Dequeued(Token(1), Ready::readable())],
+                   trace.take());
+
+        // The node is still queued, dropping the handles doesn't enqueue it
+        // again
+        drop(registration);
+        drop(set_readiness);
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+        assert_eq!(vec![TraceEvent::Awoken(false),
+                        TraceEvent::Dequeued(Token(1), Ready::none()),
+                        TraceEvent::Released(Token(1))],
+                   trace.take());
+    }
+
+    #[test]
+    pub fn test_set_readiness_after_poll_dropped() {
+        let poll = Poll::new().unwrap();
+        let trace = poll.attach_trace();
+        let handle = poll.shutdown_handle();
+
+        let (registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+        drop(poll);
+
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+        assert_eq!(Ready::readable(), set_readiness.readiness());
+        assert_eq!(vec![TraceEvent::Enqueued(Token(0), false)], trace.take());
+
+        // Nothing is left to read the awakener, so the write is skipped
+        handle.shutdown().unwrap();
+        assert!(trace.take().is_empty());
+
+        drop(registration);
+        set_readiness.set_readiness(Ready::writable()).unwrap();
+        drop(set_readiness);
+    }
+
+    #[test]
+    pub fn test_contention_stats_enqueue_retry() {
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        let (_r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+        let (_r2, s2) = Registration::new(&poll, Token(2), Ready::readable(), PollOpt::edge());
+
+        assert_eq!(0, poll.queue_contention_stats().enqueue_retries());
+
+        // Another producer wins the race on the head of the queue
+        *poll.readiness_queue.inner().enqueue_hook.lock().unwrap() = Some(Box::new(move || {
+            s2.set_readiness(Ready::readable()).unwrap();
+        }));
+
+        s1.set_readiness(Ready::readable()).unwrap();
+
+        let stats = poll.queue_contention_stats();
+        assert_eq!(1, stats.enqueue_retries());
+        assert_eq!(0, stats.dequeue_retries());
+
+        // Both nodes made it to the queue
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+        let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+        tokens.sort();
+        assert_eq!(vec![Token(1), Token(2)], tokens);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    pub fn test_fd_validation_reports_closed_fd() {
+        use libc;
+        use std::net::UdpSocket;
+        use std::os::unix::io::AsRawFd;
+        use unix::EventedFd;
+        use Event;
+
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        poll.set_fd_validation(true);
+
+        // Move the socket to a high fd number so that no other test reuses
+        // the number once it is closed.
+        let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let fd = unsafe { libc::fcntl(sock.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 200) };
+        assert!(fd >= 200);
+        drop(sock);
+
+        poll.register(&EventedFd(&fd), Token(5), Ready::readable(), PollOpt::edge()).unwrap();
+
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+        assert!(events.is_empty());
+
+        // Closed behind the back of `Poll`
+        unsafe { libc::close(fd) };
+
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Event::new(Ready::error() | Ready::hup(), Token(5)), events.get(0).unwrap());
+
+        // Only reported once
+        poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    pub fn test_saturated_awakener() {
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        // Far more than a pipe buffer can hold
+        for _ in 0..200_000 {
+            poll.readiness_queue.wakeup().unwrap();
+        }
+
+        poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+        assert!(events.is_empty());
+
+        // The awakener has been drained, `poll` blocks again
+        let now = Instant::now();
+        poll.poll(&mut events, Some(Duration::from_millis(50))).unwrap();
+        assert!(events.is_empty());
+        assert!(now.elapsed() >= Duration::from_millis(50));
+    }
+
     #[test]
     pub fn test_nodes_do_not_leak() {
         let mut poll = Poll::new().unwrap();
@@ -958,4 +4489,23 @@ mod test {
         assert_eq!(0, num);
         assert_eq!(0, readiness_node_count(&poll));
     }
+
+    #[test]
+    #[cfg(any(target_os = "android", all(target_os = "linux", not(feature = "io_uring"))))]
+    pub fn test_poll_preserves_raw_os_error() {
+        use libc;
+        use sys;
+
+        let poll = Poll::new().unwrap();
+
+        // `epoll_wait` rejects a zero sized event buffer with `EINVAL`
+        let mut events = Events {
+            inner: sys::Events::with_capacity(0),
+            handled: Vec::new(),
+            user_start: 0,
+        };
+
+        let err = poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap_err();
+        assert_eq!(Some(libc::EINVAL), err.raw_os_error());
+    }
 }
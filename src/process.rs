@@ -0,0 +1,76 @@
+//! Child process exit notifications
+//!
+//! A `ChildWatcher` owns a spawned `Child` and is registered with `Poll` like
+//! any other handle. It becomes readable once the child exits, the exit
+//! status is then returned by `ChildWatcher::try_wait`.
+//!
+//! The exit is watched with a pidfd on Linux, an `EVFILT_PROC` filter on
+//! kqueue platforms, and a thread pool wait on the process handle on
+//! Windows. Linux kernels older than 5.3 have no pidfds, a helper thread
+//! waiting for the child is used instead.
+
+use {io, sys, Evented, Ready, Poll, PollOpt, Token};
+use std::process::{Child, ExitStatus};
+
+/// Delivers a readable event when a child process exits.
+///
+/// The child is not reaped until `try_wait` is called, so the exit status is
+/// available as usual through the watcher.
+#[derive(Debug)]
+pub struct ChildWatcher {
+    // Dropped before `child`, which owns the process handle on Windows
+    sys: sys::ChildWatcher,
+    child: Child,
+}
+
+impl ChildWatcher {
+    /// Takes ownership of `child` to watch it for exit.
+    pub fn new(child: Child) -> io::Result<ChildWatcher> {
+        let sys = try!(sys::ChildWatcher::new(&child));
+
+        Ok(ChildWatcher {
+            sys: sys,
+            child: child,
+        })
+    }
+
+    /// Returns the OS-assigned process identifier of the child.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Returns the exit status of the child if it exited, without blocking.
+    ///
+    /// Call this when the watcher is readable. The child is reaped by the
+    /// first call returning a status.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    /// Returns a reference to the watched child.
+    pub fn get_ref(&self) -> &Child {
+        &self.child
+    }
+
+    /// Returns a mutable reference to the watched child.
+    ///
+    /// This gives access to the child's standard streams, and allows killing
+    /// it.
+    pub fn get_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+}
+
+impl Evented for ChildWatcher {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.sys.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.sys.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.sys.deregister(poll)
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(unix)]
 pub use self::unix::{
     Awakener,
+    ChildWatcher,
     EventedFd,
     Events,
     Io,
@@ -9,11 +10,25 @@ pub use self::unix::{
     TcpListener,
     UdpSocket,
     UnixSocket,
+    SocketAddr,
     pipe,
+    poll_fds,
+    poll_readable,
+    set_close_on_exec,
     set_nonblock,
+    set_tos,
+    tcp_builder,
+    udp_builder,
     IoVec,
 };
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use self::unix::set_bind_device;
+
+#[cfg(all(unix, any(target_os = "bitrig", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "ios", target_os = "macos",
+    target_os = "netbsd", target_os = "openbsd")))]
+pub use self::unix::KqueueTimer;
 
 #[cfg(unix)]
 mod unix;
@@ -21,6 +36,7 @@ mod unix;
 #[cfg(windows)]
 pub use self::windows::{
     Awakener,
+    ChildWatcher,
     Events,
     Selector,
     TcpStream,
@@ -28,7 +44,11 @@ pub use self::windows::{
     UdpSocket,
     Overlapped,
     Binding,
+    EventedHandle,
+    NamedPipe,
     IoVec,
+    tcp_builder,
+    udp_builder,
 };
 
 #[cfg(windows)]
@@ -1,4 +1,92 @@
-pub use self::pipe::Awakener;
+use {io, AwakenerKind, Evented, Ready, Poll, PollOpt, Token};
+
+/// Wakes up a `Poll` blocked in the selector, backed by either a pipe, an
+/// eventfd on Linux, or an `EVFILT_USER` event on kqueue.
+pub enum Awakener {
+    Pipe(pipe::Awakener),
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    EventFd(eventfd::Awakener),
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+    UserEvent(user::Awakener),
+}
+
+impl Awakener {
+    pub fn new() -> io::Result<Awakener> {
+        Awakener::with_kind(AwakenerKind::default())
+    }
+
+    pub fn with_kind(kind: AwakenerKind) -> io::Result<Awakener> {
+        match kind {
+            AwakenerKind::Pipe => pipe::Awakener::new().map(Awakener::Pipe),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            AwakenerKind::EventFd => eventfd::Awakener::new().map(Awakener::EventFd),
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            AwakenerKind::EventFd => {
+                Err(io::Error::new(io::ErrorKind::Other, "eventfd is only available on Linux"))
+            }
+            #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+            AwakenerKind::UserEvent => Ok(Awakener::UserEvent(user::Awakener::new())),
+            #[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly")))]
+            AwakenerKind::UserEvent => {
+                Err(io::Error::new(io::ErrorKind::Other, "EVFILT_USER is not available on this platform"))
+            }
+        }
+    }
+
+    pub fn wakeup(&self) -> io::Result<()> {
+        match *self {
+            Awakener::Pipe(ref awakener) => awakener.wakeup(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Awakener::EventFd(ref awakener) => awakener.wakeup(),
+            #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+            Awakener::UserEvent(ref awakener) => awakener.wakeup(),
+        }
+    }
+
+    pub fn prime(&self) -> io::Result<()> {
+        match *self {
+            Awakener::Pipe(ref awakener) => awakener.prime(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Awakener::EventFd(ref awakener) => awakener.prime(),
+            #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+            Awakener::UserEvent(ref awakener) => awakener.prime(),
+        }
+    }
+
+    pub fn cleanup(&self) {
+        match *self {
+            Awakener::Pipe(ref awakener) => awakener.cleanup(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Awakener::EventFd(ref awakener) => awakener.cleanup(),
+            #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+            Awakener::UserEvent(ref awakener) => awakener.cleanup(),
+        }
+    }
+
+    fn evented(&self) -> &dyn Evented {
+        match *self {
+            Awakener::Pipe(ref awakener) => awakener,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Awakener::EventFd(ref awakener) => awakener,
+            #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+            Awakener::UserEvent(ref awakener) => awakener,
+        }
+    }
+}
+
+impl Evented for Awakener {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.evented().register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.evented().reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.evented().deregister(poll)
+    }
+}
 
 /// Default *nix awakener implementation
 mod pipe {
@@ -27,19 +115,43 @@ mod pipe {
             })
         }
 
+        /// Wake up the associated `Poll`.
+        ///
+        /// The pipe is non-blocking. When it is full, `poll` already has
+        /// bytes to read and will wake up regardless, so `WouldBlock` is not
+        /// an error: flooding the awakener never makes `set_readiness` fail.
         pub fn wakeup(&self) -> io::Result<()> {
-            match (&self.writer).write(&[1]) {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    if e.kind() == io::ErrorKind::WouldBlock {
-                        Ok(())
-                    } else {
-                        Err(e)
+            loop {
+                match (&self.writer).write(&[1]) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => {
+                        match e.kind() {
+                            io::ErrorKind::WouldBlock => return Ok(()),
+                            io::ErrorKind::Interrupted => {}
+                            _ => return Err(e),
+                        }
                     }
                 }
             }
         }
 
+        /// Writes to and drains the pipe once, so that the first real wakeup
+        /// doesn't pay for touching it.
+        pub fn prime(&self) -> io::Result<()> {
+            try!(self.wakeup());
+            self.cleanup();
+            Ok(())
+        }
+
+        /// Drains the pipe.
+        ///
+        /// The awakener stays edge-triggered: every `wakeup` writes a byte,
+        /// and each write to a pipe is a new edge, so a wakeup racing with
+        /// `cleanup` is either drained here, in which case `poll` is already
+        /// awake and processes the readiness queue right after, or produces
+        /// a new event. The pipe must be fully drained though, otherwise
+        /// bytes are left over that report a spurious wakeup later on, so an
+        /// interrupted read is retried.
         pub fn cleanup(&self) {
             let mut buf = [0; 128];
 
@@ -47,6 +159,7 @@ mod pipe {
                 // Consume data until all bytes are purged
                 match (&self.reader).read(&mut buf) {
                     Ok(i) if i > 0 => {},
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
                     _ => return,
                 }
             }
@@ -71,3 +184,197 @@ mod pipe {
         }
     }
 }
+
+/// eventfd based awakener, a single file descriptor instead of the pipe's two
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod eventfd {
+    use std::mem;
+    use std::os::unix::io::RawFd;
+
+    use libc;
+
+    use {io, Evented, Ready, Poll, PollOpt, Token};
+    use sys::unix::{cvt, CloseGuard};
+    use sys::unix::eventedfd::EventedFd;
+
+    pub struct Awakener {
+        fd: RawFd,
+    }
+
+    impl Awakener {
+        pub fn new() -> io::Result<Awakener> {
+            let fd = unsafe {
+                try!(cvt(libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK)))
+            };
+
+            Ok(Awakener { fd: fd })
+        }
+
+        /// Wake up the associated `Poll`.
+        ///
+        /// Adds one to the eventfd counter. `WouldBlock` means the counter
+        /// is about to overflow, so `poll` has a pending wakeup regardless.
+        pub fn wakeup(&self) -> io::Result<()> {
+            let buf: u64 = 1;
+
+            loop {
+                let res = unsafe {
+                    libc::write(self.fd, &buf as *const u64 as *const libc::c_void, mem::size_of::<u64>())
+                };
+
+                match cvt(res) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => {
+                        match e.kind() {
+                            io::ErrorKind::WouldBlock => return Ok(()),
+                            io::ErrorKind::Interrupted => {}
+                            _ => return Err(e),
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Writes to and resets the counter once, see the pipe awakener.
+        pub fn prime(&self) -> io::Result<()> {
+            try!(self.wakeup());
+            self.cleanup();
+            Ok(())
+        }
+
+        /// Resets the counter. A single read returns the accumulated value
+        /// and sets it back to zero, but an interrupted read is retried.
+        pub fn cleanup(&self) {
+            let mut buf: u64 = 0;
+
+            loop {
+                let res = unsafe {
+                    libc::read(self.fd, &mut buf as *mut u64 as *mut libc::c_void, mem::size_of::<u64>())
+                };
+
+                match cvt(res) {
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                    _ => return,
+                }
+            }
+        }
+    }
+
+    impl Evented for Awakener {
+        fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+            EventedFd(&self.fd).register(poll, token, interest, opts)
+        }
+
+        fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+            EventedFd(&self.fd).reregister(poll, token, interest, opts)
+        }
+
+        fn deregister(&self, poll: &Poll) -> io::Result<()> {
+            EventedFd(&self.fd).deregister(poll)
+        }
+    }
+
+    impl Drop for Awakener {
+        fn drop(&mut self) {
+            // Cancel the poll requests of the fd before closing it
+            drop(CloseGuard::new(self.fd));
+
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+}
+
+/// kqueue `EVFILT_USER` based awakener, which needs no file descriptor of its
+/// own and a single system call per wakeup
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+mod user {
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::sync::Mutex;
+
+    use libc;
+
+    use {io, poll, Evented, Ready, Poll, PollOpt, Token};
+    use sys::unix::cvt;
+    use sys::unix::kqueue::trigger_user;
+
+    pub struct Awakener {
+        inner: Mutex<Option<AwakenerInner>>,
+    }
+
+    struct AwakenerInner {
+        // A duplicate of the kqueue descriptor, so that waking up a `Poll`
+        // that is being dropped never triggers an event on a reused fd
+        kq: RawFd,
+        token: Token,
+    }
+
+    impl Awakener {
+        pub fn new() -> Awakener {
+            Awakener {
+                inner: Mutex::new(None),
+            }
+        }
+
+        /// Wake up the associated `Poll`. Like the IOCP awakener, this
+        /// silently succeeds if it isn't registered yet.
+        pub fn wakeup(&self) -> io::Result<()> {
+            match *self.inner.lock().unwrap() {
+                Some(ref inner) => trigger_user(inner.kq, inner.token),
+                None => Ok(()),
+            }
+        }
+
+        pub fn prime(&self) -> io::Result<()> {
+            // noop, there is no buffer to touch and triggering the event
+            // would make the next `select` return early
+            Ok(())
+        }
+
+        pub fn cleanup(&self) {
+            // noop, the event is registered with EV_CLEAR and resets itself
+            // once delivered
+        }
+    }
+
+    impl Evented for Awakener {
+        fn register(&self, poll: &Poll, token: Token, _interest: Ready, _opts: PollOpt) -> io::Result<()> {
+            let selector = poll::selector(poll);
+            let kq = unsafe {
+                try!(cvt(libc::fcntl(selector.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0)))
+            };
+
+            if let Err(e) = selector.register_user(token) {
+                unsafe { libc::close(kq); }
+                return Err(e);
+            }
+
+            *self.inner.lock().unwrap() = Some(AwakenerInner {
+                kq: kq,
+                token: token,
+            });
+            Ok(())
+        }
+
+        fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+            try!(self.deregister(poll));
+            self.register(poll, token, interest, opts)
+        }
+
+        fn deregister(&self, poll: &Poll) -> io::Result<()> {
+            match self.inner.lock().unwrap().take() {
+                Some(inner) => poll::selector(poll).deregister_user(inner.token),
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for AwakenerInner {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.kq);
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use std::time::Duration;
-use std::{cmp, i32};
+use std::{cmp, i32, ptr};
 
 use libc::c_int;
 use libc;
@@ -18,8 +18,8 @@ const EPOLLRDHUP: libc::c_int = 0x00002000;
 const EPOLLONESHOT: libc::c_int = 0x40000000;
 
 use {convert, io, Ready, PollOpt, Token};
-use event::Event;
-use sys::unix::cvt;
+use event::{self, Event};
+use sys::unix::{cvt, registration_error, FdValidation};
 use sys::unix::io::set_cloexec;
 
 /// Each Selector has a globally unique(ish) ID associated with it. This ID
@@ -29,14 +29,54 @@ use sys::unix::io::set_cloexec;
 /// operation will return with an error. This matches windows behavior.
 static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
 
+/// `epoll_pwait2` (Linux 5.11) takes a `timespec` instead of milliseconds.
+/// libc doesn't bind it yet; the number is shared by every architecture but
+/// alpha. Not used on android, where seccomp kills unknown syscalls.
+#[cfg(target_os = "linux")]
+const SYS_EPOLL_PWAIT2: libc::c_long = 441;
+
+/// Whether the kernel supports `epoll_pwait2`: 0 unknown, 1 yes, 2 no.
+#[cfg(target_os = "linux")]
+static EPOLL_PWAIT2: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[cfg(target_os = "linux")]
+fn has_epoll_pwait2() -> bool {
+    match EPOLL_PWAIT2.load(Ordering::Relaxed) {
+        0 => {
+            // Probe with an invalid epoll fd, the syscall exists if it
+            // complains about the fd rather than returning ENOSYS.
+            let res = unsafe {
+                libc::syscall(SYS_EPOLL_PWAIT2,
+                              -1 as c_int,
+                              ptr::null_mut::<libc::epoll_event>(),
+                              1 as c_int,
+                              ptr::null::<libc::timespec>(),
+                              ptr::null::<libc::sigset_t>(),
+                              0 as libc::size_t)
+            };
+            let supported = res == -1 &&
+                io::Error::last_os_error().raw_os_error() == Some(libc::EBADF);
+            EPOLL_PWAIT2.store(if supported { 1 } else { 2 }, Ordering::Relaxed);
+            supported
+        }
+        n => n == 1,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn has_epoll_pwait2() -> bool {
+    false
+}
+
 #[derive(Debug)]
 pub struct Selector {
     id: usize,
     epfd: RawFd,
+    validation: FdValidation,
 }
 
 impl Selector {
-    pub fn new() -> io::Result<Selector> {
+    pub fn new(cloexec: bool) -> io::Result<Selector> {
         let epfd = unsafe {
             // Emulate `epoll_create` by using `epoll_create1` if it's available
             // and otherwise falling back to `epoll_create` followed by a call to
@@ -45,11 +85,14 @@ impl Selector {
 
             match epoll_create1.get() {
                 Some(epoll_create1_fn) => {
-                    try!(cvt(epoll_create1_fn(libc::EPOLL_CLOEXEC)))
+                    let flags = if cloexec { libc::EPOLL_CLOEXEC } else { 0 };
+                    try!(cvt(epoll_create1_fn(flags)))
                 }
                 None => {
                     let fd = try!(cvt(libc::epoll_create(1024)));
-                    drop(set_cloexec(fd));
+                    if cloexec {
+                        drop(set_cloexec(fd));
+                    }
                     fd
                 }
             }
@@ -61,6 +104,7 @@ impl Selector {
         Ok(Selector {
             id: id,
             epfd: epfd,
+            validation: FdValidation::new(),
         })
     }
 
@@ -68,31 +112,94 @@ impl Selector {
         self.id
     }
 
+    /// Returns the timeout `select` actually waits for when given `timeout`.
+    ///
+    /// With `epoll_pwait2` the timeout is used as is. Otherwise `epoll_wait`
+    /// takes milliseconds, the timeout is rounded up to the next millisecond
+    /// so that the call never returns early, and capped at `i32::MAX`
+    /// milliseconds.
+    pub fn effective_timeout(timeout: Duration) -> Duration {
+        if has_epoll_pwait2() {
+            return timeout;
+        }
+
+        Duration::from_millis(cmp::min(convert::millis(timeout), i32::MAX as u64))
+    }
+
+    /// Number of events worth requesting per call to `select`. `epoll_wait`
+    /// copies out only the ready events, so a large buffer is cheap.
+    pub fn recommended_capacity(&self) -> usize {
+        1024
+    }
+
     /// Wait for events from the OS
+    ///
+    /// The timeout may be rounded up, see `effective_timeout`.
     pub fn select(&self, evts: &mut Events, awakener: Token, timeout: Option<Duration>) -> io::Result<bool> {
-        let timeout_ms = timeout
-            .map(|to| cmp::min(convert::millis(to), i32::MAX as u64) as i32)
-            .unwrap_or(-1);
-
-        // Wait for epoll events for at most timeout_ms milliseconds
         unsafe {
-            evts.events.set_len(0);
-            let cnt = try!(cvt(libc::epoll_wait(self.epfd,
-                                                evts.events.as_mut_ptr(),
-                                                evts.events.capacity() as i32,
-                                                timeout_ms)));
-            let cnt = cnt as usize;
-            evts.events.set_len(cnt);
-
-            for i in 0..cnt {
-                if evts.events[i].u64 as usize == awakener.into() {
-                    evts.events.remove(i);
-                    return Ok(true);
+            evts.clear();
+            let cnt = match timeout {
+                Some(to) if has_epoll_pwait2() => try!(self.wait_precise(evts, to)),
+                _ => {
+                    let timeout_ms = timeout
+                        .map(|to| convert::millis(Selector::effective_timeout(to)) as i32)
+                        .unwrap_or(-1);
+
+                    // Wait for epoll events for at most timeout_ms milliseconds
+                    try!(cvt(libc::epoll_wait(self.epfd,
+                                              evts.events.as_mut_ptr(),
+                                              evts.events.capacity() as i32,
+                                              timeout_ms)))
                 }
+            };
+            evts.events.set_len(cnt as usize);
+        }
+
+        let mut awoken = false;
+
+        for i in 0..evts.events.len() {
+            if evts.events[i].u64 as usize == awakener.into() {
+                evts.events.remove(i);
+                awoken = true;
+                break;
             }
         }
 
-        Ok(false)
+        self.validation.check(|token| {
+            evts.push_event(Event::new(Ready::error() | Ready::hup(), token));
+        });
+
+        Ok(awoken)
+    }
+
+    /// Waits with `epoll_pwait2` for at most `timeout`, at nanosecond
+    /// precision.
+    #[cfg(target_os = "linux")]
+    unsafe fn wait_precise(&self, evts: &mut Events, timeout: Duration) -> io::Result<c_int> {
+        let ts = libc::timespec {
+            tv_sec: cmp::min(timeout.as_secs(), libc::time_t::max_value() as u64) as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as libc::c_long,
+        };
+
+        let cnt = try!(cvt(libc::syscall(SYS_EPOLL_PWAIT2,
+                                         self.epfd,
+                                         evts.events.as_mut_ptr(),
+                                         evts.events.capacity() as c_int,
+                                         &ts as *const libc::timespec,
+                                         ptr::null::<libc::sigset_t>(),
+                                         0 as libc::size_t)));
+        Ok(cnt as c_int)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    unsafe fn wait_precise(&self, _evts: &mut Events, _timeout: Duration) -> io::Result<c_int> {
+        unreachable!()
+    }
+
+    /// Enables or disables tracking registered fds, see
+    /// `Poll::set_fd_validation`.
+    pub fn set_fd_validation(&self, enabled: bool) {
+        self.validation.set_enabled(enabled);
     }
 
     /// Register event interests for the given IO handle with the OS
@@ -103,9 +210,12 @@ impl Selector {
         };
 
         unsafe {
-            try!(cvt(libc::epoll_ctl(self.epfd, libc::EPOLL_CTL_ADD, fd, &mut info)));
-            Ok(())
+            try!(cvt(libc::epoll_ctl(self.epfd, libc::EPOLL_CTL_ADD, fd, &mut info))
+                 .map_err(|e| registration_error(e, fd, token)));
         }
+
+        self.validation.register(fd, token);
+        Ok(())
     }
 
     /// Register event interests for the given IO handle with the OS
@@ -116,9 +226,12 @@ impl Selector {
         };
 
         unsafe {
-            try!(cvt(libc::epoll_ctl(self.epfd, libc::EPOLL_CTL_MOD, fd, &mut info)));
-            Ok(())
+            try!(cvt(libc::epoll_ctl(self.epfd, libc::EPOLL_CTL_MOD, fd, &mut info))
+                 .map_err(|e| registration_error(e, fd, token)));
         }
+
+        self.validation.register(fd, token);
+        Ok(())
     }
 
     /// Deregister event interests for the given IO handle with the OS
@@ -133,12 +246,14 @@ impl Selector {
 
         unsafe {
             try!(cvt(libc::epoll_ctl(self.epfd, libc::EPOLL_CTL_DEL, fd, &mut info)));
-            Ok(())
         }
+
+        self.validation.deregister(fd);
+        Ok(())
     }
 }
 
-fn ioevent_to_epoll(interest: Ready, opts: PollOpt) -> u32 {
+pub fn ioevent_to_epoll(interest: Ready, opts: PollOpt) -> u32 {
     let mut kind = 0;
 
     if interest.is_readable() {
@@ -147,6 +262,10 @@ fn ioevent_to_epoll(interest: Ready, opts: PollOpt) -> u32 {
         } else {
             kind |= EPOLLIN;
         }
+
+        // Report the peer shutting down its write half as hup, like kqueue's
+        // EV_EOF, so that half-closed connections are detected without a read
+        kind |= EPOLLRDHUP;
     }
 
     if interest.is_writable() {
@@ -157,6 +276,10 @@ fn ioevent_to_epoll(interest: Ready, opts: PollOpt) -> u32 {
         kind |= EPOLLRDHUP;
     }
 
+    if interest.is_priority() {
+        kind |= EPOLLPRI;
+    }
+
     if opts.is_edge() {
         kind |= EPOLLET;
     }
@@ -169,9 +292,61 @@ fn ioevent_to_epoll(interest: Ready, opts: PollOpt) -> u32 {
         kind &= !EPOLLET;
     }
 
+    // The kernel refuses EPOLLEXCLUSIVE along with anything else than
+    // EPOLLIN, EPOLLOUT and EPOLLET, EPOLLRDHUP and EPOLLPRI included
+    if opts.is_exclusive() {
+        kind &= EPOLLIN | EPOLLOUT | EPOLLET;
+        kind |= libc::EPOLLEXCLUSIVE;
+    }
+
     kind as u32
 }
 
+fn to_event(event: &libc::epoll_event) -> Event {
+    let epoll = event.events as c_int;
+    let mut kind = Ready::none();
+
+    if (epoll & EPOLLIN) != 0 || (epoll & EPOLLPRI) != 0 {
+        kind = kind | Ready::readable();
+    }
+
+    if (epoll & EPOLLPRI) != 0 {
+        kind = kind | Ready::priority();
+    }
+
+    if (epoll & EPOLLOUT) != 0 {
+        kind = kind | Ready::writable();
+    }
+
+    // EPOLLERR and EPOLLHUP are always reported, whatever the interest
+    if (epoll & EPOLLERR) != 0 {
+        kind = kind | Ready::error();
+    }
+
+    // EPOLLRDHUP is a read half-close, EPOLLHUP means both halves are closed
+    if (epoll & EPOLLRDHUP) != 0 || (epoll & EPOLLHUP) != 0 {
+        kind = kind | Ready::hup();
+    }
+
+    Event::new(kind, Token(event.u64 as usize))
+}
+
+/// Adds the readiness kept aside from the epoll flags to the event at `idx`.
+#[inline]
+fn with_extra(extra: &[Ready], idx: usize, mut event: Event) -> Event {
+    if let Some(&extra) = extra.get(idx) {
+        event::kind_mut(&mut event).insert(extra);
+    }
+
+    event
+}
+
+impl AsRawFd for Selector {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epfd
+    }
+}
+
 impl Drop for Selector {
     fn drop(&mut self) {
         unsafe {
@@ -182,12 +357,22 @@ impl Drop for Selector {
 
 pub struct Events {
     events: Vec<libc::epoll_event>,
+
+    // Readiness pushed by `Poll` that epoll flags can't carry as is
+    // (`Ready::timeout` and a lone `Ready::priority`) of each event. Empty
+    // until such an event is pushed, it then has the length of `events`.
+    extra: Vec<Ready>,
+
+    // Converted copy of `events`, filled by `as_slice`
+    slice: Vec<Event>,
 }
 
 impl Events {
     pub fn with_capacity(u: usize) -> Events {
         Events {
-            events: Vec::with_capacity(u)
+            events: Vec::with_capacity(u),
+            extra: Vec::new(),
+            slice: Vec::new(),
         }
     }
 
@@ -203,37 +388,107 @@ impl Events {
 
     #[inline]
     pub fn get(&self, idx: usize) -> Option<Event> {
-        self.events.get(idx).map(|event| {
-            let epoll = event.events as c_int;
-            let mut kind = Ready::none();
+        self.events.get(idx).map(|event| with_extra(&self.extra, idx, to_event(event)))
+    }
 
-            if (epoll & EPOLLIN) != 0 || (epoll & EPOLLPRI) != 0 {
-                kind = kind | Ready::readable();
-            }
+    /// Like `get`, but does not check that `idx` is in bounds.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, idx: usize) -> Event {
+        with_extra(&self.extra, idx, to_event(self.events.get_unchecked(idx)))
+    }
 
-            if (epoll & EPOLLOUT) != 0 {
-                kind = kind | Ready::writable();
-            }
+    pub fn truncate(&mut self, len: usize) {
+        self.events.truncate(len);
+        self.extra.truncate(len);
+    }
 
-            // EPOLLHUP - Usually means a socket error happened
-            if (epoll & EPOLLERR) != 0 {
-                kind = kind | Ready::error();
-            }
+    /// The number of events `epoll_wait` may return.
+    pub fn capacity(&self) -> usize {
+        self.events.capacity()
+    }
 
-            if (epoll & EPOLLRDHUP) != 0 || (epoll & EPOLLHUP) != 0 {
-                kind = kind | Ready::hup();
-            }
+    /// Grows the capacity by `additional` events, keeping the current ones.
+    pub fn reserve(&mut self, additional: usize) {
+        let capacity = self.events.capacity() + additional;
+        let len = self.events.len();
+
+        self.events.reserve_exact(capacity - len);
+    }
 
-            let token = self.events[idx].u64;
+    /// Returns the events as a slice. epoll returns `epoll_event` values, so
+    /// the events are converted into a buffer that is reused across calls.
+    pub fn as_slice(&mut self) -> &[Event] {
+        self.slice.clear();
 
-            Event::new(kind, Token(token as usize))
-        })
+        for (idx, event) in self.events.iter().enumerate() {
+            self.slice.push(with_extra(&self.extra, idx, to_event(event)));
+        }
+
+        &self.slice
     }
 
-    pub fn push_event(&mut self, event: Event) {
+    pub fn sort_by_token(&mut self) {
+        if self.extra.is_empty() {
+            self.events.sort_by_key(|e| e.u64);
+            return;
+        }
+
+        let mut sorted: Vec<_> = self.events.drain(..).zip(self.extra.drain(..)).collect();
+        sorted.sort_by_key(|&(ref e, _)| e.u64);
+
+        for (event, extra) in sorted {
+            self.events.push(event);
+            self.extra.push(extra);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.extra.clear();
+    }
+
+    /// Appends an event given as an epoll mask.
+    #[cfg(feature = "io_uring")]
+    pub fn push_raw(&mut self, events: u32, token: Token) {
+        self.push(events, Ready::none(), token);
+    }
+
+    fn push(&mut self, events: u32, extra: Ready, token: Token) {
+        if !extra.is_none() || !self.extra.is_empty() {
+            self.extra.resize(self.events.len(), Ready::none());
+            self.extra.push(extra);
+        }
+
         self.events.push(libc::epoll_event {
-            events: ioevent_to_epoll(event.kind(), PollOpt::empty()),
-            u64: usize::from(event.token()) as u64
+            events: events,
+            u64: usize::from(token) as u64
         });
     }
+
+    pub fn push_event(&mut self, event: Event) {
+        let kind = event.kind();
+        let mut events = 0;
+
+        // The inverse of `to_event`. `ioevent_to_epoll` maps an interest, it
+        // adds EPOLLRDHUP for readable and ignores error
+        if kind.is_readable() {
+            events |= EPOLLIN;
+        }
+
+        if kind.is_writable() {
+            events |= EPOLLOUT;
+        }
+
+        if kind.is_error() {
+            events |= EPOLLERR;
+        }
+
+        if kind.is_hup() {
+            events |= EPOLLRDHUP;
+        }
+
+        // EPOLLPRI would come back readable as well, like the priority
+        // events of the kernel, so priority is kept aside with timeout
+        self.push(events as u32, kind & (Ready::priority() | Ready::timeout()), event.token());
+    }
 }
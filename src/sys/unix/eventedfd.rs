@@ -1,4 +1,5 @@
-use {io, poll, Evented, Ready, Poll, PollOpt, Token};
+use {io, poll, Evented, Ready, Poll, PollOpt, TakeError, Token};
+use sys::unix::socket;
 use std::os::unix::io::RawFd;
 
 /*
@@ -7,8 +8,70 @@ use std::os::unix::io::RawFd;
  *
  */
 
+/// Adapter for a `RawFd` providing an `Evented` implementation.
+///
+/// `EventedFd` enables registering any type with an FD with `Poll`, such as
+/// a timerfd, an inotify instance, a device file, or a descriptor handed out
+/// by a C library. The descriptor is passed to the selector as is: it should
+/// be in non-blocking mode, and it must stay open for as long as it is
+/// registered.
+///
+/// `EventedFd` does not take ownership of the descriptor. Closing it remains
+/// the responsibility of its owner, after deregistering it.
+///
+/// # Examples
+///
+/// Basic usage
+///
+/// ```
+/// use mio::{Ready, Poll, PollOpt, Token};
+/// use mio::unix::EventedFd;
+///
+/// use std::os::unix::io::AsRawFd;
+/// use std::net::TcpListener;
+///
+/// // Bind a std listener
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+///
+/// let poll = Poll::new().unwrap();
+///
+/// // Register the listener
+/// poll.register(&EventedFd(&listener.as_raw_fd()),
+///              Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+/// ```
+///
+/// Implementing `Evented` for a custom type backed by a `RawFd`
+///
+/// ```
+/// use mio::{Ready, Poll, PollOpt, Token};
+/// use mio::unix::EventedFd;
+///
+/// use std::io;
+/// use std::os::unix::io::RawFd;
+///
+/// pub struct MyIo {
+///     fd: RawFd,
+/// }
+///
+/// impl mio::Evented for MyIo {
+///     fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+///         -> io::Result<()>
+///     {
+///         EventedFd(&self.fd).register(poll, token, interest, opts)
+///     }
+///
+///     fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+///         -> io::Result<()>
+///     {
+///         EventedFd(&self.fd).reregister(poll, token, interest, opts)
+///     }
+///
+///     fn deregister(&self, poll: &Poll) -> io::Result<()> {
+///         EventedFd(&self.fd).deregister(poll)
+///     }
+/// }
+/// ```
 #[derive(Debug)]
-
 pub struct EventedFd<'a>(pub &'a RawFd);
 
 impl<'a> Evented for EventedFd<'a> {
@@ -24,3 +87,10 @@ impl<'a> Evented for EventedFd<'a> {
         poll::selector(poll).deregister(*self.0)
     }
 }
+
+/// Fails with `ENOTSOCK` if the fd is not a socket.
+impl<'a> TakeError for EventedFd<'a> {
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        socket::take_error(*self.0)
+    }
+}
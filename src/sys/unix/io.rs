@@ -6,7 +6,7 @@ use libc;
 
 use {io, Evented, Ready, Poll, PollOpt, Token};
 use unix::EventedFd;
-use sys::unix::cvt;
+use sys::unix::{cvt, CloseGuard};
 
 pub fn set_nonblock(fd: libc::c_int) -> io::Result<()> {
     unsafe {
@@ -29,18 +29,21 @@ pub fn set_cloexec(fd: libc::c_int) -> io::Result<()> {
 
 #[derive(Debug)]
 pub struct Io {
+    // Dropped before `fd` is closed
+    _guard: CloseGuard,
     fd: File,
 }
 
 impl Io {
     pub fn try_clone(&self) -> io::Result<Io> {
-        Ok(Io { fd: try!(self.fd.try_clone()) })
+        let fd = try!(self.fd.try_clone());
+        Ok(unsafe { Io::from_raw_fd(fd.into_raw_fd()) })
     }
 }
 
 impl FromRawFd for Io {
     unsafe fn from_raw_fd(fd: RawFd) -> Io {
-        Io { fd: File::from_raw_fd(fd) }
+        Io { _guard: CloseGuard::new(fd), fd: File::from_raw_fd(fd) }
     }
 }
 
@@ -1,4 +1,7 @@
+use std::cmp;
+use std::io;
 use std::mem;
+use std::os::unix::io::RawFd;
 use std::slice;
 
 use libc;
@@ -15,6 +18,34 @@ pub unsafe fn as_iovec_slice_mut<'a>(iov: &'a mut [&mut ::IoVec]) -> &'a mut [li
     mem::transmute(iov)
 }
 
+/// Reads into `bufs` with a single `readv` call on `fd`.
+pub fn readv(fd: RawFd, bufs: &mut [&mut ::IoVec]) -> io::Result<usize> {
+    unsafe {
+        let slice = as_iovec_slice_mut(bufs);
+        let len = cmp::min(<libc::c_int>::max_value() as usize, slice.len());
+        let rc = libc::readv(fd, slice.as_ptr(), len as libc::c_int);
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(rc as usize)
+        }
+    }
+}
+
+/// Writes `bufs` with a single `writev` call on `fd`.
+pub fn writev(fd: RawFd, bufs: &[&::IoVec]) -> io::Result<usize> {
+    unsafe {
+        let slice = as_iovec_slice(bufs);
+        let len = cmp::min(<libc::c_int>::max_value() as usize, slice.len());
+        let rc = libc::writev(fd, slice.as_ptr(), len as libc::c_int);
+        if rc < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(rc as usize)
+        }
+    }
+}
+
 impl IoVec {
     pub fn as_bytes(&self) -> &[u8] {
         let vec = self.iovec();
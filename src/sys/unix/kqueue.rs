@@ -1,17 +1,48 @@
-use std::{cmp, fmt};
+use std::{cmp, fmt, isize};
 use std::cell::RefCell;
-use std::os::unix::io::RawFd;
-use std::collections::HashMap;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use std::time::Duration;
 
 use libc::{self, time_t};
 
-use {io, Ready, PollOpt, Token};
+use {convert, io, Ready, PollOpt, Token};
 use event::{self, Event};
-use sys::unix::cvt;
+use sys::unix::{cvt, registration_error, FdValidation};
 use sys::unix::io::set_cloexec;
 
+// Priority data is reported by `EVFILT_EXCEPT`, where available. Elsewhere
+// the filter is 0 and priority interest is ignored.
+#[cfg(target_os = "dragonfly")]
+const EVFILT_EXCEPT: i16 = libc::EVFILT_EXCEPT;
+#[cfg(target_os = "dragonfly")]
+const NOTE_OOB: u32 = libc::NOTE_OOB;
+
+// Only recent libc releases define these constants on macOS, iOS and
+// OpenBSD, but the kernels support them.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const EVFILT_EXCEPT: i16 = -15;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const NOTE_OOB: u32 = 0x00000002;
+
+#[cfg(target_os = "openbsd")]
+const EVFILT_EXCEPT: i16 = -9;
+#[cfg(target_os = "openbsd")]
+const NOTE_OOB: u32 = 0x00000004;
+
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "dragonfly", target_os = "openbsd")))]
+const EVFILT_EXCEPT: i16 = 0;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "dragonfly", target_os = "openbsd")))]
+const NOTE_OOB: u32 = 0;
+
+// `EVFILT_USER` events back the kqueue awakener, where available
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+const EVFILT_USER: i16 = libc::EVFILT_USER;
+
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly")))]
+const EVFILT_USER: i16 = 0;
+
 /// Each Selector has a globally unique(ish) ID associated with it. This ID
 /// gets tracked by `TcpStream`, `TcpListener`, etc... when they are first
 /// registered with the `Selector`. If a type that is previously associated with
@@ -22,20 +53,29 @@ static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
 pub struct Selector {
     id: usize,
     kq: RawFd,
+    // Fds registered with `EVFILT_EXCEPT`, which is only added for priority
+    // interest as it isn't supported by every kind of fd
+    except: RefCell<HashSet<RawFd>>,
     changes: RefCell<KeventList>,
+    validation: FdValidation,
 }
 
 impl Selector {
-    pub fn new() -> io::Result<Selector> {
+    pub fn new(cloexec: bool) -> io::Result<Selector> {
         // offset by 1 to avoid choosing 0 as the id of a selector
         let id = NEXT_ID.fetch_add(1, Ordering::Relaxed) + 1;
         let kq = unsafe { try!(cvt(libc::kqueue())) };
-        drop(set_cloexec(kq));
+
+        if cloexec {
+            drop(set_cloexec(kq));
+        }
 
         Ok(Selector {
             id: id,
             kq: kq,
+            except: RefCell::new(HashSet::new()),
             changes: RefCell::new(KeventList(Vec::new())),
+            validation: FdValidation::new(),
         })
     }
 
@@ -43,6 +83,24 @@ impl Selector {
         self.id
     }
 
+    /// Returns the timeout `select` actually waits for when given `timeout`.
+    ///
+    /// `kevent` takes a `timespec`, so the timeout is used as is. It is only
+    /// capped at the largest number of seconds `time_t` can represent.
+    pub fn effective_timeout(timeout: Duration) -> Duration {
+        if timeout.as_secs() > time_t::max_value() as u64 {
+            Duration::from_secs(time_t::max_value() as u64)
+        } else {
+            timeout
+        }
+    }
+
+    /// Number of events worth requesting per call to `select`. Like epoll,
+    /// `kevent` only copies out the triggered events.
+    pub fn recommended_capacity(&self) -> usize {
+        1024
+    }
+
     pub fn select(&self, evts: &mut Events, awakener: Token, timeout: Option<Duration>) -> io::Result<bool> {
         let timeout = timeout.map(|to| {
             libc::timespec {
@@ -62,9 +120,21 @@ impl Selector {
 
             self.changes.borrow_mut().0.clear();
             evts.sys_events.0.set_len(cnt as usize);
-
-            Ok(evts.coalesce(awakener))
         }
+
+        let awoken = evts.coalesce(awakener);
+
+        self.validation.check(|token| {
+            evts.push_event(Event::new(Ready::error() | Ready::hup(), token));
+        });
+
+        Ok(awoken)
+    }
+
+    /// Enables or disables tracking registered fds, see
+    /// `Poll::set_fd_validation`.
+    pub fn set_fd_validation(&self, enabled: bool) {
+        self.validation.set_enabled(enabled);
     }
 
     pub fn register(&self, fd: RawFd, token: Token, interests: Ready, opts: PollOpt) -> io::Result<()> {
@@ -80,8 +150,12 @@ impl Selector {
                          libc::EVFILT_WRITE,
                          interests.contains(Ready::writable()),
                          opts);
+        self.ev_register_except(fd, token.into(), interests.is_priority(), opts);
 
-        self.flush_changes()
+        try!(self.flush_changes().map_err(|e| registration_error(e, fd, token)));
+
+        self.validation.register(fd, token);
+        Ok(())
     }
 
     pub fn reregister(&self, fd: RawFd, token: Token, interests: Ready, opts: PollOpt) -> io::Result<()> {
@@ -94,6 +168,120 @@ impl Selector {
         self.ev_push(fd, 0, libc::EVFILT_READ, libc::EV_DELETE);
         self.ev_push(fd, 0, libc::EVFILT_WRITE, libc::EV_DELETE);
 
+        self.ev_delete_except(fd);
+
+        try!(self.flush_changes());
+
+        self.validation.deregister(fd);
+        Ok(())
+    }
+
+    /// Register an `EVFILT_TIMER` identified by `ident` with the kqueue.
+    ///
+    /// The timer fires after `delay` and, unless `oneshot` is set, keeps
+    /// firing every `delay` after that. The delay is rounded up to the next
+    /// millisecond as that is the default unit of `EVFILT_TIMER`.
+    pub fn register_timer(&self, ident: usize, token: Token, delay: Duration, oneshot: bool) -> io::Result<()> {
+        trace!("registering timer; token={:?}; delay={:?}", token, delay);
+
+        let mut flags = libc::EV_ADD | libc::EV_ENABLE;
+
+        if oneshot {
+            flags = flags | libc::EV_ONESHOT;
+        }
+
+        let millis = cmp::min(convert::millis(delay), isize::MAX as u64);
+
+        self.changes.borrow_mut().0.push(libc::kevent {
+            ident: ident as ::libc::uintptr_t,
+            filter: libc::EVFILT_TIMER,
+            flags: flags,
+            fflags: 0,
+            data: millis as _,
+            udata: usize::from(token) as *mut _,
+        });
+
+        self.flush_changes()
+    }
+
+    pub fn deregister_timer(&self, ident: usize) -> io::Result<()> {
+        self.changes.borrow_mut().0.push(libc::kevent {
+            ident: ident as ::libc::uintptr_t,
+            filter: libc::EVFILT_TIMER,
+            flags: libc::EV_DELETE,
+            fflags: 0,
+            data: 0,
+            udata: 0 as *mut _,
+        });
+
+        self.flush_changes()
+    }
+
+    /// Registers an `EVFILT_USER` event, identified by `token`, which is
+    /// triggered with `trigger_user`.
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+    pub fn register_user(&self, token: Token) -> io::Result<()> {
+        self.changes.borrow_mut().0.push(libc::kevent {
+            ident: usize::from(token) as ::libc::uintptr_t,
+            filter: libc::EVFILT_USER,
+            flags: libc::EV_ADD | libc::EV_CLEAR,
+            fflags: 0,
+            data: 0,
+            udata: usize::from(token) as *mut _,
+        });
+
+        self.flush_changes()
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+    pub fn deregister_user(&self, token: Token) -> io::Result<()> {
+        self.changes.borrow_mut().0.push(libc::kevent {
+            ident: usize::from(token) as ::libc::uintptr_t,
+            filter: libc::EVFILT_USER,
+            flags: libc::EV_DELETE,
+            fflags: 0,
+            data: 0,
+            udata: 0 as *mut _,
+        });
+
+        self.flush_changes()
+    }
+
+    pub fn register_proc(&self, pid: libc::pid_t, token: Token, opts: PollOpt) -> io::Result<()> {
+        trace!("registering process; token={:?}; pid={:?}", token, pid);
+
+        let mut flags = libc::EV_ADD | libc::EV_ENABLE;
+
+        if opts.contains(PollOpt::edge()) {
+            flags = flags | libc::EV_CLEAR;
+        }
+
+        if opts.contains(PollOpt::oneshot()) {
+            flags = flags | libc::EV_ONESHOT;
+        }
+
+        self.changes.borrow_mut().0.push(libc::kevent {
+            ident: pid as ::libc::uintptr_t,
+            filter: libc::EVFILT_PROC,
+            flags: flags,
+            fflags: libc::NOTE_EXIT,
+            data: 0,
+            udata: usize::from(token) as *mut _,
+        });
+
+        self.flush_changes()
+    }
+
+    pub fn deregister_proc(&self, pid: libc::pid_t) -> io::Result<()> {
+        self.changes.borrow_mut().0.push(libc::kevent {
+            ident: pid as ::libc::uintptr_t,
+            filter: libc::EVFILT_PROC,
+            flags: libc::EV_DELETE,
+            fflags: 0,
+            data: 0,
+            udata: 0 as *mut _,
+        });
+
         self.flush_changes()
     }
 
@@ -122,6 +310,44 @@ impl Selector {
         self.ev_push(fd, token, filter, flags);
     }
 
+    /// Adds `EVFILT_EXCEPT` for priority interest, or removes it if it was
+    /// added by a previous registration of `fd`.
+    fn ev_register_except(&self, fd: RawFd, token: usize, priority: bool, opts: PollOpt) {
+        if priority && EVFILT_EXCEPT != 0 {
+            self.ev_register(fd, token, EVFILT_EXCEPT, true, opts);
+
+            if let Some(change) = self.changes.borrow_mut().0.last_mut() {
+                change.fflags = NOTE_OOB;
+            }
+
+            self.except.borrow_mut().insert(fd);
+        } else {
+            self.ev_delete_except(fd);
+        }
+    }
+
+    /// Removes `EVFILT_EXCEPT` if it was added for `fd`. This is submitted
+    /// on its own and errors are ignored: if `fd` was closed and reused in
+    /// the meantime, the filter is already gone and the deletion fails.
+    fn ev_delete_except(&self, fd: RawFd) {
+        if !self.except.borrow_mut().remove(&fd) {
+            return;
+        }
+
+        let change = libc::kevent {
+            ident: fd as ::libc::uintptr_t,
+            filter: EVFILT_EXCEPT,
+            flags: libc::EV_DELETE,
+            fflags: 0,
+            data: 0,
+            udata: 0 as *mut _,
+        };
+
+        unsafe {
+            libc::kevent(self.kq, &change, 1, 0 as *mut _, 0, 0 as *const _);
+        }
+    }
+
     fn ev_push(&self,
                fd: RawFd,
                token: usize,
@@ -140,18 +366,63 @@ impl Selector {
     fn flush_changes(&self) -> io::Result<()> {
         unsafe {
             let mut changes = self.changes.borrow_mut();
-            try!(cvt(libc::kevent(self.kq,
-                                  changes.0.as_mut_ptr() as *const _,
-                                  changes.0.len() as i32,
-                                  0 as *mut _,
-                                  0,
-                                  0 as *const _)));
+            let res = cvt(libc::kevent(self.kq,
+                                       changes.0.as_mut_ptr() as *const _,
+                                       changes.0.len() as i32,
+                                       0 as *mut _,
+                                       0,
+                                       0 as *const _));
+
+            // Clear the changes even on error, a rejected change (for
+            // example a closed fd) must not be submitted again by the next
+            // flush.
             changes.0.clear();
+            try!(res);
             Ok(())
         }
     }
 }
 
+/// Deletes the `EVFILT_TIMER` event identified by `ident` from `kq`, outside
+/// of a `Selector`. Used when a `KqueueTimer` is dropped while registered.
+pub fn delete_timer(kq: RawFd, ident: usize) -> io::Result<()> {
+    let change = libc::kevent {
+        ident: ident as ::libc::uintptr_t,
+        filter: libc::EVFILT_TIMER,
+        flags: libc::EV_DELETE,
+        fflags: 0,
+        data: 0,
+        udata: 0 as *mut _,
+    };
+
+    unsafe {
+        try!(cvt(libc::kevent(kq, &change, 1, 0 as *mut _, 0, 0 as *const _)));
+    }
+
+    Ok(())
+}
+
+/// Triggers the `EVFILT_USER` event registered for `token` with
+/// `Selector::register_user`. `kq` may be a duplicate of the selector's
+/// descriptor, and the call is thread safe.
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+pub fn trigger_user(kq: RawFd, token: Token) -> io::Result<()> {
+    let change = libc::kevent {
+        ident: usize::from(token) as ::libc::uintptr_t,
+        filter: libc::EVFILT_USER,
+        flags: 0,
+        fflags: libc::NOTE_TRIGGER,
+        data: 0,
+        udata: usize::from(token) as *mut _,
+    };
+
+    unsafe {
+        try!(cvt(libc::kevent(kq, &change, 1, 0 as *mut _, 0, 0 as *const _)));
+    }
+
+    Ok(())
+}
+
 impl fmt::Debug for Selector {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Selector")
@@ -162,6 +433,12 @@ impl fmt::Debug for Selector {
     }
 }
 
+impl AsRawFd for Selector {
+    fn as_raw_fd(&self) -> RawFd {
+        self.kq
+    }
+}
+
 impl Drop for Selector {
     fn drop(&mut self) {
         unsafe {
@@ -204,6 +481,25 @@ impl Events {
         self.events.get(idx).map(|e| *e)
     }
 
+    /// Like `get`, but does not check that `idx` is in bounds.
+    pub unsafe fn get_unchecked(&self, idx: usize) -> Event {
+        *self.events.get_unchecked(idx)
+    }
+
+    /// The number of events `kevent` may return.
+    pub fn capacity(&self) -> usize {
+        self.sys_events.0.capacity()
+    }
+
+    /// Grows the capacity by `additional` events, keeping the current ones.
+    pub fn reserve(&mut self, additional: usize) {
+        let capacity = self.sys_events.0.capacity() + additional;
+        let len = self.sys_events.0.len();
+
+        self.sys_events.0.reserve_exact(capacity - len);
+        self.events.reserve(additional);
+    }
+
     fn coalesce(&mut self, awakener: Token) -> bool {
         let mut ret = false;
         self.events.clear();
@@ -237,6 +533,17 @@ impl Events {
                 event::kind_mut(&mut self.events[idx]).insert(Ready::readable());
             } else if e.filter == libc::EVFILT_WRITE {
                 event::kind_mut(&mut self.events[idx]).insert(Ready::writable());
+            } else if e.filter == libc::EVFILT_TIMER {
+                // Timer expirations are reported as readable
+                event::kind_mut(&mut self.events[idx]).insert(Ready::readable());
+            } else if e.filter == libc::EVFILT_PROC {
+                // Process exits are reported as readable
+                event::kind_mut(&mut self.events[idx]).insert(Ready::readable());
+            } else if EVFILT_EXCEPT != 0 && e.filter == EVFILT_EXCEPT {
+                event::kind_mut(&mut self.events[idx]).insert(Ready::priority());
+            } else if EVFILT_USER != 0 && e.filter == EVFILT_USER {
+                // User events are wakeups, reported as readable
+                event::kind_mut(&mut self.events[idx]).insert(Ready::readable());
             }
 
             if e.flags & libc::EV_EOF != 0 {
@@ -253,6 +560,18 @@ impl Events {
         ret
     }
 
+    pub fn truncate(&mut self, len: usize) {
+        self.events.truncate(len);
+    }
+
+    pub fn as_slice(&mut self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn sort_by_token(&mut self) {
+        self.events.sort_by_key(|e| e.token());
+    }
+
     pub fn push_event(&mut self, event: Event) {
         self.events.push(event);
     }
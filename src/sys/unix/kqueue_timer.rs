@@ -0,0 +1,108 @@
+use {io, poll, Evented, Ready, Poll, PollOpt, Token};
+use sys::unix::kqueue;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::Duration;
+
+/// Source of kqueue identifiers for timers. `EVFILT_TIMER` identifiers live in
+/// their own namespace, they only need to be unique amongst timers.
+static NEXT_TIMER_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/*
+ *
+ * ===== KqueueTimer =====
+ *
+ */
+
+/// A kernel timer backed by kqueue's `EVFILT_TIMER`.
+///
+/// When registered with a `Poll`, the timer delivers a readable event to its
+/// token every time it fires. The kernel delivers the expiration directly
+/// through the selector, so no helper thread or awakener round-trip is
+/// involved.
+///
+/// This type is only available on platforms using kqueue (BSD and macOS).
+/// Portable code should use `mio::timer::Timer` instead.
+///
+/// The delay is rounded up to the next millisecond.
+///
+/// Dropping a registered timer removes it from the kqueue, a periodic timer
+/// does not keep firing once its handle is gone.
+#[derive(Debug)]
+pub struct KqueueTimer {
+    ident: usize,
+    // The kqueue the timer was last registered with, -1 if none
+    kq: AtomicIsize,
+    delay: Duration,
+    oneshot: bool,
+}
+
+impl KqueueTimer {
+    /// Returns a timer that fires every `delay` once registered.
+    pub fn periodic(delay: Duration) -> KqueueTimer {
+        KqueueTimer::new(delay, false)
+    }
+
+    /// Returns a timer that fires a single time, `delay` after being
+    /// registered.
+    pub fn oneshot(delay: Duration) -> KqueueTimer {
+        KqueueTimer::new(delay, true)
+    }
+
+    fn new(delay: Duration, oneshot: bool) -> KqueueTimer {
+        KqueueTimer {
+            ident: NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed),
+            kq: AtomicIsize::new(-1),
+            delay: delay,
+            oneshot: oneshot,
+        }
+    }
+
+    /// Returns the delay between timer expirations.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Returns true if the timer only fires once.
+    pub fn is_oneshot(&self) -> bool {
+        self.oneshot
+    }
+}
+
+impl Evented for KqueueTimer {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, _opts: PollOpt) -> io::Result<()> {
+        if !interest.is_readable() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "timer interest must include readable"));
+        }
+
+        let selector = poll::selector(poll);
+
+        try!(selector.register_timer(self.ident, token, self.delay, self.oneshot));
+        self.kq.store(selector.as_raw_fd() as isize, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        // EV_ADD modifies an existing timer, restarting it with the new token
+        self.register(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        try!(poll::selector(poll).deregister_timer(self.ident));
+        self.kq.store(-1, Ordering::Relaxed);
+
+        Ok(())
+    }
+}
+
+impl Drop for KqueueTimer {
+    fn drop(&mut self) {
+        let kq = self.kq.load(Ordering::Relaxed);
+
+        if kq >= 0 {
+            // A oneshot timer that already fired is gone, ignore the error
+            let _ = kqueue::delete_timer(kq as _, self.ident);
+        }
+    }
+}
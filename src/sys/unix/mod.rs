@@ -4,10 +4,20 @@ use libc::{self, c_int};
 pub mod dlsym;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
+#[cfg_attr(all(target_os = "linux", feature = "io_uring"), allow(dead_code))]
 mod epoll;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
-pub use self::epoll::{Events, Selector};
+pub use self::epoll::Events;
+
+#[cfg(any(target_os = "android", all(target_os = "linux", not(feature = "io_uring"))))]
+pub use self::epoll::Selector;
+
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+mod uring;
+
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+pub use self::uring::Selector;
 
 #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
     target_os = "freebsd", target_os = "ios", target_os = "macos",
@@ -19,9 +29,21 @@ mod kqueue;
     target_os = "netbsd", target_os = "openbsd"))]
 pub use self::kqueue::{Events, Selector};
 
+#[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "ios", target_os = "macos",
+    target_os = "netbsd", target_os = "openbsd"))]
+mod kqueue_timer;
+
+#[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "ios", target_os = "macos",
+    target_os = "netbsd", target_os = "openbsd"))]
+pub use self::kqueue_timer::KqueueTimer;
+
 mod awakener;
 mod eventedfd;
 mod io;
+mod process;
+mod socket;
 mod tcp;
 mod udp;
 mod uds;
@@ -31,11 +53,17 @@ pub use self::awakener::Awakener;
 pub use self::eventedfd::EventedFd;
 pub use self::io::{Io, set_nonblock};
 pub use self::iovec::IoVec;
+pub use self::process::ChildWatcher;
+pub use self::socket::{set_close_on_exec, tcp_builder, udp_builder};
 pub use self::tcp::{TcpStream, TcpListener};
 pub use self::udp::UdpSocket;
-pub use self::uds::UnixSocket;
+pub use self::uds::{SocketAddr, UnixSocket};
 
-use std::os::unix::io::FromRawFd;
+use std::{cmp, fmt};
+use std::collections::HashMap;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::sync::Mutex;
+use std::time::Duration;
 
 pub fn pipe() -> ::io::Result<(Io, Io)> {
     // Use pipe2 for atomically setting O_CLOEXEC if we can, but otherwise
@@ -69,6 +97,9 @@ trait IsMinusOne {
 impl IsMinusOne for i32 {
     fn is_minus_one(&self) -> bool { *self == -1 }
 }
+impl IsMinusOne for i64 {
+    fn is_minus_one(&self) -> bool { *self == -1 }
+}
 impl IsMinusOne for isize {
     fn is_minus_one(&self) -> bool { *self == -1 }
 }
@@ -82,3 +113,202 @@ fn cvt<T: IsMinusOne>(t: T) -> ::io::Result<T> {
         Ok(t)
     }
 }
+
+/// Maps the errors returned by the selector when registering a file
+/// descriptor that is closed (`EBADF`) or, when re-registering, one that is
+/// not registered (`ENOENT`) to `ErrorKind::NotFound`. Other errors are
+/// returned unchanged.
+fn registration_error(err: ::io::Error, fd: RawFd, token: ::Token) -> ::io::Error {
+    use std::io;
+
+    match err.raw_os_error() {
+        Some(libc::EBADF) | Some(libc::ENOENT) => {
+            io::Error::new(io::ErrorKind::NotFound,
+                           format!("fd {} registered with {:?} is closed or not registered ({})",
+                                   fd, token, err))
+        }
+        _ => err,
+    }
+}
+
+/// Held by the types owning a file descriptor, in a field declared before
+/// the one closing it so that it is dropped first. With the io_uring
+/// selector, a pending poll request keeps the file open after the fd is
+/// closed, dropping the guard cancels the requests of the fd. This includes
+/// handing the fd over with `into_raw_fd` or `into_std`, the new owner has to
+/// register it again. Otherwise the guard is empty and does nothing.
+pub struct CloseGuard {
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    fd: RawFd,
+}
+
+impl CloseGuard {
+    #[cfg(all(target_os = "linux", feature = "io_uring"))]
+    pub fn new(fd: RawFd) -> CloseGuard {
+        CloseGuard { fd: fd }
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "io_uring")))]
+    pub fn new(_fd: RawFd) -> CloseGuard {
+        CloseGuard {}
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+impl Drop for CloseGuard {
+    fn drop(&mut self) {
+        uring::release(self.fd);
+    }
+}
+
+impl fmt::Debug for CloseGuard {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CloseGuard").finish()
+    }
+}
+
+/// File descriptors registered with a selector, tracked when fd validation
+/// is enabled with `Poll::set_fd_validation`.
+#[derive(Debug)]
+struct FdValidation {
+    fds: Mutex<Option<HashMap<RawFd, ::Token>>>,
+}
+
+impl FdValidation {
+    fn new() -> FdValidation {
+        FdValidation { fds: Mutex::new(None) }
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        let mut fds = self.fds.lock().unwrap();
+
+        if !enabled {
+            *fds = None;
+        } else if fds.is_none() {
+            *fds = Some(HashMap::new());
+        }
+    }
+
+    fn register(&self, fd: RawFd, token: ::Token) {
+        if let Some(ref mut fds) = *self.fds.lock().unwrap() {
+            fds.insert(fd, token);
+        }
+    }
+
+    fn deregister(&self, fd: RawFd) {
+        if let Some(ref mut fds) = *self.fds.lock().unwrap() {
+            fds.remove(&fd);
+        }
+    }
+
+    /// Calls `f` with the token of every tracked fd that has been closed,
+    /// and stops tracking it.
+    fn check<F: FnMut(::Token)>(&self, mut f: F) {
+        if let Some(ref mut fds) = *self.fds.lock().unwrap() {
+            let closed: Vec<RawFd> = fds.keys()
+                .cloned()
+                .filter(|&fd| is_closed(fd))
+                .collect();
+
+            for fd in closed {
+                f(fds.remove(&fd).unwrap());
+            }
+        }
+    }
+}
+
+fn is_closed(fd: RawFd) -> bool {
+    let res = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    res == -1 && ::io::Error::last_os_error().raw_os_error() == Some(libc::EBADF)
+}
+
+/// Waits until at least one of `fds` is readable or `timeout` elapses, and
+/// returns which ones are readable. The timeout is rounded up to the next
+/// millisecond.
+pub fn poll_readable(fds: &[RawFd], timeout: Option<Duration>) -> ::io::Result<Vec<bool>> {
+    let fds: Vec<_> = fds.iter().map(|&fd| (fd, ::Ready::readable())).collect();
+    let ready = try!(poll_fds(&fds, timeout));
+
+    Ok(ready.iter().map(|ready| !ready.is_none()).collect())
+}
+
+/// Waits until at least one of `fds` is ready for its interest or `timeout`
+/// elapses, and returns the readiness of each one. Errors and hang ups are
+/// reported regardless of the interest. The timeout is rounded up to the
+/// next millisecond.
+pub fn poll_fds(fds: &[(RawFd, ::Ready)], timeout: Option<Duration>) -> ::io::Result<Vec<::Ready>> {
+    let mut pollfds: Vec<libc::pollfd> = fds.iter().map(|&(fd, interest)| {
+        let mut events = 0;
+
+        if interest.is_readable() {
+            events |= libc::POLLIN;
+        }
+
+        if interest.is_writable() {
+            events |= libc::POLLOUT;
+        }
+
+        libc::pollfd {
+            fd: fd,
+            events: events,
+            revents: 0,
+        }
+    }).collect();
+
+    let timeout_ms = timeout
+        .map(|to| cmp::min(::convert::millis(to), c_int::max_value() as u64) as c_int)
+        .unwrap_or(-1);
+
+    try!(cvt(unsafe {
+        libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout_ms)
+    }));
+
+    Ok(pollfds.iter().map(|pollfd| {
+        let mut ready = ::Ready::none();
+
+        if pollfd.revents & libc::POLLIN != 0 {
+            ready.insert(::Ready::readable());
+        }
+
+        if pollfd.revents & libc::POLLOUT != 0 {
+            ready.insert(::Ready::writable());
+        }
+
+        if pollfd.revents & (libc::POLLERR | libc::POLLNVAL) != 0 {
+            ready.insert(::Ready::error());
+        }
+
+        if pollfd.revents & libc::POLLHUP != 0 {
+            ready.insert(::Ready::hup());
+        }
+
+        ready
+    }).collect())
+}
+
+/// Sets the `IP_TOS` option, the type of service field of the IPv4 packets
+/// sent from the socket.
+pub fn set_tos(fd: RawFd, tos: u32) -> ::io::Result<()> {
+    let tos = tos as c_int;
+
+    try!(cvt(unsafe {
+        libc::setsockopt(fd, libc::IPPROTO_IP, libc::IP_TOS,
+                         &tos as *const c_int as *const libc::c_void,
+                         ::std::mem::size_of::<c_int>() as libc::socklen_t)
+    }));
+
+    Ok(())
+}
+
+/// Sets the `SO_BINDTODEVICE` option, so that the socket only sends and
+/// receives packets through the network interface named `interface`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn set_bind_device(fd: RawFd, interface: &str) -> ::io::Result<()> {
+    try!(cvt(unsafe {
+        libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE,
+                         interface.as_ptr() as *const libc::c_void,
+                         interface.len() as libc::socklen_t)
+    }));
+
+    Ok(())
+}
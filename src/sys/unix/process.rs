@@ -0,0 +1,214 @@
+use {io, Evented, Ready, Poll, PollOpt, Registration, Token};
+use lazycell::LazyCell;
+use libc;
+use std::fmt;
+use std::process::Child;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::io::{AsRawFd, FromRawFd};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::thread;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use sys::unix::{cvt, EventedFd, Io};
+
+#[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "ios", target_os = "macos",
+    target_os = "netbsd", target_os = "openbsd"))]
+use poll;
+
+/// `pidfd_open` is missing from older libc releases. The syscall has the same
+/// number on every architecture supported by Linux, except alpha.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SYS_PIDFD_OPEN: libc::c_long = 434;
+
+/*
+ *
+ * ===== ChildWatcher =====
+ *
+ */
+
+/// Watches a child process for exit.
+///
+/// On Linux, a pidfd is registered with the selector. Kernels older than 5.3
+/// do not support pidfds, a thread waiting for the child is used instead. On
+/// kqueue platforms, an `EVFILT_PROC` filter is registered for `NOTE_EXIT`.
+pub struct ChildWatcher {
+    pid: libc::pid_t,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pidfd: Option<Io>,
+    // Used when the selector cannot watch the process
+    fallback: LazyCell<Registration>,
+}
+
+impl ChildWatcher {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn new(child: &Child) -> io::Result<ChildWatcher> {
+        let pid = child.id() as libc::pid_t;
+
+        // Any failure (ENOSYS, or EPERM from a seccomp filter) falls back to
+        // the waiting thread
+        let pidfd = unsafe {
+            cvt(libc::syscall(SYS_PIDFD_OPEN, pid, 0))
+                .ok()
+                .map(|fd| Io::from_raw_fd(fd as libc::c_int))
+        };
+
+        Ok(ChildWatcher {
+            pid: pid,
+            pidfd: pidfd,
+            fallback: LazyCell::new(),
+        })
+    }
+
+    #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+        target_os = "freebsd", target_os = "ios", target_os = "macos",
+        target_os = "netbsd", target_os = "openbsd"))]
+    pub fn new(child: &Child) -> io::Result<ChildWatcher> {
+        Ok(ChildWatcher {
+            pid: child.id() as libc::pid_t,
+            fallback: LazyCell::new(),
+        })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn register_native(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<bool> {
+        match self.pidfd {
+            Some(ref pidfd) => {
+                try!(EventedFd(&pidfd.as_raw_fd()).register(poll, token, interest, opts));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+        target_os = "freebsd", target_os = "ios", target_os = "macos",
+        target_os = "netbsd", target_os = "openbsd"))]
+    fn register_native(&self, poll: &Poll, token: Token, _interest: Ready, opts: PollOpt) -> io::Result<bool> {
+        match poll::selector(poll).register_proc(self.pid, token, opts) {
+            Ok(()) => Ok(true),
+            // The child already exited, there is nothing left to watch
+            Err(ref e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn reregister_native(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        match self.pidfd {
+            Some(ref pidfd) => EventedFd(&pidfd.as_raw_fd()).reregister(poll, token, interest, opts),
+            None => Err(io::Error::new(io::ErrorKind::Other, "child watcher not registered")),
+        }
+    }
+
+    #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+        target_os = "freebsd", target_os = "ios", target_os = "macos",
+        target_os = "netbsd", target_os = "openbsd"))]
+    fn reregister_native(&self, poll: &Poll, token: Token, _interest: Ready, opts: PollOpt) -> io::Result<()> {
+        // EV_ADD modifies the existing filter
+        poll::selector(poll).register_proc(self.pid, token, opts)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn deregister_native(&self, poll: &Poll) -> io::Result<()> {
+        match self.pidfd {
+            Some(ref pidfd) => EventedFd(&pidfd.as_raw_fd()).deregister(poll),
+            None => Err(io::Error::new(io::ErrorKind::Other, "child watcher not registered")),
+        }
+    }
+
+    #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+        target_os = "freebsd", target_os = "ios", target_os = "macos",
+        target_os = "netbsd", target_os = "openbsd"))]
+    fn deregister_native(&self, poll: &Poll) -> io::Result<()> {
+        match poll::selector(poll).deregister_proc(self.pid) {
+            Ok(()) => Ok(()),
+            // The filter is removed by the kernel once the process exits
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn register_fallback(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        let (registration, set_readiness) = try!(Registration::try_new(poll, token, interest, opts));
+        let pid = self.pid;
+
+        // `WNOWAIT` leaves the child waitable, so that its exit status can
+        // still be collected through `Child`
+        try!(thread::Builder::new().name("mio-child-watcher".to_string()).spawn(move || {
+            loop {
+                let mut info: libc::siginfo_t = unsafe { ::std::mem::zeroed() };
+                let res = unsafe {
+                    libc::waitid(libc::P_PID, pid as libc::id_t, &mut info,
+                                 libc::WEXITED | libc::WNOWAIT)
+                };
+
+                if res == -1 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                // On other errors the child was reaped elsewhere, it is gone
+                let _ = set_readiness.set_readiness(Ready::readable());
+                return;
+            }
+        }));
+
+        self.fallback.fill(registration).ok().expect("child watcher already registered");
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "bitrig", target_os = "dragonfly",
+        target_os = "freebsd", target_os = "ios", target_os = "macos",
+        target_os = "netbsd", target_os = "openbsd"))]
+    fn register_fallback(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        let (registration, set_readiness) = try!(Registration::try_new(poll, token, interest, opts));
+
+        // Only reached when the child already exited
+        try!(set_readiness.set_readiness(Ready::readable()));
+
+        self.fallback.fill(registration).ok().expect("child watcher already registered");
+        Ok(())
+    }
+}
+
+impl Evented for ChildWatcher {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        if !interest.is_readable() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "child watcher interest must include readable"));
+        }
+
+        if self.fallback.filled() {
+            return Err(io::Error::new(io::ErrorKind::Other, "child watcher already registered"));
+        }
+
+        if try!(self.register_native(poll, token, interest, opts)) {
+            return Ok(());
+        }
+
+        self.register_fallback(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        match self.fallback.borrow() {
+            Some(registration) => registration.update(poll, token, interest, opts),
+            None => self.reregister_native(poll, token, interest, opts),
+        }
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        match self.fallback.borrow() {
+            Some(registration) => registration.deregister(poll),
+            None => self.deregister_native(poll),
+        }
+    }
+}
+
+impl fmt::Debug for ChildWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChildWatcher")
+            .field("pid", &self.pid)
+            .field("fallback", &self.fallback.filled())
+            .finish()
+    }
+}
@@ -0,0 +1,187 @@
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+
+use libc::{self, c_int};
+use net2;
+
+use io;
+use sys::unix::{cvt, Io};
+use sys::unix::io::{set_cloexec, set_nonblock};
+
+/// The flags setting close-on-exec and non-blocking atomically when passed
+/// to `socket` or `accept4`, so that a socket cannot leak into a child
+/// process forked by another thread before close-on-exec is set.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+const SOCK_FLAGS: c_int = libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK;
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+const SOCK_FLAGS: c_int = 0;
+
+/// Creates a close-on-exec, non-blocking socket.
+pub fn socket(family: c_int, ty: c_int) -> io::Result<Io> {
+    unsafe {
+        if SOCK_FLAGS != 0 {
+            match cvt(libc::socket(family, ty | SOCK_FLAGS, 0)) {
+                Ok(fd) => return Ok(Io::from_raw_fd(fd)),
+                // Linux before 2.6.27 does not support the flags
+                Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let fd = Io::from_raw_fd(try!(cvt(libc::socket(family, ty, 0))));
+        try!(set_cloexec(fd.as_raw_fd()));
+        try!(set_nonblock(fd.as_raw_fd()));
+        Ok(fd)
+    }
+}
+
+/// Creates a pair of connected close-on-exec, non-blocking sockets.
+pub fn socketpair(family: c_int, ty: c_int) -> io::Result<(Io, Io)> {
+    let mut fds = [0; 2];
+
+    unsafe {
+        if SOCK_FLAGS != 0 {
+            match cvt(libc::socketpair(family, ty | SOCK_FLAGS, 0, fds.as_mut_ptr())) {
+                Ok(_) => return Ok((Io::from_raw_fd(fds[0]), Io::from_raw_fd(fds[1]))),
+                Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        try!(cvt(libc::socketpair(family, ty, 0, fds.as_mut_ptr())));
+        let a = Io::from_raw_fd(fds[0]);
+        let b = Io::from_raw_fd(fds[1]);
+
+        for fd in &[fds[0], fds[1]] {
+            try!(set_cloexec(*fd));
+            try!(set_nonblock(*fd));
+        }
+
+        Ok((a, b))
+    }
+}
+
+/// Accepts a connection on the listening socket `fd`, the accepted socket is
+/// close-on-exec and non-blocking.
+pub fn accept(fd: RawFd, addr: *mut libc::sockaddr, len: *mut libc::socklen_t) -> io::Result<Io> {
+    // `accept4` is missing from older libc releases
+    dlsym!(fn accept4(c_int, *mut libc::sockaddr, *mut libc::socklen_t, c_int) -> c_int);
+
+    unsafe {
+        if let Some(accept4_fn) = if SOCK_FLAGS != 0 { accept4.get() } else { None } {
+            match cvt(accept4_fn(fd, addr, len, SOCK_FLAGS)) {
+                Ok(fd) => return Ok(Io::from_raw_fd(fd)),
+                // The kernel does not implement `accept4`
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOSYS) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let fd = Io::from_raw_fd(try!(cvt(libc::accept(fd, addr, len))));
+        try!(set_cloexec(fd.as_raw_fd()));
+        try!(set_nonblock(fd.as_raw_fd()));
+        Ok(fd)
+    }
+}
+
+/// Accepts a TCP connection on the listening socket `fd`, see `accept`.
+pub fn accept_tcp(fd: RawFd) -> io::Result<(Io, SocketAddr)> {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+
+    let sock = try!(accept(fd, &mut storage as *mut _ as *mut _, &mut len));
+    let addr = try!(raw_to_socket_addr(&storage));
+    Ok((sock, addr))
+}
+
+pub fn tcp_builder(v6: bool) -> io::Result<net2::TcpBuilder> {
+    let family = if v6 { libc::AF_INET6 } else { libc::AF_INET };
+    let sock = try!(socket(family, libc::SOCK_STREAM));
+    Ok(unsafe { net2::TcpBuilder::from_raw_fd(sock.into_raw_fd()) })
+}
+
+pub fn udp_builder(v6: bool) -> io::Result<net2::UdpBuilder> {
+    let family = if v6 { libc::AF_INET6 } else { libc::AF_INET };
+    let sock = try!(socket(family, libc::SOCK_DGRAM));
+    Ok(unsafe { net2::UdpBuilder::from_raw_fd(sock.into_raw_fd()) })
+}
+
+/// Returns and clears the pending error of the socket `fd` (`SO_ERROR`).
+pub fn take_error(fd: RawFd) -> io::Result<Option<io::Error>> {
+    unsafe {
+        let mut err: c_int = 0;
+        let mut len = mem::size_of::<c_int>() as libc::socklen_t;
+        try!(cvt(libc::getsockopt(fd,
+                                  libc::SOL_SOCKET,
+                                  libc::SO_ERROR,
+                                  &mut err as *mut _ as *mut _,
+                                  &mut len)));
+
+        if err == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(io::Error::from_raw_os_error(err)))
+        }
+    }
+}
+
+/// Sets or clears the close-on-exec flag of `fd`.
+pub fn set_close_on_exec(fd: RawFd, cloexec: bool) -> io::Result<()> {
+    if cloexec {
+        set_cloexec(fd)
+    } else {
+        unsafe {
+            cvt(libc::ioctl(fd, libc::FIONCLEX)).map(|_| ())
+        }
+    }
+}
+
+/// Converts `addr` for the socket calls taking a `sockaddr`. Only used on
+/// Linux, `sin_len` is not set for the BSDs.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn socket_addr_to_raw(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+
+    let len = match *addr {
+        SocketAddr::V4(ref addr) => {
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(ref addr) => {
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr.s6_addr = addr.ip().octets();
+            sin6.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (storage, len as libc::socklen_t)
+}
+
+/// Converts an IPv4 or IPv6 address filled in by a socket call.
+pub fn raw_to_socket_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let sin = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+            Ok(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(sin.sin_port))))
+        }
+        libc::AF_INET6 => {
+            let sin6 = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+            Ok(SocketAddr::V6(SocketAddrV6::new(ip, u16::from_be(sin6.sin6_port),
+                                                sin6.sin6_flowinfo, sin6.sin6_scope_id)))
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid address family")),
+    }
+}
@@ -1,24 +1,30 @@
-use std::cmp;
 use std::io::{Read, Write};
 use std::net::{self, SocketAddr};
 use std::os::unix::io::{RawFd, FromRawFd, IntoRawFd, AsRawFd};
+use std::time::Duration;
 
 use libc;
 use net2::TcpStreamExt;
 
 use {io, Evented, Ready, Poll, PollOpt, Token, IoVec};
 
+use sys::unix::CloseGuard;
 use sys::unix::eventedfd::EventedFd;
 use sys::unix::io::set_nonblock;
 use sys::unix::iovec;
+use sys::unix::socket;
 
 #[derive(Debug)]
 pub struct TcpStream {
+    // Dropped before `inner` closes the fd
+    _guard: CloseGuard,
     inner: net::TcpStream,
 }
 
 #[derive(Debug)]
 pub struct TcpListener {
+    // Dropped before `inner` closes the fd
+    _guard: CloseGuard,
     inner: net::TcpListener,
 }
 
@@ -32,17 +38,24 @@ impl TcpStream {
             Err(e) => return Err(e),
         }
 
-        Ok(TcpStream {
-            inner: stream,
-        })
+        Ok(TcpStream::new(stream))
     }
 
     pub fn from_stream(stream: net::TcpStream) -> TcpStream {
+        TcpStream::new(stream)
+    }
+
+    fn new(stream: net::TcpStream) -> TcpStream {
         TcpStream {
+            _guard: CloseGuard::new(stream.as_raw_fd()),
             inner: stream,
         }
     }
 
+    pub fn into_std(self) -> io::Result<net::TcpStream> {
+        Ok(self.inner)
+    }
+
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
         self.inner.peer_addr()
     }
@@ -52,11 +65,7 @@ impl TcpStream {
     }
 
     pub fn try_clone(&self) -> io::Result<TcpStream> {
-        self.inner.try_clone().map(|s| {
-            TcpStream {
-                inner: s,
-            }
-        })
+        self.inner.try_clone().map(TcpStream::new)
     }
 
     pub fn shutdown(&self, how: net::Shutdown) -> io::Result<()> {
@@ -79,6 +88,22 @@ impl TcpStream {
         self.inner.keepalive_ms()
     }
 
+    pub fn set_keepalive(&self, keepalive: Option<Duration>) -> io::Result<()> {
+        TcpStreamExt::set_keepalive(&self.inner, keepalive)
+    }
+
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        TcpStreamExt::keepalive(&self.inner)
+    }
+
+    pub fn set_linger(&self, dur: Option<Duration>) -> io::Result<()> {
+        TcpStreamExt::set_linger(&self.inner, dur)
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        TcpStreamExt::linger(&self.inner)
+    }
+
     pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
         self.inner.set_ttl(ttl)
     }
@@ -92,33 +117,11 @@ impl TcpStream {
     }
 
     pub fn readv(&self, bufs: &mut [&mut IoVec]) -> io::Result<usize> {
-        unsafe {
-            let slice = iovec::as_iovec_slice_mut(bufs);
-            let len = cmp::min(<libc::c_int>::max_value() as usize, slice.len());
-            let rc = libc::readv(self.inner.as_raw_fd(),
-                                 slice.as_ptr(),
-                                 len as libc::c_int);
-            if rc < 0 {
-                Err(io::Error::last_os_error())
-            } else {
-                Ok(rc as usize)
-            }
-        }
+        iovec::readv(self.inner.as_raw_fd(), bufs)
     }
 
     pub fn writev(&self, bufs: &[&IoVec]) -> io::Result<usize> {
-        unsafe {
-            let slice = iovec::as_iovec_slice(bufs);
-            let len = cmp::min(<libc::c_int>::max_value() as usize, slice.len());
-            let rc = libc::writev(self.inner.as_raw_fd(),
-                                  slice.as_ptr(),
-                                  len as libc::c_int);
-            if rc < 0 {
-                Err(io::Error::last_os_error())
-            } else {
-                Ok(rc as usize)
-            }
-        }
+        iovec::writev(self.inner.as_raw_fd(), bufs)
     }
 }
 
@@ -156,9 +159,7 @@ impl Evented for TcpStream {
 
 impl FromRawFd for TcpStream {
     unsafe fn from_raw_fd(fd: RawFd) -> TcpStream {
-        TcpStream {
-            inner: net::TcpStream::from_raw_fd(fd),
-        }
+        TcpStream::new(net::TcpStream::from_raw_fd(fd))
     }
 }
 
@@ -177,9 +178,18 @@ impl AsRawFd for TcpStream {
 impl TcpListener {
     pub fn new(inner: net::TcpListener, _addr: &SocketAddr) -> io::Result<TcpListener> {
         try!(set_nonblock(inner.as_raw_fd()));
-        Ok(TcpListener {
+        Ok(TcpListener::from_listener(inner))
+    }
+
+    fn from_listener(inner: net::TcpListener) -> TcpListener {
+        TcpListener {
+            _guard: CloseGuard::new(inner.as_raw_fd()),
             inner: inner,
-        })
+        }
+    }
+
+    pub fn into_std(self) -> io::Result<net::TcpListener> {
+        Ok(self.inner)
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
@@ -187,20 +197,14 @@ impl TcpListener {
     }
 
     pub fn try_clone(&self) -> io::Result<TcpListener> {
-        self.inner.try_clone().map(|s| {
-            TcpListener {
-                inner: s,
-            }
-        })
+        self.inner.try_clone().map(TcpListener::from_listener)
     }
 
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
-        self.inner.accept().and_then(|(s, a)| {
-            try!(set_nonblock(s.as_raw_fd()));
-            Ok((TcpStream {
-                inner: s,
-            }, a))
-        })
+        let (sock, addr) = try!(socket::accept_tcp(self.inner.as_raw_fd()));
+        let stream = unsafe { net::TcpStream::from_raw_fd(sock.into_raw_fd()) };
+
+        Ok((TcpStream::new(stream), addr))
     }
 
     #[allow(deprecated)]
@@ -244,9 +248,7 @@ impl Evented for TcpListener {
 
 impl FromRawFd for TcpListener {
     unsafe fn from_raw_fd(fd: RawFd) -> TcpListener {
-        TcpListener {
-            inner: net::TcpListener::from_raw_fd(fd),
-        }
+        TcpListener::from_listener(net::TcpListener::from_raw_fd(fd))
     }
 }
 
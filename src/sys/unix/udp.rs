@@ -1,23 +1,44 @@
 use {io, Evented, Ready, Poll, PollOpt, Token};
 use io::MapNonBlock;
 use unix::EventedFd;
+use sys::unix::CloseGuard;
 use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::os::unix::io::{RawFd, IntoRawFd, AsRawFd, FromRawFd};
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::{cmp, mem, ptr};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use libc;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use sys::unix::cvt;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use sys::unix::socket::{raw_to_socket_addr, socket_addr_to_raw};
+
 #[allow(unused_imports)] // only here for Rust 1.8
 use net2::UdpSocketExt;
 
 #[derive(Debug)]
 pub struct UdpSocket {
+    // Dropped before `io` closes the fd
+    _guard: CloseGuard,
     io: net::UdpSocket,
 }
 
 impl UdpSocket {
     pub fn new(socket: net::UdpSocket) -> io::Result<UdpSocket> {
         try!(socket.set_nonblocking(true));
-        Ok(UdpSocket {
+        Ok(UdpSocket::from_socket(socket))
+    }
+
+    fn from_socket(socket: net::UdpSocket) -> UdpSocket {
+        UdpSocket {
+            _guard: CloseGuard::new(socket.as_raw_fd()),
             io: socket,
-        })
+        }
+    }
+
+    pub fn into_std(self) -> io::Result<net::UdpSocket> {
+        Ok(self.io)
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
@@ -25,11 +46,21 @@ impl UdpSocket {
     }
 
     pub fn try_clone(&self) -> io::Result<UdpSocket> {
-        self.io.try_clone().map(|io| {
-            UdpSocket {
-                io: io,
-            }
-        })
+        self.io.try_clone().map(UdpSocket::from_socket)
+    }
+
+    pub fn connect(&self, addr: &SocketAddr) -> io::Result<()> {
+        self.io.connect(addr)
+    }
+
+    pub fn send(&self, buf: &[u8]) -> io::Result<Option<usize>> {
+        self.io.send(buf)
+            .map_non_block()
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        self.io.recv(buf)
+            .map_non_block()
     }
 
     pub fn send_to(&self, buf: &[u8], target: &SocketAddr)
@@ -44,6 +75,60 @@ impl UdpSocket {
             .map_non_block()
     }
 
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_mmsg(&self, msgs: &[(&[u8], SocketAddr)])
+                     -> io::Result<Option<usize>> {
+        let mut addrs: Vec<_> = msgs.iter().map(|&(_, ref addr)| socket_addr_to_raw(addr)).collect();
+        let mut iovs: Vec<_> = msgs.iter().map(|&(buf, _)| {
+            libc::iovec {
+                iov_base: buf.as_ptr() as *mut _,
+                iov_len: buf.len(),
+            }
+        }).collect();
+
+        let mut hdrs: Vec<_> = iovs.iter_mut().zip(addrs.iter_mut()).map(|(iov, &mut (ref mut addr, len))| {
+            mmsghdr(iov, addr, len)
+        }).collect();
+
+        let len = cmp::min(hdrs.len(), libc::c_uint::max_value() as usize);
+        let res = unsafe {
+            libc::sendmmsg(self.io.as_raw_fd(), hdrs.as_mut_ptr(), len as libc::c_uint, 0)
+        };
+
+        cvt(res).map(|n| n as usize).map_non_block()
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn recv_mmsg(&self, bufs: &mut [&mut [u8]])
+                     -> io::Result<Option<Vec<(usize, SocketAddr)>>> {
+        let mut addrs: Vec<libc::sockaddr_storage> = bufs.iter().map(|_| unsafe { mem::zeroed() }).collect();
+        let mut iovs: Vec<_> = bufs.iter_mut().map(|buf| {
+            libc::iovec {
+                iov_base: buf.as_mut_ptr() as *mut _,
+                iov_len: buf.len(),
+            }
+        }).collect();
+
+        let mut hdrs: Vec<_> = iovs.iter_mut().zip(addrs.iter_mut()).map(|(iov, addr)| {
+            mmsghdr(iov, addr, mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t)
+        }).collect();
+
+        let len = cmp::min(hdrs.len(), libc::c_uint::max_value() as usize);
+        let res = unsafe {
+            libc::recvmmsg(self.io.as_raw_fd(), hdrs.as_mut_ptr(), len as libc::c_uint, 0,
+                           ptr::null_mut())
+        };
+
+        let n = match try!(cvt(res).map_non_block()) {
+            Some(n) => n as usize,
+            None => return Ok(None),
+        };
+
+        hdrs[..n].iter().zip(addrs.iter()).map(|(hdr, addr)| {
+            raw_to_socket_addr(addr).map(|addr| (hdr.msg_len as usize, addr))
+        }).collect::<io::Result<_>>().map(Some)
+    }
+
     pub fn broadcast(&self) -> io::Result<bool> {
         self.io.broadcast()
     }
@@ -68,6 +153,22 @@ impl UdpSocket {
         self.io.set_multicast_ttl_v4(ttl)
     }
 
+    pub fn set_multicast_if_v4(&self, interface: &Ipv4Addr) -> io::Result<()> {
+        UdpSocketExt::set_multicast_if_v4(&self.io, interface)
+    }
+
+    pub fn set_multicast_if_v6(&self, interface: u32) -> io::Result<()> {
+        UdpSocketExt::set_multicast_if_v6(&self.io, interface)
+    }
+
+    pub fn multicast_hops_v6(&self) -> io::Result<u32> {
+        UdpSocketExt::multicast_hops_v6(&self.io)
+    }
+
+    pub fn set_multicast_hops_v6(&self, hops: u32) -> io::Result<()> {
+        UdpSocketExt::set_multicast_hops_v6(&self.io, hops)
+    }
+
     pub fn multicast_loop_v6(&self) -> io::Result<bool> {
         self.io.multicast_loop_v6()
     }
@@ -129,9 +230,7 @@ impl Evented for UdpSocket {
 
 impl FromRawFd for UdpSocket {
     unsafe fn from_raw_fd(fd: RawFd) -> UdpSocket {
-        UdpSocket {
-            io: net::UdpSocket::from_raw_fd(fd),
-        }
+        UdpSocket::from_socket(net::UdpSocket::from_raw_fd(fd))
     }
 }
 
@@ -146,3 +245,14 @@ impl AsRawFd for UdpSocket {
         self.io.as_raw_fd()
     }
 }
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mmsghdr(iov: &mut libc::iovec, addr: &mut libc::sockaddr_storage, len: libc::socklen_t)
+           -> libc::mmsghdr {
+    let mut hdr: libc::mmsghdr = unsafe { mem::zeroed() };
+    hdr.msg_hdr.msg_name = addr as *mut _ as *mut _;
+    hdr.msg_hdr.msg_namelen = len;
+    hdr.msg_hdr.msg_iov = iov;
+    hdr.msg_hdr.msg_iovlen = 1;
+    hdr
+}
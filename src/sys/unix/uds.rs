@@ -1,14 +1,17 @@
+use std::ffi::OsStr;
+use std::fmt;
 use std::io::{Read, Write};
 use std::mem;
+use std::ptr;
 use std::net::Shutdown;
 use std::os::unix::prelude::*;
 use std::path::Path;
 
 use libc;
 
-use {io, Evented, Ready, Poll, PollOpt, Token};
-use sys::unix::{cvt, Io};
-use sys::unix::io::{set_nonblock, set_cloexec};
+use {io, Evented, Ready, Poll, PollOpt, Token, IoVec};
+use sys::unix::{cvt, iovec, socket, Io};
+use sys::unix::io::set_cloexec;
 
 trait MyInto<T> {
     fn my_into(self) -> T;
@@ -22,6 +25,19 @@ impl MyInto<usize> for usize {
     fn my_into(self) -> usize { self }
 }
 
+/// The length of the `SCM_RIGHTS` data for a single descriptor
+const FD_LEN: libc::c_uint = mem::size_of::<libc::c_int>() as libc::c_uint;
+
+/// Sets close-on-exec on the descriptors received with `SCM_RIGHTS`
+/// atomically, where supported.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "netbsd"))]
+const RECV_FD_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "dragonfly",
+    target_os = "freebsd", target_os = "netbsd")))]
+const RECV_FD_FLAGS: libc::c_int = 0;
+
 unsafe fn sockaddr_un(path: &Path)
                       -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
     let mut addr: libc::sockaddr_un = mem::zeroed();
@@ -57,6 +73,137 @@ fn sun_path_offset() -> usize {
     }
 }
 
+/// An address associated with a Unix domain socket.
+#[derive(Clone)]
+pub struct SocketAddr {
+    addr: libc::sockaddr_un,
+    len: libc::socklen_t,
+}
+
+impl SocketAddr {
+    fn new<F>(f: F) -> io::Result<SocketAddr>
+        where F: FnOnce(*mut libc::sockaddr, *mut libc::socklen_t) -> libc::c_int
+    {
+        SocketAddr::with(|addr, len| cvt(f(addr, len))).map(|(_, addr)| addr)
+    }
+
+    /// Like `new`, for a call returning a value along with the address.
+    fn with<F, T>(f: F) -> io::Result<(T, SocketAddr)>
+        where F: FnOnce(*mut libc::sockaddr, *mut libc::socklen_t) -> io::Result<T>
+    {
+        unsafe {
+            let mut addr: libc::sockaddr_un = mem::zeroed();
+            let mut len = mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+            let ret = try!(f(&mut addr as *mut _ as *mut _, &mut len));
+
+            if len == 0 {
+                // Some platforms return a zero length for unnamed sockets,
+                // without filling in the family
+                addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+            } else if addr.sun_family != libc::AF_UNIX as libc::sa_family_t {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "file descriptor did not correspond to a Unix socket"));
+            }
+
+            Ok((ret, SocketAddr {
+                addr: addr,
+                len: len,
+            }))
+        }
+    }
+
+    /// Creates a pathname address.
+    pub fn from_pathname<P: AsRef<Path>>(path: P) -> io::Result<SocketAddr> {
+        let (addr, len) = try!(unsafe { sockaddr_un(path.as_ref()) });
+
+        Ok(SocketAddr {
+            addr: addr,
+            len: len,
+        })
+    }
+
+    /// Creates an address in the Linux abstract namespace, from `name`
+    /// without the leading null byte.
+    ///
+    /// Abstract addresses are not backed by a file, the name is released
+    /// when the socket bound to it is closed.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn from_abstract_name(name: &[u8]) -> io::Result<SocketAddr> {
+        unsafe {
+            let mut addr: libc::sockaddr_un = mem::zeroed();
+            addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+            if name.len() + 1 > addr.sun_path.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "abstract name must be shorter than SUN_LEN"))
+            }
+
+            // The leading null byte is already there as the struct is zeroed
+            for (dst, src) in addr.sun_path[1..].iter_mut().zip(name.iter()) {
+                *dst = *src as libc::c_char;
+            }
+
+            Ok(SocketAddr {
+                addr: addr,
+                len: (sun_path_offset() + 1 + name.len()) as libc::socklen_t,
+            })
+        }
+    }
+
+    /// Returns the name of this address, without the leading null byte, if
+    /// it is in the Linux abstract namespace.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn as_abstract_name(&self) -> Option<&[u8]> {
+        let bytes = self.path_bytes();
+
+        match bytes.get(0) {
+            Some(&0) => Some(&bytes[1..]),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the address is unnamed.
+    ///
+    /// Sockets created by `pair` and sockets that were not bound, like the
+    /// local half of a connected stream, have unnamed addresses.
+    pub fn is_unnamed(&self) -> bool {
+        self.path_bytes().is_empty()
+    }
+
+    /// Returns the contents of this address if it is a pathname address.
+    pub fn as_pathname(&self) -> Option<&Path> {
+        let bytes = self.path_bytes();
+
+        match bytes.get(0) {
+            Some(&0) | None => None,
+            Some(_) => {
+                // Strip the null terminator, if it was counted
+                let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                Some(Path::new(OsStr::from_bytes(&bytes[..len])))
+            }
+        }
+    }
+
+    fn path_bytes(&self) -> &[u8] {
+        let len = (self.len as usize).saturating_sub(sun_path_offset());
+        let len = ::std::cmp::min(len, self.addr.sun_path.len());
+
+        unsafe {
+            ::std::slice::from_raw_parts(self.addr.sun_path.as_ptr() as *const u8, len)
+        }
+    }
+}
+
+impl fmt::Debug for SocketAddr {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.as_pathname() {
+            Some(path) => write!(fmt, "{:?} (pathname)", path),
+            None if self.is_unnamed() => write!(fmt, "(unnamed)"),
+            None => write!(fmt, "{:?} (abstract)", &self.path_bytes()[1..]),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct UnixSocket {
     io: Io,
@@ -65,38 +212,26 @@ pub struct UnixSocket {
 impl UnixSocket {
     /// Returns a new, unbound, non-blocking Unix domain socket
     pub fn stream() -> io::Result<UnixSocket> {
-        #[cfg(target_os = "linux")]
-        use libc::{SOCK_CLOEXEC, SOCK_NONBLOCK};
-        #[cfg(not(target_os = "linux"))]
-        const SOCK_CLOEXEC: libc::c_int = 0;
-        #[cfg(not(target_os = "linux"))]
-        const SOCK_NONBLOCK: libc::c_int = 0;
-
-        unsafe {
-            if cfg!(target_os = "linux") {
-                let flags = libc::SOCK_STREAM | SOCK_CLOEXEC | SOCK_NONBLOCK;
-                match cvt(libc::socket(libc::AF_UNIX, flags, 0)) {
-                    Ok(fd) => return Ok(UnixSocket::from_raw_fd(fd)),
-                    Err(ref e) if e.raw_os_error() == Some(libc::EINVAL) => {}
-                    Err(e) => return Err(e),
-                }
-            }
+        socket::socket(libc::AF_UNIX, libc::SOCK_STREAM).map(UnixSocket::from)
+    }
 
-            let fd = try!(cvt(libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0)));
-            let fd = UnixSocket::from_raw_fd(fd);
-            try!(set_cloexec(fd.as_raw_fd()));
-            try!(set_nonblock(fd.as_raw_fd()));
-            Ok(fd)
-        }
+    /// Returns a pair of connected, non-blocking Unix domain sockets
+    pub fn pair() -> io::Result<(UnixSocket, UnixSocket)> {
+        let (a, b) = try!(socket::socketpair(libc::AF_UNIX, libc::SOCK_STREAM));
+        Ok((UnixSocket::from(a), UnixSocket::from(b)))
     }
 
     /// Connect the socket to the specified address
     pub fn connect<P: AsRef<Path> + ?Sized>(&self, addr: &P) -> io::Result<()> {
+        self.connect_addr(&try!(SocketAddr::from_pathname(addr)))
+    }
+
+    /// Connect the socket to the specified address
+    pub fn connect_addr(&self, addr: &SocketAddr) -> io::Result<()> {
         unsafe {
-            let (addr, len) = try!(sockaddr_un(addr.as_ref()));
             try!(cvt(libc::connect(self.as_raw_fd(),
-                                   &addr as *const _ as *const _,
-                                   len)));
+                                   &addr.addr as *const _ as *const _,
+                                   addr.len)));
             Ok(())
         }
     }
@@ -109,25 +244,39 @@ impl UnixSocket {
         }
     }
 
-    pub fn accept(&self) -> io::Result<UnixSocket> {
-        unsafe {
-            let fd = try!(cvt(libc::accept(self.as_raw_fd(),
-                                           0 as *mut _,
-                                           0 as *mut _)));
-            let fd = Io::from_raw_fd(fd);
-            try!(set_cloexec(fd.as_raw_fd()));
-            try!(set_nonblock(fd.as_raw_fd()));
-            Ok(UnixSocket { io: fd })
-        }
+    pub fn accept(&self) -> io::Result<(UnixSocket, SocketAddr)> {
+        let (io, addr) = try!(SocketAddr::with(|addr, len| {
+            socket::accept(self.as_raw_fd(), addr, len)
+        }));
+
+        Ok((UnixSocket { io: io }, addr))
+    }
+
+    /// Returns the address of the local half of this socket
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe {
+            libc::getsockname(self.as_raw_fd(), addr, len)
+        })
+    }
+
+    /// Returns the address of the remote half of this socket
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        SocketAddr::new(|addr, len| unsafe {
+            libc::getpeername(self.as_raw_fd(), addr, len)
+        })
     }
 
     /// Bind the socket to the specified address
     pub fn bind<P: AsRef<Path> + ?Sized>(&self, addr: &P) -> io::Result<()> {
+        self.bind_addr(&try!(SocketAddr::from_pathname(addr)))
+    }
+
+    /// Bind the socket to the specified address
+    pub fn bind_addr(&self, addr: &SocketAddr) -> io::Result<()> {
         unsafe {
-            let (addr, len) = try!(sockaddr_un(addr.as_ref()));
             try!(cvt(libc::bind(self.as_raw_fd(),
-                                &addr as *const _ as *const _,
-                                len)));
+                                &addr.addr as *const _ as *const _,
+                                addr.len)));
             Ok(())
         }
     }
@@ -148,56 +297,100 @@ impl UnixSocket {
         }
     }
 
-    pub fn read_recv_fd(&mut self, buf: &mut [u8]) -> io::Result<(usize, Option<RawFd>)> {
+    pub fn take_error(&self) -> io::Result<Option<io::Error>> {
+        socket::take_error(self.as_raw_fd())
+    }
+
+    pub fn readv(&self, bufs: &mut [&mut IoVec]) -> io::Result<usize> {
+        iovec::readv(self.as_raw_fd(), bufs)
+    }
+
+    pub fn writev(&self, bufs: &[&IoVec]) -> io::Result<usize> {
+        iovec::writev(self.as_raw_fd(), bufs)
+    }
+
+    /// Receives data along with a file descriptor passed with `SCM_RIGHTS`.
+    /// The received descriptor is close-on-exec.
+    ///
+    /// Only the first descriptor is returned, any other one passed along
+    /// with the data is closed. An error is returned if the descriptors did
+    /// not fit in the control buffer, the data is lost in that case.
+    pub fn read_recv_fd(&self, buf: &mut [u8]) -> io::Result<(usize, Option<RawFd>)> {
         unsafe {
             let mut iov = libc::iovec {
                 iov_base: buf.as_mut_ptr() as *mut _,
                 iov_len: buf.len(),
             };
-            struct Cmsg {
-                hdr: libc::cmsghdr,
-                data: [libc::c_int; 1],
-            }
-            let mut cmsg: Cmsg = mem::zeroed();
+            // Aligned for `cmsghdr`, and large enough for a few descriptors
+            let mut cmsg_buf: [libc::cmsghdr; 8] = mem::zeroed();
             let mut msg: libc::msghdr = mem::zeroed();
             msg.msg_iov = &mut iov;
             msg.msg_iovlen = 1;
-            msg.msg_control = &mut cmsg as *mut _ as *mut _;
-            msg.msg_controllen = mem::size_of_val(&cmsg).my_into();
-            let bytes = try!(cvt(libc::recvmsg(self.as_raw_fd(), &mut msg, 0)));
+            msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+            msg.msg_controllen = mem::size_of_val(&cmsg_buf).my_into();
+            let bytes = try!(cvt(libc::recvmsg(self.as_raw_fd(), &mut msg, RECV_FD_FLAGS)));
+
+            let mut fd = None;
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let data = libc::CMSG_DATA(cmsg);
+                    let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+
+                    for i in 0..len / FD_LEN as usize {
+                        let received = ptr::read_unaligned((data as *const libc::c_int).offset(i as isize));
+
+                        if fd.is_none() {
+                            if RECV_FD_FLAGS == 0 {
+                                drop(set_cloexec(received));
+                            }
+                            fd = Some(received);
+                        } else {
+                            libc::close(received);
+                        }
+                    }
+                }
 
-            const SCM_RIGHTS: libc::c_int = 1;
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+
+            if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+                if let Some(fd) = fd {
+                    libc::close(fd);
+                }
+
+                return Err(io::Error::new(io::ErrorKind::Other,
+                                          "control message truncated, passed file descriptors were lost"));
+            }
 
-            let fd = if cmsg.hdr.cmsg_level == libc::SOL_SOCKET &&
-                        cmsg.hdr.cmsg_type == SCM_RIGHTS {
-                Some(cmsg.data[0])
-            } else {
-                None
-            };
             Ok((bytes as usize, fd))
         }
     }
 
-    pub fn write_send_fd(&mut self, buf: &[u8], fd: RawFd) -> io::Result<usize> {
+    /// Sends data along with the file descriptor `fd`, passed with
+    /// `SCM_RIGHTS`.
+    pub fn write_send_fd(&self, buf: &[u8], fd: RawFd) -> io::Result<usize> {
         unsafe {
             let mut iov = libc::iovec {
                 iov_base: buf.as_ptr() as *mut _,
                 iov_len: buf.len(),
             };
-            struct Cmsg {
-                hdr: libc::cmsghdr,
-                data: [libc::c_int; 1],
-            }
-            let mut cmsg: Cmsg = mem::zeroed();
-            cmsg.hdr.cmsg_len = mem::size_of_val(&cmsg).my_into();
-            cmsg.hdr.cmsg_level = libc::SOL_SOCKET;
-            cmsg.hdr.cmsg_type = 1; // SCM_RIGHTS
-            cmsg.data[0] = fd;
+            let mut cmsg_buf: [libc::cmsghdr; 2] = mem::zeroed();
             let mut msg: libc::msghdr = mem::zeroed();
             msg.msg_iov = &mut iov;
             msg.msg_iovlen = 1;
-            msg.msg_control = &mut cmsg as *mut _ as *mut _;
-            msg.msg_controllen = mem::size_of_val(&cmsg).my_into();
+            msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+            msg.msg_controllen = (libc::CMSG_SPACE(FD_LEN) as usize).my_into();
+
+            // The length covers exactly one descriptor, not the padding
+            // after it
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_len = (libc::CMSG_LEN(FD_LEN) as usize).my_into();
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, fd);
+
             let bytes = try!(cvt(libc::sendmsg(self.as_raw_fd(), &msg, 0)));
             Ok(bytes as usize)
         }
@@ -220,6 +413,22 @@ impl Write for UnixSocket {
     }
 }
 
+impl<'a> Read for &'a UnixSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.io).read(buf)
+    }
+}
+
+impl<'a> Write for &'a UnixSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.io).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.io).flush()
+    }
+}
+
 impl Evented for UnixSocket {
     fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
         self.io.register(poll, token, interest, opts)
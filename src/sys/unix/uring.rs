@@ -0,0 +1,932 @@
+//! Selector backed by io_uring, enabled with the `io_uring` feature.
+//!
+//! Readiness is watched with `IORING_OP_POLL_ADD` requests instead of an
+//! epoll instance. Completions are read from the shared ring without a
+//! system call, so a zero timeout `select` that finds events already
+//! completed costs none. Registering, reregistering and deregistering
+//! submit their requests right away, so that the ring fd reports readiness
+//! like an epoll fd would (see `Poll::poll_with_extra_fds`).
+//!
+//! The `PollOpt` semantics are mapped onto poll requests as follows:
+//!
+//! - Edge: a multishot poll request, which completes every time the file
+//!   becomes ready, like `EPOLLET`. It is re-armed if the kernel ends it.
+//! - Level: a single-shot poll request, re-armed by the next `select` once
+//!   it completed. A single-shot request completes immediately if the file
+//!   is still ready, so the readiness keeps being reported. Until then, the
+//!   ring fd does not report the file as ready.
+//! - Oneshot: a single-shot poll request, only re-armed by `reregister`.
+//!
+//! Events use the same layout as with epoll (the poll masks are the same
+//! bits), so `Events` is shared with the epoll selector.
+//!
+//! A pending poll request holds a reference to the file, so unlike with
+//! epoll, closing a registered file descriptor would not release the file.
+//! The types of this crate owning a file descriptor hold a `CloseGuard`,
+//! which cancels the requests of the fd in every selector before the fd is
+//! closed. Deregistering cancels the request as well. Only raw file
+//! descriptors registered with `EventedFd` must be deregistered before being
+//! closed, or the file stays open until the fd number is registered again
+//! or the selector is dropped.
+//!
+//! Waiting for completions does not hold the selector lock, so handles can
+//! be registered, deregistered and dropped from other threads while `select`
+//! blocks.
+//!
+//! This requires Linux 5.13 or later, `Selector::new` fails on older
+//! kernels.
+
+use std::collections::HashMap;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex, Once, Weak, ONCE_INIT};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::Duration;
+use std::{cmp, fmt, mem, ptr, u64};
+
+use libc;
+
+use {io, Ready, PollOpt, Token};
+use event::Event;
+use sys::unix::{cvt, is_closed, registration_error, set_close_on_exec, FdValidation};
+use sys::unix::epoll::{ioevent_to_epoll, Events};
+
+static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// Every live selector, so that `release` finds the requests of an fd about to
+// be closed. Dead entries are pruned when the list is walked.
+static SELECTORS_INIT: Once = ONCE_INIT;
+static mut SELECTORS: *const Mutex<Vec<Weak<Mutex<Inner>>>> = 0 as *const _;
+
+// Both system calls have the same number on every architecture
+const SYS_IO_URING_SETUP: libc::c_long = 425;
+const SYS_IO_URING_ENTER: libc::c_long = 426;
+
+const IORING_SETUP_CQSIZE: u32 = 1 << 3;
+const IORING_SETUP_CLAMP: u32 = 1 << 4;
+
+const IORING_FEAT_SINGLE_MMAP: u32 = 1 << 0;
+const IORING_FEAT_NODROP: u32 = 1 << 1;
+const IORING_FEAT_EXT_ARG: u32 = 1 << 8;
+// Added along with multishot poll requests in 5.13
+const IORING_FEAT_RSRC_TAGS: u32 = 1 << 10;
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+
+const IORING_OP_POLL_ADD: u8 = 6;
+const IORING_OP_POLL_REMOVE: u8 = 7;
+
+const IORING_POLL_ADD_MULTI: u32 = 1 << 0;
+const IORING_CQE_F_MORE: u32 = 1 << 1;
+
+const IORING_SQ_CQ_OVERFLOW: u32 = 1 << 1;
+
+const IORING_ENTER_GETEVENTS: u32 = 1 << 0;
+const IORING_ENTER_EXT_ARG: u32 = 1 << 3;
+
+const SQ_ENTRIES: u32 = 256;
+const CQ_ENTRIES: u32 = 4096;
+
+// `user_data` of poll remove requests, their completions are ignored
+const REMOVE: u64 = u64::MAX;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+#[repr(C)]
+struct GeteventsArg {
+    sigmask: u64,
+    sigmask_sz: u32,
+    pad: u32,
+    ts: u64,
+}
+
+#[repr(C)]
+struct KernelTimespec {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+pub struct Selector {
+    id: usize,
+    fd: RawFd,
+    inner: Arc<Mutex<Inner>>,
+    validation: FdValidation,
+}
+
+struct Inner {
+    ring: Ring,
+    registrations: HashMap<RawFd, Registration>,
+    // Registrations whose poll request completed, armed again by `select`
+    rearm: Vec<RawFd>,
+    next_gen: u32,
+    // Index in `Events` of the tokens reported by the current `select`
+    reported: HashMap<Token, usize>,
+}
+
+struct Registration {
+    token: Token,
+    interests: Ready,
+    opts: PollOpt,
+    // Distinguishes completions of the current poll request from those of
+    // requests removed by `reregister`
+    gen: u32,
+    armed: bool,
+    // Device and inode, to detect the fd number being reused
+    file: (libc::dev_t, libc::ino_t),
+}
+
+struct Ring {
+    fd: RawFd,
+    // Only kept to be unmapped on drop, after the ring is closed
+    _sq_map: Mmap,
+    _cq_map: Option<Mmap>,
+    _sqes_map: Mmap,
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_flags: *const AtomicU32,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_array: *mut u32,
+    sqes: *mut Sqe,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Cqe,
+}
+
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The ring is only accessed with the `Inner` lock held
+unsafe impl Send for Inner {}
+
+impl Selector {
+    pub fn new(cloexec: bool) -> io::Result<Selector> {
+        let ring = try!(Ring::new());
+
+        // The ring is always created close-on-exec
+        if !cloexec {
+            try!(set_close_on_exec(ring.fd, false));
+        }
+
+        // offset by 1 to avoid choosing 0 as the id of a selector
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let fd = ring.fd;
+        let inner = Arc::new(Mutex::new(Inner {
+            ring: ring,
+            registrations: HashMap::new(),
+            rearm: Vec::new(),
+            next_gen: 0,
+            reported: HashMap::new(),
+        }));
+
+        {
+            let mut selectors = selectors().lock().unwrap();
+            selectors.retain(|selector| selector.upgrade().is_some());
+            selectors.push(Arc::downgrade(&inner));
+        }
+
+        Ok(Selector {
+            id: id,
+            fd: fd,
+            inner: inner,
+            validation: FdValidation::new(),
+        })
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the timeout `select` actually waits for when given `timeout`.
+    ///
+    /// The wait takes a `timespec`, so the timeout is used as is.
+    pub fn effective_timeout(timeout: Duration) -> Duration {
+        timeout
+    }
+
+    /// Number of events worth requesting per call to `select`.
+    pub fn recommended_capacity(&self) -> usize {
+        1024
+    }
+
+    /// Wait for events from the OS
+    pub fn select(&self, evts: &mut Events, awakener: Token, timeout: Option<Duration>) -> io::Result<bool> {
+        evts.clear();
+
+        let wait = {
+            let mut inner = self.inner.lock().unwrap();
+
+            try!(inner.rearm());
+            try!(inner.ring.submit());
+
+            timeout != Some(Duration::from_millis(0)) && !inner.ring.has_completions()
+        };
+
+        // Wait without the lock, the completion ring is only consumed by
+        // `select` and other threads may queue requests meanwhile.
+        if wait {
+            try!(enter(self.fd, 0, 1, timeout));
+        }
+
+        let awoken = self.inner.lock().unwrap().reap(evts, awakener);
+
+        self.validation.check(|token| {
+            evts.push_event(Event::new(Ready::error() | Ready::hup(), token));
+        });
+
+        Ok(awoken)
+    }
+
+    /// Enables or disables tracking registered fds, see
+    /// `Poll::set_fd_validation`.
+    pub fn set_fd_validation(&self, enabled: bool) {
+        self.validation.set_enabled(enabled);
+    }
+
+    /// Register event interests for the given IO handle with the OS
+    pub fn register(&self, fd: RawFd, token: Token, interests: Ready, opts: PollOpt) -> io::Result<()> {
+        let file = try!(file_id(fd).map_err(|e| registration_error(e, fd, token)));
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(prev) = inner.registrations.remove(&fd) {
+            if prev.file == file {
+                inner.registrations.insert(fd, prev);
+                return Err(io::Error::from_raw_os_error(libc::EEXIST));
+            }
+
+            // The fd number was reused, the previous file was closed without
+            // being deregistered
+            if prev.armed {
+                try!(inner.ring.push(poll_remove(user_data(fd, prev.gen))));
+            }
+        }
+
+        let gen = inner.next_gen();
+
+        inner.registrations.insert(fd, Registration {
+            token: token,
+            interests: interests,
+            opts: opts,
+            gen: gen,
+            armed: false,
+            file: file,
+        });
+
+        try!(inner.arm(fd));
+        try!(inner.ring.submit());
+
+        self.validation.register(fd, token);
+        Ok(())
+    }
+
+    /// Register event interests for the given IO handle with the OS
+    pub fn reregister(&self, fd: RawFd, token: Token, interests: Ready, opts: PollOpt) -> io::Result<()> {
+        if is_closed(fd) {
+            return Err(registration_error(io::Error::from_raw_os_error(libc::EBADF), fd, token));
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let gen = inner.next_gen();
+
+        let prev = match inner.registrations.get_mut(&fd) {
+            Some(registration) => {
+                let prev = if registration.armed {
+                    Some(user_data(fd, registration.gen))
+                } else {
+                    None
+                };
+
+                registration.token = token;
+                registration.interests = interests;
+                registration.opts = opts;
+                registration.gen = gen;
+                registration.armed = false;
+
+                prev
+            }
+            None => {
+                let err = io::Error::from_raw_os_error(libc::ENOENT);
+                return Err(registration_error(err, fd, token));
+            }
+        };
+
+        if let Some(prev) = prev {
+            try!(inner.ring.push(poll_remove(prev)));
+        }
+
+        try!(inner.arm(fd));
+        try!(inner.ring.submit());
+
+        self.validation.register(fd, token);
+        Ok(())
+    }
+
+    /// Deregister event interests for the given IO handle with the OS
+    pub fn deregister(&self, fd: RawFd) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+
+        match inner.registrations.remove(&fd) {
+            Some(registration) => {
+                if registration.armed {
+                    try!(inner.ring.push(poll_remove(user_data(fd, registration.gen))));
+                    try!(inner.ring.submit());
+                }
+            }
+            None => return Err(io::Error::from_raw_os_error(libc::ENOENT)),
+        }
+
+        self.validation.deregister(fd);
+        Ok(())
+    }
+}
+
+/// Cancels the poll requests of `fd` in every selector and forgets its
+/// registrations. Called by `CloseGuard` right before `fd` is closed.
+pub fn release(fd: RawFd) {
+    let selectors: Vec<_> = {
+        let mut selectors = selectors().lock().unwrap();
+        selectors.retain(|selector| selector.upgrade().is_some());
+        selectors.iter().filter_map(|selector| selector.upgrade()).collect()
+    };
+
+    for selector in selectors {
+        let mut inner = selector.lock().unwrap();
+
+        if let Some(registration) = inner.registrations.remove(&fd) {
+            if registration.armed {
+                // Nothing to report on failure, the fd is about to be closed
+                let _ = inner.ring.push(poll_remove(user_data(fd, registration.gen)))
+                    .and_then(|_| inner.ring.submit());
+            }
+        }
+    }
+}
+
+fn selectors() -> &'static Mutex<Vec<Weak<Mutex<Inner>>>> {
+    unsafe {
+        SELECTORS_INIT.call_once(|| {
+            SELECTORS = Box::into_raw(Box::new(Mutex::new(Vec::new())));
+        });
+
+        &*SELECTORS
+    }
+}
+
+impl fmt::Debug for Selector {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Selector")
+            .field("id", &self.id)
+            .field("fd", &self.fd)
+            .finish()
+    }
+}
+
+impl AsRawFd for Selector {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Inner {
+    fn next_gen(&mut self) -> u32 {
+        self.next_gen = self.next_gen.wrapping_add(1);
+        self.next_gen
+    }
+
+    /// Queues the poll request of a registration.
+    fn arm(&mut self, fd: RawFd) -> io::Result<()> {
+        let sqe = match self.registrations.get_mut(&fd) {
+            Some(registration) => {
+                registration.armed = true;
+                poll_add(fd, registration)
+            }
+            None => return Ok(()),
+        };
+
+        self.ring.push(sqe)
+    }
+
+    /// Queues the poll requests of registrations that completed since the
+    /// previous call.
+    fn rearm(&mut self) -> io::Result<()> {
+        while let Some(fd) = self.rearm.pop() {
+            let armed = match self.registrations.get(&fd) {
+                Some(registration) => registration.armed,
+                None => true,
+            };
+
+            if !armed {
+                if let Err(e) = self.arm(fd) {
+                    self.rearm.push(fd);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves completions to `evts`, up to its capacity. Returns true if the
+    /// awakener fired.
+    fn reap(&mut self, evts: &mut Events, awakener: Token) -> bool {
+        let capacity = evts.capacity();
+        let mut awoken = false;
+        let mut ready: Vec<(Token, u32)> = Vec::new();
+
+        self.reported.clear();
+
+        while let Some(cqe) = self.ring.peek() {
+            if cqe.user_data == REMOVE {
+                self.ring.advance();
+                continue;
+            }
+
+            let fd = (cqe.user_data >> 32) as RawFd;
+
+            let registration = match self.registrations.get_mut(&fd) {
+                Some(registration) if user_data(fd, registration.gen) == cqe.user_data => registration,
+                // Completion of a removed request
+                _ => {
+                    self.ring.advance();
+                    continue;
+                }
+            };
+
+            // Leave the completion in the ring if there is no room for a new
+            // token, it is reported by the next call
+            if registration.token != awakener
+                && !self.reported.contains_key(&registration.token)
+                && ready.len() >= capacity
+            {
+                break;
+            }
+
+            self.ring.advance();
+
+            if cqe.flags & IORING_CQE_F_MORE == 0 {
+                registration.armed = false;
+
+                if !registration.opts.is_oneshot() {
+                    self.rearm.push(fd);
+                }
+            }
+
+            if cqe.res == -libc::ECANCELED {
+                continue;
+            }
+
+            // Closed without being deregistered
+            if cqe.res == -libc::EBADF {
+                self.registrations.remove(&fd);
+                continue;
+            }
+
+            let mut events = if cqe.res < 0 {
+                libc::EPOLLERR as u32
+            } else {
+                cqe.res as u32
+            };
+
+            // The completion reports the mask the file was woken up with,
+            // sockets include `EPOLLPRI` for any incoming data
+            if events & libc::EPOLLPRI as u32 != 0 && !has_priority(fd) {
+                events &= !(libc::EPOLLPRI | libc::EPOLLRDBAND) as u32;
+
+                if events == 0 {
+                    continue;
+                }
+            }
+
+            if registration.token == awakener {
+                awoken = true;
+                continue;
+            }
+
+            match self.reported.get(&registration.token) {
+                Some(&idx) => ready[idx].1 |= events,
+                None => {
+                    self.reported.insert(registration.token, ready.len());
+                    ready.push((registration.token, events));
+                }
+            }
+        }
+
+        for (token, events) in ready {
+            evts.push_raw(events, token);
+        }
+
+        awoken
+    }
+}
+
+fn user_data(fd: RawFd, gen: u32) -> u64 {
+    ((fd as u32 as u64) << 32) | gen as u64
+}
+
+fn poll_add(fd: RawFd, registration: &Registration) -> Sqe {
+    let mut sqe = Sqe::default();
+
+    sqe.opcode = IORING_OP_POLL_ADD;
+    sqe.fd = fd;
+    sqe.op_flags = poll_mask(poll_events(registration.interests));
+    sqe.user_data = user_data(fd, registration.gen);
+
+    if registration.opts.is_edge() && !registration.opts.is_oneshot() {
+        sqe.len = IORING_POLL_ADD_MULTI;
+    }
+
+    sqe
+}
+
+fn poll_events(interests: Ready) -> u32 {
+    let mut events = ioevent_to_epoll(interests, PollOpt::empty());
+
+    // io_uring ignores wake ups that only match `EPOLLPRI`, which it uses for
+    // its own purposes. Sockets wake up with `EPOLLRDBAND` as well when
+    // urgent data arrives, the completion still only reports `EPOLLPRI`.
+    if events & libc::EPOLLPRI as u32 != 0 {
+        events |= libc::EPOLLRDBAND as u32;
+    }
+
+    events
+}
+
+fn has_priority(fd: RawFd) -> bool {
+    let mut pollfd = libc::pollfd { fd: fd, events: libc::POLLPRI, revents: 0 };
+    let res = unsafe { libc::poll(&mut pollfd, 1, 0) };
+
+    res > 0 && pollfd.revents & libc::POLLPRI != 0
+}
+
+fn poll_remove(target: u64) -> Sqe {
+    let mut sqe = Sqe::default();
+
+    sqe.opcode = IORING_OP_POLL_REMOVE;
+    sqe.fd = -1;
+    sqe.addr = target;
+    sqe.user_data = REMOVE;
+
+    sqe
+}
+
+// The kernel swaps the 16 bit halves of the 32 bit poll mask on big endian
+#[cfg(target_endian = "little")]
+fn poll_mask(mask: u32) -> u32 {
+    mask
+}
+
+#[cfg(target_endian = "big")]
+fn poll_mask(mask: u32) -> u32 {
+    mask.rotate_left(16)
+}
+
+fn file_id(fd: RawFd) -> io::Result<(libc::dev_t, libc::ino_t)> {
+    unsafe {
+        let mut stat: libc::stat = mem::zeroed();
+        try!(cvt(libc::fstat(fd, &mut stat)));
+        Ok((stat.st_dev, stat.st_ino))
+    }
+}
+
+/*
+ *
+ * ===== Ring =====
+ *
+ */
+
+impl Ring {
+    fn new() -> io::Result<Ring> {
+        let mut params = Params::default();
+        params.flags = IORING_SETUP_CQSIZE | IORING_SETUP_CLAMP;
+        params.cq_entries = CQ_ENTRIES;
+
+        let fd = unsafe {
+            try!(cvt(libc::syscall(SYS_IO_URING_SETUP, SQ_ENTRIES, &mut params as *mut Params) as libc::c_int))
+        };
+
+        let required = IORING_FEAT_NODROP | IORING_FEAT_EXT_ARG | IORING_FEAT_RSRC_TAGS;
+
+        if params.features & required != required {
+            unsafe { libc::close(fd); }
+            return Err(io::Error::new(io::ErrorKind::Other, "io_uring selector requires Linux 5.13 or later"));
+        }
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * mem::size_of::<u32>();
+        let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let single = params.features & IORING_FEAT_SINGLE_MMAP != 0;
+
+        let sq_map = match Mmap::new(fd, if single { cmp::max(sq_len, cq_len) } else { sq_len }, IORING_OFF_SQ_RING) {
+            Ok(map) => map,
+            Err(e) => {
+                unsafe { libc::close(fd); }
+                return Err(e);
+            }
+        };
+
+        let cq_map = if single {
+            None
+        } else {
+            match Mmap::new(fd, cq_len, IORING_OFF_CQ_RING) {
+                Ok(map) => Some(map),
+                Err(e) => {
+                    unsafe { libc::close(fd); }
+                    return Err(e);
+                }
+            }
+        };
+
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+
+        let sqes_map = match Mmap::new(fd, sqes_len, IORING_OFF_SQES) {
+            Ok(map) => map,
+            Err(e) => {
+                unsafe { libc::close(fd); }
+                return Err(e);
+            }
+        };
+
+        unsafe {
+            let sq = sq_map.ptr as *mut u8;
+            let cq = cq_map.as_ref().map(|map| map.ptr).unwrap_or(sq_map.ptr) as *mut u8;
+
+            Ok(Ring {
+                fd: fd,
+                sq_head: sq.offset(params.sq_off.head as isize) as *const AtomicU32,
+                sq_tail: sq.offset(params.sq_off.tail as isize) as *const AtomicU32,
+                sq_flags: sq.offset(params.sq_off.flags as isize) as *const AtomicU32,
+                sq_mask: *(sq.offset(params.sq_off.ring_mask as isize) as *const u32),
+                sq_entries: *(sq.offset(params.sq_off.ring_entries as isize) as *const u32),
+                sq_array: sq.offset(params.sq_off.array as isize) as *mut u32,
+                sqes: sqes_map.ptr as *mut Sqe,
+                cq_head: cq.offset(params.cq_off.head as isize) as *const AtomicU32,
+                cq_tail: cq.offset(params.cq_off.tail as isize) as *const AtomicU32,
+                cq_mask: *(cq.offset(params.cq_off.ring_mask as isize) as *const u32),
+                cqes: cq.offset(params.cq_off.cqes as isize) as *const Cqe,
+                _sq_map: sq_map,
+                _cq_map: cq_map,
+                _sqes_map: sqes_map,
+            })
+        }
+    }
+
+    /// Queues a request, submitting the queued ones first if the ring is
+    /// full.
+    fn push(&mut self, sqe: Sqe) -> io::Result<()> {
+        loop {
+            let tail = unsafe { (*self.sq_tail).load(Ordering::Relaxed) };
+
+            if self.unsubmitted() < self.sq_entries {
+                let idx = tail & self.sq_mask;
+
+                unsafe {
+                    ptr::write(self.sqes.offset(idx as isize), sqe);
+                    *self.sq_array.offset(idx as isize) = idx;
+                    (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+                }
+
+                return Ok(());
+            }
+
+            try!(self.enter(0, None));
+        }
+    }
+
+    /// Submits the queued requests, without waiting.
+    fn submit(&mut self) -> io::Result<()> {
+        if self.unsubmitted() > 0 {
+            try!(self.enter(0, None));
+        }
+
+        Ok(())
+    }
+
+    /// Number of queued requests the kernel has not consumed yet.
+    fn unsubmitted(&self) -> u32 {
+        unsafe {
+            let head = (*self.sq_head).load(Ordering::Acquire);
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+
+            tail.wrapping_sub(head)
+        }
+    }
+
+    fn has_completions(&self) -> bool {
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+
+            head != tail
+        }
+    }
+
+    /// Submits the queued requests and waits for `min_complete`
+    /// completions, for at most `timeout`.
+    fn enter(&mut self, min_complete: u32, timeout: Option<Duration>) -> io::Result<()> {
+        enter(self.fd, self.unsubmitted(), min_complete, timeout)
+    }
+
+    /// Returns the next completion. Completions that did not fit in the
+    /// ring are moved to it once it is empty.
+    fn peek(&mut self) -> Option<Cqe> {
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            let mut tail = (*self.cq_tail).load(Ordering::Acquire);
+
+            if head == tail && self.flush_overflow() {
+                tail = (*self.cq_tail).load(Ordering::Acquire);
+            }
+
+            if head == tail {
+                return None;
+            }
+
+            let cqe = &*self.cqes.offset((head & self.cq_mask) as isize);
+
+            Some(Cqe {
+                user_data: cqe.user_data,
+                res: cqe.res,
+                flags: cqe.flags,
+            })
+        }
+    }
+
+    /// Moves the completions the kernel kept aside while the ring was full
+    /// to the ring. Returns false if there were none.
+    fn flush_overflow(&mut self) -> bool {
+        let flags = unsafe { (*self.sq_flags).load(Ordering::Acquire) };
+
+        if flags & IORING_SQ_CQ_OVERFLOW == 0 {
+            return false;
+        }
+
+        let res = unsafe {
+            libc::syscall(SYS_IO_URING_ENTER, self.fd, 0, 0, IORING_ENTER_GETEVENTS,
+                          ptr::null::<GeteventsArg>(), 0)
+        };
+
+        res != -1
+    }
+
+    fn advance(&mut self) {
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            (*self.cq_head).store(head.wrapping_add(1), Ordering::Release);
+        }
+    }
+}
+
+/// Submits `to_submit` queued requests and waits for `min_complete`
+/// completions, for at most `timeout`. Only reads the rings, so waiting does
+/// not need the selector lock.
+fn enter(fd: RawFd, to_submit: u32, min_complete: u32, timeout: Option<Duration>) -> io::Result<()> {
+    let mut flags = 0;
+    let mut arg = ptr::null::<GeteventsArg>();
+    let mut arg_len = 0;
+
+    let ts;
+    let getevents_arg;
+
+    if min_complete > 0 {
+        flags |= IORING_ENTER_GETEVENTS;
+
+        if let Some(timeout) = timeout {
+            ts = KernelTimespec {
+                tv_sec: cmp::min(timeout.as_secs(), i64::max_value() as u64) as i64,
+                tv_nsec: timeout.subsec_nanos() as i64,
+            };
+
+            getevents_arg = GeteventsArg {
+                sigmask: 0,
+                sigmask_sz: 0,
+                pad: 0,
+                ts: &ts as *const KernelTimespec as u64,
+            };
+
+            flags |= IORING_ENTER_EXT_ARG;
+            arg = &getevents_arg;
+            arg_len = mem::size_of::<GeteventsArg>();
+        }
+    }
+
+    let res = unsafe {
+        libc::syscall(SYS_IO_URING_ENTER, fd, to_submit, min_complete, flags, arg, arg_len)
+    };
+
+    if res == -1 {
+        let err = io::Error::last_os_error();
+
+        // The timeout elapsed
+        if err.raw_os_error() == Some(libc::ETIME) {
+            return Ok(());
+        }
+
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // Closing the ring cancels the pending requests, releasing the files
+        // they reference. The mappings are released afterwards.
+        unsafe {
+            let _ = libc::close(self.fd);
+        }
+    }
+}
+
+impl Mmap {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Mmap> {
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_SHARED | libc::MAP_POPULATE, fd, offset)
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Mmap { ptr: ptr, len: len })
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
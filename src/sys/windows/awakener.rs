@@ -1,7 +1,7 @@
 use std::sync::Mutex;
 
 use miow::iocp::CompletionStatus;
-use {io, poll, Evented, Ready, Poll, PollOpt, Token};
+use {io, poll, AwakenerKind, Evented, Ready, Poll, PollOpt, Token};
 use sys::windows::Selector;
 
 pub struct Awakener {
@@ -20,6 +20,12 @@ impl Awakener {
         })
     }
 
+    /// The awakener always posts to the completion port, `kind` only
+    /// matters on Unix.
+    pub fn with_kind(_kind: AwakenerKind) -> io::Result<Awakener> {
+        Awakener::new()
+    }
+
     pub fn wakeup(&self) -> io::Result<()> {
         // Each wakeup notification has NULL as its `OVERLAPPED` pointer to
         // indicate that it's from this awakener and not part of an I/O
@@ -36,6 +42,12 @@ impl Awakener {
         Ok(())
     }
 
+    pub fn prime(&self) -> io::Result<()> {
+        // noop, posting a completion status has nothing to warm up and
+        // would make the next `select` return early
+        Ok(())
+    }
+
     pub fn cleanup(&self) {
         // noop
     }
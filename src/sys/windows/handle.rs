@@ -0,0 +1,158 @@
+use std::fmt;
+use std::io;
+use std::os::windows::prelude::*;
+use std::ptr;
+use std::sync::Mutex;
+
+use kernel32;
+use winapi::*;
+
+use {Evented, Ready, Poll, PollOpt, Registration, SetReadiness, Token};
+
+/// Adapter for a waitable `RawHandle` providing an `Evented` implementation.
+///
+/// `EventedHandle` becomes readable once the kernel object behind the handle
+/// is signaled, such as an event object, a process, a thread, a waitable
+/// timer or a change notification. The wait is performed by the system
+/// thread pool, which sets the readiness through a `Registration`.
+///
+/// Each registration waits for a single signal. Once the event is handled,
+/// call `reregister` to wait for the next one; this also clears the
+/// readiness. This keeps a manual-reset object, which stays signaled, from
+/// being reported over and over.
+///
+/// Handles opened for overlapped I/O are instead associated with the
+/// completion port through `Binding`, and driven with `Overlapped`.
+///
+/// `EventedHandle` does not take ownership of the handle. It must stay open
+/// for as long as the `EventedHandle` exists.
+pub struct EventedHandle {
+    handle: HANDLE,
+    registered: Mutex<Option<Registered>>,
+}
+
+struct Registered {
+    registration: Registration,
+    // Boxed, its address is handed to the wait callback
+    set_readiness: *mut SetReadiness,
+    wait: HANDLE,
+}
+
+// The wrapped handle and the wait handle can be used from any thread
+unsafe impl Send for EventedHandle {}
+unsafe impl Sync for EventedHandle {}
+
+impl EventedHandle {
+    /// Wraps a waitable handle.
+    ///
+    /// # Safety
+    ///
+    /// The handle must stay valid until the returned value is dropped.
+    pub unsafe fn new(handle: RawHandle) -> EventedHandle {
+        EventedHandle {
+            handle: handle as HANDLE,
+            registered: Mutex::new(None),
+        }
+    }
+
+    /// Returns the wrapped handle.
+    pub fn handle(&self) -> RawHandle {
+        self.handle as RawHandle
+    }
+}
+
+unsafe extern "system" fn signaled(context: PVOID, _timed_out: BOOLEAN) {
+    let set_readiness = &*(context as *const SetReadiness);
+    let _ = set_readiness.set_readiness(Ready::readable());
+}
+
+impl Registered {
+    fn wait(&mut self, handle: HANDLE) -> io::Result<()> {
+        let ret = unsafe {
+            kernel32::RegisterWaitForSingleObject(&mut self.wait, handle, Some(signaled),
+                                                  self.set_readiness as PVOID, INFINITE,
+                                                  WT_EXECUTEONLYONCE)
+        };
+
+        if ret == 0 {
+            self.wait = ptr::null_mut();
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn unwait(&mut self) {
+        if !self.wait.is_null() {
+            // Blocks until a running callback returns
+            unsafe { kernel32::UnregisterWaitEx(self.wait, INVALID_HANDLE_VALUE) };
+            self.wait = ptr::null_mut();
+        }
+    }
+}
+
+impl Evented for EventedHandle {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        if !interest.is_readable() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "handle interest must include readable"));
+        }
+
+        let mut registered = self.registered.lock().unwrap();
+
+        if registered.is_some() {
+            return Err(io::Error::new(io::ErrorKind::Other, "handle already registered"));
+        }
+
+        let (registration, set_readiness) = try!(Registration::try_new(poll, token, interest, opts));
+
+        let mut new = Registered {
+            registration: registration,
+            set_readiness: Box::into_raw(Box::new(set_readiness)),
+            wait: ptr::null_mut(),
+        };
+
+        try!(new.wait(self.handle));
+        *registered = Some(new);
+
+        Ok(())
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        let mut registered = self.registered.lock().unwrap();
+
+        match *registered {
+            Some(ref mut registered) => {
+                registered.unwait();
+                try!(registered.registration.update(poll, token, interest, opts));
+                try!(unsafe { &*registered.set_readiness }.set_readiness(Ready::none()));
+                registered.wait(self.handle)
+            }
+            None => Err(io::Error::new(io::ErrorKind::Other, "handle not registered")),
+        }
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        match *self.registered.lock().unwrap() {
+            Some(ref registered) => registered.registration.deregister(poll),
+            None => Err(io::Error::new(io::ErrorKind::Other, "handle not registered")),
+        }
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        // The callback no longer runs once unregistered, the readiness can be
+        // freed
+        self.unwait();
+        drop(unsafe { Box::from_raw(self.set_readiness) });
+    }
+}
+
+impl fmt::Debug for EventedHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventedHandle")
+            .field("handle", &self.handle)
+            .field("registered", &self.registered.lock().unwrap().is_some())
+            .finish()
+    }
+}
@@ -123,8 +123,6 @@
 //!   done on Unix). Additionally, it hinders event loops moving across threads.
 //!   This should be solved by likely having a global `Selector` which all
 //!   others then communicate with.
-//! * Although Unix sockets don't exist on Windows, there are named pipes and
-//!   those should likely be bound here in a similar fashion to `TcpStream`.
 //!
 //! Next up, there are a few performance improvements and optimizations that can
 //! still be implemented
@@ -140,11 +138,15 @@ use std::io;
 use std::os::windows::prelude::*;
 
 use kernel32;
+use net2;
 use winapi;
 
 mod awakener;
 #[macro_use]
 mod selector;
+mod handle;
+mod named_pipe;
+mod process;
 mod tcp;
 mod udp;
 mod from_raw_arc;
@@ -153,6 +155,9 @@ mod iovec;
 
 pub use self::awakener::Awakener;
 pub use self::selector::{Events, Selector, Overlapped, Binding};
+pub use self::handle::EventedHandle;
+pub use self::named_pipe::NamedPipe;
+pub use self::process::ChildWatcher;
 pub use self::tcp::{TcpStream, TcpListener};
 pub use self::udp::UdpSocket;
 pub use self::iovec::IoVec;
@@ -162,6 +167,22 @@ enum Family {
     V4, V6,
 }
 
+pub fn tcp_builder(v6: bool) -> io::Result<net2::TcpBuilder> {
+    if v6 {
+        net2::TcpBuilder::new_v6()
+    } else {
+        net2::TcpBuilder::new_v4()
+    }
+}
+
+pub fn udp_builder(v6: bool) -> io::Result<net2::UdpBuilder> {
+    if v6 {
+        net2::UdpBuilder::new_v6()
+    } else {
+        net2::UdpBuilder::new_v4()
+    }
+}
+
 fn wouldblock() -> io::Error {
     io::Error::new(io::ErrorKind::WouldBlock, "operation would block")
 }
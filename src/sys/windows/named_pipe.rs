@@ -0,0 +1,658 @@
+//! Named pipes for IOCP
+//!
+//! Like TCP streams, pipes translate completions to readiness through internal
+//! buffers: a read is kept in flight while the pipe is connected and its
+//! completion makes the pipe readable, while writes are copied and scheduled,
+//! the pipe becoming writable again once the write completed. Note that most
+//! of this module is quite similar to the TCP module, so if something seems
+//! odd you may also want to try the docs over there.
+
+use std::cmp;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::prelude::*;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use kernel32;
+use miow::iocp::CompletionStatus;
+use miow::pipe;
+use winapi::*;
+
+use {Evented, Ready, Poll, PollOpt, Token};
+use poll;
+use sys::windows::from_raw_arc::FromRawArc;
+use sys::windows::selector::{Overlapped, ReadyBinding};
+use sys::windows::wouldblock;
+
+/// Size of the buffer of each read, messages longer than this are read in
+/// several steps.
+const READ_SIZE: usize = 8 * 1024;
+
+/// A named pipe, either the server or the client end, which can be used with
+/// `Poll`.
+///
+/// The server end is created with `new` or `new_message` and waits for a
+/// client with `connect`. The client end is opened with `open`. Both ends are
+/// read and written through the `Read` and `Write` implementations, which
+/// return a "would block" error instead of blocking.
+///
+/// With message pipes, each write sends a single message, and reads never
+/// return bytes of two different messages: a message which doesn't fit in the
+/// buffer given to `read` is returned by consecutive reads.
+pub struct NamedPipe {
+    imp: Imp,
+    registration: Mutex<Option<poll::Registration>>,
+}
+
+#[derive(Clone)]
+struct Imp {
+    /// See the docs of `StreamImp` in the TCP module for the reference
+    /// counting of pending operations.
+    inner: FromRawArc<Io>,
+}
+
+struct Io {
+    connect: Overlapped,
+    read: Overlapped,
+    write: Overlapped,
+    handle: pipe::NamedPipe,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    iocp: ReadyBinding,
+    connected: bool,
+    connecting: bool,
+    /// `connect` was called before the pipe was registered
+    deferred_connect: bool,
+    connect_error: Option<io::Error>,
+    /// Pending holds the buffer and how much of it was already filled, Ready
+    /// the buffer and how much of it was already consumed
+    read: State<(Vec<u8>, usize), (Vec<u8>, usize)>,
+    write: State<(Vec<u8>, usize), ()>,
+}
+
+enum State<T, U> {
+    Empty,              // no I/O operation in progress
+    Pending(T),         // an I/O operation is in progress
+    Ready(U),           // I/O has finished with this value
+    Error(io::Error),   // there was an I/O error
+}
+
+impl NamedPipe {
+    /// Creates a new instance of the server end of the byte pipe named
+    /// `addr`, like `\\.\pipe\name`.
+    ///
+    /// Several instances of the same pipe can be created to serve several
+    /// clients at once, each instance is connected to a single client.
+    pub fn new<A: AsRef<OsStr>>(addr: A) -> io::Result<NamedPipe> {
+        NamedPipe::create(addr.as_ref(), PIPE_TYPE_BYTE | PIPE_READMODE_BYTE)
+    }
+
+    /// Creates a new instance of the server end of the message pipe named
+    /// `addr`.
+    pub fn new_message<A: AsRef<OsStr>>(addr: A) -> io::Result<NamedPipe> {
+        NamedPipe::create(addr.as_ref(), PIPE_TYPE_MESSAGE | PIPE_READMODE_MESSAGE)
+    }
+
+    fn create(addr: &OsStr, mode: DWORD) -> io::Result<NamedPipe> {
+        let name = wide(addr);
+        let handle = unsafe {
+            kernel32::CreateNamedPipeW(name.as_ptr(),
+                                       PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED,
+                                       mode,
+                                       PIPE_UNLIMITED_INSTANCES,
+                                       65536,
+                                       65536,
+                                       0,
+                                       ptr::null_mut())
+        };
+
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+
+        let handle = unsafe { pipe::NamedPipe::from_raw_handle(handle) };
+        Ok(NamedPipe::from_pipe(handle, false))
+    }
+
+    /// Opens the client end of the pipe named `addr`.
+    ///
+    /// The client end is connected right away. This fails with the
+    /// `ERROR_PIPE_BUSY` OS error if no instance of the server end is waiting
+    /// for a client. The client reads whole messages if the server end is a
+    /// message pipe.
+    pub fn open<A: AsRef<OsStr>>(addr: A) -> io::Result<NamedPipe> {
+        let name = wide(addr.as_ref());
+        let handle = unsafe {
+            kernel32::CreateFileW(name.as_ptr(),
+                                  GENERIC_READ | GENERIC_WRITE,
+                                  0,
+                                  ptr::null_mut(),
+                                  OPEN_EXISTING,
+                                  FILE_FLAG_OVERLAPPED,
+                                  ptr::null_mut())
+        };
+
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+
+        let handle = unsafe { pipe::NamedPipe::from_raw_handle(handle) };
+
+        let mut flags = 0;
+        try!(cvt(unsafe {
+            kernel32::GetNamedPipeInfo(handle.as_raw_handle(), &mut flags,
+                                       ptr::null_mut(), ptr::null_mut(),
+                                       ptr::null_mut())
+        }));
+
+        if flags & PIPE_TYPE_MESSAGE != 0 {
+            let mut mode = PIPE_READMODE_MESSAGE;
+            try!(cvt(unsafe {
+                kernel32::SetNamedPipeHandleState(handle.as_raw_handle(), &mut mode,
+                                                  ptr::null_mut(), ptr::null_mut())
+            }));
+        }
+
+        Ok(NamedPipe::from_pipe(handle, true))
+    }
+
+    fn from_pipe(handle: pipe::NamedPipe, connected: bool) -> NamedPipe {
+        NamedPipe {
+            registration: Mutex::new(None),
+            imp: Imp {
+                inner: FromRawArc::new(Io {
+                    connect: Overlapped::new(connect_done),
+                    read: Overlapped::new(read_done),
+                    write: Overlapped::new(write_done),
+                    handle: handle,
+                    inner: Mutex::new(Inner {
+                        iocp: ReadyBinding::new(),
+                        connected: connected,
+                        connecting: false,
+                        deferred_connect: false,
+                        connect_error: None,
+                        read: State::Empty,
+                        write: State::Empty,
+                    }),
+                }),
+            },
+        }
+    }
+
+    /// Waits for a client to connect to this instance of the server end.
+    ///
+    /// Returns `Ok(())` if a client is connected. Otherwise a "would block"
+    /// error is returned and the pipe becomes writable once a client
+    /// connected, or the wait failed. In both cases the next call to
+    /// `connect` returns the outcome.
+    ///
+    /// If the pipe is not registered yet, the wait starts once it is.
+    pub fn connect(&self) -> io::Result<()> {
+        let mut me = self.inner();
+
+        if let Some(e) = me.connect_error.take() {
+            return Err(e);
+        }
+
+        if me.connected {
+            return Ok(());
+        }
+
+        if me.connecting || me.deferred_connect {
+            return Err(wouldblock());
+        }
+
+        if !me.iocp.registered() {
+            me.deferred_connect = true;
+            return Err(wouldblock());
+        }
+
+        try!(self.imp.schedule_connect(&mut me));
+
+        if me.connected {
+            Ok(())
+        } else {
+            Err(wouldblock())
+        }
+    }
+
+    /// Disconnects this instance of the server end from its client.
+    ///
+    /// Data which was not read yet is discarded. The instance can then wait
+    /// for another client with `connect`.
+    pub fn disconnect(&self) -> io::Result<()> {
+        let mut me = self.inner();
+
+        try!(self.imp.inner.handle.disconnect());
+
+        me.connected = false;
+
+        match me.read {
+            State::Ready(_) | State::Error(_) => me.read = State::Empty,
+            State::Empty | State::Pending(_) => {}
+        }
+
+        me.iocp.set_readiness(Ready::none());
+        Ok(())
+    }
+
+    fn inner(&self) -> MutexGuard<Inner> {
+        self.imp.inner()
+    }
+
+    fn post_register(&self, interest: Ready, me: &mut Inner) {
+        if me.deferred_connect {
+            me.deferred_connect = false;
+
+            if let Err(e) = self.imp.schedule_connect(me) {
+                me.connect_error = Some(e);
+                self.imp.add_readiness(me, Ready::writable());
+            }
+
+            return;
+        }
+
+        if !me.connected {
+            return;
+        }
+
+        if interest.is_readable() {
+            self.imp.schedule_read(me);
+        }
+
+        if interest.is_writable() {
+            if let State::Empty = me.write {
+                self.imp.add_readiness(me, Ready::writable());
+            }
+        }
+    }
+
+    fn read_buf(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut me = self.inner();
+
+        let (data, pos) = match mem::replace(&mut me.read, State::Empty) {
+            State::Empty => {
+                self.imp.schedule_read(&mut me);
+                return Err(wouldblock());
+            }
+            State::Pending(p) => {
+                me.read = State::Pending(p);
+                return Err(wouldblock());
+            }
+            // The other end is gone, keep returning EOF until reconnected
+            State::Error(ref e) if is_eof(e) => {
+                me.read = State::Error(eof());
+                return Ok(0);
+            }
+            State::Error(e) => {
+                self.imp.schedule_read(&mut me);
+                return Err(e);
+            }
+            State::Ready(r) => r,
+        };
+
+        let n = cmp::min(buf.len(), data.len() - pos);
+        buf[..n].copy_from_slice(&data[pos..pos + n]);
+
+        if pos + n == data.len() {
+            me.iocp.put_buffer(data);
+            self.imp.schedule_read(&mut me);
+        } else {
+            me.read = State::Ready((data, pos + n));
+        }
+
+        Ok(n)
+    }
+
+    fn write_buf(&self, buf: &[u8]) -> io::Result<usize> {
+        let mut me = self.inner();
+        let me = &mut *me;
+
+        match mem::replace(&mut me.write, State::Empty) {
+            State::Empty => {}
+            State::Error(e) => return Err(e),
+            s => {
+                me.write = s;
+                return Err(wouldblock());
+            }
+        }
+
+        if !me.iocp.registered() || !me.connected {
+            return Err(wouldblock());
+        }
+
+        let mut intermediate = me.iocp.get_buffer(buf.len());
+        intermediate.extend_from_slice(buf);
+        self.imp.schedule_write(intermediate, 0, me);
+        Ok(buf.len())
+    }
+}
+
+impl Imp {
+    fn inner(&self) -> MutexGuard<Inner> {
+        self.inner.inner.lock().unwrap()
+    }
+
+    fn schedule_connect(&self, me: &mut Inner) -> io::Result<()> {
+        trace!("scheduling a pipe connect");
+        let connected = unsafe {
+            try!(self.inner.handle.connect_overlapped(self.inner.connect.as_mut_ptr()))
+        };
+
+        if connected {
+            me.connected = true;
+            self.post_connect(me);
+        } else {
+            // see docs above on Imp.inner for rationale on forget
+            me.connecting = true;
+            mem::forget(self.clone());
+        }
+
+        Ok(())
+    }
+
+    fn post_connect(&self, me: &mut Inner) {
+        if let State::Error(_) = me.read {
+            me.read = State::Empty;
+        }
+
+        self.add_readiness(me, Ready::writable());
+        self.schedule_read(me);
+    }
+
+    /// Schedule a read into an internal buffer, the pipe becomes readable once
+    /// it completes.
+    fn schedule_read(&self, me: &mut Inner) {
+        match me.read {
+            State::Empty => {}
+            State::Ready(_) | State::Error(_) => {
+                self.add_readiness(me, Ready::readable());
+                return;
+            }
+            State::Pending(_) => return,
+        }
+
+        if !me.iocp.registered() || !me.connected {
+            return;
+        }
+
+        me.iocp.set_readiness(me.iocp.readiness() & !Ready::readable());
+
+        let buf = me.iocp.get_buffer(READ_SIZE);
+        self.read_into(buf, 0, me);
+    }
+
+    /// Reads into `buf` after its first `filled` bytes, growing it to
+    /// `filled + READ_SIZE` bytes if it is shorter.
+    fn read_into(&self, mut buf: Vec<u8>, filled: usize, me: &mut Inner) {
+        if buf.capacity() < filled + READ_SIZE {
+            let additional = filled + READ_SIZE - buf.len();
+            buf.reserve(additional);
+        }
+
+        trace!("scheduling a pipe read");
+        let res = unsafe {
+            let cap = buf.capacity();
+            buf.set_len(cap);
+            self.inner.handle.read_overlapped(&mut buf[filled..], self.inner.read.as_mut_ptr())
+        };
+
+        match res {
+            // A completion packet is queued even if the read completed
+            // immediately
+            Ok(_) => {
+                // see docs above on Imp.inner for rationale on forget
+                me.read = State::Pending((buf, filled));
+                mem::forget(self.clone());
+            }
+            Err(e) => {
+                let set = if is_eof(&e) {
+                    Ready::readable() | Ready::hup()
+                } else {
+                    Ready::readable()
+                };
+
+                me.read = State::Error(e);
+                me.iocp.put_buffer(buf);
+                self.add_readiness(me, set);
+            }
+        }
+    }
+
+    /// Similar to the TCP version, writes all of `buf` after `pos` before the
+    /// pipe becomes writable again.
+    fn schedule_write(&self, buf: Vec<u8>, pos: usize, me: &mut Inner) {
+        // About to write, clear any pending level triggered events
+        me.iocp.set_readiness(me.iocp.readiness() & !Ready::writable());
+
+        trace!("scheduling a pipe write");
+        let res = unsafe {
+            self.inner.handle.write_overlapped(&buf[pos..], self.inner.write.as_mut_ptr())
+        };
+
+        match res {
+            Ok(_) => {
+                // see docs above on Imp.inner for rationale on forget
+                me.write = State::Pending((buf, pos));
+                mem::forget(self.clone());
+            }
+            Err(e) => {
+                me.write = State::Error(e);
+                me.iocp.put_buffer(buf);
+                self.add_readiness(me, Ready::writable());
+            }
+        }
+    }
+
+    fn add_readiness(&self, me: &mut Inner, set: Ready) {
+        me.iocp.set_readiness(set | me.iocp.readiness());
+    }
+}
+
+fn connect_done(status: &OVERLAPPED_ENTRY) {
+    let status = CompletionStatus::from_entry(status);
+    let me2 = Imp {
+        inner: unsafe { overlapped2arc!(status.overlapped(), Io, connect) },
+    };
+
+    let mut me = me2.inner();
+    me.connecting = false;
+
+    trace!("finished a pipe connect");
+    match unsafe { me2.inner.handle.result(status.overlapped()) } {
+        Ok(_) => {
+            me.connected = true;
+            me2.post_connect(&mut me);
+        }
+        Err(e) => {
+            me.connect_error = Some(e);
+            me2.add_readiness(&mut me, Ready::writable());
+        }
+    }
+}
+
+fn read_done(status: &OVERLAPPED_ENTRY) {
+    let status = CompletionStatus::from_entry(status);
+    let me2 = Imp {
+        inner: unsafe { overlapped2arc!(status.overlapped(), Io, read) },
+    };
+
+    let mut me = me2.inner();
+    let (mut buf, filled) = match mem::replace(&mut me.read, State::Empty) {
+        State::Pending(pair) => pair,
+        _ => unreachable!(),
+    };
+
+    let transferred = status.bytes_transferred() as usize;
+    trace!("finished a pipe read: {}", transferred);
+
+    match unsafe { me2.inner.handle.result(status.overlapped()) } {
+        Ok(_) => {
+            unsafe { buf.set_len(filled + transferred); }
+            me.read = State::Ready((buf, 0));
+            me2.add_readiness(&mut me, Ready::readable());
+        }
+        // Only part of a message was read, read the rest into the same buffer
+        Err(ref e) if e.raw_os_error() == Some(ERROR_MORE_DATA as i32) => {
+            unsafe { buf.set_len(filled + transferred); }
+            me2.read_into(buf, filled + transferred, &mut me);
+        }
+        Err(e) => {
+            let set = if is_eof(&e) {
+                Ready::readable() | Ready::hup()
+            } else {
+                Ready::readable()
+            };
+
+            me.read = State::Error(e);
+            me.iocp.put_buffer(buf);
+            me2.add_readiness(&mut me, set);
+        }
+    }
+}
+
+fn write_done(status: &OVERLAPPED_ENTRY) {
+    let status = CompletionStatus::from_entry(status);
+    trace!("finished a pipe write {}", status.bytes_transferred());
+    let me2 = Imp {
+        inner: unsafe { overlapped2arc!(status.overlapped(), Io, write) },
+    };
+
+    let mut me = me2.inner();
+    let (buf, pos) = match mem::replace(&mut me.write, State::Empty) {
+        State::Pending(pair) => pair,
+        _ => unreachable!(),
+    };
+
+    match unsafe { me2.inner.handle.result(status.overlapped()) } {
+        Ok(n) if pos + n < buf.len() => me2.schedule_write(buf, pos + n, &mut me),
+        Ok(_) => {
+            me.iocp.put_buffer(buf);
+            me2.add_readiness(&mut me, Ready::writable());
+        }
+        Err(e) => {
+            me.write = State::Error(e);
+            me.iocp.put_buffer(buf);
+            me2.add_readiness(&mut me, Ready::writable());
+        }
+    }
+}
+
+impl Read for NamedPipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_buf(buf)
+    }
+}
+
+impl<'a> Read for &'a NamedPipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_buf(buf)
+    }
+}
+
+impl Write for NamedPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buf(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Write for &'a NamedPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buf(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Evented for NamedPipe {
+    fn register(&self, poll: &Poll, token: Token,
+                interest: Ready, opts: PollOpt) -> io::Result<()> {
+        let mut me = self.inner();
+        try!(me.iocp.register_handle(&self.imp.inner.handle, poll, token,
+                                     interest, opts, &self.registration));
+        self.post_register(interest, &mut me);
+        Ok(())
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token,
+                  interest: Ready, opts: PollOpt) -> io::Result<()> {
+        let mut me = self.inner();
+        try!(me.iocp.reregister_handle(&self.imp.inner.handle, poll, token,
+                                       interest, opts, &self.registration));
+        self.post_register(interest, &mut me);
+        Ok(())
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.inner().iocp.deregister_handle(&self.imp.inner.handle,
+                                            poll, &self.registration)
+    }
+}
+
+impl AsRawHandle for NamedPipe {
+    fn as_raw_handle(&self) -> HANDLE {
+        self.imp.inner.handle.as_raw_handle()
+    }
+}
+
+impl fmt::Debug for NamedPipe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        "NamedPipe { ... }".fmt(f)
+    }
+}
+
+impl Drop for NamedPipe {
+    fn drop(&mut self) {
+        // Like TCP streams, pending writes are left to complete
+        let me = self.inner();
+
+        if me.connecting {
+            trace!("cancelling active pipe connect");
+            drop(cancel(&self.imp.inner.handle, &self.imp.inner.connect));
+        }
+
+        if let State::Pending(_) = me.read {
+            trace!("cancelling active pipe read");
+            drop(cancel(&self.imp.inner.handle, &self.imp.inner.read));
+        }
+    }
+}
+
+fn cancel(handle: &AsRawHandle, overlapped: &Overlapped) -> io::Result<()> {
+    cvt(unsafe {
+        kernel32::CancelIoEx(handle.as_raw_handle(), overlapped.as_mut_ptr())
+    })
+}
+
+fn cvt(ret: BOOL) -> io::Result<()> {
+    if ret == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain(Some(0)).collect()
+}
+
+/// Whether the error means the other end closed the pipe.
+fn is_eof(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(ERROR_BROKEN_PIPE as i32)
+}
+
+fn eof() -> io::Error {
+    io::Error::from_raw_os_error(ERROR_BROKEN_PIPE as i32)
+}
@@ -0,0 +1,41 @@
+use std::io;
+use std::os::windows::prelude::*;
+use std::process::Child;
+
+use {Evented, Ready, Poll, PollOpt, Token};
+use sys::windows::handle::EventedHandle;
+
+/// Watches a child process for exit.
+///
+/// The process handle is signaled once the process exits, it is waited on as
+/// an `EventedHandle`. Job objects report exits through a completion port,
+/// but their completion packets do not carry an `Overlapped` and cannot be
+/// told apart from I/O completions by the selector.
+#[derive(Debug)]
+pub struct ChildWatcher {
+    handle: EventedHandle,
+}
+
+impl ChildWatcher {
+    /// The process handle stays owned by `child`, which must outlive the
+    /// returned watcher.
+    pub fn new(child: &Child) -> io::Result<ChildWatcher> {
+        Ok(ChildWatcher {
+            handle: unsafe { EventedHandle::new(child.as_raw_handle()) },
+        })
+    }
+}
+
+impl Evented for ChildWatcher {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.handle.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        self.handle.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        self.handle.deregister(poll)
+    }
+}
@@ -50,7 +50,9 @@ struct SelectorInner {
 }
 
 impl Selector {
-    pub fn new() -> io::Result<Selector> {
+    /// Handles are never inherited by child processes unless asked for, so
+    /// there is nothing to do for `cloexec`.
+    pub fn new(_cloexec: bool) -> io::Result<Selector> {
         // offset by 1 to avoid choosing 0 as the id of a selector
         let id = NEXT_ID.fetch_add(1, Ordering::Relaxed) + 1;
 
@@ -65,6 +67,26 @@ impl Selector {
         })
     }
 
+    /// Returns the timeout `select` actually waits for when given `timeout`.
+    ///
+    /// The timeout is rounded up to the next millisecond and capped just
+    /// below `INFINITE`.
+    pub fn effective_timeout(timeout: Duration) -> Duration {
+        Duration::from_millis(cmp::min(convert::millis(timeout), (u32::MAX - 1) as u64))
+    }
+
+    /// Number of events worth requesting per call to `select`. Each dequeued
+    /// completion is processed before `select` returns, which may schedule
+    /// more I/O, so smaller batches keep the latency of a single call down.
+    pub fn recommended_capacity(&self) -> usize {
+        256
+    }
+
+    /// Closed handles are reported through their completion callbacks, there
+    /// is nothing to validate.
+    pub fn set_fd_validation(&self, _enabled: bool) {
+    }
+
     pub fn select(&self,
                   events: &mut Events,
                   awakener: Token,
@@ -74,6 +96,10 @@ impl Selector {
         // Clear out the previous list of I/O events and get some more!
         events.events.truncate(0);
 
+        // `GetQueuedCompletionStatusEx` takes milliseconds and miow rounds
+        // down. Round up here so that short timeouts don't become zero.
+        let timeout = timeout.map(Selector::effective_timeout);
+
         trace!("polling IOCP");
         let n = match self.inner.port.get_many(&mut events.statuses, timeout) {
             Ok(statuses) => statuses.len(),
@@ -353,17 +379,37 @@ impl ReadyBinding {
             try!(self.binding.register_socket(socket, token, poll));
         }
 
-        // To keep the same semantics as epoll, if I/O objects are interested in
-        // being readable then they're also interested in listening for hup
-        let events = if events.is_readable() {
-            events | Ready::hup()
-        }  else {
-            events
-        };
-        let (r, s) = poll::Registration::new(poll, token, events, opts);
+        self.register(poll, token, events, opts, registration);
+        Ok(())
+    }
+
+    /// Same as `register_socket`, but for handles.
+    pub fn register_handle(&mut self,
+                           handle: &AsRawHandle,
+                           poll: &Poll,
+                           token: Token,
+                           events: Ready,
+                           opts: PollOpt,
+                           registration: &Mutex<Option<poll::Registration>>)
+                           -> io::Result<()> {
+        trace!("register {:?} {:?}", token, events);
+        unsafe {
+            try!(self.binding.register_handle(handle, token, poll));
+        }
+
+        self.register(poll, token, events, opts, registration);
+        Ok(())
+    }
+
+    fn register(&mut self,
+                poll: &Poll,
+                token: Token,
+                events: Ready,
+                opts: PollOpt,
+                registration: &Mutex<Option<poll::Registration>>) {
+        let (r, s) = poll::Registration::new(poll, token, with_hup(events), opts);
         self.readiness = Some(s);
         *registration.lock().unwrap() = Some(r);
-        Ok(())
     }
 
     /// Implementation of `Evented::reregister` function.
@@ -380,16 +426,28 @@ impl ReadyBinding {
             try!(self.binding.reregister_socket(socket, token, poll));
         }
 
-        // To keep the same semantics as epoll, if I/O objects are interested in
-        // being readable then they're also interested in listening for hup
-        let events = if events.is_readable() {
-            events | Ready::hup()
-        }  else {
-            events
-        };
         registration.lock().unwrap()
                     .as_mut().unwrap()
-                    .update(poll, token, events, opts)
+                    .update(poll, token, with_hup(events), opts)
+    }
+
+    /// Same as `reregister_socket`, but for handles.
+    pub fn reregister_handle(&mut self,
+                             handle: &AsRawHandle,
+                             poll: &Poll,
+                             token: Token,
+                             events: Ready,
+                             opts: PollOpt,
+                             registration: &Mutex<Option<poll::Registration>>)
+                             -> io::Result<()> {
+        trace!("reregister {:?} {:?}", token, events);
+        unsafe {
+            try!(self.binding.reregister_handle(handle, token, poll));
+        }
+
+        registration.lock().unwrap()
+                    .as_mut().unwrap()
+                    .update(poll, token, with_hup(events), opts)
     }
 
     /// Implementation of the `Evented::deregister` function.
@@ -410,6 +468,28 @@ impl ReadyBinding {
                     .as_ref().unwrap()
                     .deregister(poll)
     }
+
+    /// Same as `deregister`, but for handles.
+    pub fn deregister_handle(&mut self,
+                             handle: &AsRawHandle,
+                             poll: &Poll,
+                             registration: &Mutex<Option<poll::Registration>>)
+                             -> io::Result<()> {
+        trace!("deregistering");
+        unsafe {
+            try!(self.binding.deregister_handle(handle, poll));
+        }
+
+        registration.lock().unwrap()
+                    .as_ref().unwrap()
+                    .deregister(poll)
+    }
+}
+
+// To keep the same semantics as epoll, I/O objects always listen for hup and
+// error, whatever their interest
+fn with_hup(events: Ready) -> Ready {
+    events | Ready::hup() | Ready::error()
 }
 
 fn other(s: &str) -> io::Error {
@@ -452,6 +532,37 @@ impl Events {
         self.events.get(idx).map(|e| *e)
     }
 
+    /// Like `get`, but does not check that `idx` is in bounds.
+    pub unsafe fn get_unchecked(&self, idx: usize) -> Event {
+        *self.events.get_unchecked(idx)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.events.truncate(len);
+    }
+
+    /// The number of completions a single call may dequeue.
+    pub fn capacity(&self) -> usize {
+        self.statuses.len()
+    }
+
+    /// Grows the capacity by `additional` events, keeping the current ones.
+    /// The statuses are only scratch space, they are not carried over.
+    pub fn reserve(&mut self, additional: usize) {
+        let capacity = self.statuses.len() + additional;
+
+        self.statuses = vec![CompletionStatus::zero(); capacity].into_boxed_slice();
+        self.events.reserve(additional);
+    }
+
+    pub fn as_slice(&mut self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn sort_by_token(&mut self) {
+        self.events.sort_by_key(|e| e.token());
+    }
+
     pub fn push_event(&mut self, event: Event) {
         self.events.push(event);
     }
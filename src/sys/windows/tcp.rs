@@ -4,6 +4,7 @@ use std::mem;
 use std::net::{self, SocketAddr};
 use std::os::windows::prelude::*;
 use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 
 use miow;
 use miow::iocp::CompletionStatus;
@@ -124,6 +125,12 @@ impl TcpStream {
         TcpStream::new(stream, None)
     }
 
+    /// The socket cannot be taken out of the `Arc` shared with pending I/O,
+    /// or dissociated from the completion port, a duplicate is returned.
+    pub fn into_std(self) -> io::Result<net::TcpStream> {
+        self.imp.inner.socket.try_clone()
+    }
+
     pub fn peer_addr(&self) -> io::Result<SocketAddr> {
         self.imp.inner.socket.peer_addr()
     }
@@ -156,6 +163,22 @@ impl TcpStream {
         self.imp.inner.socket.keepalive_ms()
     }
 
+    pub fn set_keepalive(&self, keepalive: Option<Duration>) -> io::Result<()> {
+        Net2TcpExt::set_keepalive(&self.imp.inner.socket, keepalive)
+    }
+
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        Net2TcpExt::keepalive(&self.imp.inner.socket)
+    }
+
+    pub fn set_linger(&self, dur: Option<Duration>) -> io::Result<()> {
+        Net2TcpExt::set_linger(&self.imp.inner.socket, dur)
+    }
+
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        Net2TcpExt::linger(&self.imp.inner.socket)
+    }
+
     pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
         self.imp.inner.socket.set_ttl(ttl)
     }
@@ -373,7 +396,7 @@ impl StreamImp {
             Err(e) => {
                 // Like above, be sure to indicate that hup has happened
                 // whenever we get `ECONNRESET`
-                let mut set = Ready::readable();
+                let mut set = Ready::readable() | Ready::error();
                 if e.raw_os_error() == Some(WSAECONNRESET as i32) {
                     trace!("tcp stream at hup: econnreset");
                     set = set | Ready::hup();
@@ -423,7 +446,7 @@ impl StreamImp {
                 }
                 Err(e) => {
                     me.write = State::Error(e);
-                    self.add_readiness(me, Ready::writable());
+                    self.add_readiness(me, Ready::writable() | Ready::error());
                     me.iocp.put_buffer(buf);
                     break;
                 }
@@ -467,7 +490,7 @@ fn read_done(status: &OVERLAPPED_ENTRY) {
             me2.schedule_read(&mut me);
         }
         Err(e) => {
-            me2.add_readiness(&mut me, Ready::readable());
+            me2.add_readiness(&mut me, Ready::readable() | Ready::error());
             me.read = State::Error(e);
         }
     }
@@ -606,6 +629,11 @@ impl TcpListener {
         return ret
     }
 
+    /// See `TcpStream::into_std`.
+    pub fn into_std(self) -> io::Result<net::TcpListener> {
+        self.imp.inner.socket.try_clone()
+    }
+
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.imp.inner.socket.local_addr()
     }
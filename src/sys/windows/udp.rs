@@ -74,6 +74,12 @@ impl UdpSocket {
         })
     }
 
+    /// The socket cannot be taken out of the `Arc` shared with pending I/O,
+    /// or dissociated from the completion port, a duplicate is returned.
+    pub fn into_std(self) -> io::Result<net::UdpSocket> {
+        self.imp.inner.socket.try_clone()
+    }
+
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.imp.inner.socket.local_addr()
     }
@@ -82,6 +88,10 @@ impl UdpSocket {
         self.imp.inner.socket.try_clone().and_then(UdpSocket::new)
     }
 
+    pub fn connect(&self, addr: &SocketAddr) -> io::Result<()> {
+        self.imp.inner.socket.connect(addr)
+    }
+
     /// Note that unlike `TcpStream::write` this function will not attempt to
     /// continue writing `buf` until its entirely written.
     ///
@@ -90,6 +100,21 @@ impl UdpSocket {
     ///       that we don't actually end up writing all of them!
     pub fn send_to(&self, buf: &[u8], target: &SocketAddr)
                    -> io::Result<Option<usize>> {
+        self.schedule_send(buf, |socket, buf, overlapped| unsafe {
+            socket.send_to_overlapped(buf, target, overlapped)
+        })
+    }
+
+    /// The socket must be connected.
+    pub fn send(&self, buf: &[u8]) -> io::Result<Option<usize>> {
+        self.schedule_send(buf, |socket, buf, overlapped| unsafe {
+            socket.send_overlapped(buf, overlapped)
+        })
+    }
+
+    fn schedule_send<F>(&self, buf: &[u8], send: F) -> io::Result<Option<usize>>
+        where F: FnOnce(&net::UdpSocket, &[u8], *mut OVERLAPPED) -> io::Result<Option<usize>>
+    {
         let mut me = self.inner();
         let me = &mut *me;
 
@@ -107,16 +132,19 @@ impl UdpSocket {
 
         let mut owned_buf = me.iocp.get_buffer(64 * 1024);
         let amt = try!(owned_buf.write(buf));
-        try!(unsafe {
-            trace!("scheduling a send");
-            self.imp.inner.socket.send_to_overlapped(&owned_buf, target,
-                                                     self.imp.inner.write.as_mut_ptr())
-        });
+        trace!("scheduling a send");
+        try!(send(&self.imp.inner.socket, &owned_buf, self.imp.inner.write.as_mut_ptr()));
         me.write = State::Pending(owned_buf);
         mem::forget(self.imp.clone());
         Ok(Some(amt))
     }
 
+    /// Reads are always issued with `WSARecvFrom`, which works on connected
+    /// sockets as well.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        self.recv_from(buf).map(|res| res.map(|(n, _)| n))
+    }
+
     pub fn recv_from(&self, mut buf: &mut [u8])
                      -> io::Result<Option<(usize, SocketAddr)>> {
         let mut me = self.inner();
@@ -173,6 +201,22 @@ impl UdpSocket {
         self.imp.inner.socket.set_multicast_ttl_v4(ttl)
     }
 
+    pub fn set_multicast_if_v4(&self, interface: &Ipv4Addr) -> io::Result<()> {
+        UdpSocketExt::set_multicast_if_v4(&self.imp.inner.socket, interface)
+    }
+
+    pub fn set_multicast_if_v6(&self, interface: u32) -> io::Result<()> {
+        UdpSocketExt::set_multicast_if_v6(&self.imp.inner.socket, interface)
+    }
+
+    pub fn multicast_hops_v6(&self) -> io::Result<u32> {
+        UdpSocketExt::multicast_hops_v6(&self.imp.inner.socket)
+    }
+
+    pub fn set_multicast_hops_v6(&self, hops: u32) -> io::Result<()> {
+        UdpSocketExt::set_multicast_hops_v6(&self.imp.inner.socket, hops)
+    }
+
     pub fn multicast_loop_v6(&self) -> io::Result<bool> {
         self.imp.inner.socket.multicast_loop_v6()
     }
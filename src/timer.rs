@@ -1,6 +1,14 @@
 //! Timer optimized for I/O related operations
-
-use {convert, io, Evented, Ready, Poll, PollOpt, Registration, SetReadiness, Token};
+//!
+//! A `Timer` holds many timeouts in a hashed wheel and is registered with
+//! `Poll` like any other handle. It becomes readable once the earliest
+//! timeout expired, the expired timeouts are then returned by `Timer::poll`.
+//!
+//! Readiness is set from a helper thread, which is only started once a
+//! timeout is scheduled on a registered timer, and stops when the timer is
+//! dropped.
+
+use {convert, io, poll, Evented, Ready, Poll, PollOpt, Registration, SetReadiness, Token};
 use lazycell::LazyCell;
 use std::{cmp, error, fmt, u64, usize, iter, thread};
 use std::sync::Arc;
@@ -9,6 +17,10 @@ use std::time::{Duration, Instant};
 
 use self::TimerErrorKind::TimerOverflow;
 
+/// Delivers the state of timeouts once they expire.
+///
+/// The resolution of a timer is its tick duration, timeouts are rounded to
+/// the closest tick.
 pub struct Timer<T> {
     // Size of each tick in milliseconds
     tick_ms: u64,
@@ -50,7 +62,8 @@ struct Inner {
     registration: Registration,
     set_readiness: SetReadiness,
     wakeup_state: WakeupState,
-    wakeup_thread: thread::JoinHandle<()>,
+    // Started by the first scheduled wakeup
+    wakeup_thread: LazyCell<thread::JoinHandle<()>>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -155,6 +168,10 @@ impl<T> Timer<T> {
         }
     }
 
+    /// Schedules `state` to be returned by `poll` once `delay_from_now`
+    /// elapsed.
+    ///
+    /// Fails if the timer already holds as many timeouts as its capacity.
     pub fn set_timeout(&mut self, delay_from_now: Duration, state: T) -> Result<Timeout> {
         let delay_from_start = self.start.elapsed() + delay_from_now;
         self.set_timeout_at(delay_from_start, state)
@@ -205,6 +222,7 @@ impl<T> Timer<T> {
         })
     }
 
+    /// Cancels a timeout, returning its state if it did not expire yet.
     pub fn cancel_timeout(&mut self, timeout: &Timeout) -> Option<T> {
         let links = match self.entries.get(timeout.token) {
             Some(e) => e.links,
@@ -220,6 +238,10 @@ impl<T> Timer<T> {
         self.entries.remove(timeout.token).map(|e| e.state)
     }
 
+    /// Returns the state of the next expired timeout, if any.
+    ///
+    /// Once this returns `None`, the timer is no longer readable until the
+    /// next timeout expires.
     pub fn poll(&mut self) -> Option<T> {
         let target_tick = current_tick(self.start, self.tick_ms);
         self.poll_to(target_tick)
@@ -280,7 +302,7 @@ impl<T> Timer<T> {
         // No more timeouts to poll
         if let Some(inner) = self.inner.borrow() {
             trace!("unsetting readiness");
-            let _ = inner.set_readiness.set_readiness(Ready::none());
+            let _ = poll::set_readiness_unlimited(&inner.set_readiness, Ready::none());
 
             if let Some(tick) = self.next_tick() {
                 self.schedule_readiness(tick);
@@ -328,10 +350,24 @@ impl<T> Timer<T> {
                 let actual = inner.wakeup_state.compare_and_swap(curr, tick as usize, Ordering::Release);
 
                 if actual == curr {
-                    // Signal to the wakeup thread that the wakeup time has
-                    // been changed.
-                    trace!("unparking wakeup thread");
-                    inner.wakeup_thread.thread().unpark();
+                    match inner.wakeup_thread.borrow() {
+                        Some(handle) => {
+                            // Signal to the wakeup thread that the wakeup time
+                            // has been changed.
+                            trace!("unparking wakeup thread");
+                            handle.thread().unpark();
+                        }
+                        None => {
+                            // The thread reads the wakeup time when it starts
+                            trace!("starting wakeup thread");
+                            let handle = spawn_wakeup_thread(
+                                inner.wakeup_state.clone(),
+                                inner.set_readiness.clone(),
+                                self.start, self.tick_ms);
+                            let _ = inner.wakeup_thread.fill(handle);
+                        }
+                    }
+
                     return;
                 }
 
@@ -371,18 +407,14 @@ impl<T> Evented for Timer<T> {
             return Err(io::Error::new(io::ErrorKind::Other, "timer already registered"));
         }
 
-        let (registration, set_readiness) = Registration::new(poll, token, interest, opts);
+        let (registration, set_readiness) = try!(Registration::try_new(poll, token, interest, opts));
         let wakeup_state = Arc::new(AtomicUsize::new(usize::MAX));
-        let thread_handle = spawn_wakeup_thread(
-            wakeup_state.clone(),
-            set_readiness.clone(),
-            self.start, self.tick_ms);
 
         self.inner.fill(Inner {
             registration: registration,
             set_readiness: set_readiness,
             wakeup_state: wakeup_state,
-            wakeup_thread: thread_handle,
+            wakeup_thread: LazyCell::new(),
         }).ok().expect("timer already registered");
 
         if let Some(next_tick) = self.next_tick() {
@@ -407,6 +439,17 @@ impl<T> Evented for Timer<T> {
     }
 }
 
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Stop the wakeup thread, it would otherwise outlive the timer
+        self.wakeup_state.store(TERMINATE_THREAD, Ordering::Release);
+
+        if let Some(handle) = self.wakeup_thread.borrow() {
+            handle.thread().unpark();
+        }
+    }
+}
+
 impl fmt::Debug for Inner {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Inner")
@@ -452,7 +495,7 @@ fn spawn_wakeup_thread(state: WakeupState, set_readiness: SetReadiness, start: I
 
                 if actual == sleep_until_tick {
                     trace!("setting readiness from wakeup thread");
-                    let _ = set_readiness.set_readiness(Ready::readable());
+                    let _ = poll::set_readiness_unlimited(&set_readiness, Ready::readable());
                     sleep_until_tick = usize::MAX as Tick;
                 } else {
                     sleep_until_tick = actual as Tick;
@@ -680,6 +723,18 @@ mod test {
         assert_eq!(0, count(&t));
     }
 
+    #[test]
+    pub fn test_wakeup_thread_started_by_first_timeout() {
+        let poll = Poll::new().unwrap();
+        let mut t = timer();
+
+        poll.register(&t, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+        assert!(t.inner.borrow().unwrap().wakeup_thread.borrow().is_none());
+
+        t.set_timeout(Duration::from_millis(100), "a").unwrap();
+        assert!(t.inner.borrow().unwrap().wakeup_thread.borrow().is_some());
+    }
+
     const TICK: u64 = 100;
     const SLOTS: usize = 16;
     const CAPACITY: usize = 32;
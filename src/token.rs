@@ -1,8 +1,31 @@
+use poll;
+
 /// Used to identify which `Evented` value is associated with an `Event`
 /// notification returned by `Poll`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Token(pub usize);
 
+impl Token {
+    /// Returns `Token(self.0 + n)`, or `None` if the addition overflows.
+    ///
+    /// The result may be reserved, check it with `is_reserved` before using
+    /// it.
+    pub fn checked_add(self, n: usize) -> Option<Token> {
+        self.0.checked_add(n).map(Token)
+    }
+
+    /// Returns true if the token is reserved for internal use and must not
+    /// be used to register a handle.
+    ///
+    /// The reserved tokens are at the top of the `usize` range: the token
+    /// used to wake up `Poll`, the tokens handed out by
+    /// `Poll::with_wake_tokens` and a few more kept for internal use. Token
+    /// allocators should stop below the first reserved token.
+    pub fn is_reserved(&self) -> bool {
+        poll::is_reserved_token(*self)
+    }
+}
+
 impl From<usize> for Token {
     fn from(val: usize) -> Token {
         Token(val)
@@ -14,3 +37,140 @@ impl From<Token> for usize {
         val.0
     }
 }
+
+#[cfg(target_pointer_width = "64")]
+const INDEX_BITS: usize = 48;
+#[cfg(target_pointer_width = "32")]
+const INDEX_BITS: usize = 24;
+
+const GENERATION_BITS: usize = ::std::mem::size_of::<usize>() * 8 - INDEX_BITS;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+
+// The all-ones generation is skipped, so that allocated tokens never reach
+// the reserved range at the top of `usize`
+const MAX_GENERATION: usize = (1 << GENERATION_BITS) - 2;
+
+/// Hands out unique tokens, and detects events for recycled tokens.
+///
+/// A token is made of an index and a generation. The index is reused once
+/// the token is released, which keeps the indices dense: use `index` to look
+/// up the state associated with a token in a `Vec` or a slab. The generation
+/// is incremented every time an index is released, so that a token handed
+/// out before the release no longer matches.
+///
+/// This matters when a handle is deregistered and its token recycled while
+/// events for it are still in flight, e.g. already returned by the same call
+/// to `Poll::poll`. Check events with `contains` and drop the ones for stale
+/// tokens.
+///
+/// On 64-bit platforms, the index has 48 bits and the generation 16 bits. On
+/// 32-bit platforms, the index has 24 bits and the generation 8 bits. The
+/// generation wraps around, so a stale token is only detected until its
+/// index has been recycled that many times. Allocated tokens are never
+/// reserved.
+#[derive(Debug, Clone, Default)]
+pub struct TokenAllocator {
+    // Current generation of each index, and whether it is allocated
+    slots: Vec<Slot>,
+    // Released indices, reused first
+    free: Vec<usize>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    generation: usize,
+    allocated: bool,
+}
+
+impl TokenAllocator {
+    /// Returns an empty allocator.
+    pub fn new() -> TokenAllocator {
+        TokenAllocator::default()
+    }
+
+    /// Returns an empty allocator with room for `capacity` tokens before
+    /// reallocating.
+    pub fn with_capacity(capacity: usize) -> TokenAllocator {
+        TokenAllocator {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Allocates a new token.
+    ///
+    /// Returns `None` once all indices are allocated.
+    pub fn allocate(&mut self) -> Option<Token> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = self.slots.len();
+
+                if index > INDEX_MASK {
+                    return None;
+                }
+
+                self.slots.push(Slot { generation: 0, allocated: false });
+                index
+            }
+        };
+
+        let slot = &mut self.slots[index];
+        slot.allocated = true;
+        self.len += 1;
+
+        Some(Token(slot.generation << INDEX_BITS | index))
+    }
+
+    /// Releases `token`, so that its index can be reused.
+    ///
+    /// Returns false if the token is not allocated, e.g. it was already
+    /// released.
+    pub fn release(&mut self, token: Token) -> bool {
+        if !self.contains(token) {
+            return false;
+        }
+
+        let index = TokenAllocator::index(token);
+        let slot = &mut self.slots[index];
+
+        slot.allocated = false;
+        slot.generation = if slot.generation == MAX_GENERATION { 0 } else { slot.generation + 1 };
+
+        self.free.push(index);
+        self.len -= 1;
+
+        true
+    }
+
+    /// Returns true if `token` is allocated, and was not recycled since.
+    ///
+    /// Events for tokens that are not contained are stale and should be
+    /// dropped.
+    pub fn contains(&self, token: Token) -> bool {
+        match self.slots.get(TokenAllocator::index(token)) {
+            Some(slot) => slot.allocated && slot.generation == token.0 >> INDEX_BITS,
+            None => false,
+        }
+    }
+
+    /// Returns the index of `token`.
+    ///
+    /// Indices start at 0 and are reused once released, they are suited to
+    /// index the state associated with the tokens.
+    pub fn index(token: Token) -> usize {
+        token.0 & INDEX_MASK
+    }
+
+    /// Returns the number of allocated tokens.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no token is allocated.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
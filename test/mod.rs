@@ -4,19 +4,29 @@ extern crate bytes;
 #[macro_use]
 extern crate log;
 extern crate env_logger;
+#[cfg(unix)]
+extern crate libc;
 extern crate slab;
 extern crate tempdir;
 
 pub use ports::localhost;
 
+mod test_benchmarks;
+mod test_child_watcher;
 mod test_close_on_drop;
+mod test_deadline;
+mod test_debounce;
 mod test_double_register;
 mod test_echo_server;
 mod test_local_addr_ready;
 mod test_multicast;
 mod test_notify;
 mod test_oneshot;
+mod test_poll;
 mod test_poll_channel;
+mod test_poll_opt;
+mod test_poll_shutdown;
+mod test_ready;
 mod test_register_deregister;
 mod test_register_multiple_event_loops;
 mod test_reregister_without_poll;
@@ -24,10 +34,19 @@ mod test_smoke;
 mod test_tcp;
 mod test_tcp_level;
 mod test_tick;
+mod test_token;
 mod test_udp_level;
 mod test_udp_socket;
 mod test_write_then_drop;
 
+#[cfg(unix)]
+mod test_uds;
+
+#[cfg(windows)]
+mod test_evented_handle;
+#[cfg(windows)]
+mod test_named_pipe;
+
 // The following tests are for deprecated features. Only run these tests on
 // platforms that were supported from before the features were deprecated
 #[cfg(any(target_os = "macos", target_os = "linux", target_os = "windows"))]
@@ -45,6 +64,14 @@ mod test_uds_shutdown;
 mod test_subprocess_pipe;
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 mod test_broken_pipe;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+mod test_register_closed_fd;
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+mod test_kqueue_timer;
+
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+mod test_uring;
 
 use bytes::{Buf, MutBuf};
 use std::io::{self, Read, Write};
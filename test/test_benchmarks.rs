@@ -0,0 +1,223 @@
+//! Micro benchmarks. These are ignored by default, run them with:
+//!
+//! cargo test --release -- --ignored --nocapture bench_
+
+use mio::*;
+use std::time::{Duration, Instant};
+
+fn timed<F: FnMut()>(label: &str, mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+    println!("  {}: {}", label, elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0);
+    elapsed
+}
+
+fn ready_events(count: usize) -> (Poll, Events, Vec<Registration>) {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(count);
+    let mut registrations = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let (r, s) = Registration::new(&poll, Token(i), Ready::readable(), PollOpt::level());
+        s.set_readiness(Ready::readable()).unwrap();
+        registrations.push(r);
+    }
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(count, events.len());
+
+    (poll, events, registrations)
+}
+
+#[test]
+#[ignore]
+fn bench_events_get_checked_vs_unchecked() {
+    const ITERS: usize = 10_000;
+    let (_poll, events, _registrations) = ready_events(1024);
+
+    let mut sum = 0;
+    timed("get", || {
+        for _ in 0..ITERS {
+            for i in 0..events.len() {
+                sum += usize::from(events.get(i).unwrap().token());
+            }
+        }
+    });
+
+    let mut sum_unchecked = 0;
+    timed("get_unchecked", || {
+        for _ in 0..ITERS {
+            for i in 0..events.len() {
+                sum_unchecked += usize::from(unsafe { events.get_unchecked(i) }.token());
+            }
+        }
+    });
+
+    assert_eq!(sum, sum_unchecked);
+}
+
+#[test]
+#[ignore]
+fn bench_zero_timeout_poll() {
+    const ITERS: usize = 100_000;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    timed("empty", || {
+        for _ in 0..ITERS {
+            poll.poll(&mut events, Duration::from_millis(0)).unwrap();
+        }
+    });
+
+    // One level triggered registration keeps the readiness queue busy
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::level());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    timed("one ready registration", || {
+        for _ in 0..ITERS {
+            poll.poll(&mut events, Duration::from_millis(0)).unwrap();
+        }
+    });
+}
+
+#[test]
+#[ignore]
+fn bench_first_wakeup_latency() {
+    use std::thread;
+
+    const ITERS: usize = 100;
+
+    // Time from `set_readiness` on another thread until `poll` returns, for
+    // the first wakeup of a fresh `Poll`
+    fn first_wakeup(prime: bool) -> Duration {
+        let poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(1024);
+
+        if prime {
+            poll.prime_awakener().unwrap();
+        }
+
+        let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+        let th = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(5));
+            let start = Instant::now();
+            set_readiness.set_readiness(Ready::readable()).unwrap();
+            start
+        });
+
+        loop {
+            poll.poll(&mut events, None).unwrap();
+
+            if !events.is_empty() {
+                break;
+            }
+        }
+
+        let end = Instant::now();
+        end - th.join().unwrap()
+    }
+
+    let mut cold = Duration::from_millis(0);
+    let mut primed = Duration::from_millis(0);
+
+    timed("total", || {
+        for _ in 0..ITERS {
+            cold += first_wakeup(false);
+            primed += first_wakeup(true);
+        }
+    });
+
+    println!("  cold: {:?} / wakeup", cold / ITERS as u32);
+    println!("  primed: {:?} / wakeup", primed / ITERS as u32);
+}
+
+#[test]
+#[ignore]
+fn bench_spin_wakeup_latency() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    const ITERS: usize = 1_000;
+
+    // Time from `set_readiness` on another thread until `poll` returns. The
+    // spinning `Poll` keeps a core busy while it waits, which is the price
+    // for the lower latency; with a single core it competes with the
+    // producer instead.
+    fn wakeup_latency(spin_iters: usize) -> Duration {
+        let poll = Poll::new_with_spin(spin_iters).unwrap();
+        let mut events = Events::with_capacity(1024);
+        let seen = Arc::new(AtomicUsize::new(0));
+
+        let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+        let th = {
+            let seen = seen.clone();
+
+            thread::spawn(move || {
+                let mut total = Duration::from_millis(0);
+
+                for i in 0..ITERS {
+                    thread::sleep(Duration::from_micros(50));
+
+                    let start = Instant::now();
+                    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+                    while seen.load(Ordering::Acquire) == i {
+                        thread::yield_now();
+                    }
+
+                    total += start.elapsed();
+                }
+
+                total
+            })
+        };
+
+        for i in 0..ITERS {
+            loop {
+                poll.poll(&mut events, None).unwrap();
+
+                if !events.is_empty() {
+                    break;
+                }
+            }
+
+            seen.store(i + 1, Ordering::Release);
+        }
+
+        th.join().unwrap()
+    }
+
+    for &spin_iters in &[0, 100, 10_000] {
+        let latency = wakeup_latency(spin_iters);
+        println!("  spin {}: {:?} / wakeup", spin_iters, latency / ITERS as u32);
+    }
+}
+
+#[test]
+#[ignore]
+fn bench_poll_no_userspace() {
+    const ITERS: usize = 100_000;
+
+    let mut events = Events::with_capacity(1024);
+
+    for &(label, userspace) in &[("default", true), ("no userspace", false)] {
+        let poll = if userspace {
+            Poll::new().unwrap()
+        } else {
+            Poll::new_no_userspace().unwrap()
+        };
+
+        let elapsed = timed(label, || {
+            for _ in 0..ITERS {
+                poll.poll(&mut events, Duration::from_millis(0)).unwrap();
+            }
+        });
+
+        println!("  {}: {:?} / poll", label, elapsed / ITERS as u32);
+    }
+}
@@ -0,0 +1,71 @@
+use mio::*;
+use mio::process::ChildWatcher;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const CHILD: Token = Token(0);
+
+#[cfg(unix)]
+fn exit_with(code: u32, delay: u32) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(format!("sleep {}; exit {}", delay, code));
+    cmd
+}
+
+#[cfg(windows)]
+fn exit_with(code: u32, delay: u32) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(format!("ping -n {} 127.0.0.1 >NUL & exit {}", delay + 1, code));
+    cmd
+}
+
+fn wait_for_exit(poll: &Poll, events: &mut Events) {
+    for _ in 0..20 {
+        poll.poll(events, Some(Duration::from_millis(500))).unwrap();
+
+        for event in events.iter() {
+            if event.token() == CHILD && event.kind().is_readable() {
+                return;
+            }
+        }
+    }
+
+    panic!("child exit was never reported");
+}
+
+#[test]
+pub fn test_child_watcher_exit() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let child = exit_with(3, 1).stdout(Stdio::null()).spawn().unwrap();
+    let mut watcher = ChildWatcher::new(child).unwrap();
+    poll.register(&watcher, CHILD, Ready::readable(), PollOpt::edge()).unwrap();
+
+    // The child is still sleeping
+    assert!(watcher.try_wait().unwrap().is_none());
+
+    wait_for_exit(&poll, &mut events);
+
+    let status = watcher.try_wait().unwrap().unwrap();
+    assert_eq!(Some(3), status.code());
+}
+
+#[test]
+pub fn test_child_watcher_already_exited() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let child = exit_with(0, 0).stdout(Stdio::null()).spawn().unwrap();
+
+    // Wait for the exit without reaping the child
+    let id = child.id();
+    let mut watcher = ChildWatcher::new(child).unwrap();
+    assert_eq!(id, watcher.id());
+    ::std::thread::sleep(Duration::from_millis(500));
+
+    poll.register(&watcher, CHILD, Ready::readable(), PollOpt::edge()).unwrap();
+    wait_for_exit(&poll, &mut events);
+
+    assert!(watcher.try_wait().unwrap().unwrap().success());
+}
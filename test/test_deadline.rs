@@ -0,0 +1,214 @@
+use mio::*;
+use mio::tcp::TcpListener;
+use mio::udp::UdpSocket;
+use std::time::{Duration, Instant};
+use localhost;
+
+#[test]
+pub fn test_deadline_fires() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let listener = TcpListener::bind(&localhost()).unwrap();
+    let start = Instant::now();
+
+    poll.register_with_deadline(&listener, Token(1), Ready::readable(), PollOpt::edge(),
+                                start + Duration::from_millis(50)).unwrap();
+
+    // The deadline wakes up a poll blocking with no timeout
+    assert_eq!(1, poll.poll(&mut events, None).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    let event = events.get(0).unwrap();
+    assert_eq!(Token(1), event.token());
+    assert!(event.is_timeout());
+    assert!(!event.is_readable());
+
+    // Deadlines are one-shot
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap());
+    assert!(!poll.clear_deadline(Token(1)));
+}
+
+#[test]
+pub fn test_deadline_disarmed_by_readiness() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&localhost()).unwrap();
+
+    poll.register_with_deadline(&sock, Token(1), Ready::writable(), PollOpt::edge(),
+                                Instant::now() + Duration::from_millis(50)).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+    assert!(events.get(0).unwrap().is_writable());
+    assert!(!events.get(0).unwrap().is_timeout());
+
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(150))).unwrap());
+}
+
+#[test]
+pub fn test_deadline_poll_timeout() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let start = Instant::now();
+
+    poll.set_deadline(Token(1), start + Duration::from_millis(100)).unwrap();
+
+    // A shorter timeout still applies
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(20))).unwrap());
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    assert_eq!(1, poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap());
+    assert!(events.get(0).unwrap().is_timeout());
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+pub fn test_deadline_clear_and_rearm() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let now = Instant::now();
+
+    poll.set_deadline(Token(1), now + Duration::from_millis(20)).unwrap();
+    assert!(poll.clear_deadline(Token(1)));
+    assert!(!poll.clear_deadline(Token(1)));
+
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(50))).unwrap());
+
+    // Rearming replaces the previous deadline
+    poll.set_deadline(Token(2), now).unwrap();
+    poll.set_deadline(Token(2), Instant::now() + Duration::from_millis(30)).unwrap();
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+
+    assert_eq!(1, poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap());
+    assert_eq!(Token(2), events.get(0).unwrap().token());
+}
+
+#[test]
+pub fn test_deadline_order() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let now = Instant::now();
+
+    poll.set_deadline(Token(1), now + Duration::from_millis(60)).unwrap();
+    poll.set_deadline(Token(2), now + Duration::from_millis(20)).unwrap();
+    poll.set_deadline(Token(3), now + Duration::from_millis(40)).unwrap();
+
+    let mut fired = Vec::new();
+
+    while fired.len() < 3 {
+        poll.poll(&mut events, None).unwrap();
+
+        for event in &events {
+            assert!(event.is_timeout());
+            fired.push(event.token());
+        }
+    }
+
+    assert_eq!(vec![Token(2), Token(3), Token(1)], fired);
+}
+
+#[test]
+pub fn test_deadline_invalid_token() {
+    let poll = Poll::new().unwrap();
+
+    assert!(poll.set_deadline(Token(::std::usize::MAX), Instant::now()).is_err());
+
+    // Every reserved token is rejected, not only the awakener's
+    let token = Token(::std::usize::MAX - 0xff);
+    assert!(token.is_reserved());
+    assert!(poll.set_deadline(token, Instant::now()).is_err());
+}
+
+#[test]
+pub fn test_deadline_past_revolution() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let start = Instant::now();
+
+    // Further than a revolution of the wheel, the poll wakes up early
+    // internally but only returns once the deadline fires
+    poll.set_deadline(Token(1), start + Duration::from_millis(2500)).unwrap();
+
+    assert_eq!(1, poll.poll(&mut events, None).unwrap());
+    assert!(events.get(0).unwrap().is_timeout());
+    assert!(start.elapsed() >= Duration::from_millis(2500));
+}
+
+#[test]
+pub fn test_deadline_single_wait() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let start = Instant::now();
+
+    poll.set_deadline(Token(1), start + Duration::from_millis(2500)).unwrap();
+
+    // The wheel cuts the wait short at the end of its revolution, the
+    // single wait returns then
+    assert_eq!(0, poll.poll_once_or_timeout(&mut events, Duration::from_secs(5)).unwrap());
+    assert!(start.elapsed() < Duration::from_millis(2500));
+
+    assert_eq!(1, poll.poll(&mut events, None).unwrap());
+    assert_eq!(Event::new(Ready::timeout(), Token(1)), events.get(0).unwrap());
+}
+
+#[test]
+pub fn test_deadline_events_capacity() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(2);
+    let now = Instant::now();
+
+    for i in 0..5 {
+        poll.set_deadline(Token(i), now).unwrap();
+    }
+
+    // The expired deadlines that don't fit stay armed for the next calls
+    let mut fired = Vec::new();
+
+    for &expected in &[2, 2, 1] {
+        assert_eq!(expected, poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+        fired.extend(events.iter().map(|event| event.token()));
+    }
+
+    fired.sort();
+    assert_eq!((0..5).map(Token).collect::<Vec<_>>(), fired);
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_deadline_poll_with_extra_fds() {
+    use std::os::unix::io::AsRawFd;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let idle = UdpSocket::bind(&localhost()).unwrap();
+    let start = Instant::now();
+
+    poll.set_deadline(Token(1), start + Duration::from_millis(50)).unwrap();
+
+    // The deadline wakes up the wait on the extra fds too
+    let mut extra = [(idle.as_raw_fd(), Ready::readable())];
+    assert_eq!(1, poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_secs(5)).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    assert_eq!(Event::new(Ready::timeout(), Token(1)), events.get(0).unwrap());
+    assert_eq!(Ready::none(), extra[0].1);
+}
+
+#[test]
+pub fn test_deadline_poll_split_past_revolution() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let start = Instant::now();
+
+    poll.set_deadline(Token(1), start + Duration::from_millis(2500)).unwrap();
+
+    // Like `poll`, the split counts are not cut short by the wheel
+    assert_eq!((0, 1), poll.poll_split(&mut events, Duration::from_secs(5)).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(2500));
+    assert!(events.get(0).unwrap().is_timeout());
+}
@@ -0,0 +1,109 @@
+use mio::*;
+use mio::debounce::{Debounce, DebounceMode};
+use mio::udp::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn burst(target: &UdpSocket, count: usize, interval: Duration) {
+    let sender = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = target.local_addr().unwrap();
+
+    for i in 0..count {
+        if i > 0 {
+            thread::sleep(interval);
+        }
+
+        sender.send_to(b"x", &addr).unwrap();
+    }
+}
+
+fn drain(sock: &UdpSocket) -> usize {
+    let mut buf = [0; 16];
+    let mut n = 0;
+
+    while let Ok(Some(_)) = sock.recv_from(&mut buf) {
+        n += 1;
+    }
+
+    n
+}
+
+#[test]
+pub fn test_debounce_extend() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let window = Duration::from_millis(100);
+    let debounce = Debounce::new(sock, Ready::readable(), window, DebounceMode::Extend).unwrap();
+
+    poll.register(&debounce, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+
+    let start = Instant::now();
+    burst(debounce.get_ref(), 5, Duration::from_millis(20));
+    let burst_end = Instant::now();
+
+    // Nothing until the burst has been quiet for the whole window
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(0)), events.get(0).unwrap());
+    assert!(Instant::now() >= burst_end + window - Duration::from_millis(20));
+    assert!(start.elapsed() >= Duration::from_millis(180));
+
+    assert_eq!(5, drain(debounce.get_ref()));
+
+    // A single event for the whole burst
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+pub fn test_debounce_fixed() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let window = Duration::from_millis(100);
+    let debounce = Debounce::new(sock, Ready::readable(), window, DebounceMode::Fixed).unwrap();
+
+    poll.register(&debounce, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+
+    // The burst lasts longer than the window, the first event is delivered
+    // before it ends
+    let sock = debounce.get_ref().try_clone().unwrap();
+    let th = thread::spawn(move || burst(&sock, 10, Duration::from_millis(30)));
+
+    let start = Instant::now();
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+    assert!(start.elapsed() < Duration::from_millis(280));
+
+    th.join().unwrap();
+}
+
+#[test]
+pub fn test_debounce_level_and_registration() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let debounce = Debounce::new(sock, Ready::readable(), Duration::from_millis(20), DebounceMode::Extend).unwrap();
+
+    assert!(poll.reregister(&debounce, Token(0), Ready::readable(), PollOpt::level()).is_err());
+    poll.register(&debounce, Token(0), Ready::readable(), PollOpt::level()).unwrap();
+    assert!(poll.register(&debounce, Token(0), Ready::readable(), PollOpt::level()).is_err());
+
+    burst(debounce.get_ref(), 1, Duration::from_millis(0));
+
+    // Level-triggered, reported until cleared
+    for _ in 0..2 {
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(1, events.len());
+    }
+
+    debounce.clear_readiness().unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+    assert!(events.is_empty());
+
+    drop(debounce);
+}
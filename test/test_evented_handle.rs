@@ -0,0 +1,45 @@
+use mio::*;
+use mio::windows::EventedHandle;
+use std::os::windows::io::AsRawHandle;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const HANDLE: Token = Token(0);
+
+fn wait_for_signal(poll: &Poll, events: &mut Events) {
+    for _ in 0..20 {
+        poll.poll(events, Some(Duration::from_millis(500))).unwrap();
+
+        for event in events.iter() {
+            if event.token() == HANDLE && event.kind().is_readable() {
+                return;
+            }
+        }
+    }
+
+    panic!("handle was never signaled");
+}
+
+#[test]
+pub fn test_evented_handle_signaled() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    // A process handle is signaled once the process exits
+    let mut child = Command::new("cmd")
+        .arg("/C").arg("ping -n 2 127.0.0.1 >NUL")
+        .stdout(Stdio::null())
+        .spawn().unwrap();
+
+    let handle = unsafe { EventedHandle::new(child.as_raw_handle()) };
+    poll.register(&handle, HANDLE, Ready::readable(), PollOpt::edge()).unwrap();
+
+    wait_for_signal(&poll, &mut events);
+
+    // The process handle stays signaled, waiting again completes right away
+    poll.reregister(&handle, HANDLE, Ready::readable(), PollOpt::edge()).unwrap();
+    wait_for_signal(&poll, &mut events);
+
+    drop(handle);
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,64 @@
+use mio::*;
+use mio::unix::KqueueTimer;
+use std::time::{Duration, Instant};
+
+#[test]
+pub fn test_kqueue_timer_oneshot() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let timer = KqueueTimer::oneshot(Duration::from_millis(100));
+    let start = Instant::now();
+
+    poll.register(&timer, Token(1), Ready::readable(), PollOpt::edge()).unwrap();
+
+    let num = poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, num);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    let event = events.get(0).unwrap();
+    assert_eq!(event.token(), Token(1));
+    assert!(event.kind().is_readable());
+
+    // A oneshot timer does not fire again
+    let num = poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(0, num);
+}
+
+#[test]
+pub fn test_kqueue_timer_periodic() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let timer = KqueueTimer::periodic(Duration::from_millis(50));
+    poll.register(&timer, Token(2), Ready::readable(), PollOpt::edge()).unwrap();
+
+    for _ in 0..3 {
+        let num = poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(1, num);
+        assert_eq!(events.get(0).unwrap().token(), Token(2));
+    }
+
+    poll.deregister(&timer).unwrap();
+
+    let num = poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(0, num);
+}
+
+#[test]
+pub fn test_kqueue_timer_drop_removes_timer() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let timer = KqueueTimer::periodic(Duration::from_millis(50));
+    poll.register(&timer, Token(3), Ready::readable(), PollOpt::edge()).unwrap();
+
+    let num = poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, num);
+
+    // Dropping the handle deletes the kqueue timer, it stops firing
+    drop(timer);
+
+    let num = poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(0, num);
+}
@@ -102,3 +102,18 @@ pub fn test_multicast() {
     info!("Starting event loop to test with...");
     event_loop.run(&mut UdpHandler::new(tx, rx, "hello world")).unwrap();
 }
+
+#[test]
+pub fn test_multicast_interface_options() {
+    let any = "0.0.0.0:0".parse().unwrap();
+    let tx = UdpSocket::bind(&any).unwrap();
+
+    let loopback = Ipv4Addr::new(127, 0, 0, 1);
+    tx.set_multicast_if_v4(&loopback).unwrap();
+
+    tx.set_multicast_ttl_v4(4).unwrap();
+    assert_eq!(4, tx.multicast_ttl_v4().unwrap());
+
+    tx.set_multicast_loop_v4(false).unwrap();
+    assert!(!tx.multicast_loop_v4().unwrap());
+}
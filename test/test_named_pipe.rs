@@ -0,0 +1,101 @@
+use mio::*;
+use mio::windows::NamedPipe;
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SERVER: Token = Token(0);
+const CLIENT: Token = Token(1);
+
+fn name(test: &str) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+    format!(r"\\.\pipe\mio-test-{}-{}", test, nanos)
+}
+
+fn wait_for(poll: &Poll, events: &mut Events, token: Token, ready: Ready) {
+    for _ in 0..10 {
+        poll.poll(events, Some(Duration::from_millis(500))).unwrap();
+
+        for event in events.iter() {
+            if event.token() == token && event.kind().contains(ready) {
+                return;
+            }
+        }
+    }
+
+    panic!("{:?} never became {:?}", token, ready);
+}
+
+fn read_retry(poll: &Poll, events: &mut Events, pipe: &NamedPipe, token: Token,
+              buf: &mut [u8]) -> usize {
+    loop {
+        match (&*pipe).read(buf) {
+            Ok(n) => return n,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                wait_for(poll, events, token, Ready::readable());
+            }
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+}
+
+#[test]
+pub fn test_named_pipe_connect() {
+    let name = name("connect");
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let server = NamedPipe::new(&name).unwrap();
+
+    // The wait for a client only starts once registered
+    assert_eq!(io::ErrorKind::WouldBlock, server.connect().unwrap_err().kind());
+    poll.register(&server, SERVER, Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
+
+    let client = NamedPipe::open(&name).unwrap();
+    poll.register(&client, CLIENT, Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
+
+    wait_for(&poll, &mut events, SERVER, Ready::writable());
+    server.connect().unwrap();
+
+    assert_eq!(5, (&client).write(b"hello").unwrap());
+
+    let mut buf = [0; 16];
+    assert_eq!(5, read_retry(&poll, &mut events, &server, SERVER, &mut buf));
+    assert_eq!(b"hello", &buf[..5]);
+
+    // The client is gone, reads return EOF
+    drop(client);
+    assert_eq!(0, read_retry(&poll, &mut events, &server, SERVER, &mut buf));
+
+    server.disconnect().unwrap();
+}
+
+#[test]
+pub fn test_named_pipe_message() {
+    let name = name("message");
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let server = NamedPipe::new_message(&name).unwrap();
+    poll.register(&server, SERVER, Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
+    let _ = server.connect();
+
+    let client = NamedPipe::open(&name).unwrap();
+    poll.register(&client, CLIENT, Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
+
+    wait_for(&poll, &mut events, CLIENT, Ready::writable());
+    (&client).write(b"first").unwrap();
+    wait_for(&poll, &mut events, CLIENT, Ready::writable());
+    (&client).write(b"second").unwrap();
+
+    // Reads stop at message boundaries
+    let mut buf = [0; 16];
+    assert_eq!(5, read_retry(&poll, &mut events, &server, SERVER, &mut buf));
+    assert_eq!(b"first", &buf[..5]);
+
+    // A message longer than the buffer is returned by consecutive reads
+    let mut small = [0; 4];
+    assert_eq!(4, read_retry(&poll, &mut events, &server, SERVER, &mut small));
+    assert_eq!(b"seco", &small);
+    assert_eq!(2, read_retry(&poll, &mut events, &server, SERVER, &mut small));
+    assert_eq!(b"nd", &small[..2]);
+}
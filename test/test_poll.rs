@@ -0,0 +1,2138 @@
+use mio::*;
+use mio::udp::UdpSocket;
+use std::time::Duration;
+
+#[test]
+pub fn test_poll_split() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    // A UDP socket is writable straight away
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (_r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    let (_r2, s2) = Registration::new(&poll, Token(2), Ready::readable(), PollOpt::edge());
+    s1.set_readiness(Ready::readable()).unwrap();
+    s2.set_readiness(Ready::readable()).unwrap();
+
+    let (sys, user) = poll.poll_split(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, sys);
+    assert_eq!(2, user);
+    assert_eq!(3, events.len());
+
+    let (sys, user) = poll.poll_split(&mut events, Duration::from_millis(0)).unwrap();
+    assert_eq!((0, 0), (sys, user));
+}
+
+#[test]
+pub fn test_readiness_group() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let mut group = ReadinessGroup::new();
+    let mut registrations = vec![];
+
+    for i in 0..4 {
+        let (r, s) = Registration::new(&poll, Token(i), Ready::readable(), PollOpt::edge());
+        registrations.push(r);
+        group.push(s).unwrap();
+    }
+
+    // Dropped members never yield events
+    drop(registrations.pop());
+
+    group.set_all(Ready::readable()).unwrap();
+
+    let num = poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(3, num);
+
+    let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(tokens, vec![Token(0), Token(1), Token(2)]);
+
+    // Handles from another poll are rejected
+    let other = Poll::new().unwrap();
+    let (_r, s) = Registration::new(&other, Token(9), Ready::readable(), PollOpt::edge());
+    assert!(group.push(s).is_err());
+}
+
+#[test]
+pub fn test_poll_reason_events() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_r, s) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    s.set_readiness(Ready::readable()).unwrap();
+
+    let reason = poll.poll_reason(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(PollReason::Events(1), reason);
+}
+
+#[test]
+pub fn test_poll_reason_immediate() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let reason = poll.poll_reason(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(PollReason::Immediate, reason);
+
+    // Readiness without interest forces a zero timeout but yields no events
+    let (_r, s) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    s.set_readiness(Ready::writable()).unwrap();
+
+    let reason = poll.poll_reason(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(PollReason::Immediate, reason);
+}
+
+#[test]
+pub fn test_poll_reason_timed_out() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let reason = poll.poll_reason(&mut events, Duration::from_millis(50)).unwrap();
+    assert_eq!(PollReason::TimedOut, reason);
+}
+
+#[test]
+pub fn test_set_readiness_read_write_tokens() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (r, s) = Registration::new(&poll, Token(5), Ready::readable(), PollOpt::edge());
+    assert_eq!(Token(5), s.latest_token());
+    assert_eq!(Token(5), s.current_token());
+
+    // `update` moves the write position only
+    r.update(&poll, Token(7), Ready::readable(), PollOpt::edge()).unwrap();
+    assert_eq!(Token(7), s.latest_token());
+    assert_eq!(Token(5), s.current_token());
+
+    // Delivering an event moves the read position
+    s.set_readiness(Ready::readable()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(Token(7), events.get(0).unwrap().token());
+    assert_eq!(Token(7), s.current_token());
+}
+
+#[test]
+pub fn test_forward_ready() {
+    let parent = Poll::new().unwrap();
+    let child = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    child.register(&sock, Token(1), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (_r, s) = Registration::new(&parent, Token(10), Ready::writable(), PollOpt::edge());
+    child.forward_to(Token(1), s);
+
+    child.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+
+    for event in events.iter() {
+        child.forward_ready(event.token(), event.kind()).unwrap();
+    }
+
+    let num = parent.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, num);
+    assert_eq!(Token(10), events.get(0).unwrap().token());
+    assert!(events.get(0).unwrap().kind().is_writable());
+
+    assert!(child.stop_forwarding(Token(1)).is_some());
+    assert!(child.forward_ready(Token(1), Ready::writable()).is_err());
+}
+
+#[test]
+pub fn test_registration_priority_ordering() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    let (_r2, s2) = Registration::new_with_priority(&poll, Token(2), Ready::readable(), PollOpt::edge(), 5);
+    let (r3, s3) = Registration::new(&poll, Token(3), Ready::readable(), PollOpt::edge());
+    let (_r4, s4) = Registration::new_with_priority(&poll, Token(4), Ready::readable(), PollOpt::edge(), 10);
+
+    r3.update_with_priority(&poll, Token(3), Ready::readable(), PollOpt::edge(), 1).unwrap();
+
+    s1.set_readiness(Ready::readable()).unwrap();
+    s2.set_readiness(Ready::readable()).unwrap();
+    s3.set_readiness(Ready::readable()).unwrap();
+    s4.set_readiness(Ready::readable()).unwrap();
+
+    let n = poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(4, n);
+
+    let tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    assert_eq!(vec![Token(4), Token(2), Token(3), Token(1)], tokens);
+}
+
+#[test]
+pub fn test_registration_fifo_ordering() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_ra, sa) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::level());
+    let (_rb, sb) = Registration::new(&poll, Token(2), Ready::readable(), PollOpt::edge());
+    let (_rc, sc) = Registration::new(&poll, Token(3), Ready::readable(), PollOpt::edge());
+    let (_rd, sd) = Registration::new(&poll, Token(4), Ready::readable(), PollOpt::level());
+
+    let tokens = |events: &Events| events.iter().map(|e| e.token()).collect::<Vec<Token>>();
+
+    // Delivered in the order the registrations were queued, regardless of
+    // the order they were created in
+    sc.set_readiness(Ready::readable()).unwrap();
+    sa.set_readiness(Ready::readable()).unwrap();
+    sb.set_readiness(Ready::readable()).unwrap();
+    sd.set_readiness(Ready::readable()).unwrap();
+
+    // Already queued, keeps its place
+    sc.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(vec![Token(3), Token(1), Token(2), Token(4)], tokens(&events));
+
+    // The level-triggered registrations were requeued in order, ahead of the
+    // edge-triggered ones queued since
+    sb.set_readiness(Ready::readable()).unwrap();
+    sc.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(vec![Token(1), Token(4), Token(2), Token(3)], tokens(&events));
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(vec![Token(1), Token(4)], tokens(&events));
+}
+
+#[test]
+pub fn test_poll_moved_to_another_thread() {
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+
+    // Register on the current thread
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    // Poll on another thread
+    let tokens = thread::spawn(move || {
+        let mut events = Events::with_capacity(1024);
+        let mut tokens = vec![];
+
+        while tokens.len() < 2 {
+            poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+            assert!(!events.is_empty(), "seeded readiness was not delivered");
+            tokens.extend(events.iter().map(|e| e.token()));
+        }
+
+        drop(registration);
+        drop(sock);
+        tokens
+    }).join().unwrap();
+
+    assert!(tokens.contains(&Token(0)));
+    assert!(tokens.contains(&Token(1)));
+}
+
+#[test]
+pub fn test_poll_registered() {
+    let poll = Poll::new().unwrap();
+    assert!(poll.registered().is_empty());
+
+    // OS backed handles are not listed
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    let (r2, _s2) = Registration::new(&poll, Token(2), Ready::all(), PollOpt::level());
+    let (r3, _s3) = Registration::new(&poll, Token(3), Ready::readable(), PollOpt::edge());
+
+    s1.set_readiness(Ready::readable()).unwrap();
+    r2.update(&poll, Token(20), Ready::writable(), PollOpt::level()).unwrap();
+
+    assert_eq!(vec![(Token(1), Ready::readable(), Ready::readable(), PollOpt::edge()),
+                    (Token(20), Ready::writable(), Ready::none(), PollOpt::level()),
+                    (Token(3), Ready::readable(), Ready::none(), PollOpt::edge())],
+               poll.registered());
+
+    r3.deregister(&poll).unwrap();
+    drop(r1);
+
+    assert_eq!(vec![(Token(20), Ready::writable(), Ready::none(), PollOpt::level())],
+               poll.registered());
+}
+
+#[test]
+pub fn test_poll_registered_named() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (r1, _s1) = Registration::new_named(&poll, Token(1), Ready::readable(), PollOpt::edge(), "accept-listener");
+    let (r2, _s2) = Registration::new(&poll, Token(2), Ready::readable(), PollOpt::edge());
+    let (mut r3, s3) = Registration::new_named(&poll, Token(3), Ready::readable(), PollOpt::edge(), format!("db-conn-{}", 42));
+
+    assert_eq!(Some("accept-listener"), r1.name());
+    assert_eq!(None, r2.name());
+    assert_eq!("Registration { name: Some(\"db-conn-42\") }", format!("{:?}", r3));
+
+    let names: Vec<_> = poll.registered_named().into_iter().map(|r| (r.0, r.1)).collect();
+    assert_eq!(vec![(Token(1), Some("accept-listener".into())),
+                    (Token(2), None),
+                    (Token(3), Some("db-conn-42".into()))],
+               names);
+
+    // The name follows the registration to another `Poll`
+    let other = Poll::new().unwrap();
+    r3.migrate(&poll, &other).unwrap();
+    assert_eq!(Some("db-conn-42"), r3.name());
+
+    s3.set_readiness(Ready::readable()).unwrap();
+    other.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+
+    let names: Vec<_> = other.registered_named().into_iter().map(|r| r.1).collect();
+    assert_eq!(vec![Some("db-conn-42".into())], names);
+}
+
+#[test]
+pub fn test_wakeup_tokens() {
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let w1 = poll.register_wakeup_token(Token(1)).unwrap();
+    let w2 = poll.register_wakeup_token(Token(2)).unwrap();
+    assert_eq!(Token(1), w1.token());
+
+    // Nothing is delivered until woken
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    // Coalesced wake from another thread
+    let w = w2.clone();
+    thread::spawn(move || {
+        w.wake().unwrap();
+        w.wake().unwrap();
+    }).join().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(2)), events.get(0).unwrap());
+
+    // Both tokens at once, and again after having been delivered
+    w1.wake().unwrap();
+    w2.wake().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(vec![Token(1), Token(2)], tokens);
+
+    // Deregistered tokens are not delivered anymore
+    assert!(poll.deregister_wakeup_token(Token(1)));
+    assert!(!poll.deregister_wakeup_token(Token(1)));
+    w1.wake().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+pub fn test_concurrent_set_readiness_is_not_deferred() {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    const THREADS: usize = 4;
+    const PER_THREAD: usize = 250;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let mut registrations = vec![];
+    let mut threads = vec![];
+
+    for t in 0..THREADS {
+        let mut handles = vec![];
+
+        for i in 0..PER_THREAD {
+            let token = Token(t * PER_THREAD + i);
+            let (registration, set_readiness) = Registration::new(&poll, token, Ready::readable(), PollOpt::edge());
+            registrations.push(registration);
+            handles.push(set_readiness);
+        }
+
+        let barrier = barrier.clone();
+
+        // All producers race on the head of the readiness queue while the
+        // current thread drains it.
+        threads.push(thread::spawn(move || {
+            barrier.wait();
+
+            for set_readiness in handles {
+                set_readiness.set_readiness(Ready::readable()).unwrap();
+            }
+        }));
+    }
+
+    let mut seen = HashSet::new();
+
+    while seen.len() < THREADS * PER_THREAD {
+        // Every pending readiness must wake up `poll`, a timeout means an
+        // event has been deferred.
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        assert!(!events.is_empty(), "readiness deferred; seen {} events", seen.len());
+
+        for event in events.iter() {
+            seen.insert(event.token());
+        }
+    }
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+pub fn test_poll_with_wake_tokens() {
+    let (poll, wakers) = Poll::with_wake_tokens(3).unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    assert_eq!(3, wakers.len());
+
+    // Tokens are distinct and reserved
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+
+    for (i, waker) in wakers.iter().enumerate() {
+        assert!(wakers.iter().skip(i + 1).all(|w| w.token() != waker.token()));
+        assert!(poll.register(&sock, waker.token(), Ready::readable(), PollOpt::edge()).is_err());
+        assert!(poll.register_wakeup_token(waker.token()).is_err());
+    }
+
+    wakers[2].wake().unwrap();
+    wakers[0].wake().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+
+    let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(vec![wakers[0].token(), wakers[2].token()], tokens);
+
+    assert!(Poll::with_wake_tokens(1 << 20).is_err());
+}
+
+#[test]
+pub fn test_poll_into_appends() {
+    let poll = Poll::new().unwrap();
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    let prior = Event::new(Ready::hup(), Token(99));
+    let mut dst = vec![prior];
+
+    let n = poll.poll_into(&mut dst, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(2, n);
+    assert_eq!(3, dst.len());
+    assert_eq!(prior, dst[0]);
+
+    let mut tokens: Vec<Token> = dst[1..].iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(vec![Token(0), Token(1)], tokens);
+
+    // Nothing left, nothing appended
+    let n = poll.poll_into(&mut dst, Duration::from_millis(0)).unwrap();
+    assert_eq!(0, n);
+    assert_eq!(3, dst.len());
+}
+
+#[test]
+pub fn test_auto_reset_registration() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_edge, s1) = Registration::new_auto_reset(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    let (_level, s2) = Registration::new_auto_reset(&poll, Token(2), Ready::readable(), PollOpt::level());
+
+    s1.set_readiness(Ready::readable()).unwrap();
+    s2.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(vec![Token(1), Token(2)], tokens);
+
+    // The readiness has been cleared by the delivery, even when level
+    // triggered
+    assert_eq!(Ready::none(), s1.readiness());
+    assert_eq!(Ready::none(), s2.readiness());
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    // Setting the readiness again fires again
+    s1.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(1)), events.get(0).unwrap());
+}
+
+#[test]
+pub fn test_auto_reset_concurrent_set_readiness() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_registration, set_readiness) = Registration::new_auto_reset(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    let observer = set_readiness.clone();
+
+    let sets = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicBool::new(false));
+
+    let th = {
+        let sets = sets.clone();
+        let done = done.clone();
+
+        thread::spawn(move || {
+            for _ in 0..10_000 {
+                set_readiness.set_readiness(Ready::readable()).unwrap();
+                sets.fetch_add(1, Ordering::SeqCst);
+            }
+
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+
+    let mut delivered = 0;
+
+    loop {
+        let finished = done.load(Ordering::SeqCst);
+
+        poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+        delivered += events.len();
+
+        // The last readiness set before the producer finished must never be
+        // swallowed by the reset of an earlier delivery.
+        if finished && events.is_empty() {
+            break;
+        }
+    }
+
+    th.join().unwrap();
+
+    assert!(delivered >= 1);
+    assert!(delivered <= sets.load(Ordering::SeqCst));
+    assert_eq!(Ready::none(), observer.readiness());
+}
+
+#[test]
+pub fn test_effective_timeout_rounds_up() {
+    use std::time::Instant;
+
+    let requested = [
+        Duration::new(0, 1),
+        Duration::new(0, 100_000),
+        Duration::new(0, 1_500_000),
+        Duration::from_millis(2),
+        Duration::new(1, 1),
+    ];
+
+    for &to in &requested {
+        let effective = Poll::effective_timeout(to);
+        assert!(effective >= to, "{:?} rounded down to {:?}", to, effective);
+        assert!(effective - to < Duration::from_millis(1));
+    }
+
+    assert_eq!(Duration::from_millis(0), Poll::effective_timeout(Duration::from_millis(0)));
+
+    // A sub-millisecond timeout actually blocks instead of spinning
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+    let to = Duration::new(0, 200_000);
+
+    let now = Instant::now();
+    poll.poll(&mut events, Some(to)).unwrap();
+    assert!(now.elapsed() >= to);
+}
+
+#[test]
+pub fn test_registration_drop_callback() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let (registration, set_readiness) = {
+        let drops = drops.clone();
+        Registration::new_with_drop_callback(&poll, Token(0), Ready::readable(), PollOpt::edge(), move || {
+            drops.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    let set_readiness2 = set_readiness.clone();
+
+    drop(registration);
+    drop(set_readiness);
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+    // A handle is still alive
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+
+    // Dropping the last handle queues the release, the node is only freed
+    // once `poll` processes it.
+    drop(set_readiness2);
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    drop(poll);
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+}
+
+#[test]
+pub fn test_registration_drop_callback_on_poll_drop() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let poll = Poll::new().unwrap();
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let (registration, set_readiness) = {
+        let drops = drops.clone();
+        Registration::new_with_drop_callback(&poll, Token(0), Ready::readable(), PollOpt::edge(), move || {
+            drops.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    // Never polled, the node is freed along with the `Poll` instance
+    drop(registration);
+    drop(set_readiness);
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+
+    drop(poll);
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+}
+
+#[test]
+pub fn test_registration_drop_callback_after_migrate() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let poll1 = Poll::new().unwrap();
+    let poll2 = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let (mut registration, set_readiness) = {
+        let drops = drops.clone();
+        Registration::new_with_drop_callback(&poll1, Token(0), Ready::readable(), PollOpt::edge(), move || {
+            drops.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    registration.migrate(&poll1, &poll2).unwrap();
+
+    // Releasing the old state doesn't run the callback
+    poll1.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+
+    drop(registration);
+    drop(set_readiness);
+    poll1.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+
+    poll2.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+
+    drop(poll1);
+    drop(poll2);
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+}
+
+#[test]
+pub fn test_poll_timeout_forms() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+    assert_eq!(0, poll.poll(&mut events, Duration::from_millis(10)).unwrap());
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(10))).unwrap());
+
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    assert_eq!(1, poll.poll(&mut events, None).unwrap());
+}
+
+#[test]
+pub fn test_registration_migrate() {
+    let poll1 = Poll::new().unwrap();
+    let poll2 = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (mut registration, set_readiness) = Registration::new(&poll1, Token(7), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    assert_eq!(vec![Token(7)], poll1.registered().into_iter().map(|r| r.0).collect::<Vec<_>>());
+
+    registration.migrate(&poll1, &poll2).unwrap();
+
+    // The source no longer reports the registration
+    assert!(poll1.registered().is_empty());
+    assert_eq!(vec![Token(7)], poll2.registered().into_iter().map(|r| r.0).collect::<Vec<_>>());
+
+    // The pending readiness moved along with the registration
+    poll1.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    poll2.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(7)), events.get(0).unwrap());
+
+    // Existing handles follow the registration
+    assert_eq!(Token(7), set_readiness.latest_token());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    poll1.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    poll2.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(7), events.get(0).unwrap().token());
+
+    // The registration now belongs to the new `Poll`
+    assert!(registration.update(&poll1, Token(8), Ready::readable(), PollOpt::edge()).is_err());
+    assert!(registration.migrate(&poll1, &poll2).is_err());
+    registration.update(&poll2, Token(8), Ready::readable(), PollOpt::edge()).unwrap();
+    assert_eq!(Token(8), set_readiness.latest_token());
+
+    // Migrating back works as well
+    registration.migrate(&poll2, &poll1).unwrap();
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    poll2.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    poll1.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(8), events.get(0).unwrap().token());
+}
+
+#[test]
+pub fn test_poll_mut_resurfaces_unhandled() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (_r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    let (_r2, s2) = Registration::new(&poll, Token(2), Ready::readable(), PollOpt::edge());
+    s1.set_readiness(Ready::readable()).unwrap();
+    s2.set_readiness(Ready::readable()).unwrap();
+
+    assert_eq!(3, poll.poll_mut(&mut events, Some(Duration::from_millis(300))).unwrap());
+
+    // Only handle token 1
+    for mut event in events.iter_mut() {
+        assert!(!event.is_handled());
+
+        if event.token() == Token(1) {
+            event.set_handled(true);
+        }
+    }
+
+    // Token 2 is returned again without blocking, the OS event is not
+    assert_eq!(1, poll.poll_mut(&mut events, None).unwrap());
+    assert_eq!(Event::new(Ready::readable(), Token(2)), events.get(0).unwrap());
+
+    // Handle it this time
+    for mut event in events.iter_mut() {
+        event.set_handled(true);
+    }
+
+    assert_eq!(0, poll.poll_mut(&mut events, Some(Duration::from_millis(0))).unwrap());
+
+    // A token that is delivered again is not duplicated
+    s1.set_readiness(Ready::readable()).unwrap();
+    assert_eq!(1, poll.poll_mut(&mut events, Some(Duration::from_millis(300))).unwrap());
+    s1.set_readiness(Ready::readable()).unwrap();
+    assert_eq!(1, poll.poll_mut(&mut events, Some(Duration::from_millis(300))).unwrap());
+
+    // `poll` discards the handled state
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+    assert_eq!(0, poll.poll_mut(&mut events, Some(Duration::from_millis(0))).unwrap());
+}
+
+#[test]
+pub fn test_poll_slice() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    {
+        let slice = poll.poll_slice(&mut events, Some(Duration::from_millis(300))).unwrap();
+        assert_eq!(2, slice.len());
+        assert!(slice.contains(&Event::new(Ready::writable(), Token(0))));
+        assert!(slice.contains(&Event::new(Ready::readable(), Token(1))));
+    }
+
+    assert_eq!(2, events.len());
+
+    let slice = poll.poll_slice(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(slice.is_empty());
+}
+
+#[test]
+pub fn test_incremental_registration() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_r1, s1) = Registration::new_incremental(&poll, Token(1), Ready::readable() | Ready::writable(), PollOpt::edge());
+    let (_r2, s2) = Registration::new(&poll, Token(2), Ready::readable() | Ready::writable(), PollOpt::edge());
+
+    s1.set_readiness(Ready::readable()).unwrap();
+    s2.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(2, events.len());
+    assert!(events.iter().all(|e| e.kind() == Ready::readable()));
+
+    s1.set_readiness(Ready::readable() | Ready::writable()).unwrap();
+    s2.set_readiness(Ready::readable() | Ready::writable()).unwrap();
+
+    // Only writable is new for the incremental registration, the default
+    // reports the full readiness
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(2, events.len());
+    assert!(events.iter().any(|e| e == Event::new(Ready::writable(), Token(1))));
+    assert!(events.iter().any(|e| e == Event::new(Ready::readable() | Ready::writable(), Token(2))));
+
+    // Setting the same readiness again reports nothing new
+    s1.set_readiness(Ready::readable() | Ready::writable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    // Clearing the readiness and setting it again is a new edge
+    s1.set_readiness(Ready::none()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    s1.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(1)), events.get(0).unwrap());
+}
+
+#[test]
+pub fn test_registration_disable_and_release() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let (registration, set_readiness) = {
+        let drops = drops.clone();
+        Registration::new_with_drop_callback(&poll, Token(0), Ready::readable(), PollOpt::edge(), move || {
+            drops.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    let (_other, _other_set) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    registration.disable_and_release(&poll).unwrap();
+
+    // The pending readiness is not delivered and `poll` gives up the node
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    let tokens: Vec<Token> = poll.registered().into_iter().map(|r| r.0).collect();
+    assert_eq!(vec![Token(1)], tokens);
+
+    // The handles are inert
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+    assert!(registration.update(&poll, Token(0), Ready::readable(), PollOpt::edge()).is_err());
+    registration.disable_and_release(&poll).unwrap();
+
+    // Dropping the last handle frees the node without going through `poll`
+    let set_readiness2 = set_readiness.clone();
+    drop(registration);
+    drop(set_readiness);
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+
+    drop(set_readiness2);
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    drop(poll);
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+}
+
+#[test]
+pub fn test_registration_disable_and_release_dropped_before_poll() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let drops = Arc::new(AtomicUsize::new(0));
+
+    let (registration, set_readiness) = {
+        let drops = drops.clone();
+        Registration::new_with_drop_callback(&poll, Token(0), Ready::readable(), PollOpt::edge(), move || {
+            drops.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    registration.disable_and_release(&poll).unwrap();
+    drop(registration);
+    drop(set_readiness);
+
+    // `poll` had not detached the node yet, so it releases it as usual
+    assert_eq!(0, drops.load(Ordering::SeqCst));
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(1, drops.load(Ordering::SeqCst));
+    assert!(poll.registered().is_empty());
+}
+
+#[test]
+pub fn test_poll_once_or_timeout_returns_on_wakeup() {
+    use std::thread;
+    use std::time::Instant;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    // Setting a readiness outside of the interest wakes `Poll` up without
+    // producing an event
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::writable(), PollOpt::edge());
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    let now = Instant::now();
+    let n = poll.poll_once_or_timeout(&mut events, Duration::from_secs(10)).unwrap();
+
+    assert_eq!(0, n);
+    assert!(events.is_empty());
+    assert!(now.elapsed() < Duration::from_secs(10));
+
+    th.join().unwrap();
+
+    let now = Instant::now();
+    let n = poll.poll_once_or_timeout(&mut events, Duration::from_millis(50)).unwrap();
+
+    assert_eq!(0, n);
+    assert!(now.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+pub fn test_poll_with_blocking_flag() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+    // Nothing pending, the requested timeout is used
+    let (n, blocked) = poll.poll_with_blocking_flag(&mut events, Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(0, n);
+    assert!(blocked);
+
+    // A zero timeout never blocks
+    let (_, blocked) = poll.poll_with_blocking_flag(&mut events, Duration::from_millis(0)).unwrap();
+    assert!(!blocked);
+
+    // Pending readiness forces a zero timeout
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    let (n, blocked) = poll.poll_with_blocking_flag(&mut events, None).unwrap();
+    assert_eq!(1, n);
+    assert!(!blocked);
+}
+
+#[test]
+pub fn test_poll_condvar() {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let condvar = PollCondvar::new();
+    let waiter = condvar.register(&poll, Token(0)).unwrap();
+    assert_eq!(Token(0), waiter.token());
+    assert_eq!(1, condvar.waiters());
+
+    // A producer pushes values and notifies the consumer after each one
+    let queue = Arc::new(Mutex::new(Vec::new()));
+
+    let th = {
+        let queue = queue.clone();
+        let condvar = condvar.clone();
+
+        thread::spawn(move || {
+            for i in 0..100 {
+                queue.lock().unwrap().push(i);
+                condvar.notify_one().unwrap();
+            }
+        })
+    };
+
+    // The consumer waits in `poll` until the queue is non-empty
+    let mut received = Vec::new();
+
+    while received.len() < 100 {
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        assert!(!events.is_empty(), "timed out waiting for a notification");
+
+        for event in events.iter() {
+            assert_eq!(Token(0), event.token());
+            received.extend(queue.lock().unwrap().drain(..));
+        }
+    }
+
+    th.join().unwrap();
+    assert_eq!((0..100).collect::<Vec<_>>(), received);
+
+    // Notifications are consumed by their delivery
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    drop(waiter);
+    assert_eq!(0, condvar.waiters());
+}
+
+#[test]
+pub fn test_poll_condvar_notify_before_register() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let condvar = PollCondvar::new();
+
+    // Kept for the next waiter
+    condvar.notify_one().unwrap();
+
+    // Dropped, there is nobody to notify
+    condvar.notify_all().unwrap();
+
+    let w1 = condvar.register(&poll, Token(1)).unwrap();
+    let w2 = condvar.register(&poll, Token(2)).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(1)), events.get(0).unwrap());
+
+    // Both waiters are notified, before they poll
+    condvar.notify_all().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(vec![Token(1), Token(2)], tokens);
+
+    drop(w1);
+    drop(w2);
+}
+
+#[test]
+pub fn test_events_with_zero_capacity() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(0);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    let mut tokens = Vec::new();
+
+    for _ in 0..10 {
+        poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+        tokens.extend(events.iter().map(|e| e.token()));
+
+        if tokens.len() >= 2 {
+            break;
+        }
+    }
+
+    tokens.sort();
+    assert_eq!(vec![Token(0), Token(1)], tokens);
+}
+
+#[test]
+pub fn test_coalesce_readiness() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    assert!(poll.coalesce_readiness());
+
+    let interest = Ready::readable() | Ready::writable();
+    let (_edge, s1) = Registration::new(&poll, Token(1), interest, PollOpt::edge());
+    let (_level, s2) = Registration::new(&poll, Token(2), interest, PollOpt::level());
+
+    s1.set_readiness(interest).unwrap();
+    s2.set_readiness(interest).unwrap();
+
+    // Default, a single event per registration
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(2, events.len());
+    assert!(events.iter().all(|e| e.kind() == interest));
+
+    poll.set_coalesce_readiness(false);
+    assert!(!poll.coalesce_readiness());
+
+    // The level registration is delivered again, split in two
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    let evts: Vec<Event> = events.iter().collect();
+    assert_eq!(vec![Event::new(Ready::readable(), Token(2)),
+                    Event::new(Ready::writable(), Token(2))], evts);
+
+    s1.set_readiness(interest).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    let mut evts: Vec<(Token, usize)> = events.iter().map(|e| (e.token(), e.kind().bits())).collect();
+    evts.sort();
+    assert_eq!(vec![(Token(1), 1), (Token(1), 2), (Token(2), 1), (Token(2), 2)], evts);
+}
+
+#[test]
+pub fn test_registration_priority_readiness() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (_r1, s1) = Registration::new(&poll, Token(1), Ready::priority(), PollOpt::edge());
+
+    s1.set_readiness(Ready::priority()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(1), events.get(0).unwrap().token());
+    assert!(events.get(0).unwrap().is_priority());
+    assert!(!events.get(0).unwrap().is_readable());
+
+    // Split into one event per kind, priority included
+    poll.set_coalesce_readiness(false);
+
+    let interest = Ready::writable() | Ready::priority();
+    let (_r2, s2) = Registration::new(&poll, Token(2), interest, PollOpt::edge());
+
+    s2.set_readiness(interest).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(2, events.len());
+    assert_eq!(Ready::writable(), events.get(0).unwrap().kind());
+    assert_eq!(Ready::priority(), events.get(1).unwrap().kind());
+}
+
+#[test]
+pub fn test_select_multiple_polls() {
+    use std::thread;
+    use std::time::Instant;
+
+    let p1 = Poll::new().unwrap();
+    let p2 = Poll::new().unwrap();
+    let p3 = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    // Nothing is ready, the timeout elapses
+    let now = Instant::now();
+    let ready = select(&[&p1, &p2, &p3], Some(Duration::from_millis(50))).unwrap();
+    assert!(ready.is_empty());
+    assert!(now.elapsed() >= Duration::from_millis(50));
+
+    // OS readiness
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    p2.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let ready = select(&[&p1, &p2, &p3], Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(vec![1], ready);
+
+    // `select` does not consume the events
+    p2.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(1, events.len());
+
+    // Custom readiness set from another thread wakes up `select`
+    let (_registration, set_readiness) = Registration::new(&p3, Token(1), Ready::readable(), PollOpt::edge());
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    let ready = select(&[&p1, &p2, &p3], Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(vec![2], ready);
+    th.join().unwrap();
+
+    p3.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(1), events.get(0).unwrap().token());
+
+    // All the ready instances are reported
+    let (_r1, s1) = Registration::new(&p1, Token(2), Ready::readable(), PollOpt::edge());
+    let (_r3, s3) = Registration::new(&p3, Token(3), Ready::readable(), PollOpt::edge());
+    s1.set_readiness(Ready::readable()).unwrap();
+    s3.set_readiness(Ready::readable()).unwrap();
+
+    let ready = select(&[&p1, &p2, &p3], None).unwrap();
+    assert_eq!(vec![0, 2], ready);
+
+    // Polling an instance that was not ready does not skip blocking
+    let now = Instant::now();
+    p2.poll(&mut events, Some(Duration::from_millis(50))).unwrap();
+    assert!(events.is_empty());
+    assert!(now.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+pub fn test_short_timeouts_with_concurrent_wakeups() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Instant;
+
+    const THREADS: usize = 4;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let done = Arc::new(AtomicBool::new(false));
+    let mut registrations = vec![];
+    let mut threads = vec![];
+
+    for i in 0..THREADS {
+        let (registration, set_readiness) = Registration::new(&poll, Token(i), Ready::readable(), PollOpt::edge());
+        registrations.push(registration);
+
+        let done = done.clone();
+
+        // Producers set readiness at random points of the sleep handoff
+        threads.push(thread::spawn(move || {
+            let mut n = 0;
+
+            while !done.load(Ordering::SeqCst) {
+                set_readiness.set_readiness(Ready::readable()).unwrap();
+                thread::sleep(Duration::from_millis((n % 3) as u64));
+                set_readiness.set_readiness(Ready::none()).unwrap();
+                n += 1;
+            }
+        }));
+    }
+
+    let start = Instant::now();
+
+    while start.elapsed() < Duration::from_secs(2) {
+        let timeout = Duration::from_millis(5);
+        let now = Instant::now();
+
+        poll.poll(&mut events, Some(timeout)).unwrap();
+
+        // Never hangs past the deadline, with generous slack for slow
+        // machines
+        assert!(now.elapsed() < timeout + Duration::from_secs(1));
+    }
+
+    done.store(true, Ordering::SeqCst);
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Readiness set once the producers are done is never lost
+    let (_registration, set_readiness) = Registration::new(&poll, Token(THREADS), Ready::readable(), PollOpt::edge());
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    let mut seen = false;
+
+    while !seen {
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        assert!(!events.is_empty(), "readiness lost");
+        seen = events.iter().any(|e| e.token() == Token(THREADS));
+    }
+
+    th.join().unwrap();
+}
+
+#[test]
+pub fn test_set_readiness_release() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    const ROUNDS: usize = 100;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let data = Arc::new(AtomicUsize::new(0));
+    let ack = Arc::new(AtomicUsize::new(0));
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+    let th = {
+        let data = data.clone();
+        let ack = ack.clone();
+
+        thread::spawn(move || {
+            for round in 1..ROUNDS + 1 {
+                // The data is only published through the readiness
+                data.store(round, Ordering::Relaxed);
+                set_readiness.set_readiness_release(Ready::readable()).unwrap();
+
+                while ack.load(Ordering::SeqCst) != round {
+                    thread::yield_now();
+                }
+
+                set_readiness.set_readiness(Ready::none()).unwrap();
+            }
+        })
+    };
+
+    for round in 1..ROUNDS + 1 {
+        loop {
+            poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+            assert!(!events.is_empty(), "readiness lost");
+
+            if events.iter().any(|e| e.kind().is_readable()) {
+                break;
+            }
+        }
+
+        assert_eq!(round, data.load(Ordering::Relaxed));
+        ack.store(round, Ordering::SeqCst);
+    }
+
+    th.join().unwrap();
+}
+
+#[test]
+pub fn test_registration_interest() {
+    use std::cell::RefCell;
+    use std::io;
+
+    // Records the interest changes it sees on reregister
+    struct Composite {
+        registration: Registration,
+        deltas: RefCell<Vec<(Ready, Ready)>>,
+    }
+
+    impl Evented for Composite {
+        fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+            self.registration.update(poll, token, interest, opts)
+        }
+
+        fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+            let previous = try!(self.registration.interest(poll));
+            self.deltas.borrow_mut().push((interest - previous, previous - interest));
+            self.registration.update(poll, token, interest, opts)
+        }
+
+        fn deregister(&self, poll: &Poll) -> io::Result<()> {
+            self.registration.deregister(poll)
+        }
+    }
+
+    let poll = Poll::new().unwrap();
+    let (registration, _set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    assert_eq!(Ready::readable(), registration.interest(&poll).unwrap());
+
+    let composite = Composite { registration: registration, deltas: RefCell::new(vec![]) };
+
+    poll.reregister(&composite, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+    poll.reregister(&composite, Token(0), Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
+
+    assert_eq!(*composite.deltas.borrow(), vec![
+        (Ready::writable(), Ready::readable()),
+        (Ready::readable(), Ready::none()),
+    ]);
+
+    poll.deregister(&composite).unwrap();
+    assert_eq!(Ready::none(), composite.registration.interest(&poll).unwrap());
+
+    // Another `Poll` instance is rejected
+    let other = Poll::new().unwrap();
+    assert!(composite.registration.interest(&other).is_err());
+}
+
+#[test]
+pub fn test_poll_with_spin() {
+    use std::thread;
+    use std::time::Instant;
+
+    let poll = Poll::new_with_spin(100).unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    // Still blocks for the timeout once the spin comes back empty
+    let now = Instant::now();
+    poll.poll(&mut events, Some(Duration::from_millis(50))).unwrap();
+    assert!(events.is_empty());
+    assert!(now.elapsed() >= Duration::from_millis(50));
+
+    // Selector events found while spinning are returned
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(0), events.get(0).unwrap().token());
+
+    // As well as custom readiness, set before or during the call
+    let (_registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(1)), events.get(0).unwrap());
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+
+    th.join().unwrap();
+}
+
+#[test]
+pub fn test_events_sort_by_token() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(5), Ready::writable(), PollOpt::edge()).unwrap();
+
+    let mut registrations = vec![];
+
+    for &i in &[9, 2, 7, 0, 3] {
+        let (r, s) = Registration::new(&poll, Token(i), Ready::readable(), PollOpt::edge());
+        s.set_readiness(Ready::readable()).unwrap();
+        registrations.push((r, s));
+    }
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(6, events.len());
+
+    events.sort_by_token();
+
+    let tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    assert_eq!(tokens, vec![Token(0), Token(2), Token(3), Token(5), Token(7), Token(9)]);
+
+    // The readiness travels with its token
+    assert_eq!(Ready::writable(), events.get(3).unwrap().kind());
+
+    // Nothing is carried over by `poll_mut` after sorting
+    let n = poll.poll_mut(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(0, n);
+}
+
+#[test]
+pub fn test_set_readiness_ptr_eq() {
+    use std::collections::HashSet;
+
+    let poll = Poll::new().unwrap();
+
+    let (_r1, s1) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    let (_r2, s2) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    let s1b = s1.clone();
+
+    assert!(s1.ptr_eq(&s1b));
+    assert!(!s1.ptr_eq(&s2));
+    assert_eq!(s1.id(), s1b.id());
+    assert!(s1.id() != s2.id());
+
+    let mut ids = HashSet::new();
+
+    for s in &[&s1, &s2, &s1b] {
+        ids.insert(s.id());
+    }
+
+    assert_eq!(2, ids.len());
+}
+
+#[test]
+pub fn test_panicking_drop_callback_does_not_wedge_poll() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    // Queued before the registration below, so it is processed first
+    let (r, s) = Registration::new_with_drop_callback(&poll, Token(1), Ready::readable(), PollOpt::edge(), || {
+        panic!("drop callback");
+    });
+    drop(r);
+    drop(s);
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _ = poll.poll(&mut events, Some(Duration::from_millis(0)));
+    }));
+    assert!(res.is_err());
+
+    // The readiness that was pending when the callback panicked is kept
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(0)), events.get(0).unwrap());
+
+    // And the registration keeps working
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+}
+
+#[test]
+pub fn test_rapid_wakeups_are_never_lost() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Instant;
+
+    const THREADS: usize = 4;
+    const ROUNDS: usize = 500;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let mut registrations = vec![];
+    let mut seen = vec![];
+    let mut threads = vec![];
+
+    for i in 0..THREADS {
+        let (registration, set_readiness) = Registration::new(&poll, Token(i), Ready::readable(), PollOpt::edge());
+        let acked = Arc::new(AtomicUsize::new(0));
+
+        registrations.push(registration);
+        seen.push(acked.clone());
+
+        // Each round wakes `poll` up and waits until it has seen the event
+        threads.push(thread::spawn(move || {
+            for round in 0..ROUNDS {
+                set_readiness.set_readiness(Ready::readable()).unwrap();
+
+                while acked.load(Ordering::Acquire) == round {
+                    thread::yield_now();
+                }
+            }
+        }));
+    }
+
+    let start = Instant::now();
+    let mut done = 0;
+
+    while done < THREADS {
+        assert!(start.elapsed() < Duration::from_secs(30), "wakeup lost");
+
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+
+        for event in events.iter() {
+            let acked = &seen[usize::from(event.token())];
+            let round = acked.load(Ordering::Relaxed) + 1;
+
+            acked.store(round, Ordering::Release);
+
+            if round == ROUNDS {
+                done += 1;
+            }
+        }
+    }
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+pub fn test_block_timing() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    // Off by default
+    poll.poll(&mut events, Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(None, poll.last_block_duration());
+
+    poll.enable_block_timing(true);
+    assert_eq!(None, poll.last_block_duration());
+
+    poll.poll(&mut events, Some(Duration::from_millis(50))).unwrap();
+    let blocked = poll.last_block_duration().unwrap();
+    assert!(blocked >= Duration::from_millis(50));
+
+    // Pending custom readiness means no blocking
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_secs(1))).unwrap();
+    assert_eq!(1, events.len());
+    assert!(poll.last_block_duration().unwrap() < Duration::from_millis(500));
+
+    poll.enable_block_timing(false);
+    assert_eq!(None, poll.last_block_duration());
+}
+
+#[test]
+pub fn test_poll_no_userspace() {
+    use mio::timer::Timer;
+
+    let poll = Poll::new_no_userspace().unwrap();
+    let mut events = Events::with_capacity(1024);
+    assert!(!poll.has_userspace());
+    assert!(Poll::new().unwrap().has_userspace());
+
+    // OS handles work as usual
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    poll.register(&sock, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(0), events.get(0).unwrap().token());
+
+    poll.poll(&mut events, Some(Duration::from_millis(10))).unwrap();
+    assert!(events.is_empty());
+
+    // Custom registrations are refused
+    assert!(Registration::try_new(&poll, Token(1), Ready::readable(), PollOpt::edge()).is_err());
+    assert!(poll.register_wakeup_token(Token(2)).is_err());
+
+    let timer = Timer::<()>::default();
+    assert!(poll.register(&timer, Token(3), Ready::readable(), PollOpt::edge()).is_err());
+
+    poll.shutdown().unwrap();
+    assert!(poll.poll(&mut events, Some(Duration::from_millis(0))).is_err());
+}
+
+#[test]
+#[should_panic]
+pub fn test_registration_new_panics_without_userspace() {
+    let poll = Poll::new_no_userspace().unwrap();
+    let _ = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+}
+
+#[test]
+pub fn test_registration_producer_hup() {
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_registration, set_readiness) = Registration::new_with_producer_hup(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    let other = set_readiness.clone();
+
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(Event::new(Ready::readable(), Token(0)), events.get(0).unwrap());
+
+    // One producer left
+    drop(set_readiness);
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+
+    thread::spawn(move || drop(other)).join().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable() | Ready::hup(), Token(0)), events.get(0).unwrap());
+
+    // Dropping every handle of a default registration delivers nothing
+    let (_registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    drop(set_readiness);
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+pub fn test_set_readiness_coalesced_count() {
+    const SETS: usize = 10_000;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+    assert_eq!(0, set_readiness.coalesced_count());
+
+    for _ in 0..SETS {
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    }
+
+    // Only the first call queued the registration
+    assert_eq!(SETS - 1, set_readiness.coalesced_count());
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(1, events.len());
+
+    // Once dequeued, the next call queues it again
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    assert_eq!(SETS - 1, set_readiness.coalesced_count());
+
+    set_readiness.clone().set_readiness(Ready::readable()).unwrap();
+    assert_eq!(SETS, set_readiness.coalesced_count());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_poll_with_extra_fds() {
+    use std::os::unix::io::AsRawFd;
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let registered = UdpSocket::bind(&addr).unwrap();
+    let raw = UdpSocket::bind(&addr).unwrap();
+
+    poll.register(&registered, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+
+    // Nothing to read, the extra fd is reported as not ready
+    let mut extra = [(raw.as_raw_fd(), Ready::readable())];
+    let n = poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_millis(50)).unwrap();
+    assert_eq!(0, n);
+    assert_eq!(Ready::none(), extra[0].1);
+
+    // A datagram for the extra fd wakes the call up
+    let sender = UdpSocket::bind(&addr).unwrap();
+    let target = raw.local_addr().unwrap();
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        sender.send_to(b"hello", &target).unwrap();
+    });
+
+    let mut extra = [(raw.as_raw_fd(), Ready::readable())];
+    let n = poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_secs(5)).unwrap();
+    th.join().unwrap();
+
+    assert_eq!(0, n);
+    assert_eq!(Ready::readable(), extra[0].1);
+
+    // Level-triggered, and only the requested interest is reported
+    let mut extra = [(raw.as_raw_fd(), Ready::readable() | Ready::writable())];
+    poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_secs(5)).unwrap();
+    assert_eq!(Ready::readable() | Ready::writable(), extra[0].1);
+
+    let mut extra = [(raw.as_raw_fd(), Ready::writable())];
+    poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_secs(5)).unwrap();
+    assert_eq!(Ready::writable(), extra[0].1);
+
+    // Custom registrations are still delivered
+    let idle = UdpSocket::bind(&addr).unwrap();
+    let (_registration, set_readiness) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    let mut extra = [(idle.as_raw_fd(), Ready::readable())];
+    let n = poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_secs(5)).unwrap();
+    th.join().unwrap();
+
+    assert_eq!(1, n);
+    assert_eq!(Event::new(Ready::readable(), Token(1)), events.get(0).unwrap());
+    assert_eq!(Ready::none(), extra[0].1);
+}
+
+#[test]
+pub fn test_readiness_limit() {
+    use std::io::ErrorKind;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    assert_eq!(None, poll.readiness_limit());
+    poll.set_readiness_limit(Some(2));
+    assert_eq!(Some(2), poll.readiness_limit());
+
+    let (_r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    let (_r2, s2) = Registration::new(&poll, Token(2), Ready::readable(), PollOpt::edge());
+    let (_r3, s3) = Registration::new(&poll, Token(3), Ready::readable(), PollOpt::edge());
+
+    s1.set_readiness(Ready::readable()).unwrap();
+    s2.set_readiness(Ready::readable()).unwrap();
+
+    let err = s3.set_readiness(Ready::readable()).unwrap_err();
+    assert_eq!(ErrorKind::WouldBlock, err.kind());
+    assert_eq!(Ready::none(), s3.readiness());
+
+    // Already ready registrations are not counted twice
+    s1.set_readiness(Ready::readable()).unwrap();
+    s3.set_readiness(Ready::none()).unwrap();
+
+    let mut group = ReadinessGroup::new();
+    group.push(s1.clone()).unwrap();
+    group.push(s3.clone()).unwrap();
+    assert_eq!(ErrorKind::WouldBlock, group.set_all(Ready::readable()).unwrap_err().kind());
+
+    // Wakeups from mio itself are never refused
+    let wake = poll.register_wakeup_token(Token(10)).unwrap();
+    wake.wake().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    assert_eq!(3, events.len());
+
+    // `poll` caught up
+    s3.set_readiness(Ready::readable()).unwrap();
+    group.set_all(Ready::readable()).unwrap();
+
+    poll.set_readiness_limit(None);
+    s2.set_readiness(Ready::readable()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+    let tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    assert_eq!(vec![Token(3), Token(1), Token(2)], tokens);
+}
+
+#[test]
+pub fn test_events_reserve_after_saturation() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(2);
+    assert!(events.capacity() >= 2);
+
+    let socks: Vec<UdpSocket> = (0..4)
+        .map(|_| UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap())
+        .collect();
+
+    for (i, sock) in socks.iter().enumerate() {
+        poll.register(sock, Token(i), Ready::writable(), PollOpt::level()).unwrap();
+    }
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(events.capacity(), events.len());
+
+    // Growing keeps the events polled so far
+    let polled: Vec<Event> = events.iter().collect();
+    events.reserve(4);
+    assert!(events.capacity() >= 6);
+    assert_eq!(polled, events.iter().collect::<Vec<Event>>());
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(4, events.len());
+
+    let mut tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+    tokens.sort();
+    assert_eq!(vec![Token(0), Token(1), Token(2), Token(3)], tokens);
+}
+
+#[test]
+pub fn test_events_clear() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (_r1, s1) = Registration::new(&poll, Token(1), Ready::readable(), PollOpt::edge());
+    s1.set_readiness(Ready::readable()).unwrap();
+
+    assert_eq!(1, poll.poll_mut(&mut events, Some(Duration::from_millis(300))).unwrap());
+    let capacity = events.capacity();
+
+    events.clear();
+    assert!(events.is_empty());
+    assert_eq!(0, events.len());
+    assert_eq!(capacity, events.capacity());
+
+    // The unhandled event is not carried over once cleared
+    assert_eq!(0, poll.poll_mut(&mut events, Some(Duration::from_millis(0))).unwrap());
+}
+
+#[test]
+pub fn test_events_drain() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let socks: Vec<UdpSocket> = (0..3)
+        .map(|_| UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap())
+        .collect();
+
+    for (i, sock) in socks.iter().enumerate() {
+        poll.register(sock, Token(i), Ready::writable(), PollOpt::level()).unwrap();
+    }
+
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    assert_eq!(3, events.len());
+
+    let polled: Vec<Event> = events.iter().collect();
+    let drained: Vec<Event> = events.drain().collect();
+    assert_eq!(polled, drained);
+    assert!(events.is_empty());
+
+    // Dropping a partially consumed drain still empties the buffer
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+
+    {
+        let mut drain = events.drain();
+        assert_eq!((3, Some(3)), drain.size_hint());
+        assert!(drain.next().is_some());
+        assert_eq!((2, Some(2)), drain.size_hint());
+    }
+
+    assert!(events.is_empty());
+
+    // The owning iterator
+    poll.poll(&mut events, Some(Duration::from_millis(300))).unwrap();
+    let owned: Vec<Event> = events.into_iter().collect();
+    assert_eq!(3, owned.len());
+}
+
+#[test]
+pub fn test_waker() {
+    use std::sync::Arc;
+    use std::thread;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let waker = Arc::new(Waker::new(&poll, Token(7)).unwrap());
+    let remote = waker.clone();
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        remote.wake().unwrap();
+    });
+
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(7)), events.get(0).unwrap());
+
+    th.join().unwrap();
+
+    // Nothing more until the next wake
+    poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+    assert!(events.is_empty());
+
+    // Many wakeups never fill up the awakener
+    for _ in 0..100_000 {
+        waker.wake().unwrap();
+    }
+
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+
+    waker.wake().unwrap();
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Token(7), events.get(0).unwrap().token());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_waker_without_userspace() {
+    let poll = Poll::new_no_userspace().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let waker = Waker::new(&poll, Token(3)).unwrap();
+    waker.wake().unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(3)), events.get(0).unwrap());
+}
+
+#[test]
+pub fn test_poll_until() {
+    use std::thread;
+    use std::time::Instant;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let (_r, s) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+    // A readiness outside of the interest wakes the selector up without
+    // delivering an event, the wait goes on until the deadline
+    let deadline = Instant::now() + Duration::from_millis(300);
+    let handle = {
+        let s = s.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            s.set_readiness(Ready::writable()).unwrap();
+        })
+    };
+
+    assert_eq!(0, poll.poll_until(&mut events, deadline).unwrap());
+    assert!(Instant::now() >= deadline);
+    handle.join().unwrap();
+
+    s.set_readiness(Ready::readable()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    assert_eq!(1, poll.poll_until(&mut events, deadline).unwrap());
+    assert!(Instant::now() < deadline);
+    assert_eq!(Token(0), events.get(0).unwrap().token());
+
+    // A past deadline does not block
+    assert_eq!(0, poll.poll_until(&mut events, Instant::now()).unwrap());
+}
+
+#[test]
+pub fn test_sub_millisecond_timeout() {
+    use std::time::Instant;
+
+    let to = Duration::new(0, 200_000);
+
+    // Only meaningful where the selector doesn't round to milliseconds
+    if Poll::effective_timeout(to) != to {
+        return;
+    }
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    // Take the fastest of a few runs to tolerate scheduling hiccups
+    let mut best = Duration::from_secs(1);
+    for _ in 0..5 {
+        let now = Instant::now();
+        poll.poll(&mut events, Some(to)).unwrap();
+        let elapsed = now.elapsed();
+        assert!(elapsed >= to);
+        best = ::std::cmp::min(best, elapsed);
+    }
+
+    assert!(best < Duration::from_millis(1), "waited {:?}", best);
+}
+
+#[test]
+pub fn test_poll_builder() {
+    use std::thread;
+
+    let poll = Poll::builder().userspace(false).build().unwrap();
+    assert!(!poll.has_userspace());
+
+    // Setting readiness from another thread goes through the awakener
+    let mut kinds = vec![AwakenerKind::Pipe];
+    if cfg!(any(target_os = "linux", target_os = "android", windows)) {
+        kinds.push(AwakenerKind::EventFd);
+    }
+    if cfg!(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly", windows)) {
+        kinds.push(AwakenerKind::UserEvent);
+    }
+
+    for &kind in &kinds {
+        let poll = Poll::builder()
+            .awakener(kind)
+            .cloexec(false)
+            .poll_into_capacity(16)
+            .build()
+            .unwrap();
+        assert!(poll.has_userspace());
+
+        let mut events = Events::with_capacity(16);
+        let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+        let th = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            set_readiness.set_readiness(Ready::readable()).unwrap();
+        });
+
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        th.join().unwrap();
+
+        assert_eq!(1, events.len());
+        assert_eq!(Token(0), events.get(0).unwrap().token());
+
+        let mut dst = Vec::new();
+        poll.poll_into(&mut dst, Some(Duration::from_millis(0))).unwrap();
+        assert!(dst.is_empty());
+    }
+}
+
+#[test]
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub fn test_poll_builder_eventfd_unsupported() {
+    assert!(Poll::builder().awakener(AwakenerKind::EventFd).build().is_err());
+}
+
+#[test]
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))))]
+pub fn test_poll_builder_user_event_unsupported() {
+    assert!(Poll::builder().awakener(AwakenerKind::UserEvent).build().is_err());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_nested_poll_raw_fd() {
+    use mio::unix::EventedFd;
+    use std::os::unix::io::AsRawFd;
+    use std::thread;
+
+    let outer = Poll::new().unwrap();
+    let inner = Poll::new().unwrap();
+    let mut outer_events = Events::with_capacity(16);
+    let mut events = Events::with_capacity(16);
+
+    let fd = inner.as_raw_fd();
+    outer.register(&EventedFd(&fd), Token(0), Ready::readable(), PollOpt::level()).unwrap();
+
+    // Nothing registered yet, the outer poll times out
+    assert!(inner.prepare_external_wait());
+    assert_eq!(0, outer.poll(&mut outer_events, Some(Duration::from_millis(50))).unwrap());
+    assert_eq!(0, inner.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+
+    // Readiness set from another thread wakes the outer loop
+    let (_registration, set_readiness) = Registration::new(&inner, Token(1), Ready::readable(), PollOpt::edge());
+    assert!(inner.prepare_external_wait());
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    assert_eq!(1, outer.poll(&mut outer_events, Some(Duration::from_secs(5))).unwrap());
+    th.join().unwrap();
+
+    assert_eq!(1, inner.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+    assert_eq!(Token(1), events.get(0).unwrap().token());
+
+    // So do OS handles
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    inner.register(&sock, Token(2), Ready::writable(), PollOpt::edge()).unwrap();
+
+    assert_eq!(1, outer.poll(&mut outer_events, Some(Duration::from_secs(5))).unwrap());
+    assert_eq!(1, inner.poll(&mut events, Some(Duration::from_millis(0))).unwrap());
+    assert_eq!(Token(2), events.get(0).unwrap().token());
+}
+
+#[test]
+pub fn test_waker_with_select() {
+    let poll1 = Poll::new().unwrap();
+    let poll2 = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    let waker = Waker::new(&poll2, Token(4)).unwrap();
+    waker.wake().unwrap();
+
+    let ready = select(&[&poll1, &poll2], Duration::from_secs(5)).unwrap();
+    assert_eq!(vec![1], ready);
+
+    // Checking the instances doesn't consume the wakeup
+    poll2.poll(&mut events, Duration::from_secs(5)).unwrap();
+    assert_eq!(1, events.len());
+    assert_eq!(Event::new(Ready::readable(), Token(4)), events.get(0).unwrap());
+}
@@ -0,0 +1,123 @@
+use mio::PollOpt;
+
+#[test]
+pub fn test_poll_opt_predicates() {
+    let edge = PollOpt::edge();
+    assert!(edge.is_edge());
+    assert!(!edge.is_level());
+    assert!(!edge.is_oneshot());
+
+    let level = PollOpt::level();
+    assert!(!level.is_edge());
+    assert!(level.is_level());
+    assert!(!level.is_oneshot());
+
+    let edge_oneshot = PollOpt::edge() | PollOpt::oneshot();
+    assert!(edge_oneshot.is_edge());
+    assert!(!edge_oneshot.is_level());
+    assert!(edge_oneshot.is_oneshot());
+
+    let level_oneshot = PollOpt::level() | PollOpt::oneshot();
+    assert!(!level_oneshot.is_edge());
+    assert!(level_oneshot.is_level());
+    assert!(level_oneshot.is_oneshot());
+
+    let empty = PollOpt::empty();
+    assert!(!empty.is_edge());
+    assert!(!empty.is_level());
+    assert!(!empty.is_oneshot());
+    assert!(!empty.is_urgent());
+
+    assert!(PollOpt::urgent().is_urgent());
+    assert!(!edge_oneshot.is_urgent());
+
+    let mut opts = edge_oneshot;
+    opts.remove(PollOpt::oneshot());
+    assert_eq!(PollOpt::edge(), opts);
+    assert!(!opts.is_oneshot());
+}
+
+#[test]
+pub fn test_poll_opt_display() {
+    assert_eq!("(none)", format!("{}", PollOpt::empty()));
+    assert_eq!("edge", format!("{}", PollOpt::edge()));
+    assert_eq!("level", format!("{}", PollOpt::level()));
+    assert_eq!("edge|oneshot", format!("{}", PollOpt::edge() | PollOpt::oneshot()));
+    assert_eq!("level|oneshot", format!("{}", PollOpt::oneshot() | PollOpt::level()));
+    assert_eq!("edge|urgent", format!("{}", PollOpt::edge() | PollOpt::urgent()));
+}
+
+#[test]
+pub fn test_poll_opt_builder() {
+    assert_eq!(PollOpt::empty(), PollOpt::builder().build().unwrap());
+    assert_eq!(PollOpt::edge(), PollOpt::builder().edge().build().unwrap());
+    assert_eq!(PollOpt::level() | PollOpt::oneshot(), PollOpt::builder().level().oneshot().build().unwrap());
+    assert_eq!(PollOpt::edge() | PollOpt::urgent(), PollOpt::builder().urgent().edge().build().unwrap());
+
+    // Requesting a flag twice is harmless
+    assert_eq!(PollOpt::edge(), PollOpt::builder().edge().edge().build().unwrap());
+}
+
+#[test]
+pub fn test_poll_opt_builder_rejects_edge_and_level() {
+    use std::io::ErrorKind;
+
+    let err = PollOpt::builder().edge().level().build().unwrap_err();
+    assert_eq!(ErrorKind::InvalidInput, err.kind());
+
+    assert!(PollOpt::builder().level().oneshot().edge().build().is_err());
+    assert!(PollOpt::builder().edge().urgent().level().build().is_err());
+}
+
+#[test]
+pub fn test_poll_opt_exclusive() {
+    use std::io::ErrorKind;
+
+    let opts = PollOpt::edge() | PollOpt::exclusive();
+    assert!(opts.is_exclusive());
+    assert!(opts.is_edge());
+    assert!(!PollOpt::edge().is_exclusive());
+    assert_eq!("edge|exclusive", format!("{}", opts));
+
+    assert_eq!(opts, PollOpt::builder().exclusive().edge().build().unwrap());
+
+    let err = PollOpt::builder().exclusive().oneshot().build().unwrap_err();
+    assert_eq!(ErrorKind::InvalidInput, err.kind());
+    assert!(PollOpt::builder().urgent().exclusive().build().is_err());
+}
+
+#[test]
+pub fn test_poll_opt_exclusive_listener() {
+    use mio::{Events, Poll, Ready, Token};
+    use mio::tcp::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // The same listening socket, shared by two pollers
+    let listeners = [listener.try_clone().unwrap(), listener.try_clone().unwrap()];
+    let polls = [Poll::new().unwrap(), Poll::new().unwrap()];
+    for (poll, listener) in polls.iter().zip(listeners.iter()) {
+        poll.register(listener, Token(0), Ready::readable(), PollOpt::edge() | PollOpt::exclusive()).unwrap();
+    }
+
+    let _stream = TcpStream::connect(&addr).unwrap();
+
+    let mut woken = 0;
+    for poll in &polls {
+        let mut events = Events::with_capacity(16);
+        poll.poll(&mut events, Some(Duration::from_millis(500))).unwrap();
+        woken += events.len();
+    }
+
+    // At least one is woken up, with epoll usually only one
+    assert!(woken >= 1);
+    assert!(listener.accept().is_ok());
+
+    // Exclusive registrations can't be modified on Linux, only replaced
+    for (poll, listener) in polls.iter().zip(listeners.iter()) {
+        poll.deregister(listener).unwrap();
+        poll.register(listener, Token(1), Ready::readable(), PollOpt::edge() | PollOpt::exclusive()).unwrap();
+    }
+}
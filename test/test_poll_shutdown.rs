@@ -0,0 +1,156 @@
+use mio::*;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+pub fn test_shutdown_blocked_poll() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let handle = poll.shutdown_handle();
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        handle.shutdown().unwrap();
+    });
+
+    // Blocks with no timeout until the other thread shuts the poll down
+    let err = poll.poll(&mut events, None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    assert!(poll.is_shutdown());
+
+    // All subsequent polls fail as well
+    assert!(poll.poll(&mut events, Some(Duration::from_millis(0))).is_err());
+
+    th.join().unwrap();
+}
+
+#[test]
+pub fn test_shutdown_same_thread() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+
+    assert!(!poll.is_shutdown());
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+    poll.shutdown().unwrap();
+    assert!(poll.poll(&mut events, None).is_err());
+}
+
+#[test]
+pub fn test_shutdown_is_permanent() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let handle = poll.shutdown_handle();
+
+    poll.shutdown().unwrap();
+    handle.shutdown().unwrap();
+    assert!(poll.is_shutdown());
+
+    for _ in 0..3 {
+        assert!(poll.poll(&mut events, None).is_err());
+    }
+
+    // The handle may outlive the `Poll`
+    drop(poll);
+    handle.shutdown().unwrap();
+}
+
+#[test]
+pub fn test_cancel_blocked_poll() {
+    use std::time::Instant;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let token = poll.cancel_token();
+
+    let th = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        token.cancel().unwrap();
+    });
+
+    // Blocks with no timeout until the other thread cancels it
+    assert_eq!(0, poll.poll(&mut events, None).unwrap());
+    assert!(!poll.is_shutdown());
+
+    th.join().unwrap();
+
+    // Subsequent polls behave normally
+    let start = Instant::now();
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(200))).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
+
+#[test]
+pub fn test_cancel_is_one_shot() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let token = poll.cancel_token();
+
+    let (_registration, set_readiness) = Registration::new(&poll, Token(0), Ready::readable(), PollOpt::edge());
+
+    // Each call cancels exactly one poll
+    set_readiness.set_readiness(Ready::readable()).unwrap();
+    token.cancel().unwrap();
+    token.cancel().unwrap();
+
+    assert_eq!(0, poll.poll(&mut events, None).unwrap());
+    assert_eq!(0, poll.poll(&mut events, None).unwrap());
+
+    // The readiness set meanwhile is kept for the next call
+    assert_eq!(1, poll.poll(&mut events, None).unwrap());
+    assert_eq!(Event::new(Ready::readable(), Token(0)), events.get(0).unwrap());
+
+    // Also with nothing else to wake up for
+    token.cancel().unwrap();
+    token.cancel().unwrap();
+
+    assert_eq!(0, poll.poll(&mut events, None).unwrap());
+    assert_eq!(0, poll.poll(&mut events, None).unwrap());
+
+    assert_eq!(0, poll.poll(&mut events, Some(Duration::from_millis(50))).unwrap());
+}
+
+#[test]
+#[cfg(unix)]
+pub fn test_cancel_poll_with_extra_fds() {
+    use mio::udp::UdpSocket;
+    use std::os::unix::io::AsRawFd;
+    use std::time::Instant;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(1024);
+    let token = poll.cancel_token();
+
+    let idle = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+
+    // A cancellation issued before the call returns it right away, even
+    // once the awakener was drained by an earlier call
+    token.cancel().unwrap();
+    token.cancel().unwrap();
+    assert_eq!(0, poll.poll(&mut events, None).unwrap());
+
+    let start = Instant::now();
+    let mut extra = [(idle.as_raw_fd(), Ready::readable())];
+    assert_eq!(0, poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_secs(5)).unwrap());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(Ready::none(), extra[0].1);
+
+    // The cancellation was consumed
+    let start = Instant::now();
+    let mut extra = [(idle.as_raw_fd(), Ready::readable())];
+    assert_eq!(0, poll.poll_with_extra_fds(&mut events, &mut extra, Duration::from_millis(200)).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
+
+fn assert_send<T: Send>() {
+}
+
+fn assert_sync<T: Sync>() {
+}
+
+#[test]
+fn test_shutdown_handle_is_send() {
+    assert_send::<ShutdownHandle>();
+    assert_sync::<ShutdownHandle>();
+}
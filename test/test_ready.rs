@@ -0,0 +1,138 @@
+use mio::{Interest, Poll, PollOpt, Ready, Token};
+use mio::tcp::TcpListener;
+
+const ALL: [fn() -> Ready; 4] = [Ready::readable, Ready::writable, Ready::error, Ready::hup];
+
+#[test]
+pub fn test_ready_default_is_empty() {
+    #[derive(Default)]
+    struct Interest {
+        ready: Ready,
+    }
+
+    assert_eq!(Ready::none(), Ready::default());
+    assert_eq!(Ready::none(), Interest::default().ready);
+
+    assert!(Ready::default().is_empty());
+    assert!(Ready::none().is_empty());
+    assert!(!Ready::readable().is_empty());
+    assert!(!Ready::all().is_empty());
+}
+
+#[test]
+pub fn test_ready_not_stays_within_public_bits() {
+    assert_eq!(Ready::all(), !Ready::none());
+    assert_eq!(Ready::none(), !Ready::all());
+
+    // Every combination of the public flags round-trips through the
+    // complement and never sets bits outside of `Ready::all()`.
+    for mask in 0..(1 << ALL.len()) {
+        let mut ready = Ready::none();
+
+        for (i, flag) in ALL.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                ready.insert(flag());
+            }
+        }
+
+        let complement = !ready;
+
+        assert_eq!(0, complement.bits() & !Ready::all().bits());
+        assert_eq!(Ready::none(), ready & complement);
+        assert_eq!(Ready::all(), ready | complement);
+        assert_eq!(ready, !complement);
+        assert_eq!(ready.is_empty(), complement == Ready::all());
+    }
+}
+
+#[test]
+pub fn test_ready_display() {
+    assert_eq!("(none)", format!("{}", Ready::none()));
+    assert_eq!("readable", format!("{}", Ready::readable()));
+    assert_eq!("writable", format!("{}", Ready::writable()));
+    assert_eq!("readable|writable", format!("{}", Ready::readable() | Ready::writable()));
+    assert_eq!("readable|hup", format!("{}", Ready::hup() | Ready::readable()));
+    assert_eq!("readable|writable|error|hup", format!("{}", Ready::all()));
+
+    // Debug stays distinct
+    assert_eq!("Ready {Readable | Writable}", format!("{:?}", Ready::readable() | Ready::writable()));
+}
+
+#[test]
+pub fn test_event_predicates() {
+    use mio::{Event, Token};
+
+    let event = Event::new(Ready::none(), Token(0));
+    assert!(!event.is_readable());
+    assert!(!event.is_writable());
+    assert!(!event.is_error());
+    assert!(!event.is_hup());
+
+    let event = Event::new(Ready::readable() | Ready::hup(), Token(0));
+    assert!(event.is_readable());
+    assert!(!event.is_writable());
+    assert!(!event.is_error());
+    assert!(event.is_hup());
+
+    let event = Event::new(Ready::writable() | Ready::error(), Token(0));
+    assert!(!event.is_readable());
+    assert!(event.is_writable());
+    assert!(event.is_error());
+    assert!(!event.is_hup());
+
+    // Each predicate matches the accessor on `kind()`
+    for flag in ALL.iter() {
+        let event = Event::new(flag(), Token(1));
+
+        assert_eq!(event.kind().is_readable(), event.is_readable());
+        assert_eq!(event.kind().is_writable(), event.is_writable());
+        assert_eq!(event.kind().is_error(), event.is_error());
+        assert_eq!(event.kind().is_hup(), event.is_hup());
+    }
+}
+
+#[test]
+pub fn test_interest_converts_to_ready() {
+    assert_eq!(Ready::readable(), Ready::from(Interest::readable()));
+    assert_eq!(Ready::writable(), Ready::from(Interest::writable()));
+    assert_eq!(Ready::readable() | Ready::writable(),
+               Ready::from(Interest::readable() | Interest::writable()));
+
+    let both = Interest::readable() | Interest::writable();
+    assert!(both.contains(Interest::writable()));
+    assert!(!Interest::readable().contains(both));
+    assert_eq!("Interest {Readable|Writable}", format!("{:?}", both));
+}
+
+#[test]
+pub fn test_register_with_interest() {
+    let poll = Poll::new().unwrap();
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+
+    poll.register(&listener, Token(0), Interest::readable(), PollOpt::edge()).unwrap();
+    poll.reregister(&listener, Token(0), Interest::readable() | Interest::writable(), PollOpt::edge()).unwrap();
+
+    // A `Ready` is still accepted
+    poll.reregister(&listener, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+}
+
+#[test]
+pub fn test_ready_priority() {
+    use mio::{Event, Token};
+
+    let ready = Ready::readable() | Ready::priority();
+    assert!(ready.is_priority());
+    assert!(!Ready::readable().is_priority());
+    assert!(!Ready::all().is_priority());
+    assert!(!Ready::priority().is_none());
+    assert_eq!("readable|priority", format!("{}", ready));
+    assert_eq!("Ready {Readable | Priority}", format!("{:?}", ready));
+
+    let event = Event::new(Ready::priority(), Token(0));
+    assert!(event.is_priority());
+    assert!(!event.is_readable());
+
+    let interest = Interest::readable() | Interest::priority();
+    assert!(interest.is_priority());
+    assert_eq!(Ready::readable() | Ready::priority(), Ready::from(interest));
+}
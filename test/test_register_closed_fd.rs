@@ -0,0 +1,32 @@
+use mio::*;
+use mio::unix::EventedFd;
+use mio::udp::UdpSocket;
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+#[test]
+pub fn test_register_closed_fd() {
+    let poll = Poll::new().unwrap();
+
+    // Closing a real fd would race with other tests reusing the number, so
+    // use one that is above any file descriptor limit and thus never open.
+    let fd: RawFd = i32::max_value() - 1;
+
+    let err = poll.register(&EventedFd(&fd), Token(7), Ready::readable(), PollOpt::edge())
+        .unwrap_err();
+
+    assert_eq!(ErrorKind::NotFound, err.kind());
+    assert!(format!("{}", err).contains("Token(7)"), "{}", err);
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+pub fn test_reregister_unregistered_fd() {
+    let poll = Poll::new().unwrap();
+    let sock = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+
+    let err = poll.reregister(&EventedFd(&sock.as_raw_fd()), Token(3), Ready::readable(), PollOpt::edge())
+        .unwrap_err();
+
+    assert_eq!(ErrorKind::NotFound, err.kind());
+}
@@ -443,3 +443,292 @@ fn multiple_writes_immediate_success() {
 
     t.join().unwrap();
 }
+
+// Windows only learns about the peer closing through a read
+#[cfg(unix)]
+#[test]
+fn read_half_close_is_hup() {
+    let l = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = l.local_addr().unwrap();
+
+    let poll = Poll::new().unwrap();
+    let s = TcpStream::connect(&addr).unwrap();
+    poll.register(&s, Token(1), Ready::readable(), PollOpt::edge()).unwrap();
+    let mut events = Events::with_capacity(16);
+
+    // The peer stops writing but keeps the connection open
+    let peer = l.accept().unwrap().0;
+    peer.shutdown(net::Shutdown::Write).unwrap();
+
+    'outer: loop {
+        poll.poll(&mut events, None).unwrap();
+        for event in events.iter() {
+            if event.token() == Token(1) && event.kind().is_hup() {
+                assert!(event.kind().is_readable());
+                assert!(!event.kind().is_error());
+                break 'outer
+            }
+        }
+    }
+
+    drop(peer);
+}
+
+#[test]
+fn stream_socket_options() {
+    let l = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let s = TcpStream::connect(&l.local_addr().unwrap()).unwrap();
+
+    s.set_nodelay(true).unwrap();
+    assert!(s.nodelay().unwrap());
+
+    s.set_keepalive(Some(Duration::from_secs(60))).unwrap();
+    assert_eq!(Some(Duration::from_secs(60)), s.keepalive().unwrap());
+    s.set_keepalive(None).unwrap();
+    assert_eq!(None, s.keepalive().unwrap());
+
+    s.set_linger(Some(Duration::from_secs(0))).unwrap();
+    assert_eq!(Some(Duration::from_secs(0)), s.linger().unwrap());
+    s.set_linger(None).unwrap();
+    assert_eq!(None, s.linger().unwrap());
+
+    s.set_ttl(32).unwrap();
+    assert_eq!(32, s.ttl().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn bind_reuse_port() {
+    use mio::tcp::BindOptions;
+
+    let options = BindOptions::new().reuse_port(true);
+
+    let l1 = TcpListener::bind_with_options(&"127.0.0.1:0".parse().unwrap(), &options).unwrap();
+    let addr = l1.local_addr().unwrap();
+
+    let l2 = TcpListener::bind_with_options(&addr, &options).unwrap();
+    assert_eq!(addr, l2.local_addr().unwrap());
+
+    // Like `bind_twice_bad`, a listener without the option is refused
+    assert!(TcpListener::bind(&addr).is_err());
+}
+
+#[test]
+fn builder_listen_and_connect() {
+    use mio::tcp::TcpBuilder;
+
+    let builder = TcpBuilder::new_v4().unwrap();
+    builder.reuse_address(true).unwrap().ttl(32).unwrap();
+    builder.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = builder.local_addr().unwrap();
+
+    let l = builder.listen(16).unwrap();
+    assert_eq!(addr, l.local_addr().unwrap());
+    assert_eq!(32, l.ttl().unwrap());
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let s = TcpBuilder::new_v4().unwrap().connect(&addr).unwrap();
+    poll.register(&s, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+
+    'outer: loop {
+        poll.poll(&mut events, None).unwrap();
+
+        for event in &events {
+            if event.token() == Token(0) && event.kind().is_writable() {
+                break 'outer;
+            }
+        }
+    }
+
+    assert_eq!(addr, s.peer_addr().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn builder_socket_options() {
+    use mio::tcp::TcpBuilder;
+
+    let builder = TcpBuilder::new_v4().unwrap();
+    builder.reuse_port(true).unwrap().tos(0x10).unwrap();
+    builder.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = builder.local_addr().unwrap();
+    let l1 = builder.listen(16).unwrap();
+
+    // A second listener may share the port as both set `SO_REUSEPORT`
+    let builder = TcpBuilder::new_v4().unwrap();
+    builder.reuse_port(true).unwrap();
+    builder.bind(&addr).unwrap();
+    let l2 = builder.listen(16).unwrap();
+
+    assert_eq!(l1.local_addr().unwrap(), l2.local_addr().unwrap());
+}
+
+#[test]
+fn std_conversions() {
+    let std_listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = std_listener.local_addr().unwrap();
+
+    let l = TcpListener::from_std(std_listener).unwrap();
+    assert_eq!(addr, l.local_addr().unwrap());
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+    poll.register(&l, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+
+    let std_stream = net::TcpStream::connect(&addr).unwrap();
+    let s = TcpStream::from_std(std_stream).unwrap();
+
+    let accepted = loop {
+        poll.poll(&mut events, None).unwrap();
+
+        match l.accept() {
+            Ok((stream, _)) => break stream,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("accept failed: {}", e),
+        }
+    };
+
+    poll.deregister(&l).unwrap();
+    let std_listener = l.into_std().unwrap();
+    assert_eq!(addr, std_listener.local_addr().unwrap());
+
+    // Back in the std type, the stream can be used with blocking calls
+    let mut std_stream = s.into_std().unwrap();
+    std_stream.set_nonblocking(false).unwrap();
+    std_stream.write_all(b"ping").unwrap();
+
+    let mut accepted = accepted.into_std().unwrap();
+    accepted.set_nonblocking(false).unwrap();
+    let mut buf = [0; 4];
+    accepted.read_exact(&mut buf).unwrap();
+    assert_eq!(b"ping", &buf);
+}
+
+#[cfg(unix)]
+#[test]
+fn sockets_are_cloexec() {
+    use std::os::unix::io::AsRawFd;
+    use mio::tcp::TcpBuilder;
+
+    fn is_cloexec<T: AsRawFd>(t: &T) -> bool {
+        let flags = unsafe { ::libc::fcntl(t.as_raw_fd(), ::libc::F_GETFD) };
+        assert!(flags != -1);
+        flags & ::libc::FD_CLOEXEC != 0
+    }
+
+    let l = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    assert!(is_cloexec(&l));
+    let addr = l.local_addr().unwrap();
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+    poll.register(&l, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+
+    // Inheritable on request
+    let builder = TcpBuilder::new_v4().unwrap();
+    builder.cloexec(false).unwrap();
+    let s = builder.connect(&addr).unwrap();
+    assert!(!is_cloexec(&s));
+
+    let accepted = loop {
+        poll.poll(&mut events, None).unwrap();
+
+        match l.accept() {
+            Ok((stream, _)) => break stream,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("accept failed: {}", e),
+        }
+    };
+
+    assert!(is_cloexec(&accepted));
+    assert_eq!(s.local_addr().unwrap(), accepted.peer_addr().unwrap());
+}
+
+#[test]
+fn connect_refused_take_error() {
+    use mio::TakeError;
+
+    fn pending_error<S: TakeError>(socket: &S) -> Option<io::ErrorKind> {
+        socket.take_error().unwrap().map(|e| e.kind())
+    }
+
+    // Grab a port nobody listens on
+    let addr = {
+        let l = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        l.local_addr().unwrap()
+    };
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let s = TcpStream::connect(&addr).unwrap();
+    poll.register(&s, Token(0), Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
+
+    let mut kind = Ready::none();
+    while !(kind.is_error() || kind.is_hup()) {
+        poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+        for event in &events {
+            kind = kind | event.kind();
+        }
+    }
+
+    assert_eq!(Some(io::ErrorKind::ConnectionRefused), pending_error(&s));
+    assert_eq!(None, pending_error(&s));
+
+    let l = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    assert_eq!(None, pending_error(&l));
+}
+
+#[cfg(unix)]
+#[test]
+fn evented_fd_take_error() {
+    use std::os::unix::io::AsRawFd;
+    use mio::TakeError;
+    use mio::unix::EventedFd;
+
+    let l = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let fd = l.as_raw_fd();
+    assert!(EventedFd(&fd).take_error().unwrap().is_none());
+
+    // Not a socket
+    let file = ::std::fs::File::open("/dev/null").unwrap();
+    let fd = file.as_raw_fd();
+    assert!(EventedFd(&fd).take_error().is_err());
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn priority_urgent_data() {
+    use std::os::unix::io::AsRawFd;
+    use mio::Interest;
+
+    let l = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = l.local_addr().unwrap();
+
+    let s = net::TcpStream::connect(&addr).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let (stream, _) = l.accept().unwrap();
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+    poll.register(&stream, Token(0), Interest::priority(), PollOpt::edge()).unwrap();
+
+    // Regular data doesn't wake up a priority-only registration
+    (&s).write_all(b"a").unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(100))).unwrap();
+    assert!(events.iter().all(|e| !e.is_priority()));
+
+    let buf = [b'!'];
+    let res = unsafe {
+        ::libc::send(s.as_raw_fd(), buf.as_ptr() as *const _, 1, ::libc::MSG_OOB)
+    };
+    assert_eq!(1, res);
+
+    poll.poll(&mut events, Some(Duration::from_secs(5))).unwrap();
+    let event = events.get(0).unwrap();
+    assert_eq!(Token(0), event.token());
+    assert!(event.is_priority());
+    assert!(event.kind().is_priority());
+}
@@ -0,0 +1,89 @@
+use mio::{Token, TokenAllocator};
+use std::usize;
+
+#[test]
+pub fn test_token_checked_add() {
+    assert_eq!(Some(Token(5)), Token(2).checked_add(3));
+    assert_eq!(Some(Token(usize::MAX)), Token(usize::MAX - 1).checked_add(1));
+    assert_eq!(None, Token(usize::MAX).checked_add(1));
+    assert_eq!(None, Token(1).checked_add(usize::MAX));
+}
+
+#[test]
+pub fn test_token_is_reserved() {
+    assert!(!Token(0).is_reserved());
+    assert!(!Token(1 << 20).is_reserved());
+
+    // The awakener's token
+    assert!(Token(usize::MAX).is_reserved());
+
+    // Wake tokens
+    assert!(Token(usize::MAX - 0x100ff).is_reserved());
+    assert!(!Token(usize::MAX - 0x10100).is_reserved());
+}
+
+#[test]
+pub fn test_token_allocation_stops_at_reserved() {
+    let mut next = Some(Token(usize::MAX - 0x10102));
+    let mut allocated = vec![];
+
+    while let Some(token) = next {
+        if token.is_reserved() {
+            break;
+        }
+
+        allocated.push(token);
+        next = token.checked_add(1);
+    }
+
+    assert_eq!(vec![Token(usize::MAX - 0x10102),
+                    Token(usize::MAX - 0x10101),
+                    Token(usize::MAX - 0x10100)], allocated);
+}
+
+#[test]
+pub fn test_token_allocator_recycles_indices() {
+    let mut tokens = TokenAllocator::new();
+    assert!(tokens.is_empty());
+
+    let a = tokens.allocate().unwrap();
+    let b = tokens.allocate().unwrap();
+    assert_eq!(0, TokenAllocator::index(a));
+    assert_eq!(1, TokenAllocator::index(b));
+    assert_eq!(2, tokens.len());
+
+    assert!(tokens.release(a));
+    assert!(!tokens.release(a));
+
+    // The index is reused, with a new generation
+    let c = tokens.allocate().unwrap();
+    assert_eq!(0, TokenAllocator::index(c));
+    assert!(c != a);
+    assert!(!c.is_reserved());
+
+    // Events for the released token are detected as stale
+    assert!(!tokens.contains(a));
+    assert!(tokens.contains(b));
+    assert!(tokens.contains(c));
+    assert!(!tokens.contains(Token(2)));
+}
+
+#[test]
+pub fn test_token_allocator_generation_wraps() {
+    let mut tokens = TokenAllocator::new();
+    let first = tokens.allocate().unwrap();
+    let mut token = first;
+    let mut wrapped = false;
+
+    for _ in 0..0x1_0000 {
+        assert!(tokens.release(token));
+        token = tokens.allocate().unwrap();
+
+        assert!(!token.is_reserved());
+        assert_eq!(0, TokenAllocator::index(token));
+        wrapped |= token == first;
+    }
+
+    assert!(wrapped);
+    assert_eq!(1, tokens.len());
+}
@@ -97,3 +97,193 @@ pub fn test_udp_socket() {
     info!("Starting event loop to test with...");
     event_loop.run(&mut UdpHandler::new(tx, rx, "hello world")).unwrap();
 }
+
+#[cfg(unix)]
+#[test]
+pub fn test_udp_bind_reuse_port() {
+    let options = BindOptions::new().reuse_port(true);
+
+    let first = UdpSocket::bind_with_options(&"127.0.0.1:0".parse().unwrap(), &options).unwrap();
+    let addr = first.local_addr().unwrap();
+
+    // Both sockets share the port
+    let second = UdpSocket::bind_with_options(&addr, &options).unwrap();
+    assert_eq!(addr, second.local_addr().unwrap());
+
+    // Without the option, the port is taken
+    assert!(UdpSocket::bind(&addr).is_err());
+}
+
+#[test]
+pub fn test_udp_builder() {
+    let builder = UdpBuilder::new_v4().unwrap();
+    builder.reuse_address(true).unwrap().ttl(32).unwrap();
+
+    let socket = builder.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    assert_eq!(32, socket.ttl().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+pub fn test_udp_builder_reuse_port() {
+    let builder = UdpBuilder::new_v4().unwrap();
+    builder.reuse_port(true).unwrap().tos(0x10).unwrap();
+    let first = builder.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = first.local_addr().unwrap();
+
+    let builder = UdpBuilder::new_v4().unwrap();
+    builder.reuse_port(true).unwrap();
+    let second = builder.bind(&addr).unwrap();
+
+    assert_eq!(addr, second.local_addr().unwrap());
+}
+
+#[test]
+pub fn test_udp_mmsg() {
+    use std::time::Duration;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let tx = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let rx1 = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let rx2 = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr1 = rx1.local_addr().unwrap();
+    let addr2 = rx2.local_addr().unwrap();
+
+    poll.register(&tx, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+    poll.register(&rx1, Token(1), Ready::readable(), PollOpt::edge()).unwrap();
+
+    poll.poll(&mut events, Some(Duration::from_millis(500))).unwrap();
+
+    let msgs: &[(&[u8], _)] = &[(b"one", addr1), (b"three", addr1), (b"two", addr2)];
+    let mut sent = 0;
+
+    while sent < msgs.len() {
+        if let Some(n) = tx.send_mmsg(&msgs[sent..]).unwrap() {
+            sent += n;
+        } else {
+            poll.poll(&mut events, Some(Duration::from_millis(500))).unwrap();
+        }
+    }
+
+    let mut a = [0; 16];
+    let mut b = [0; 16];
+    let mut c = [0; 16];
+    let mut received = Vec::new();
+
+    for _ in 0..10 {
+        poll.poll(&mut events, Some(Duration::from_millis(500))).unwrap();
+
+        let mut bufs: [&mut [u8]; 3] = [&mut a, &mut b, &mut c];
+        let start = received.len();
+        if let Some(datagrams) = rx1.recv_mmsg(&mut bufs[start..]).unwrap() {
+            received.extend(datagrams);
+        }
+
+        if received.len() == 2 {
+            break;
+        }
+    }
+
+    let tx_addr = tx.local_addr().unwrap();
+    assert_eq!(vec![(3, tx_addr), (5, tx_addr)], received);
+    assert_eq!(b"one", &a[..3]);
+    assert_eq!(b"three", &b[..5]);
+
+    let mut buf = [0; 16];
+    let mut bufs: [&mut [u8]; 1] = [&mut buf];
+    let mut datagrams = None;
+    for _ in 0..10 {
+        datagrams = rx2.recv_mmsg(&mut bufs).unwrap();
+        if datagrams.is_some() {
+            break;
+        }
+        ::sleep_ms(50);
+    }
+
+    assert_eq!(Some(vec![(3, tx_addr)]), datagrams);
+}
+
+#[test]
+pub fn test_udp_connected() {
+    use std::time::Duration;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let a = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let b = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    a.connect(&b.local_addr().unwrap()).unwrap();
+    b.connect(&a.local_addr().unwrap()).unwrap();
+
+    poll.register(&a, Token(0), Ready::writable(), PollOpt::edge()).unwrap();
+    poll.register(&b, Token(1), Ready::readable(), PollOpt::edge()).unwrap();
+
+    let mut sent = false;
+    let mut buf = [0; 16];
+    let mut received = None;
+
+    for _ in 0..10 {
+        poll.poll(&mut events, Some(Duration::from_millis(500))).unwrap();
+
+        if !sent {
+            sent = a.send(b"hello").unwrap().is_some();
+        }
+
+        received = b.recv(&mut buf).unwrap();
+        if received.is_some() {
+            break;
+        }
+    }
+
+    assert_eq!(Some(5), received);
+    assert_eq!(b"hello", &buf[..5]);
+}
+
+#[cfg(unix)]
+#[test]
+pub fn test_udp_connected_icmp_error() {
+    use std::io::ErrorKind;
+
+    let a = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+
+    // Nothing listens on the port once the socket is gone
+    let closed = UdpSocket::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = closed.local_addr().unwrap();
+    drop(closed);
+
+    a.connect(&addr).unwrap();
+    assert_eq!(Some(5), a.send(b"hello").unwrap());
+
+    let mut error = None;
+    for _ in 0..10 {
+        ::sleep_ms(50);
+        error = a.take_error().unwrap();
+        if error.is_some() {
+            break;
+        }
+    }
+
+    assert_eq!(Some(ErrorKind::ConnectionRefused), error.map(|e| e.kind()));
+}
+
+#[test]
+pub fn test_udp_std_conversions() {
+    let std_socket = ::std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = std_socket.local_addr().unwrap();
+
+    let socket = UdpSocket::from_std(std_socket).unwrap();
+    assert_eq!(addr, socket.local_addr().unwrap());
+
+    // `from_std` leaves the socket in non-blocking mode
+    let mut buf = [0; 16];
+    assert!(socket.recv_from(&mut buf).unwrap().is_none());
+
+    let std_socket = socket.into_std().unwrap();
+    assert_eq!(addr, std_socket.local_addr().unwrap());
+
+    std_socket.set_nonblocking(false).unwrap();
+    std_socket.send_to(b"hello", &addr).unwrap();
+    assert_eq!((5, addr), std_socket.recv_from(&mut buf).unwrap());
+}
@@ -0,0 +1,297 @@
+use mio::*;
+use mio::uds::{UnixListener, UnixStream};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use tempdir::TempDir;
+
+const LISTENER: Token = Token(0);
+const CLIENT: Token = Token(1);
+const SERVER: Token = Token(2);
+
+fn wait_for(poll: &Poll, events: &mut Events, expect: &[(Token, Ready)]) {
+    let mut seen = vec![false; expect.len()];
+
+    for _ in 0..10 {
+        poll.poll(events, Some(Duration::from_millis(500))).unwrap();
+
+        for event in events.iter() {
+            for (i, &(token, ready)) in expect.iter().enumerate() {
+                if event.token() == token && event.kind().contains(ready) {
+                    seen[i] = true;
+                }
+            }
+        }
+
+        if seen.iter().all(|&s| s) {
+            return;
+        }
+    }
+
+    panic!("never received all of {:?}", expect);
+}
+
+#[test]
+pub fn test_uds_connect_accept() {
+    let dir = TempDir::new("uds").unwrap();
+    let path = dir.path().join("sock");
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let listener = UnixListener::bind(&path).unwrap();
+    assert_eq!(Some(path.as_path()), listener.local_addr().unwrap().as_pathname());
+
+    poll.register(&listener, LISTENER, Ready::readable(), PollOpt::edge()).unwrap();
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    poll.register(&client, CLIENT, Ready::writable(), PollOpt::edge()).unwrap();
+
+    wait_for(&poll, &mut events, &[(LISTENER, Ready::readable()), (CLIENT, Ready::writable())]);
+
+    let (mut server, addr) = listener.accept().unwrap();
+    assert!(addr.is_unnamed());
+    assert_eq!(Some(path.as_path()), client.peer_addr().unwrap().as_pathname());
+    assert!(client.local_addr().unwrap().is_unnamed());
+
+    // No more pending connections
+    assert_eq!(io::ErrorKind::WouldBlock, listener.accept().unwrap_err().kind());
+
+    poll.register(&server, SERVER, Ready::readable(), PollOpt::edge()).unwrap();
+
+    // The connection is established once accepted
+    client.write_all(b"hello").unwrap();
+
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+
+    let mut buf = [0; 16];
+    assert_eq!(5, server.read(&mut buf).unwrap());
+    assert_eq!(b"hello", &buf[..5]);
+}
+
+#[test]
+pub fn test_uds_pair() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (mut a, b) = UnixStream::pair().unwrap();
+    assert!(a.local_addr().unwrap().is_unnamed());
+    assert!(b.peer_addr().unwrap().is_unnamed());
+
+    poll.register(&b, SERVER, Ready::readable(), PollOpt::edge()).unwrap();
+
+    // Nothing to read yet, the socket does not block
+    let mut buf = [0; 16];
+    assert_eq!(io::ErrorKind::WouldBlock, (&b).read(&mut buf).unwrap_err().kind());
+
+    a.write_all(b"ping").unwrap();
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+
+    assert_eq!(4, (&b).read(&mut buf).unwrap());
+    assert_eq!(b"ping", &buf[..4]);
+
+    // The peer closing is reported as readable, with a read of 0 bytes
+    drop(a);
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+    assert_eq!(0, (&b).read(&mut buf).unwrap());
+}
+
+#[test]
+pub fn test_uds_vectored_io() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (a, b) = UnixStream::pair().unwrap();
+    poll.register(&b, SERVER, Ready::readable(), PollOpt::edge()).unwrap();
+
+    let header: &[u8] = b"head";
+    let body: &[u8] = b"body!";
+    assert_eq!(9, a.write_bufs(&[header.into(), body.into()]).unwrap());
+
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+
+    let mut first = [0; 2];
+    let mut second = [0; 16];
+    let n = {
+        let mut bufs: [&mut IoVec; 2] = [(&mut first[..]).into(), (&mut second[..]).into()];
+        b.read_bufs(&mut bufs).unwrap()
+    };
+
+    assert_eq!(9, n);
+    assert_eq!(b"he", &first);
+    assert_eq!(b"adbody!", &second[..7]);
+}
+
+#[test]
+pub fn test_uds_pass_fd() {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let (c, mut d) = UnixStream::pair().unwrap();
+    poll.register(&b, SERVER, Ready::readable(), PollOpt::edge()).unwrap();
+    poll.register(&d, CLIENT, Ready::readable(), PollOpt::edge()).unwrap();
+
+    assert_eq!(1, a.send_with_fd(b"x", c.as_raw_fd()).unwrap());
+    drop(c);
+
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+
+    let mut buf = [0; 16];
+    let (n, fd) = b.recv_with_fd(&mut buf).unwrap();
+    assert_eq!(1, n);
+    assert_eq!(b'x', buf[0]);
+
+    let fd = fd.expect("no descriptor received");
+    let flags = unsafe { ::libc::fcntl(fd, ::libc::F_GETFD) };
+    assert!(flags & ::libc::FD_CLOEXEC != 0);
+
+    // The received descriptor is the other half of `d`
+    let mut received = unsafe { UnixStream::from_raw_fd(fd) };
+    received.write_all(b"hi").unwrap();
+
+    wait_for(&poll, &mut events, &[(CLIENT, Ready::readable())]);
+    assert_eq!(2, d.read(&mut buf).unwrap());
+    assert_eq!(b"hi", &buf[..2]);
+}
+
+// Sends a byte along with `fds` in a single `SCM_RIGHTS` message
+fn send_fds(sock: &UnixStream, fds: &[::std::os::unix::io::RawFd]) {
+    use std::os::unix::io::AsRawFd;
+    use std::{mem, ptr};
+    use libc;
+
+    unsafe {
+        let mut byte = [b'x'];
+        let mut iov = libc::iovec {
+            iov_base: byte.as_mut_ptr() as *mut _,
+            iov_len: 1,
+        };
+        let data_len = (fds.len() * mem::size_of::<libc::c_int>()) as libc::c_uint;
+        let mut cmsg_buf = vec![0u64; libc::CMSG_SPACE(data_len) as usize / 8 + 1];
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+        msg.msg_controllen = libc::CMSG_SPACE(data_len) as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_len = libc::CMSG_LEN(data_len) as _;
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut libc::c_int, fds.len());
+
+        assert_eq!(1, libc::sendmsg(sock.as_raw_fd(), &msg, 0));
+    }
+}
+
+#[test]
+pub fn test_uds_pass_two_fds() {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let (c1, mut d1) = UnixStream::pair().unwrap();
+    let (c2, mut d2) = UnixStream::pair().unwrap();
+    poll.register(&b, SERVER, Ready::readable(), PollOpt::edge()).unwrap();
+    poll.register(&d2, CLIENT, Ready::readable(), PollOpt::edge()).unwrap();
+
+    send_fds(&a, &[c1.as_raw_fd(), c2.as_raw_fd()]);
+    drop(c1);
+    drop(c2);
+
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+
+    let mut buf = [0; 16];
+    let (n, fd) = b.recv_with_fd(&mut buf).unwrap();
+    assert_eq!(1, n);
+
+    // The first descriptor is returned
+    let mut received = unsafe { UnixStream::from_raw_fd(fd.expect("no descriptor received")) };
+    received.write_all(b"hi").unwrap();
+    drop(received);
+
+    let mut buf = [0; 16];
+    assert_eq!(2, d1.read(&mut buf).unwrap());
+
+    // The second one was closed, its peer sees the end of the stream
+    wait_for(&poll, &mut events, &[(CLIENT, Ready::readable())]);
+    assert_eq!(0, d2.read(&mut buf).unwrap());
+}
+
+#[test]
+pub fn test_uds_pass_fds_truncated() {
+    use std::os::unix::io::AsRawFd;
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let (c, mut d) = UnixStream::pair().unwrap();
+    poll.register(&b, SERVER, Ready::readable(), PollOpt::edge()).unwrap();
+    poll.register(&d, CLIENT, Ready::readable(), PollOpt::edge()).unwrap();
+
+    // More descriptors than the receive buffer holds
+    send_fds(&a, &[c.as_raw_fd(); 64]);
+    drop(c);
+
+    wait_for(&poll, &mut events, &[(SERVER, Ready::readable())]);
+
+    let mut buf = [0; 16];
+    assert!(b.recv_with_fd(&mut buf).is_err());
+
+    // None of the received copies is leaked
+    wait_for(&poll, &mut events, &[(CLIENT, Ready::readable())]);
+    assert_eq!(0, d.read(&mut buf).unwrap());
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+pub fn test_uds_abstract_namespace() {
+    use mio::uds::SocketAddr;
+
+    let name = format!("mio-test-{}", ::std::process::id());
+    let addr = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+    assert_eq!(Some(name.as_bytes()), addr.as_abstract_name());
+    assert!(addr.as_pathname().is_none());
+    assert!(!addr.is_unnamed());
+
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let listener = UnixListener::bind_addr(&addr).unwrap();
+    assert_eq!(Some(name.as_bytes()), listener.local_addr().unwrap().as_abstract_name());
+
+    // The name is taken while the listener is open
+    assert!(UnixListener::bind_addr(&addr).is_err());
+
+    poll.register(&listener, LISTENER, Ready::readable(), PollOpt::edge()).unwrap();
+
+    let client = UnixStream::connect_addr(&addr).unwrap();
+    assert_eq!(Some(name.as_bytes()), client.peer_addr().unwrap().as_abstract_name());
+
+    wait_for(&poll, &mut events, &[(LISTENER, Ready::readable())]);
+    listener.accept().unwrap();
+
+    // No file is left behind, the name is released once the listener closes
+    drop(listener);
+    UnixListener::bind_addr(&addr).unwrap();
+}
+
+#[test]
+pub fn test_uds_pathname_addr() {
+    use mio::uds::SocketAddr;
+
+    let dir = TempDir::new("uds").unwrap();
+    let path = dir.path().join("sock");
+
+    let addr = SocketAddr::from_pathname(&path).unwrap();
+    assert_eq!(Some(path.as_path()), addr.as_pathname());
+
+    let listener = UnixListener::bind_addr(&addr).unwrap();
+    assert_eq!(Some(path.as_path()), listener.local_addr().unwrap().as_pathname());
+    assert!(path.exists());
+}
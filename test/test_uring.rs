@@ -0,0 +1,70 @@
+use mio::*;
+use mio::tcp::{TcpListener, TcpStream};
+use mio::unix::EventedFd;
+use libc;
+use std::io::{self, Read};
+use std::net;
+use std::time::Duration;
+
+#[test]
+pub fn test_uring_drop_registered_stream() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let stream = TcpStream::connect(&addr).unwrap();
+    let (mut peer, _) = listener.accept().unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    poll.register(&stream, Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+    // The pending poll request no longer keeps the socket open
+    drop(stream);
+
+    let mut buf = [0; 16];
+    assert_eq!(0, peer.read(&mut buf).unwrap());
+}
+
+#[test]
+pub fn test_uring_drop_registered_listener() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    poll.register(&listener, Token(0), Ready::readable(), PollOpt::level()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+    drop(listener);
+
+    // The address is free again
+    let listener = TcpListener::bind(&addr).unwrap();
+    assert_eq!(addr, listener.local_addr().unwrap());
+}
+
+#[test]
+pub fn test_uring_deregister_evented_fd() {
+    let poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(16);
+
+    let mut fds = [0; 2];
+    assert_eq!(0, unsafe { libc::pipe(fds.as_mut_ptr()) });
+
+    poll.register(&EventedFd(&fds[0]), Token(0), Ready::readable(), PollOpt::edge()).unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(0))).unwrap();
+
+    // Raw fds are not tracked, deregistering releases the read end
+    poll.deregister(&EventedFd(&fds[0])).unwrap();
+    unsafe { libc::close(fds[0]) };
+
+    let res = unsafe { libc::write(fds[1], b"x".as_ptr() as *const _, 1) };
+    let err = io::Error::last_os_error();
+    unsafe { libc::close(fds[1]) };
+
+    assert_eq!(-1, res);
+    assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+}